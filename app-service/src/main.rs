@@ -1,27 +1,67 @@
 use std::env;
 
 use askama::Template;
+use auth_middleware::{AuthConfig, AuthLayer, AuthenticatedUser, RequirePermission};
 use axum::{
-    http::StatusCode,
-    response::{Html, IntoResponse},
+    extract::Request,
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
     routing::get,
     Json, Router,
 };
-use axum_extra::extract::CookieJar;
+use opentelemetry::{
+    global,
+    propagation::TextMapPropagator,
+    trace::{SpanKind, TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
 use serde::Serialize;
 use tower_http::services::ServeDir;
 
 #[tokio::main]
 async fn main() {
+    let tracer_provider = init_tracer_provider();
+    global::set_tracer_provider(tracer_provider.clone());
+
+    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
+    let auth_layer = AuthLayer::new(AuthConfig::remote(format!("http://{}:3000", auth_hostname)));
+
+    // When set, only users granted this permission by auth-service may see /protected
+    let protected_route = match env::var("PROTECTED_PERMISSION") {
+        Ok(permission) if !permission.is_empty() => get(protected)
+            .layer(RequirePermission::new(permission))
+            .layer(auth_layer),
+        _ => get(protected).layer(auth_layer),
+    };
+
     let app = Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/", get(root))
-        .route("/protected", get(protected));
+        .route("/protected", protected_route)
+        .layer(middleware::from_fn(trace_context));
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
 
     println!("listening on {}", listener.local_addr().unwrap());
     axum::serve(listener, app).await.unwrap();
+    tracer_provider.shutdown().unwrap();
+}
+
+// Spans are exported only when OTEL_EXPORTER_OTLP_ENDPOINT points at a collector.
+// Without one, trace ids are still generated and passed on to the auth service.
+fn init_tracer_provider() -> SdkTracerProvider {
+    let builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name("app-service").build());
+    if env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_err() {
+        return builder.build();
+    }
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+        .expect("Failed to build OTLP span exporter");
+    builder.with_batch_exporter(exporter).build()
 }
 
 #[derive(Template)]
@@ -46,40 +86,31 @@ async fn root() -> impl IntoResponse {
     Html(template.render().unwrap())
 }
 
-async fn protected(jar: CookieJar) -> impl IntoResponse {
-    let jwt_cookie = match jar.get("jwt") {
-        Some(cookie) => cookie,
-        None => {
-            return StatusCode::UNAUTHORIZED.into_response();
-        }
-    };
-
-    let api_client = reqwest::Client::builder().build().unwrap();
-
-    let verify_token_body = serde_json::json!({
-        "token": &jwt_cookie.value(),
-    });
-
-    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
-    let url = format!("http://{}:3000/verify-token", auth_hostname);
+async fn protected(_user: AuthenticatedUser) -> impl IntoResponse {
+    Json(ProtectedRouteResponse {
+        img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
+    })
+}
 
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
-        Ok(response) => response,
-        Err(_) => {
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-        }
-    };
+// Continue the caller's trace (or start a new one) for the request, and pass it on in the
+// request's headers, which the auth layer forwards to the auth service
+async fn trace_context(mut request: Request, next: Next) -> Response {
+    let propagator = TraceContextPropagator::new();
+    let parent = propagator.extract(&HeaderExtractor(request.headers()));
+    let tracer = global::tracer("app-service");
+    let span = tracer
+        .span_builder(format!("{} {}", request.method(), request.uri().path()))
+        .with_kind(SpanKind::Server)
+        .start_with_context(&tracer, &parent);
+    let cx = Context::current_with_span(span);
+    propagator.inject_context(&cx, &mut HeaderInjector(request.headers_mut()));
 
-    match response.status() {
-        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::BAD_REQUEST => {
-            StatusCode::UNAUTHORIZED.into_response()
-        }
-        reqwest::StatusCode::OK => Json(ProtectedRouteResponse {
-            img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
-        })
-        .into_response(),
-        _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    }
+    let response = next.run(request).await;
+    cx.span().set_attribute(KeyValue::new(
+        "http.response.status_code",
+        i64::from(response.status().as_u16()),
+    ));
+    response
 }
 
 #[derive(Serialize)]
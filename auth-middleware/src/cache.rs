@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::AuthenticatedUser;
+
+// The last validation result of a token
+#[derive(Clone, Debug)]
+pub(crate) struct CachedResult {
+    // `None` when the token was invalid
+    pub user: Option<AuthenticatedUser>,
+    pub checked_at: Instant,
+}
+
+impl CachedResult {
+    // Whether the result can still be used `max_age` after it was checked. Valid
+    // results never outlive the token itself.
+    pub fn usable_for(&self, max_age: Duration) -> bool {
+        let token_live = match &self.user {
+            Some(user) => user
+                .expires_at
+                .is_none_or(|expires_at| expires_at > unix_now()),
+            None => true,
+        };
+        token_live && self.checked_at.elapsed() < max_age
+    }
+}
+
+// Validation results by token. Entries are kept for `keep_for`, which covers both the
+// cache TTL and any time stale results may still be used for.
+pub(crate) struct Cache {
+    entries: Mutex<HashMap<String, CachedResult>>,
+    capacity: usize,
+    keep_for: Duration,
+}
+
+impl Cache {
+    pub fn new(capacity: usize, keep_for: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            capacity,
+            keep_for,
+        }
+    }
+
+    pub fn get(&self, token: &str) -> Option<CachedResult> {
+        self.entries
+            .lock()
+            .expect("cache lock poisoned")
+            .get(token)
+            .cloned()
+    }
+
+    pub fn insert(&self, token: &str, user: Option<AuthenticatedUser>) {
+        if self.capacity == 0 || self.keep_for.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.lock().expect("cache lock poisoned");
+        if entries.len() >= self.capacity && !entries.contains_key(token) {
+            entries.retain(|_, entry| entry.usable_for(self.keep_for));
+            // Still full of live entries, e.g. during a flood of random tokens
+            if entries.len() >= self.capacity {
+                entries.clear();
+            }
+        }
+        entries.insert(
+            token.to_owned(),
+            CachedResult {
+                user,
+                checked_at: Instant::now(),
+            },
+        );
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.lock().expect("cache lock poisoned").len()
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(expires_at: Option<u64>) -> AuthenticatedUser {
+        AuthenticatedUser {
+            id: "0b7a8f0e-3d5c-4a4e-9f59-7f4a1c2e6d10".to_owned(),
+            email: Some("test@example.com".to_owned()),
+            session_id: None,
+            expires_at,
+            roles: Vec::new(),
+            permissions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn should_return_inserted_results() {
+        let cache = Cache::new(10, Duration::from_secs(60));
+        cache.insert("valid", Some(user(Some(unix_now() + 600))));
+        cache.insert("invalid", None);
+
+        let valid = cache.get("valid").unwrap();
+        assert!(valid.usable_for(Duration::from_secs(60)));
+        assert!(!valid.usable_for(Duration::ZERO));
+        assert_eq!(
+            valid.user.unwrap().email.as_deref(),
+            Some("test@example.com")
+        );
+        assert_eq!(cache.get("invalid").unwrap().user, None);
+        assert!(cache.get("unknown").is_none());
+    }
+
+    #[test]
+    fn should_not_use_results_of_expired_tokens() {
+        let cache = Cache::new(10, Duration::from_secs(60));
+        cache.insert("expired", Some(user(Some(unix_now() - 1))));
+
+        assert!(!cache
+            .get("expired")
+            .unwrap()
+            .usable_for(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn should_use_results_of_api_keys_without_expiry() {
+        let cache = Cache::new(10, Duration::from_secs(60));
+        cache.insert("ak_key", Some(user(None)));
+
+        assert!(cache
+            .get("ak_key")
+            .unwrap()
+            .usable_for(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn should_stay_within_capacity() {
+        let cache = Cache::new(2, Duration::from_secs(60));
+        for token in ["a", "b", "c"] {
+            cache.insert(token, None);
+        }
+
+        assert!(cache.len() <= 2);
+        assert!(cache.get("c").is_some());
+    }
+
+    #[test]
+    fn should_not_cache_with_zero_ttl() {
+        let cache = Cache::new(10, Duration::ZERO);
+        cache.insert("token", None);
+
+        assert!(cache.get("token").is_none());
+    }
+}
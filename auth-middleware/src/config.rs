@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+// How tokens are checked
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationMode {
+    // Ask auth-service's `/verify-token`, which also knows about logouts and revoked sessions
+    Remote {
+        auth_service_url: String,
+    },
+    // Check signatures against the keys published at `jwks_url`, without a round trip per
+    // token. Only `at+jwt` tokens signed with an asymmetric key are accepted. Logouts and
+    // revoked sessions cannot be seen, so such tokens stay valid until they expire.
+    Jwks {
+        jwks_url: String,
+        // Required `iss` and `aud` claims, if any
+        issuer: Option<String>,
+        audience: Option<String>,
+    },
+}
+
+// What to do when a token cannot be checked at all
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailurePolicy {
+    // Reject the request with 503 Service Unavailable
+    Deny,
+    // Keep accepting tokens that were valid when last checked, for up to this long past
+    // the end of their cache TTL, and deny the rest
+    AllowStale(Duration),
+}
+
+#[derive(Clone, Debug)]
+pub struct AuthConfig {
+    pub mode: ValidationMode,
+    // How long a token's validation result is reused. Longer TTLs mean fewer calls to
+    // auth-service, but a revoked token keeps working for up to this long.
+    pub cache_ttl: Duration,
+    // Cached results kept at most, so a flood of random tokens cannot exhaust memory
+    pub cache_capacity: usize,
+    pub failure_policy: FailurePolicy,
+    // Timeout of each call to auth-service or the JWKS endpoint
+    pub request_timeout: Duration,
+    // How long fetched JWKS keys are used before they are fetched again
+    pub jwks_refresh_interval: Duration,
+}
+
+impl AuthConfig {
+    pub fn remote(auth_service_url: impl Into<String>) -> Self {
+        Self::new(ValidationMode::Remote {
+            auth_service_url: auth_service_url.into().trim_end_matches('/').to_owned(),
+        })
+    }
+
+    pub fn jwks(jwks_url: impl Into<String>) -> Self {
+        Self::new(ValidationMode::Jwks {
+            jwks_url: jwks_url.into(),
+            issuer: None,
+            audience: None,
+        })
+    }
+
+    fn new(mode: ValidationMode) -> Self {
+        Self {
+            mode,
+            cache_ttl: Duration::from_secs(5),
+            cache_capacity: 10_000,
+            failure_policy: FailurePolicy::Deny,
+            request_timeout: Duration::from_secs(2),
+            jwks_refresh_interval: Duration::from_secs(300),
+        }
+    }
+
+    // A zero TTL turns caching off
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    pub fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self
+    }
+
+    pub fn failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.failure_policy = policy;
+        self
+    }
+
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn jwks_refresh_interval(mut self, interval: Duration) -> Self {
+        self.jwks_refresh_interval = interval;
+        self
+    }
+
+    // Only has an effect in JWKS mode
+    pub fn issuer(mut self, expected: impl Into<String>) -> Self {
+        if let ValidationMode::Jwks { issuer, .. } = &mut self.mode {
+            *issuer = Some(expected.into());
+        }
+        self
+    }
+
+    // Only has an effect in JWKS mode
+    pub fn audience(mut self, expected: impl Into<String>) -> Self {
+        if let ValidationMode::Jwks { audience, .. } = &mut self.mode {
+            *audience = Some(expected.into());
+        }
+        self
+    }
+}
@@ -0,0 +1,55 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use thiserror::Error;
+
+// Why a request was not let through, returned to the client as `{"error": ...}`
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    #[error("Missing auth token")]
+    MissingToken,
+    #[error("Invalid auth token")]
+    InvalidToken,
+    // The token could not be checked, e.g. because auth-service is down
+    #[error("Authentication unavailable")]
+    Unavailable,
+    // `AuthenticatedUser` was extracted on a route without an `AuthLayer`
+    #[error("Authentication not configured")]
+    MissingLayer,
+    // The user is authenticated but was not granted the permission a route requires
+    #[error("Missing permission")]
+    MissingPermission,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::MissingToken | Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Self::MissingLayer => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::MissingPermission => StatusCode::FORBIDDEN,
+        };
+        let body = Json(ErrorResponse {
+            error: self.to_string(),
+        });
+        (status, body).into_response()
+    }
+}
+
+// Why a token could not be validated
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("token is invalid")]
+    Invalid,
+    // Not the token's fault, so never cached
+    #[error("token could not be checked: {0}")]
+    Unavailable(String),
+}
@@ -0,0 +1,24 @@
+//! Authentication for services sitting behind auth-service.
+//!
+//! Wrap protected routes in an [`AuthLayer`] and take an [`AuthenticatedUser`] in their
+//! handlers. Tokens are read from an `Authorization: Bearer` header or the `jwt` cookie,
+//! and checked either remotely against auth-service or locally against a JWKS. Local checks
+//! cannot tell that a token was logged out or revoked. API keys in an `Authorization: ApiKey`
+//! header are checked by auth-service.
+//!
+//! Routes that need more than a valid token can also be wrapped in a [`RequirePermission`],
+//! inside the `AuthLayer`.
+mod cache;
+mod config;
+mod error;
+mod layer;
+mod permission;
+mod user;
+mod validator;
+
+pub use config::{AuthConfig, FailurePolicy, ValidationMode};
+pub use error::{AuthError, ValidationError};
+pub use layer::{AuthLayer, AuthService, JWT_COOKIE_NAME};
+pub use permission::{PermissionService, RequirePermission};
+pub use user::AuthenticatedUser;
+pub use validator::FORWARDED_HEADERS;
@@ -0,0 +1,132 @@
+use axum::{
+    extract::Request,
+    response::{IntoResponse, Response},
+};
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+use crate::{AuthError, AuthenticatedUser};
+
+// Rejects authenticated users whose token does not grant `permission`. It reads the user
+// an `AuthLayer` found, so it has to be applied inside one:
+// `.layer(RequirePermission::new("reports:read")).layer(AuthLayer::new(config))`
+#[derive(Clone)]
+pub struct RequirePermission {
+    permission: Arc<str>,
+}
+
+impl RequirePermission {
+    pub fn new(permission: impl Into<String>) -> Self {
+        Self {
+            permission: permission.into().into(),
+        }
+    }
+}
+
+impl<S> Layer<S> for RequirePermission {
+    type Service = PermissionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PermissionService {
+            inner,
+            permission: self.permission.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PermissionService<S> {
+    inner: S,
+    permission: Arc<str>,
+}
+
+impl<S> Service<Request> for PermissionService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let allowed = match request.extensions().get::<AuthenticatedUser>() {
+            Some(user) if user.has_permission(&self.permission) => Ok(()),
+            Some(_) => Err(AuthError::MissingPermission),
+            None => Err(AuthError::MissingLayer),
+        };
+        if let Err(e) = allowed {
+            return Box::pin(async move { Ok(e.into_response()) });
+        }
+
+        // The clone is not ready yet, so the one that was polled is used and the clone kept
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move { inner.call(request).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::StatusCode, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn user(permissions: &[&str]) -> AuthenticatedUser {
+        AuthenticatedUser {
+            id: "0b7a8f0e-3d5c-4a4e-9f59-7f4a1c2e6d10".to_owned(),
+            email: Some("test@example.com".to_owned()),
+            session_id: None,
+            expires_at: None,
+            roles: vec!["viewer".to_owned()],
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    async fn call(user: Option<AuthenticatedUser>) -> StatusCode {
+        let app = Router::new()
+            .route("/reports", get(|| async { "reports" }))
+            .layer(RequirePermission::new("reports:read"));
+        let mut request = Request::builder()
+            .uri("/reports")
+            .body(Body::empty())
+            .unwrap();
+        if let Some(user) = user {
+            request.extensions_mut().insert(user);
+        }
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn should_allow_users_with_permission() {
+        assert_eq!(
+            call(Some(user(&["reports:write", "reports:read"]))).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn should_forbid_users_without_permission() {
+        assert_eq!(call(Some(user(&[]))).await, StatusCode::FORBIDDEN);
+        assert_eq!(
+            call(Some(user(&["reports:write"]))).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn should_fail_without_auth_layer() {
+        assert_eq!(call(None).await, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,47 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use serde::Deserialize;
+
+use crate::AuthError;
+
+// The caller of a request that passed an `AuthLayer`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct AuthenticatedUser {
+    // The user's id, which stays the same when their email changes
+    #[serde(rename = "sub")]
+    pub id: String,
+    // The user's email as of when the token was issued. Tokens of clients acting on their
+    // own have none.
+    #[serde(default)]
+    pub email: Option<String>,
+    // The auth-service session the token was issued for
+    #[serde(rename = "sid", default)]
+    pub session_id: Option<String>,
+    // When the token expires, in seconds since the Unix epoch. API keys without an expiry
+    // have none.
+    #[serde(rename = "exp", default)]
+    pub expires_at: Option<u64>,
+    // The roles auth-service assigned the user when the token was issued
+    #[serde(default)]
+    pub roles: Vec<String>,
+    // Every permission those roles grant
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+impl AuthenticatedUser {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthenticatedUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthenticatedUser>()
+            .cloned()
+            .ok_or(AuthError::MissingLayer)
+    }
+}
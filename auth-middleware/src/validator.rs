@@ -0,0 +1,228 @@
+use axum::http::{header::AUTHORIZATION, HeaderMap, HeaderName, StatusCode};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Header, Validation,
+};
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    config::{AuthConfig, ValidationMode},
+    layer::Credential,
+    AuthenticatedUser, ValidationError,
+};
+
+// Passed on to auth-service, so its logs and traces line up with the caller's
+pub const FORWARDED_HEADERS: [HeaderName; 3] = [
+    HeaderName::from_static("x-request-id"),
+    HeaderName::from_static("traceparent"),
+    HeaderName::from_static("tracestate"),
+];
+// Unknown key ids cause the JWKS to be fetched again, as keys may have been rotated,
+// but no more often than this
+const MIN_JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(30);
+// The `typ` header of auth-service's access tokens (RFC 9068)
+const ACCESS_TOKEN_TYPE: &str = "at+jwt";
+
+pub(crate) enum Validator {
+    Remote(RemoteValidator),
+    Jwks(JwksValidator),
+}
+
+impl Validator {
+    pub fn new(config: &AuthConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()
+            .expect("Failed to build HTTP client");
+        match &config.mode {
+            ValidationMode::Remote { auth_service_url } => Self::Remote(RemoteValidator {
+                client,
+                verify_url: format!("{}/verify-token", auth_service_url),
+            }),
+            ValidationMode::Jwks {
+                jwks_url,
+                issuer,
+                audience,
+            } => Self::Jwks(JwksValidator {
+                client,
+                jwks_url: jwks_url.clone(),
+                issuer: issuer.clone(),
+                audience: audience.clone(),
+                refresh_interval: config.jwks_refresh_interval,
+                keys: RwLock::new(None),
+            }),
+        }
+    }
+
+    // `headers` are those of the request being authenticated
+    pub async fn validate(
+        &self,
+        credential: &Credential,
+        headers: &HeaderMap,
+    ) -> Result<AuthenticatedUser, ValidationError> {
+        match (self, credential) {
+            (Self::Remote(validator), credential) => validator.validate(credential, headers).await,
+            (Self::Jwks(validator), Credential::Token(token)) => validator.validate(token).await,
+            // Only auth-service knows which API keys it issued
+            (Self::Jwks(_), Credential::ApiKey(_)) => Err(ValidationError::Invalid),
+        }
+    }
+}
+
+pub(crate) struct RemoteValidator {
+    client: reqwest::Client,
+    verify_url: String,
+}
+
+impl RemoteValidator {
+    async fn validate(
+        &self,
+        credential: &Credential,
+        headers: &HeaderMap,
+    ) -> Result<AuthenticatedUser, ValidationError> {
+        let mut forwarded = HeaderMap::new();
+        for name in FORWARDED_HEADERS {
+            if let Some(value) = headers.get(&name) {
+                forwarded.insert(name, value.clone());
+            }
+        }
+        let request = self.client.post(&self.verify_url).headers(forwarded);
+        let request = match credential {
+            Credential::Token(token) => request.json(&serde_json::json!({ "token": token })),
+            Credential::ApiKey(key) => request.header(AUTHORIZATION, format!("ApiKey {}", key)),
+        };
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ValidationError::Unavailable(e.to_string()))?;
+
+        match response.status() {
+            // auth-service describes the caller, whatever they authenticated with
+            StatusCode::OK => response
+                .json::<AuthenticatedUser>()
+                .await
+                .map_err(|e| ValidationError::Unavailable(e.to_string())),
+            StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED => Err(ValidationError::Invalid),
+            status => Err(ValidationError::Unavailable(format!(
+                "auth-service returned {}",
+                status
+            ))),
+        }
+    }
+}
+
+struct FetchedKeys {
+    set: JwkSet,
+    fetched_at: Instant,
+}
+
+impl FetchedKeys {
+    // Tokens without a key id can only be matched when there is a single key
+    fn find(&self, kid: Option<&str>) -> Option<Jwk> {
+        match kid {
+            Some(kid) => self.set.find(kid).cloned(),
+            None if self.set.keys.len() == 1 => self.set.keys.first().cloned(),
+            None => None,
+        }
+    }
+}
+
+pub(crate) struct JwksValidator {
+    client: reqwest::Client,
+    jwks_url: String,
+    issuer: Option<String>,
+    audience: Option<String>,
+    refresh_interval: Duration,
+    keys: RwLock<Option<FetchedKeys>>,
+}
+
+impl JwksValidator {
+    async fn validate(&self, token: &str) -> Result<AuthenticatedUser, ValidationError> {
+        let header = decode_header(token).map_err(|_| ValidationError::Invalid)?;
+        let jwk = self.key_for(header.kid.as_deref()).await?;
+        let validation = self.validation(&header, &jwk)?;
+        let key = DecodingKey::from_jwk(&jwk).map_err(|_| ValidationError::Invalid)?;
+        decode::<AuthenticatedUser>(token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|_| ValidationError::Invalid)
+    }
+
+    fn validation(&self, header: &Header, jwk: &Jwk) -> Result<Validation, ValidationError> {
+        // ID tokens are signed with the same published keys, but are not meant for APIs
+        let is_access_token = header.typ.as_deref().is_some_and(|typ| {
+            let typ = typ.to_ascii_lowercase();
+            typ == ACCESS_TOKEN_TYPE || typ.strip_prefix("application/") == Some(ACCESS_TOKEN_TYPE)
+        });
+        // A published key is public, so it must never be used as an HMAC secret
+        let is_hmac = matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        );
+        if !is_access_token || is_hmac {
+            return Err(ValidationError::Invalid);
+        }
+        // A key published for one algorithm must not be used to check another
+        if let Some(key_algorithm) = jwk.common.key_algorithm {
+            let algorithm = Algorithm::from_str(&key_algorithm.to_string())
+                .map_err(|_| ValidationError::Invalid)?;
+            if algorithm != header.alg {
+                return Err(ValidationError::Invalid);
+            }
+        }
+        let mut validation = Validation::new(header.alg);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        Ok(validation)
+    }
+
+    async fn key_for(&self, kid: Option<&str>) -> Result<Jwk, ValidationError> {
+        {
+            let keys = self.keys.read().await;
+            if let Some(keys) = keys.as_ref() {
+                let age = keys.fetched_at.elapsed();
+                match keys.find(kid) {
+                    Some(jwk) if age < self.refresh_interval => return Ok(jwk),
+                    None if age < MIN_JWKS_REFETCH_INTERVAL => {
+                        return Err(ValidationError::Invalid)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut keys = self.keys.write().await;
+        match self.fetch().await {
+            Ok(set) => {
+                let fetched = keys.insert(FetchedKeys {
+                    set,
+                    fetched_at: Instant::now(),
+                });
+                fetched.find(kid).ok_or(ValidationError::Invalid)
+            }
+            // Keys that are due for a refresh are still better than none
+            Err(e) => keys.as_ref().and_then(|keys| keys.find(kid)).ok_or(e),
+        }
+    }
+
+    async fn fetch(&self) -> Result<JwkSet, ValidationError> {
+        self.client
+            .get(&self.jwks_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| ValidationError::Unavailable(e.to_string()))?
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| ValidationError::Unavailable(e.to_string()))
+    }
+}
@@ -32,6 +32,18 @@ signupLoginLink.addEventListener("click", (e) => {
 
 // -----------------------------------------------------
 
+// OAuth clients and the device page send users here to log in, and get them back
+// through `next`
+function continueAuthorization() {
+    const next = new URLSearchParams(window.location.search).get("next");
+    // Only the authorization endpoint and device page of this service, never another site
+    if (next !== null && (next.startsWith("/authorize?") || next.startsWith("/device?"))) {
+        window.location.assign(next);
+        return true;
+    }
+    return false;
+}
+
 const loginForm = document.getElementById("login-form");
 const loginButton = document.getElementById("login-form-submit");
 const loginErrAlter = document.getElementById("login-err-alert");
@@ -66,6 +78,9 @@ loginButton.addEventListener("click", (e) => {
             loginForm.email.value = "";
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
+            if (continueAuthorization()) {
+                return;
+            }
             alert("You have successfully logged in.");
         } else {
             response.json().then(data => {
@@ -145,6 +160,9 @@ TwoFAButton.addEventListener("click", (e) => {
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
+            if (continueAuthorization()) {
+                return;
+            }
             alert("You have successfully logged in.");
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
@@ -0,0 +1,91 @@
+const codeSection = document.getElementById("code-section");
+const approveSection = document.getElementById("approve-section");
+const doneSection = document.getElementById("done-section");
+
+const codeForm = document.getElementById("code-form");
+const codeButton = document.getElementById("code-form-submit");
+const codeErrAlert = document.getElementById("code-err-alert");
+const approveErrAlert = document.getElementById("approve-err-alert");
+
+// The code being approved, as the user typed it
+let userCode = null;
+
+function showSection(section) {
+    codeSection.style.display = section === codeSection ? "block" : "none";
+    approveSection.style.display = section === approveSection ? "block" : "none";
+    doneSection.style.display = section === doneSection ? "block" : "none";
+}
+
+function showError(alert, response) {
+    response.json().then(data => {
+        let error_msg = data.error;
+        if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+            alert.textContent = `Error: ${error_msg}`;
+            alert.style.display = "block";
+        } else {
+            alert.style.display = "none";
+        }
+    });
+}
+
+// Users who are not logged in log in first, and are sent back here with their code
+function login(code) {
+    const next = `/device?${new URLSearchParams({ user_code: code })}`;
+    window.location.assign(`/?${new URLSearchParams({ next })}`);
+}
+
+function lookUp(code) {
+    fetch(`/device/verify?${new URLSearchParams({ user_code: code })}`).then(response => {
+        if (response.status === 401) {
+            login(code);
+        } else if (response.ok) {
+            response.json().then(data => {
+                userCode = code;
+                // Client names are chosen by whoever registered them, so they are never markup
+                document.getElementById("client-name").textContent = data.clientName;
+                document.getElementById("scope").textContent = data.scope ?? "";
+                document.getElementById("scope-line").style.display = data.scope ? "block" : "none";
+                codeErrAlert.style.display = "none";
+                approveErrAlert.style.display = "none";
+                showSection(approveSection);
+            });
+        } else {
+            showError(codeErrAlert, response);
+        }
+    });
+}
+
+function decide(approve) {
+    fetch('/device/verify', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ userCode, approve }),
+    }).then(response => {
+        if (response.status === 401) {
+            login(userCode);
+        } else if (response.ok) {
+            document.getElementById("done-message").textContent =
+                approve ? "Your device is connected." : "Access was denied.";
+            showSection(doneSection);
+        } else {
+            showError(approveErrAlert, response);
+        }
+    });
+}
+
+codeButton.addEventListener("click", (e) => {
+    e.preventDefault();
+    lookUp(codeForm.user_code.value);
+});
+
+document.getElementById("approve-button").addEventListener("click", () => decide(true));
+document.getElementById("deny-button").addEventListener("click", () => decide(false));
+
+// Devices may link here with the code filled in
+const prefilled = new URLSearchParams(window.location.search).get("user_code");
+if (prefilled !== null && prefilled !== "") {
+    codeForm.user_code.value = prefilled;
+    lookUp(prefilled);
+}
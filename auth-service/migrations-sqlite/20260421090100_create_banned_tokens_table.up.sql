@@ -0,0 +1,6 @@
+-- expires_at holds a Unix timestamp in seconds
+CREATE TABLE IF NOT EXISTS banned_tokens(
+  token TEXT NOT NULL PRIMARY KEY,
+  expires_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS banned_tokens_expires_at_idx ON banned_tokens (expires_at);
@@ -0,0 +1,13 @@
+-- Timestamps hold Unix timestamps in microseconds, so last_seen_at keeps its precision
+CREATE TABLE IF NOT EXISTS sessions(
+  id TEXT NOT NULL PRIMARY KEY,
+  email TEXT NOT NULL,
+  token TEXT NOT NULL,
+  created_at INTEGER NOT NULL,
+  last_seen_at INTEGER NOT NULL,
+  expires_at INTEGER NOT NULL,
+  ip TEXT,
+  user_agent TEXT
+);
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions (email);
+CREATE INDEX IF NOT EXISTS sessions_expires_at_idx ON sessions (expires_at);
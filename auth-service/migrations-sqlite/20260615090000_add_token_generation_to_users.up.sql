@@ -0,0 +1,2 @@
+-- Tokens carry the generation they were issued under; bumping it invalidates them all
+ALTER TABLE users ADD COLUMN token_generation INTEGER NOT NULL DEFAULT 0;
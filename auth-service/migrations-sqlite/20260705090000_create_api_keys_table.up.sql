@@ -0,0 +1,14 @@
+-- Timestamps hold Unix timestamps in microseconds, like those of sessions
+CREATE TABLE IF NOT EXISTS api_keys(
+  id TEXT NOT NULL PRIMARY KEY,
+  email TEXT NOT NULL,
+  name TEXT NOT NULL,
+  key_hash TEXT NOT NULL UNIQUE,
+  key_hint TEXT NOT NULL,
+  scope TEXT,
+  created_at INTEGER NOT NULL,
+  expires_at INTEGER,
+  last_used_at INTEGER
+);
+CREATE INDEX IF NOT EXISTS api_keys_email_idx ON api_keys (email);
+CREATE INDEX IF NOT EXISTS api_keys_expires_at_idx ON api_keys (expires_at);
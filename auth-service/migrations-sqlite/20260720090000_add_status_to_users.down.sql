@@ -0,0 +1,6 @@
+ALTER TABLE users ADD COLUMN disabled BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE users SET disabled = TRUE WHERE status <> 'active';
+DROP INDEX IF EXISTS users_scheduled_for_deletion_idx;
+ALTER TABLE users DROP COLUMN status_changed_at;
+ALTER TABLE users DROP COLUMN status_reason;
+ALTER TABLE users DROP COLUMN status;
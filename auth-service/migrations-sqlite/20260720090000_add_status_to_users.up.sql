@@ -0,0 +1,11 @@
+-- Accounts are active, disabled, pending verification or scheduled for deletion, replacing
+-- the disabled flag. status_changed_at holds a Unix timestamp in microseconds.
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'disabled', 'pending_verification', 'scheduled_for_deletion'));
+ALTER TABLE users ADD COLUMN status_reason TEXT;
+ALTER TABLE users ADD COLUMN status_changed_at INTEGER NOT NULL DEFAULT 0;
+UPDATE users SET status = 'disabled' WHERE disabled;
+UPDATE users SET status_changed_at = CAST((julianday('now') - 2440587.5) * 86400000000 AS INTEGER);
+ALTER TABLE users DROP COLUMN disabled;
+CREATE INDEX IF NOT EXISTS users_scheduled_for_deletion_idx ON users (status_changed_at)
+    WHERE status = 'scheduled_for_deletion';
@@ -0,0 +1,49 @@
+ALTER TABLE api_keys ADD COLUMN email TEXT NOT NULL DEFAULT '';
+UPDATE api_keys SET email = coalesce(
+    (SELECT email FROM users WHERE users.id = api_keys.user_id), '');
+DELETE FROM api_keys WHERE email = '';
+DROP INDEX IF EXISTS api_keys_user_id_idx;
+ALTER TABLE api_keys DROP COLUMN user_id;
+CREATE INDEX IF NOT EXISTS api_keys_email_idx ON api_keys (email);
+
+ALTER TABLE sessions ADD COLUMN email TEXT NOT NULL DEFAULT '';
+UPDATE sessions SET email = coalesce(
+    (SELECT email FROM users WHERE users.id = sessions.user_id), '');
+DELETE FROM sessions WHERE email = '';
+DROP INDEX IF EXISTS sessions_user_id_idx;
+ALTER TABLE sessions DROP COLUMN user_id;
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions (email);
+
+CREATE TABLE two_fa_codes_by_email(
+  email TEXT NOT NULL PRIMARY KEY,
+  login_attempt_id TEXT NOT NULL,
+  code TEXT NOT NULL,
+  expires_at INTEGER NOT NULL
+);
+INSERT INTO two_fa_codes_by_email (email, login_attempt_id, code, expires_at)
+  SELECT users.email, login_attempt_id, code, expires_at
+  FROM two_fa_codes JOIN users ON users.id = two_fa_codes.user_id;
+DROP TABLE two_fa_codes;
+ALTER TABLE two_fa_codes_by_email RENAME TO two_fa_codes;
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes (expires_at);
+
+CREATE TABLE users_by_email(
+  email TEXT NOT NULL PRIMARY KEY,
+  password_hash TEXT NOT NULL,
+  requires_2fa BOOLEAN NOT NULL DEFAULT FALSE,
+  token_generation INTEGER NOT NULL DEFAULT 0,
+  password_reset_required BOOLEAN NOT NULL DEFAULT FALSE,
+  status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'disabled', 'pending_verification', 'scheduled_for_deletion')),
+  status_reason TEXT,
+  status_changed_at INTEGER NOT NULL DEFAULT 0
+);
+INSERT INTO users_by_email (email, password_hash, requires_2fa, token_generation,
+    password_reset_required, status, status_reason, status_changed_at)
+  SELECT email, password_hash, requires_2fa, token_generation, password_reset_required, status,
+    status_reason, status_changed_at
+  FROM users;
+DROP TABLE users;
+ALTER TABLE users_by_email RENAME TO users;
+CREATE INDEX IF NOT EXISTS users_scheduled_for_deletion_idx ON users (status_changed_at)
+    WHERE status = 'scheduled_for_deletion';
@@ -0,0 +1,61 @@
+-- Users are identified by a UUID, so their email can change. Emails stay unique, ignoring
+-- case, which fails here if two existing users only differ by the case of their email.
+-- SQLite cannot change a primary key in place, so the tables keyed by email are rebuilt.
+CREATE TABLE users_by_id(
+  id TEXT NOT NULL PRIMARY KEY,
+  email TEXT NOT NULL,
+  password_hash TEXT NOT NULL,
+  requires_2fa BOOLEAN NOT NULL DEFAULT FALSE,
+  token_generation INTEGER NOT NULL DEFAULT 0,
+  password_reset_required BOOLEAN NOT NULL DEFAULT FALSE,
+  status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'disabled', 'pending_verification', 'scheduled_for_deletion')),
+  status_reason TEXT,
+  status_changed_at INTEGER NOT NULL DEFAULT 0
+);
+-- Random version 4 UUIDs
+INSERT INTO users_by_id (id, email, password_hash, requires_2fa, token_generation,
+    password_reset_required, status, status_reason, status_changed_at)
+  SELECT lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4'
+      || substr(hex(randomblob(2)), 2) || '-'
+      || substr('89ab', 1 + (abs(random()) % 4), 1) || substr(hex(randomblob(2)), 2) || '-'
+      || hex(randomblob(6))),
+    email, password_hash, requires_2fa, token_generation, password_reset_required, status,
+    status_reason, status_changed_at
+  FROM users;
+DROP TABLE users;
+ALTER TABLE users_by_id RENAME TO users;
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_idx ON users (lower(email));
+CREATE INDEX IF NOT EXISTS users_scheduled_for_deletion_idx ON users (status_changed_at)
+    WHERE status = 'scheduled_for_deletion';
+
+-- Everything kept by email is kept by user id instead. Rows of users who no longer exist
+-- are dropped, as nothing could use them anymore.
+CREATE TABLE two_fa_codes_by_user_id(
+  user_id TEXT NOT NULL PRIMARY KEY,
+  login_attempt_id TEXT NOT NULL,
+  code TEXT NOT NULL,
+  expires_at INTEGER NOT NULL
+);
+INSERT INTO two_fa_codes_by_user_id (user_id, login_attempt_id, code, expires_at)
+  SELECT users.id, login_attempt_id, code, expires_at
+  FROM two_fa_codes JOIN users ON users.email = two_fa_codes.email;
+DROP TABLE two_fa_codes;
+ALTER TABLE two_fa_codes_by_user_id RENAME TO two_fa_codes;
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes (expires_at);
+
+ALTER TABLE sessions ADD COLUMN user_id TEXT NOT NULL DEFAULT '';
+UPDATE sessions SET user_id = coalesce(
+    (SELECT id FROM users WHERE users.email = sessions.email), '');
+DELETE FROM sessions WHERE user_id = '';
+DROP INDEX IF EXISTS sessions_email_idx;
+ALTER TABLE sessions DROP COLUMN email;
+CREATE INDEX IF NOT EXISTS sessions_user_id_idx ON sessions (user_id);
+
+ALTER TABLE api_keys ADD COLUMN user_id TEXT NOT NULL DEFAULT '';
+UPDATE api_keys SET user_id = coalesce(
+    (SELECT id FROM users WHERE users.email = api_keys.email), '');
+DELETE FROM api_keys WHERE user_id = '';
+DROP INDEX IF EXISTS api_keys_email_idx;
+ALTER TABLE api_keys DROP COLUMN email;
+CREATE INDEX IF NOT EXISTS api_keys_user_id_idx ON api_keys (user_id);
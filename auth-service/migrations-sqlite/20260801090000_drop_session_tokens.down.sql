@@ -0,0 +1,4 @@
+-- The tokens are gone, so the sessions cannot be kept
+DELETE FROM sessions;
+ALTER TABLE sessions ADD COLUMN token TEXT NOT NULL DEFAULT '';
+ALTER TABLE sessions DROP COLUMN client_id;
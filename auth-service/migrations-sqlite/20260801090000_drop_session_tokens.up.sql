@@ -0,0 +1,5 @@
+-- Sessions no longer keep their token. Revoking one bans its id instead, and the client it
+-- was issued to is kept to find the sessions of a client. Existing sessions get no client;
+-- OpenID Connect logout misses their tokens, which are short-lived, until they expire.
+ALTER TABLE sessions ADD COLUMN client_id TEXT;
+ALTER TABLE sessions DROP COLUMN token;
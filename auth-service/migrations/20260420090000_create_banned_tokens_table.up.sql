@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS banned_tokens(
+  token TEXT NOT NULL PRIMARY KEY,
+  expires_at TIMESTAMPTZ NOT NULL
+);
+CREATE INDEX IF NOT EXISTS banned_tokens_expires_at_idx ON banned_tokens (expires_at);
@@ -0,0 +1,28 @@
+CREATE TABLE IF NOT EXISTS audit_log(
+  id BIGSERIAL PRIMARY KEY,
+  recorded_at TIMESTAMPTZ NOT NULL,
+  event TEXT NOT NULL,
+  actor TEXT,
+  ip TEXT,
+  user_agent TEXT,
+  outcome TEXT NOT NULL,
+  reason TEXT,
+  -- Each entry chains onto exactly one predecessor, so the chain cannot fork
+  prev_hash TEXT NOT NULL UNIQUE,
+  hash TEXT NOT NULL UNIQUE
+);
+
+-- Entries can only ever be appended. The hash chain still exposes anyone who gets around this.
+CREATE OR REPLACE FUNCTION audit_log_reject_change() RETURNS trigger AS $$
+BEGIN
+  RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_no_update_or_delete
+  BEFORE UPDATE OR DELETE ON audit_log
+  FOR EACH ROW EXECUTE FUNCTION audit_log_reject_change();
+
+CREATE TRIGGER audit_log_no_truncate
+  BEFORE TRUNCATE ON audit_log
+  FOR EACH STATEMENT EXECUTE FUNCTION audit_log_reject_change();
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS sessions(
+  id UUID PRIMARY KEY,
+  email TEXT NOT NULL,
+  token TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  last_seen_at TIMESTAMPTZ NOT NULL,
+  expires_at TIMESTAMPTZ NOT NULL,
+  ip TEXT,
+  user_agent TEXT
+);
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions (email);
+CREATE INDEX IF NOT EXISTS sessions_expires_at_idx ON sessions (expires_at);
@@ -0,0 +1,2 @@
+-- Tokens carry the generation they were issued under; bumping it invalidates them all
+ALTER TABLE users ADD COLUMN IF NOT EXISTS token_generation BIGINT NOT NULL DEFAULT 0;
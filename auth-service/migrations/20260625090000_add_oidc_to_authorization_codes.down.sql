@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS refresh_tokens_client_id_email_idx;
+ALTER TABLE authorization_codes
+  DROP COLUMN IF EXISTS nonce,
+  DROP COLUMN IF EXISTS auth_time,
+  DROP COLUMN IF EXISTS amr,
+  DROP COLUMN IF EXISTS session_id;
@@ -0,0 +1,8 @@
+-- Codes live for a minute, so any issued before the upgrade can simply be dropped
+DELETE FROM authorization_codes;
+ALTER TABLE authorization_codes
+  ADD COLUMN IF NOT EXISTS nonce TEXT,
+  ADD COLUMN IF NOT EXISTS auth_time TIMESTAMPTZ NOT NULL,
+  ADD COLUMN IF NOT EXISTS amr TEXT[] NOT NULL,
+  ADD COLUMN IF NOT EXISTS session_id UUID NOT NULL;
+CREATE INDEX IF NOT EXISTS refresh_tokens_client_id_email_idx ON refresh_tokens (client_id, email);
@@ -0,0 +1,3 @@
+ALTER TABLE oauth_clients
+  DROP COLUMN IF EXISTS secret_hash,
+  DROP COLUMN IF EXISTS allowed_scopes;
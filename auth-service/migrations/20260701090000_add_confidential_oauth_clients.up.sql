@@ -0,0 +1,5 @@
+-- Confidential clients have a secret, stored as its SHA-256 digest, and the scopes they may
+-- ask for with the client credentials grant
+ALTER TABLE oauth_clients
+  ADD COLUMN IF NOT EXISTS secret_hash TEXT,
+  ADD COLUMN IF NOT EXISTS allowed_scopes TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,14 @@
+-- Keys are stored as their SHA-256 digest, and never expire without an expires_at
+CREATE TABLE IF NOT EXISTS api_keys(
+  id UUID PRIMARY KEY,
+  email TEXT NOT NULL,
+  name TEXT NOT NULL,
+  key_hash TEXT NOT NULL UNIQUE,
+  key_hint TEXT NOT NULL,
+  scope TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
+  expires_at TIMESTAMPTZ,
+  last_used_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS api_keys_email_idx ON api_keys (email);
+CREATE INDEX IF NOT EXISTS api_keys_expires_at_idx ON api_keys (expires_at);
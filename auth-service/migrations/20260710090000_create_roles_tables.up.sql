@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS roles(
+  name TEXT PRIMARY KEY,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE TABLE IF NOT EXISTS role_permissions(
+  role TEXT NOT NULL REFERENCES roles (name) ON DELETE CASCADE,
+  permission TEXT NOT NULL,
+  PRIMARY KEY (role, permission)
+);
+CREATE TABLE IF NOT EXISTS user_roles(
+  email TEXT NOT NULL,
+  role TEXT NOT NULL REFERENCES roles (name) ON DELETE CASCADE,
+  PRIMARY KEY (email, role)
+);
+CREATE INDEX IF NOT EXISTS user_roles_role_idx ON user_roles (role);
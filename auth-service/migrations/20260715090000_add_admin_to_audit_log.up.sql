@@ -0,0 +1,2 @@
+-- Who made an admin request. Appending a nullable column leaves existing entries untouched.
+ALTER TABLE audit_log ADD COLUMN IF NOT EXISTS admin TEXT;
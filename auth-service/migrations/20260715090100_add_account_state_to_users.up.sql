@@ -0,0 +1,4 @@
+-- Disabled users cannot log in, and users who have to reset their password cannot log in
+-- until they did
+ALTER TABLE users ADD COLUMN IF NOT EXISTS disabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS password_reset_required BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,10 @@
+-- Accounts are active, disabled, pending verification or scheduled for deletion, replacing
+-- the disabled flag. Accounts scheduled for deletion are purged once their retention ends.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'disabled', 'pending_verification', 'scheduled_for_deletion'));
+ALTER TABLE users ADD COLUMN IF NOT EXISTS status_reason TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS status_changed_at TIMESTAMPTZ NOT NULL DEFAULT now();
+UPDATE users SET status = 'disabled' WHERE disabled;
+ALTER TABLE users DROP COLUMN IF EXISTS disabled;
+CREATE INDEX IF NOT EXISTS users_scheduled_for_deletion_idx ON users (status_changed_at)
+    WHERE status = 'scheduled_for_deletion';
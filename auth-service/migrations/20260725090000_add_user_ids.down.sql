@@ -0,0 +1,46 @@
+ALTER TABLE user_roles ADD COLUMN IF NOT EXISTS email TEXT;
+UPDATE user_roles SET email = users.email FROM users WHERE users.id = user_roles.user_id;
+DELETE FROM user_roles WHERE email IS NULL;
+ALTER TABLE user_roles DROP CONSTRAINT IF EXISTS user_roles_pkey;
+ALTER TABLE user_roles DROP COLUMN IF EXISTS user_id;
+ALTER TABLE user_roles ADD PRIMARY KEY (email, role);
+
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS email TEXT;
+UPDATE api_keys SET email = users.email FROM users WHERE users.id = api_keys.user_id;
+DELETE FROM api_keys WHERE email IS NULL;
+ALTER TABLE api_keys ALTER COLUMN email SET NOT NULL;
+ALTER TABLE api_keys DROP COLUMN IF EXISTS user_id;
+CREATE INDEX IF NOT EXISTS api_keys_email_idx ON api_keys (email);
+
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS email TEXT;
+UPDATE refresh_tokens SET email = users.email FROM users WHERE users.id = refresh_tokens.user_id;
+DELETE FROM refresh_tokens WHERE email IS NULL;
+ALTER TABLE refresh_tokens ALTER COLUMN email SET NOT NULL;
+ALTER TABLE refresh_tokens DROP COLUMN IF EXISTS user_id;
+CREATE INDEX IF NOT EXISTS refresh_tokens_client_id_email_idx ON refresh_tokens (client_id, email);
+
+ALTER TABLE authorization_codes ADD COLUMN IF NOT EXISTS email TEXT;
+UPDATE authorization_codes SET email = users.email
+    FROM users WHERE users.id = authorization_codes.user_id;
+DELETE FROM authorization_codes WHERE email IS NULL;
+ALTER TABLE authorization_codes ALTER COLUMN email SET NOT NULL;
+ALTER TABLE authorization_codes DROP COLUMN IF EXISTS user_id;
+
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS email TEXT;
+UPDATE sessions SET email = users.email FROM users WHERE users.id = sessions.user_id;
+DELETE FROM sessions WHERE email IS NULL;
+ALTER TABLE sessions ALTER COLUMN email SET NOT NULL;
+ALTER TABLE sessions DROP COLUMN IF EXISTS user_id;
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions (email);
+
+ALTER TABLE two_fa_codes ADD COLUMN IF NOT EXISTS email TEXT;
+UPDATE two_fa_codes SET email = users.email FROM users WHERE users.id = two_fa_codes.user_id;
+DELETE FROM two_fa_codes WHERE email IS NULL;
+ALTER TABLE two_fa_codes DROP CONSTRAINT IF EXISTS two_fa_codes_pkey;
+ALTER TABLE two_fa_codes DROP COLUMN IF EXISTS user_id;
+ALTER TABLE two_fa_codes ADD PRIMARY KEY (email);
+
+DROP INDEX IF EXISTS users_email_lower_idx;
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_pkey;
+ALTER TABLE users DROP COLUMN IF EXISTS id;
+ALTER TABLE users ADD PRIMARY KEY (email);
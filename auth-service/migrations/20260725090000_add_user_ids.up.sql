@@ -0,0 +1,52 @@
+-- Users are identified by a UUID, so their email can change. Emails stay unique, ignoring
+-- case, which fails here if two existing users only differ by the case of their email.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS id UUID NOT NULL DEFAULT gen_random_uuid();
+ALTER TABLE users ALTER COLUMN id DROP DEFAULT;
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_pkey;
+ALTER TABLE users ADD PRIMARY KEY (id);
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_idx ON users (lower(email));
+
+-- Everything kept by email is kept by user id instead. Rows of users who no longer exist
+-- are dropped, as nothing could use them anymore.
+ALTER TABLE two_fa_codes ADD COLUMN IF NOT EXISTS user_id UUID;
+UPDATE two_fa_codes SET user_id = users.id FROM users WHERE users.email = two_fa_codes.email;
+DELETE FROM two_fa_codes WHERE user_id IS NULL;
+ALTER TABLE two_fa_codes DROP CONSTRAINT IF EXISTS two_fa_codes_pkey;
+ALTER TABLE two_fa_codes DROP COLUMN IF EXISTS email;
+ALTER TABLE two_fa_codes ADD PRIMARY KEY (user_id);
+
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS user_id UUID;
+UPDATE sessions SET user_id = users.id FROM users WHERE users.email = sessions.email;
+DELETE FROM sessions WHERE user_id IS NULL;
+ALTER TABLE sessions ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE sessions DROP COLUMN IF EXISTS email;
+CREATE INDEX IF NOT EXISTS sessions_user_id_idx ON sessions (user_id);
+
+ALTER TABLE authorization_codes ADD COLUMN IF NOT EXISTS user_id UUID;
+UPDATE authorization_codes SET user_id = users.id
+    FROM users WHERE users.email = authorization_codes.email;
+DELETE FROM authorization_codes WHERE user_id IS NULL;
+ALTER TABLE authorization_codes ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE authorization_codes DROP COLUMN IF EXISTS email;
+
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS user_id UUID;
+UPDATE refresh_tokens SET user_id = users.id FROM users WHERE users.email = refresh_tokens.email;
+DELETE FROM refresh_tokens WHERE user_id IS NULL;
+ALTER TABLE refresh_tokens ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE refresh_tokens DROP COLUMN IF EXISTS email;
+CREATE INDEX IF NOT EXISTS refresh_tokens_client_id_user_id_idx
+    ON refresh_tokens (client_id, user_id);
+
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS user_id UUID;
+UPDATE api_keys SET user_id = users.id FROM users WHERE users.email = api_keys.email;
+DELETE FROM api_keys WHERE user_id IS NULL;
+ALTER TABLE api_keys ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE api_keys DROP COLUMN IF EXISTS email;
+CREATE INDEX IF NOT EXISTS api_keys_user_id_idx ON api_keys (user_id);
+
+ALTER TABLE user_roles ADD COLUMN IF NOT EXISTS user_id UUID;
+UPDATE user_roles SET user_id = users.id FROM users WHERE users.email = user_roles.email;
+DELETE FROM user_roles WHERE user_id IS NULL;
+ALTER TABLE user_roles DROP CONSTRAINT IF EXISTS user_roles_pkey;
+ALTER TABLE user_roles DROP COLUMN IF EXISTS email;
+ALTER TABLE user_roles ADD PRIMARY KEY (user_id, role);
@@ -0,0 +1,4 @@
+-- The tokens are gone, so the sessions cannot be kept
+DELETE FROM sessions;
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS token TEXT NOT NULL;
+ALTER TABLE sessions DROP COLUMN IF EXISTS client_id;
@@ -1,13 +1,109 @@
+#[cfg(any(
+    feature = "postgres",
+    feature = "redis",
+    feature = "sqlite",
+    feature = "smtp"
+))]
+use color_eyre::eyre::Context;
+use color_eyre::eyre::{bail, Result};
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+use secrecy::ExposeSecret;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::{domain::BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+#[cfg(feature = "smtp")]
+use crate::services::{
+    data_stores::smtp_email_client::SmtpEmailClient, health_checks::SmtpHealthCheck,
+};
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+use crate::settings::DatabaseKind;
+use crate::{
+    domain::{
+        ApiKeyStore, AuditLog, AuthorizationCodeStore, BannedTokenStore, DeviceCodeStore,
+        ExpiringStore, OAuthClientStore, RefreshTokenStore, RoleStore, SessionStore,
+    },
+    services::data_stores::{
+        hashmap_2fa_code_store::HashmapTwoFACodeStore, hashmap_api_key_store::HashmapApiKeyStore,
+        hashmap_authorization_code_store::HashmapAuthorizationCodeStore,
+        hashmap_device_code_store::HashmapDeviceCodeStore,
+        hashmap_oauth_client_store::HashmapOAuthClientStore,
+        hashmap_refresh_token_store::HashmapRefreshTokenStore,
+        hashmap_role_store::HashmapRoleStore, hashmap_session_store::HashmapSessionStore,
+        hashmap_user_store::HashmapUserStore, hashset_banned_token_store::HashsetBannedTokenStore,
+        metered_store::MeteredStore, mock_email_client::MockEmailClient,
+        vec_audit_log::VecAuditLog,
+    },
+    services::health_checks::HealthCheckType,
+    settings::{
+        AuditLogBackend, DeviceCodeStoreBackend, EmailClientBackend, OAuthStoreBackend,
+        RoleStoreBackend, Settings, TokenStoreBackend, UserStoreBackend,
+    },
+    spawn_expired_rows_purge,
+    utils::oidc::IdTokenKeys,
+    EmailClient, TwoFACodeStore, UserStore,
+};
+#[cfg(feature = "postgres")]
+use crate::{
+    get_postgres_pool,
+    services::data_stores::{
+        postgrep_user_store::PostgresUserStore, postgres_api_key_store::PostgresApiKeyStore,
+        postgres_audit_log::PostgresAuditLog,
+        postgres_authorization_code_store::PostgresAuthorizationCodeStore,
+        postgres_banned_token_store::PostgresBannedTokenStore,
+        postgres_oauth_client_store::PostgresOAuthClientStore,
+        postgres_refresh_token_store::PostgresRefreshTokenStore,
+        postgres_role_store::PostgresRoleStore, postgres_session_store::PostgresSessionStore,
+        postgres_two_fa_code_store::PostgresTwoFACodeStore,
+    },
+    services::health_checks::PostgresHealthCheck,
+    POSTGRES_MIGRATOR,
+};
+#[cfg(feature = "redis")]
+use crate::{
+    get_redis_client,
+    services::data_stores::{
+        redis_banned_token_store::RedisBannedTokenStore,
+        redis_device_code_store::RedisDeviceCodeStore,
+        redis_two_fa_code_store::RedisTwoFACodeStore,
+    },
+    services::health_checks::RedisHealthCheck,
+};
+#[cfg(feature = "sqlite")]
+use crate::{
+    get_sqlite_pool,
+    services::data_stores::{
+        sqlite_api_key_store::SqliteApiKeyStore, sqlite_banned_token_store::SqliteBannedTokenStore,
+        sqlite_session_store::SqliteSessionStore, sqlite_two_fa_code_store::SqliteTwoFACodeStore,
+        sqlite_user_store::SqliteUserStore,
+    },
+    services::health_checks::SqliteHealthCheck,
+    SQLITE_MIGRATOR,
+};
 
 // Using a type alias to improve readability!
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
 pub type EmailClientType = Arc<RwLock<dyn EmailClient + Send + Sync>>;
+pub type ExpiringStoreType = Arc<dyn ExpiringStore + Send + Sync>;
+pub type AuditLogType = Arc<RwLock<dyn AuditLog + Send + Sync>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore + Send + Sync>>;
+pub type OAuthClientStoreType = Arc<RwLock<dyn OAuthClientStore + Send + Sync>>;
+pub type AuthorizationCodeStoreType = Arc<RwLock<dyn AuthorizationCodeStore + Send + Sync>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type DeviceCodeStoreType = Arc<RwLock<dyn DeviceCodeStore + Send + Sync>>;
+pub type ApiKeyStoreType = Arc<RwLock<dyn ApiKeyStore + Send + Sync>>;
+pub type RoleStoreType = Arc<RwLock<dyn RoleStore + Send + Sync>>;
+
+// Everything the OAuth authorization server keeps. Device codes have a backend of their own,
+// the rest share one.
+#[derive(Clone)]
+pub struct OAuthStores {
+    pub clients: OAuthClientStoreType,
+    pub codes: AuthorizationCodeStoreType,
+    pub refresh_tokens: RefreshTokenStoreType,
+    pub device_codes: DeviceCodeStoreType,
+}
 
 #[derive(Clone)]
 pub struct AppState {
@@ -15,20 +111,376 @@ pub struct AppState {
     pub banned_tokens: BannedTokenStoreType,
     pub two_fa_codes: TwoFACodeStoreType,
     pub email_client: EmailClientType,
+    pub audit_log: AuditLogType,
+    pub sessions: SessionStoreType,
+    pub oauth: OAuthStores,
+    pub api_keys: ApiKeyStoreType,
+    pub roles: RoleStoreType,
+    pub id_token_keys: Arc<IdTokenKeys>,
+    pub settings: Arc<Settings>,
+    // One per external dependency, run by `/readyz`
+    pub health_checks: Arc<Vec<HealthCheckType>>,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_tokens: BannedTokenStoreType,
         two_fa_codes: TwoFACodeStoreType,
         email_client: EmailClientType,
+        audit_log: AuditLogType,
+        sessions: SessionStoreType,
+        oauth: OAuthStores,
+        api_keys: ApiKeyStoreType,
+        roles: RoleStoreType,
+        id_token_keys: IdTokenKeys,
+        settings: Arc<Settings>,
+        health_checks: Vec<HealthCheckType>,
     ) -> Self {
         Self {
             user_store,
             banned_tokens,
             two_fa_codes,
             email_client,
+            audit_log,
+            sessions,
+            oauth,
+            api_keys,
+            roles,
+            id_token_keys: Arc::new(id_token_keys),
+            settings,
+            health_checks: Arc::new(health_checks),
         }
     }
+
+    // Connect to the backends selected in `settings.stores` and build the stores on top of them.
+    // Only the services that are actually used get connected to (and migrated).
+    pub async fn from_settings(settings: Settings) -> Result<Self> {
+        let stores = &settings.stores;
+        let ttl = &settings.ttl;
+        // Stays empty when every store is kept in memory
+        #[allow(unused_mut)]
+        let mut health_checks: Vec<HealthCheckType> = Vec::new();
+
+        #[cfg(feature = "postgres")]
+        let pg_pool = match stores.uses_database(DatabaseKind::Postgres) {
+            true => {
+                let pool = get_postgres_pool(
+                    settings.database.url.expose_secret(),
+                    settings.database.max_connections,
+                )
+                .await
+                .wrap_err("failed to create Postgres connection pool")?;
+                POSTGRES_MIGRATOR
+                    .run(&pool)
+                    .await
+                    .wrap_err("failed to run Postgres migrations")?;
+                health_checks.push(Arc::new(PostgresHealthCheck::new(pool.clone())));
+                Some(pool)
+            }
+            false => None,
+        };
+        #[cfg(feature = "sqlite")]
+        let sqlite_pool = match stores.uses_database(DatabaseKind::Sqlite) {
+            true => {
+                let pool = get_sqlite_pool(
+                    settings.database.url.expose_secret(),
+                    settings.database.max_connections,
+                )
+                .await
+                .wrap_err("failed to create SQLite connection pool")?;
+                SQLITE_MIGRATOR
+                    .run(&pool)
+                    .await
+                    .wrap_err("failed to run SQLite migrations")?;
+                health_checks.push(Arc::new(SqliteHealthCheck::new(pool.clone())));
+                Some(pool)
+            }
+            false => None,
+        };
+        #[cfg(feature = "redis")]
+        let redis_conn = match stores.uses_redis() {
+            true => {
+                let client = get_redis_client(settings.redis.host_name.clone())
+                    .wrap_err("failed to get Redis client")?;
+                let conn = client
+                    .get_connection()
+                    .wrap_err("failed to get Redis connection")?;
+                health_checks.push(Arc::new(RedisHealthCheck::new(
+                    client,
+                    settings.health.timeout(),
+                )));
+                Some(Arc::new(RwLock::new(conn)))
+            }
+            false => None,
+        };
+        // `Settings::validate` guarantees the pools above exist for every store that needs one
+        #[cfg(feature = "postgres")]
+        let pg = || pg_pool.clone().expect("Postgres pool is configured");
+        #[cfg(feature = "sqlite")]
+        let sqlite = || sqlite_pool.clone().expect("SQLite pool is configured");
+        #[cfg(feature = "redis")]
+        let redis = || redis_conn.clone().expect("Redis connection is configured");
+
+        let user_store: UserStoreType = match stores.users {
+            #[cfg(feature = "postgres")]
+            UserStoreBackend::Postgres => {
+                metered(PostgresUserStore::new(pg()), "users", "postgres")
+            }
+            #[cfg(feature = "sqlite")]
+            UserStoreBackend::Sqlite => metered(SqliteUserStore::new(sqlite()), "users", "sqlite"),
+            UserStoreBackend::Memory => metered(HashmapUserStore::new(), "users", "memory"),
+            #[allow(unreachable_patterns)]
+            backend => bail!("{:?} user store was not compiled in", backend),
+        };
+
+        // Redis expires keys on its own; SQL rows have to be purged.
+        let (banned_tokens, banned_tokens_purge): (
+            BannedTokenStoreType,
+            Option<ExpiringStoreType>,
+        ) = match stores.banned_tokens {
+            #[cfg(feature = "redis")]
+            TokenStoreBackend::Redis => (
+                metered(
+                    RedisBannedTokenStore::new(redis(), ttl.token()),
+                    "banned_tokens",
+                    "redis",
+                ),
+                None,
+            ),
+            #[cfg(feature = "postgres")]
+            TokenStoreBackend::Postgres => {
+                let store = metered(
+                    PostgresBannedTokenStore::new(pg(), ttl.token()),
+                    "banned_tokens",
+                    "postgres",
+                );
+                (store.clone(), Some(store))
+            }
+            #[cfg(feature = "sqlite")]
+            TokenStoreBackend::Sqlite => {
+                let store = metered(
+                    SqliteBannedTokenStore::new(sqlite(), ttl.token()),
+                    "banned_tokens",
+                    "sqlite",
+                );
+                (store.clone(), Some(store))
+            }
+            TokenStoreBackend::Memory => (
+                metered(
+                    HashsetBannedTokenStore::default(),
+                    "banned_tokens",
+                    "memory",
+                ),
+                None,
+            ),
+            #[allow(unreachable_patterns)]
+            backend => bail!("{:?} banned token store was not compiled in", backend),
+        };
+        let (two_fa_codes, two_fa_codes_purge): (TwoFACodeStoreType, Option<ExpiringStoreType>) =
+            match stores.two_fa_codes {
+                #[cfg(feature = "redis")]
+                TokenStoreBackend::Redis => (
+                    metered(
+                        RedisTwoFACodeStore::new(redis(), ttl.two_fa_code()),
+                        "two_fa_codes",
+                        "redis",
+                    ),
+                    None,
+                ),
+                #[cfg(feature = "postgres")]
+                TokenStoreBackend::Postgres => {
+                    let store = metered(
+                        PostgresTwoFACodeStore::new(pg(), ttl.two_fa_code()),
+                        "two_fa_codes",
+                        "postgres",
+                    );
+                    (store.clone(), Some(store))
+                }
+                #[cfg(feature = "sqlite")]
+                TokenStoreBackend::Sqlite => {
+                    let store = metered(
+                        SqliteTwoFACodeStore::new(sqlite(), ttl.two_fa_code()),
+                        "two_fa_codes",
+                        "sqlite",
+                    );
+                    (store.clone(), Some(store))
+                }
+                TokenStoreBackend::Memory => (
+                    metered(HashmapTwoFACodeStore::default(), "two_fa_codes", "memory"),
+                    None,
+                ),
+                #[allow(unreachable_patterns)]
+                backend => bail!("{:?} 2FA code store was not compiled in", backend),
+            };
+        let (sessions, sessions_purge): (SessionStoreType, Option<ExpiringStoreType>) =
+            match stores.sessions {
+                #[cfg(feature = "postgres")]
+                UserStoreBackend::Postgres => {
+                    let store = metered(PostgresSessionStore::new(pg()), "sessions", "postgres");
+                    (store.clone(), Some(store))
+                }
+                #[cfg(feature = "sqlite")]
+                UserStoreBackend::Sqlite => {
+                    let store = metered(SqliteSessionStore::new(sqlite()), "sessions", "sqlite");
+                    (store.clone(), Some(store))
+                }
+                UserStoreBackend::Memory => (
+                    metered(HashmapSessionStore::default(), "sessions", "memory"),
+                    None,
+                ),
+                #[allow(unreachable_patterns)]
+                backend => bail!("{:?} session store was not compiled in", backend),
+            };
+        let (api_keys, api_keys_purge): (ApiKeyStoreType, Option<ExpiringStoreType>) =
+            match stores.api_keys {
+                #[cfg(feature = "postgres")]
+                UserStoreBackend::Postgres => {
+                    let store = metered(PostgresApiKeyStore::new(pg()), "api_keys", "postgres");
+                    (store.clone(), Some(store))
+                }
+                #[cfg(feature = "sqlite")]
+                UserStoreBackend::Sqlite => {
+                    let store = metered(SqliteApiKeyStore::new(sqlite()), "api_keys", "sqlite");
+                    (store.clone(), Some(store))
+                }
+                UserStoreBackend::Memory => (
+                    metered(HashmapApiKeyStore::default(), "api_keys", "memory"),
+                    None,
+                ),
+                #[allow(unreachable_patterns)]
+                backend => bail!("{:?} API key store was not compiled in", backend),
+            };
+        let roles: RoleStoreType = match stores.roles {
+            #[cfg(feature = "postgres")]
+            RoleStoreBackend::Postgres => {
+                metered(PostgresRoleStore::new(pg()), "roles", "postgres")
+            }
+            RoleStoreBackend::Memory => metered(HashmapRoleStore::default(), "roles", "memory"),
+            #[allow(unreachable_patterns)]
+            backend => bail!("{:?} role store was not compiled in", backend),
+        };
+        // Redis expires device codes on its own
+        let device_codes: DeviceCodeStoreType = match stores.device_codes {
+            #[cfg(feature = "redis")]
+            DeviceCodeStoreBackend::Redis => {
+                metered(RedisDeviceCodeStore::new(redis()), "device_codes", "redis")
+            }
+            DeviceCodeStoreBackend::Memory => {
+                metered(HashmapDeviceCodeStore::default(), "device_codes", "memory")
+            }
+            #[allow(unreachable_patterns)]
+            backend => bail!("{:?} device code store was not compiled in", backend),
+        };
+        let (oauth, oauth_purge): (OAuthStores, Vec<ExpiringStoreType>) = match stores.oauth {
+            #[cfg(feature = "postgres")]
+            OAuthStoreBackend::Postgres => {
+                let codes = metered(
+                    PostgresAuthorizationCodeStore::new(pg()),
+                    "authorization_codes",
+                    "postgres",
+                );
+                let refresh_tokens = metered(
+                    PostgresRefreshTokenStore::new(pg()),
+                    "refresh_tokens",
+                    "postgres",
+                );
+                (
+                    OAuthStores {
+                        clients: metered(
+                            PostgresOAuthClientStore::new(pg()),
+                            "oauth_clients",
+                            "postgres",
+                        ),
+                        codes: codes.clone(),
+                        refresh_tokens: refresh_tokens.clone(),
+                        device_codes,
+                    },
+                    vec![codes, refresh_tokens],
+                )
+            }
+            OAuthStoreBackend::Memory => (
+                OAuthStores {
+                    clients: metered(
+                        HashmapOAuthClientStore::default(),
+                        "oauth_clients",
+                        "memory",
+                    ),
+                    codes: metered(
+                        HashmapAuthorizationCodeStore::default(),
+                        "authorization_codes",
+                        "memory",
+                    ),
+                    refresh_tokens: metered(
+                        HashmapRefreshTokenStore::default(),
+                        "refresh_tokens",
+                        "memory",
+                    ),
+                    device_codes,
+                },
+                Vec::new(),
+            ),
+            #[allow(unreachable_patterns)]
+            backend => bail!("{:?} OAuth stores were not compiled in", backend),
+        };
+        let expiring: Vec<ExpiringStoreType> = [
+            banned_tokens_purge,
+            two_fa_codes_purge,
+            sessions_purge,
+            api_keys_purge,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(oauth_purge)
+        .collect();
+        if !expiring.is_empty() {
+            spawn_expired_rows_purge(expiring, ttl.purge_interval());
+        }
+
+        let email_client: EmailClientType = match stores.email_client {
+            EmailClientBackend::Mock => Arc::new(RwLock::new(MockEmailClient {})),
+            #[cfg(feature = "smtp")]
+            EmailClientBackend::Smtp => {
+                let client = SmtpEmailClient::new(&settings.smtp)
+                    .wrap_err("failed to create SMTP client")?;
+                health_checks.push(Arc::new(SmtpHealthCheck::new(client.transport())));
+                Arc::new(RwLock::new(client))
+            }
+            #[allow(unreachable_patterns)]
+            backend => bail!("{:?} email client was not compiled in", backend),
+        };
+
+        let audit_log: AuditLogType = match stores.audit_log {
+            #[cfg(feature = "postgres")]
+            AuditLogBackend::Postgres => {
+                metered(PostgresAuditLog::new(pg()), "audit_log", "postgres")
+            }
+            AuditLogBackend::Memory => metered(VecAuditLog::default(), "audit_log", "memory"),
+            #[allow(unreachable_patterns)]
+            backend => bail!("{:?} audit log was not compiled in", backend),
+        };
+
+        let id_token_keys = IdTokenKeys::from_settings(&settings.oidc)?;
+
+        Ok(Self::new(
+            user_store,
+            banned_tokens,
+            two_fa_codes,
+            email_client,
+            audit_log,
+            sessions,
+            oauth,
+            api_keys,
+            roles,
+            id_token_keys,
+            Arc::new(settings),
+            health_checks,
+        ))
+    }
+}
+
+// Record the latency of every operation on `store` under the given store and backend labels
+fn metered<S>(store: S, name: &'static str, backend: &'static str) -> Arc<RwLock<MeteredStore<S>>> {
+    Arc::new(RwLock::new(MeteredStore::new(store, name, backend)))
 }
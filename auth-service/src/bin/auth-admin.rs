@@ -0,0 +1,10 @@
+use auth_service::cli::{self, Cli};
+use clap::Parser;
+use color_eyre::eyre::Result;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    let cli = Cli::parse();
+    cli::run(cli.command).await
+}
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Report;
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::UserId;
+
+// Every key starts with this, so keys are told apart from JWTs and easy to spot in leaks
+pub const API_KEY_PREFIX: &str = "ak_";
+
+// A long-lived credential a user created for scripts, usable wherever their token is checked
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub user_id: UserId,
+    // Chosen by the user, to tell their keys apart
+    pub name: String,
+    // The digest of the key, see `api_key_hash`. The key itself is only shown once.
+    pub key_hash: String,
+    // The first characters of the key, so users can recognise it in their scripts
+    pub key_hint: String,
+    pub scope: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+// Keys are generated with plenty of entropy, so a plain digest is enough to keep a leaked
+// table from being used to authenticate
+pub fn api_key_hash(key: &SecretString) -> String {
+    format!("{:x}", Sha256::digest(key.expose_secret().as_bytes()))
+}
+
+// Expired keys are never returned, whether or not the backend has deleted them yet
+#[async_trait::async_trait]
+pub trait ApiKeyStore {
+    async fn add_api_key(&mut self, key: ApiKey) -> Result<(), ApiKeyStoreError>;
+    async fn get_api_key(&self, key_hash: &str) -> Result<ApiKey, ApiKeyStoreError>;
+    // Newest first
+    async fn get_api_keys(&self, user_id: UserId) -> Result<Vec<ApiKey>, ApiKeyStoreError>;
+    async fn touch_api_key(
+        &mut self,
+        id: Uuid,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), ApiKeyStoreError>;
+    // Only removes the key if it belongs to the user, so users cannot revoke others' keys
+    async fn remove_api_key(&mut self, user_id: UserId, id: Uuid) -> Result<(), ApiKeyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum ApiKeyStoreError {
+    #[error("API key not found")]
+    ApiKeyNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for ApiKeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ApiKeyNotFound, Self::ApiKeyNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
@@ -0,0 +1,504 @@
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use color_eyre::eyre::Report;
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+use thiserror::Error;
+
+// The `prev_hash` of the first entry in the chain
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+// How many entries `AuditLog::verify` loads at a time
+const VERIFY_BATCH_SIZE: i64 = 1_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditEventKind {
+    Signup,
+    Login,
+    Verify2FA,
+    Logout,
+    VerifyToken,
+    RevokeSession,
+    LogoutAll,
+    AdminLogoutAll,
+    Introspect,
+    RevokeToken,
+    Authorize,
+    OAuthToken,
+    AdminRegisterClient,
+    AdminDeleteClient,
+    EndSession,
+    DeviceAuthorization,
+    DeviceApproval,
+    CreateApiKey,
+    RevokeApiKey,
+    AdminPutRole,
+    AdminDeleteRole,
+    AdminAssignRole,
+    AdminUnassignRole,
+    AdminDisableUser,
+    AdminEnableUser,
+    AdminForcePasswordReset,
+    AdminReset2FA,
+    AdminDisable2FA,
+    AdminRevokeSessions,
+    AdminDeleteUser,
+    ResetPassword,
+    AdminCreateUser,
+    AdminSetPassword,
+    AdminEnable2FA,
+    AdminSetUserStatus,
+    PurgeUser,
+    AdminChangeEmail,
+}
+
+impl AuditEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Signup => "signup",
+            Self::Login => "login",
+            Self::Verify2FA => "verify_2fa",
+            Self::Logout => "logout",
+            Self::VerifyToken => "verify_token",
+            Self::RevokeSession => "revoke_session",
+            Self::LogoutAll => "logout_all",
+            Self::AdminLogoutAll => "admin_logout_all",
+            Self::Introspect => "introspect",
+            Self::RevokeToken => "revoke_token",
+            Self::Authorize => "authorize",
+            Self::OAuthToken => "oauth_token",
+            Self::AdminRegisterClient => "admin_register_client",
+            Self::AdminDeleteClient => "admin_delete_client",
+            Self::EndSession => "end_session",
+            Self::DeviceAuthorization => "device_authorization",
+            Self::DeviceApproval => "device_approval",
+            Self::CreateApiKey => "create_api_key",
+            Self::RevokeApiKey => "revoke_api_key",
+            Self::AdminPutRole => "admin_put_role",
+            Self::AdminDeleteRole => "admin_delete_role",
+            Self::AdminAssignRole => "admin_assign_role",
+            Self::AdminUnassignRole => "admin_unassign_role",
+            Self::AdminDisableUser => "admin_disable_user",
+            Self::AdminEnableUser => "admin_enable_user",
+            Self::AdminForcePasswordReset => "admin_force_password_reset",
+            Self::AdminReset2FA => "admin_reset_2fa",
+            Self::AdminDisable2FA => "admin_disable_2fa",
+            Self::AdminRevokeSessions => "admin_revoke_sessions",
+            Self::AdminDeleteUser => "admin_delete_user",
+            Self::ResetPassword => "reset_password",
+            Self::AdminCreateUser => "admin_create_user",
+            Self::AdminSetPassword => "admin_set_password",
+            Self::AdminEnable2FA => "admin_enable_2fa",
+            Self::AdminSetUserStatus => "admin_set_user_status",
+            Self::PurgeUser => "purge_user",
+            Self::AdminChangeEmail => "admin_change_email",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        [
+            Self::Signup,
+            Self::Login,
+            Self::Verify2FA,
+            Self::Logout,
+            Self::VerifyToken,
+            Self::RevokeSession,
+            Self::LogoutAll,
+            Self::AdminLogoutAll,
+            Self::Introspect,
+            Self::RevokeToken,
+            Self::Authorize,
+            Self::OAuthToken,
+            Self::AdminRegisterClient,
+            Self::AdminDeleteClient,
+            Self::EndSession,
+            Self::DeviceAuthorization,
+            Self::DeviceApproval,
+            Self::CreateApiKey,
+            Self::RevokeApiKey,
+            Self::AdminPutRole,
+            Self::AdminDeleteRole,
+            Self::AdminAssignRole,
+            Self::AdminUnassignRole,
+            Self::AdminDisableUser,
+            Self::AdminEnableUser,
+            Self::AdminForcePasswordReset,
+            Self::AdminReset2FA,
+            Self::AdminDisable2FA,
+            Self::AdminRevokeSessions,
+            Self::AdminDeleteUser,
+            Self::ResetPassword,
+            Self::AdminCreateUser,
+            Self::AdminSetPassword,
+            Self::AdminEnable2FA,
+            Self::AdminSetUserStatus,
+            Self::PurgeUser,
+            Self::AdminChangeEmail,
+        ]
+        .into_iter()
+        .find(|k| k.as_str() == kind)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+impl AuditOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+        }
+    }
+
+    pub fn parse(outcome: &str) -> Option<Self> {
+        match outcome {
+            "success" => Some(Self::Success),
+            "failure" => Some(Self::Failure),
+            _ => None,
+        }
+    }
+}
+
+// Who did what, from where, and how it went
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditEvent {
+    pub kind: AuditEventKind,
+    // The email the request was made for, as sent by the client
+    pub actor: Option<String>,
+    // Who made an admin request: the id of an admin user, `admin-token`, or `cli` for
+    // `auth-admin`
+    pub admin: Option<String>,
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub outcome: AuditOutcome,
+    // Why the request failed, or what is still missing (e.g. `2fa_required`)
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub recorded_at: DateTime<Utc>,
+    pub event: AuditEvent,
+    // Hash of the entry before this one, or `GENESIS_HASH`
+    pub prev_hash: String,
+    // Hash over `prev_hash` and every other field, see `chain_hash`
+    pub hash: String,
+}
+
+impl AuditEntry {
+    // Chain `event` onto the entry whose hash is `prev_hash`. The timestamp is truncated to
+    // microseconds, the precision databases store it with, so the hash can be recomputed.
+    pub fn chain(id: i64, event: AuditEvent, prev_hash: String) -> Self {
+        let recorded_at = Utc::now().trunc_subsecs(6);
+        let hash = chain_hash(&prev_hash, &recorded_at, &event);
+        Self {
+            id,
+            recorded_at,
+            event,
+            prev_hash,
+            hash,
+        }
+    }
+}
+
+// SHA-256 over a JSON array of the fields, so no two different entries share an input
+pub fn chain_hash(prev_hash: &str, recorded_at: &DateTime<Utc>, event: &AuditEvent) -> String {
+    let mut fields = serde_json::json!([
+        prev_hash,
+        recorded_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+        event.kind.as_str(),
+        event.actor,
+        event.ip.map(|ip| ip.to_string()),
+        event.user_agent,
+        event.outcome.as_str(),
+        event.reason,
+    ]);
+    // Only events of admin requests have the field, so entries from before it was added
+    // still hash the same
+    if let (Some(admin), Some(fields)) = (&event.admin, fields.as_array_mut()) {
+        fields.push(admin.as_str().into());
+    }
+    format!("{:x}", Sha256::digest(fields.to_string().as_bytes()))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChainVerification {
+    // `head_hash` is the hash of the latest entry. Keeping a copy of it elsewhere also
+    // catches entries deleted from the end of the log.
+    Intact {
+        entries: u64,
+        head_hash: String,
+    },
+    Broken {
+        entry_id: i64,
+        problem: ChainProblem,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum ChainProblem {
+    #[error("entry does not match its hash")]
+    Modified,
+    #[error("entry does not follow the one before it")]
+    Unlinked,
+}
+
+// Checks entries one at a time, in the order they were appended
+pub struct ChainVerifier {
+    expected_prev_hash: String,
+    entries: u64,
+}
+
+impl Default for ChainVerifier {
+    fn default() -> Self {
+        Self {
+            expected_prev_hash: GENESIS_HASH.to_owned(),
+            entries: 0,
+        }
+    }
+}
+
+impl ChainVerifier {
+    pub fn check(&mut self, entry: &AuditEntry) -> Result<(), ChainProblem> {
+        if entry.prev_hash != self.expected_prev_hash {
+            return Err(ChainProblem::Unlinked);
+        }
+        if chain_hash(&entry.prev_hash, &entry.recorded_at, &entry.event) != entry.hash {
+            return Err(ChainProblem::Modified);
+        }
+        self.expected_prev_hash = entry.hash.clone();
+        self.entries += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> ChainVerification {
+        ChainVerification::Intact {
+            entries: self.entries,
+            head_hash: self.expected_prev_hash,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+pub trait AuditLog {
+    async fn append(&mut self, event: AuditEvent) -> Result<(), AuditLogError>;
+    // Up to `limit` entries with an id above `after_id`, oldest first
+    async fn entries(&self, after_id: i64, limit: i64) -> Result<Vec<AuditEntry>, AuditLogError>;
+
+    // Walk the whole chain and report the first entry that was modified, or that no longer
+    // follows the one before it because entries were deleted, inserted or reordered
+    async fn verify(&self) -> Result<ChainVerification, AuditLogError> {
+        let mut verifier = ChainVerifier::default();
+        let mut after_id = 0;
+        loop {
+            let batch = match self.entries(after_id, VERIFY_BATCH_SIZE).await {
+                Ok(batch) => batch,
+                Err(AuditLogError::CorruptEntry(entry_id)) => {
+                    return Ok(ChainVerification::Broken {
+                        entry_id,
+                        problem: ChainProblem::Modified,
+                    })
+                }
+                Err(e) => return Err(e),
+            };
+            for entry in &batch {
+                if let Err(problem) = verifier.check(entry) {
+                    return Ok(ChainVerification::Broken {
+                        entry_id: entry.id,
+                        problem,
+                    });
+                }
+            }
+            match batch.last() {
+                Some(last) if batch.len() as i64 == VERIFY_BATCH_SIZE => after_id = last.id,
+                _ => return Ok(verifier.finish()),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AuditLogError {
+    // A stored entry could not even be read back, e.g. an unknown event kind
+    #[error("Audit entry {0} is corrupt")]
+    CorruptEntry(i64),
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: AuditEventKind, outcome: AuditOutcome) -> AuditEvent {
+        AuditEvent {
+            kind,
+            actor: Some("user@example.com".to_owned()),
+            admin: None,
+            ip: Some("127.0.0.1".parse().unwrap()),
+            user_agent: Some("curl/8.0".to_owned()),
+            outcome,
+            reason: None,
+        }
+    }
+
+    fn chain_of(events: Vec<AuditEvent>) -> Vec<AuditEntry> {
+        let mut entries: Vec<AuditEntry> = Vec::new();
+        for (i, event) in events.into_iter().enumerate() {
+            let prev_hash = entries
+                .last()
+                .map(|e| e.hash.clone())
+                .unwrap_or_else(|| GENESIS_HASH.to_owned());
+            entries.push(AuditEntry::chain(i as i64 + 1, event, prev_hash));
+        }
+        entries
+    }
+
+    fn verify(entries: &[AuditEntry]) -> ChainVerification {
+        let mut verifier = ChainVerifier::default();
+        for entry in entries {
+            if let Err(problem) = verifier.check(entry) {
+                return ChainVerification::Broken {
+                    entry_id: entry.id,
+                    problem,
+                };
+            }
+        }
+        verifier.finish()
+    }
+
+    fn sample_chain() -> Vec<AuditEntry> {
+        chain_of(vec![
+            event(AuditEventKind::Signup, AuditOutcome::Success),
+            event(AuditEventKind::Login, AuditOutcome::Failure),
+            event(AuditEventKind::Login, AuditOutcome::Success),
+        ])
+    }
+
+    #[test]
+    fn should_accept_untouched_chain() {
+        let entries = sample_chain();
+
+        assert_eq!(
+            verify(&entries),
+            ChainVerification::Intact {
+                entries: 3,
+                head_hash: entries[2].hash.clone()
+            }
+        );
+    }
+
+    #[test]
+    fn should_detect_modified_entry() {
+        let mut entries = sample_chain();
+        entries[1].event.outcome = AuditOutcome::Success;
+
+        assert_eq!(
+            verify(&entries),
+            ChainVerification::Broken {
+                entry_id: 2,
+                problem: ChainProblem::Modified
+            }
+        );
+    }
+
+    #[test]
+    fn should_detect_deleted_entry() {
+        let mut entries = sample_chain();
+        entries.remove(1);
+
+        assert_eq!(
+            verify(&entries),
+            ChainVerification::Broken {
+                entry_id: 3,
+                problem: ChainProblem::Unlinked
+            }
+        );
+    }
+
+    #[test]
+    fn should_detect_rehashed_entry() {
+        // Recomputing the hash of a modified entry breaks the link to the next one
+        let mut entries = sample_chain();
+        entries[1].event.reason = Some("covered up".to_owned());
+        entries[1].hash = chain_hash(
+            &entries[1].prev_hash,
+            &entries[1].recorded_at,
+            &entries[1].event,
+        );
+
+        assert_eq!(
+            verify(&entries),
+            ChainVerification::Broken {
+                entry_id: 3,
+                problem: ChainProblem::Unlinked
+            }
+        );
+    }
+
+    #[test]
+    fn should_hash_admin_only_when_present() {
+        let recorded_at = Utc::now();
+        let mut event = event(AuditEventKind::AdminDeleteUser, AuditOutcome::Success);
+        let without_admin = chain_hash(GENESIS_HASH, &recorded_at, &event);
+
+        event.admin = Some("admin@example.com".to_owned());
+        let with_admin = chain_hash(GENESIS_HASH, &recorded_at, &event);
+        event.admin = Some("other@example.com".to_owned());
+
+        assert_ne!(with_admin, without_admin);
+        assert_ne!(chain_hash(GENESIS_HASH, &recorded_at, &event), with_admin);
+    }
+
+    #[test]
+    fn should_round_trip_kinds_and_outcomes() {
+        for kind in [
+            AuditEventKind::Signup,
+            AuditEventKind::Login,
+            AuditEventKind::Verify2FA,
+            AuditEventKind::Logout,
+            AuditEventKind::VerifyToken,
+            AuditEventKind::RevokeSession,
+            AuditEventKind::LogoutAll,
+            AuditEventKind::AdminLogoutAll,
+            AuditEventKind::Introspect,
+            AuditEventKind::RevokeToken,
+            AuditEventKind::Authorize,
+            AuditEventKind::OAuthToken,
+            AuditEventKind::AdminRegisterClient,
+            AuditEventKind::AdminDeleteClient,
+            AuditEventKind::EndSession,
+            AuditEventKind::DeviceAuthorization,
+            AuditEventKind::DeviceApproval,
+            AuditEventKind::CreateApiKey,
+            AuditEventKind::RevokeApiKey,
+            AuditEventKind::AdminPutRole,
+            AuditEventKind::AdminDeleteRole,
+            AuditEventKind::AdminAssignRole,
+            AuditEventKind::AdminUnassignRole,
+            AuditEventKind::AdminDisableUser,
+            AuditEventKind::AdminEnableUser,
+            AuditEventKind::AdminForcePasswordReset,
+            AuditEventKind::AdminReset2FA,
+            AuditEventKind::AdminDisable2FA,
+            AuditEventKind::AdminRevokeSessions,
+            AuditEventKind::AdminDeleteUser,
+            AuditEventKind::ResetPassword,
+            AuditEventKind::AdminCreateUser,
+            AuditEventKind::AdminSetPassword,
+            AuditEventKind::AdminEnable2FA,
+            AuditEventKind::AdminSetUserStatus,
+            AuditEventKind::PurgeUser,
+            AuditEventKind::AdminChangeEmail,
+        ] {
+            assert_eq!(AuditEventKind::parse(kind.as_str()), Some(kind));
+        }
+        for outcome in [AuditOutcome::Success, AuditOutcome::Failure] {
+            assert_eq!(AuditOutcome::parse(outcome.as_str()), Some(outcome));
+        }
+        assert_eq!(AuditEventKind::parse("sudo"), None);
+    }
+}
@@ -1,20 +1,43 @@
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::{eyre, Context, Report, Result};
 use rand::Rng;
 use secrecy::{ExposeSecret, SecretString};
 use thiserror::Error;
 use uuid::Uuid;
 
-use super::{Email, User};
+use super::{Email, User, UserId};
 
 #[async_trait::async_trait]
 pub trait UserStore {
+    // Fails with `UserAlreadyExists` if another user has the email, whatever its case
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    // Emails are matched ignoring case
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
+    async fn get_user_by_id(&self, id: UserId) -> Result<User, UserStoreError>;
     async fn validate_user(
         &self,
         email: &Email,
         raw_password: &SecretString,
     ) -> Result<(), UserStoreError>;
+    // Returns the new generation
+    async fn bump_token_generation(&mut self, id: UserId) -> Result<i64, UserStoreError>;
+    // Up to `limit` users whose email contains `query`, ignoring case, by email
+    async fn search_users(
+        &self,
+        query: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<User>, UserStoreError>;
+    // Save the email, password, 2FA setting and account state of an existing user, found by
+    // id. Fails with `UserAlreadyExists` if another user has the email. The token generation
+    // is left alone, it only changes through `bump_token_generation`.
+    async fn update_user(&mut self, user: &User) -> Result<(), UserStoreError>;
+    async fn delete_user(&mut self, id: UserId) -> Result<(), UserStoreError>;
+    // Users scheduled for deletion before `before`, oldest first
+    async fn users_scheduled_for_deletion(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<UserId>, UserStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -57,14 +80,14 @@ pub enum BannedTokenStoreError {
 pub trait TwoFACodeStore {
     async fn add_code(
         &mut self,
-        email: Email,
+        user_id: UserId,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    async fn remove_code(&mut self, user_id: UserId) -> Result<(), TwoFACodeStoreError>;
     async fn get_code(
         &self,
-        email: &Email,
+        user_id: UserId,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
 }
 
@@ -86,6 +109,13 @@ impl PartialEq for TwoFACodeStoreError {
     }
 }
 
+// Implemented by stores whose backend does not expire entries on its own,
+// so expired entries have to be deleted periodically.
+#[async_trait::async_trait]
+pub trait ExpiringStore {
+    async fn purge_expired(&self) -> Result<u64>;
+}
+
 #[derive(Debug, Clone)]
 pub struct LoginAttemptId(SecretString);
 
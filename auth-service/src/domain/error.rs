@@ -15,4 +15,88 @@ pub enum AuthAPIError {
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Invalid client")]
+    InvalidClient,
+    #[error("Unauthorized client")]
+    UnauthorizedClient,
+    #[error("Client not found")]
+    ClientNotFound,
+    #[error("Insufficient scope")]
+    InsufficientScope,
+    #[error("User code not found")]
+    UserCodeNotFound,
+    #[error("API key not found")]
+    ApiKeyNotFound,
+    #[error("Role not found")]
+    RoleNotFound,
+    // The caller is authenticated, but may not do this
+    #[error("Forbidden")]
+    Forbidden,
+    #[error("Account disabled")]
+    AccountDisabled,
+    #[error("Account pending verification")]
+    AccountPendingVerification,
+    #[error("Account scheduled for deletion")]
+    AccountScheduledForDeletion,
+    #[error("Password reset required")]
+    PasswordResetRequired,
+}
+
+// RFC 6749 errors of the OAuth endpoints. `/token` answers with them in the body,
+// `/authorize` in the query of the redirect back to the client.
+#[derive(Debug, Error)]
+pub enum OAuthError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(&'static str),
+    #[error("Invalid client")]
+    InvalidClient,
+    #[error("Invalid grant")]
+    InvalidGrant,
+    #[error("Invalid scope")]
+    InvalidScope,
+    #[error("Unauthorized client")]
+    UnauthorizedClient,
+    #[error("Unsupported grant type")]
+    UnsupportedGrantType,
+    #[error("Unsupported response type")]
+    UnsupportedResponseType,
+    #[error("Authorization pending")]
+    AuthorizationPending,
+    #[error("Slow down")]
+    SlowDown,
+    #[error("Access denied")]
+    AccessDenied,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl OAuthError {
+    // The `error` code RFC 6749 (or RFC 8628 section 3.5, for devices) defines for this error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::InvalidClient => "invalid_client",
+            Self::InvalidGrant => "invalid_grant",
+            Self::InvalidScope => "invalid_scope",
+            Self::UnauthorizedClient => "unauthorized_client",
+            Self::UnsupportedGrantType => "unsupported_grant_type",
+            Self::UnsupportedResponseType => "unsupported_response_type",
+            Self::AuthorizationPending => "authorization_pending",
+            Self::SlowDown => "slow_down",
+            Self::AccessDenied => "access_denied",
+            Self::UnexpectedError(_) => "server_error",
+        }
+    }
+
+    // Sent as `error_description`, to help client developers
+    pub fn description(&self) -> Option<&'static str> {
+        match self {
+            Self::InvalidRequest(description) => Some(description),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,40 @@
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+// A dependency the service needs to reach before it can serve traffic
+#[async_trait::async_trait]
+pub trait HealthCheck {
+    // Name of the dependency in the readiness report, e.g. "postgres"
+    fn name(&self) -> &'static str;
+    async fn check(&self) -> Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyState {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyStatus {
+    pub status: DependencyState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DependencyStatus {
+    pub fn up() -> Self {
+        Self {
+            status: DependencyState::Up,
+            error: None,
+        }
+    }
+
+    pub fn down(error: impl Into<String>) -> Self {
+        Self {
+            status: DependencyState::Down,
+            error: Some(error.into()),
+        }
+    }
+}
@@ -1,14 +1,26 @@
+pub mod api_key;
+pub mod audit;
 pub mod data_stores;
 pub mod email;
 pub mod email_client;
 pub mod error;
+pub mod health;
+pub mod oauth;
 pub mod password;
+pub mod role;
+pub mod session;
 pub mod user;
 
 // re-export items from sub-modules
+pub use api_key::*;
+pub use audit::*;
 pub use data_stores::*;
 pub use email::*;
 pub use email_client::*;
 pub use error::*;
+pub use health::*;
+pub use oauth::*;
 pub use password::*;
+pub use role::*;
+pub use session::*;
 pub use user::*;
@@ -0,0 +1,368 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Report;
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::UserId;
+
+// An app that logs users in through `/authorize`, or a backend service acting on its own.
+// Public clients have no secret, so every authorization code has to be redeemed with the
+// PKCE verifier it was issued for. Confidential clients authenticate with a secret, and
+// may also get tokens for themselves with the client credentials grant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OAuthClient {
+    pub id: String,
+    pub name: String,
+    // Compared exactly, without any normalization
+    pub redirect_uris: Vec<String>,
+    // The digest of a confidential client's secret, see `client_secret_hash`
+    pub secret_hash: Option<String>,
+    // What the client may ask for, at `/authorize` or with the client credentials grant
+    pub allowed_scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OAuthClient {
+    pub fn allows_redirect_uri(&self, uri: &str) -> bool {
+        self.redirect_uris.iter().any(|allowed| allowed == uri)
+    }
+
+    pub fn is_confidential(&self) -> bool {
+        self.secret_hash.is_some()
+    }
+
+    // Public clients must not send a secret, confidential ones must send theirs. Digests are
+    // compared, so the time taken does not depend on how much of the secret matched.
+    pub fn verify_secret(&self, secret: Option<&SecretString>) -> bool {
+        match (&self.secret_hash, secret) {
+            (None, None) => true,
+            (Some(hash), Some(secret)) => *hash == client_secret_hash(secret),
+            _ => false,
+        }
+    }
+
+    // Whether every token of the space-separated `scope` is one of the allowed scopes
+    pub fn allows_scope(&self, scope: &str) -> bool {
+        scope
+            .split(' ')
+            .all(|token| self.allowed_scopes.iter().any(|allowed| allowed == token))
+    }
+}
+
+// Client secrets are generated with plenty of entropy, so a plain digest is enough to keep
+// a leaked table from being used to authenticate
+pub fn client_secret_hash(secret: &SecretString) -> String {
+    format!("{:x}", Sha256::digest(secret.expose_secret().as_bytes()))
+}
+
+// What an authorization code stands for, until it is redeemed at `/token`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorizationGrant {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub user_id: UserId,
+    pub scope: Option<String>,
+    // BASE64URL(SHA256(code_verifier)), the only PKCE method accepted
+    pub code_challenge: String,
+    // OpenID Connect: echoed in the ID token, along with how and when the user logged in
+    pub nonce: Option<String>,
+    pub auth_time: DateTime<Utc>,
+    pub amr: Vec<String>,
+    // The login session the code was issued from, which RP-initiated logout ends
+    pub session_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AuthorizationGrant {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
+
+// What a refresh token stands for. `generation` is the user's token generation at the time
+// it was issued, so logging out everywhere invalidates refresh tokens too.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefreshGrant {
+    pub client_id: String,
+    pub user_id: UserId,
+    pub scope: Option<String>,
+    pub generation: i64,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl RefreshGrant {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
+
+// What a device code stands for, from `/device/code` until the device redeems it at `/token`
+// (RFC 8628). The user approves or denies it on the verification page, by its user code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceGrant {
+    pub client_id: String,
+    // Normalized, see `normalize_user_code`
+    pub user_code: String,
+    pub scope: Option<String>,
+    pub status: DeviceGrantStatus,
+    // How long the device has to wait between polls, raised whenever it polls too soon
+    pub interval_seconds: u64,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceGrantStatus {
+    Pending,
+    Approved(UserId),
+    Denied,
+}
+
+// RFC 8628 section 3.5: a device that polls too soon has to wait 5 seconds longer from then on
+const SLOW_DOWN_SECONDS: u64 = 5;
+
+impl DeviceGrant {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+
+    pub fn polled_too_soon(&self, now: DateTime<Utc>) -> bool {
+        self.last_polled_at.is_some_and(|last_polled_at| {
+            now < last_polled_at + chrono::Duration::seconds(self.interval_seconds as i64)
+        })
+    }
+
+    // Record a poll at `now`, slowing the device down if it came too soon
+    pub fn record_poll(&mut self, now: DateTime<Utc>) {
+        if self.polled_too_soon(now) {
+            self.interval_seconds += SLOW_DOWN_SECONDS;
+        }
+        self.last_polled_at = Some(now);
+    }
+}
+
+// Codes and refresh tokens are stored by their digest, so a leaked table cannot be replayed
+pub fn grant_key(token: &SecretString) -> String {
+    format!("{:x}", Sha256::digest(token.expose_secret().as_bytes()))
+}
+
+#[async_trait::async_trait]
+pub trait OAuthClientStore {
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError>;
+    async fn get_client(&self, id: &str) -> Result<OAuthClient, OAuthClientStoreError>;
+    // Oldest first
+    async fn get_clients(&self) -> Result<Vec<OAuthClient>, OAuthClientStoreError>;
+    async fn remove_client(&mut self, id: &str) -> Result<(), OAuthClientStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum OAuthClientStoreError {
+    #[error("Client not found")]
+    ClientNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for OAuthClientStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ClientNotFound, Self::ClientNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Expired codes are never returned, whether or not the backend has deleted them yet
+#[async_trait::async_trait]
+pub trait AuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: &SecretString,
+        grant: AuthorizationGrant,
+    ) -> Result<(), GrantStoreError>;
+    // Codes are single-use, so redeeming one removes it
+    async fn take_code(
+        &mut self,
+        code: &SecretString,
+    ) -> Result<AuthorizationGrant, GrantStoreError>;
+}
+
+// Expired refresh tokens are never returned, whether or not the backend has deleted them yet
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    async fn add_refresh_token(
+        &mut self,
+        token: &SecretString,
+        grant: RefreshGrant,
+    ) -> Result<(), GrantStoreError>;
+    async fn get_refresh_token(
+        &self,
+        token: &SecretString,
+    ) -> Result<RefreshGrant, GrantStoreError>;
+    // Fails with `GrantNotFound` if the token was removed in the meantime, so of two
+    // concurrent refreshes with the same token only one succeeds
+    async fn remove_refresh_token(&mut self, token: &SecretString) -> Result<(), GrantStoreError>;
+    // Remove every refresh token the client holds for the user. Returns how many there were.
+    async fn remove_refresh_tokens(
+        &mut self,
+        client_id: &str,
+        user_id: UserId,
+    ) -> Result<u64, GrantStoreError>;
+    // Remove every refresh token of the user, whichever client holds it
+    async fn remove_user_refresh_tokens(&mut self, user_id: UserId)
+        -> Result<u64, GrantStoreError>;
+}
+
+// Expired device codes are never returned, whether or not the backend has deleted them yet
+#[async_trait::async_trait]
+pub trait DeviceCodeStore {
+    // Fails if another device code that has not expired yet has the same user code
+    async fn add_device_code(
+        &mut self,
+        device_code: &SecretString,
+        grant: DeviceGrant,
+    ) -> Result<(), GrantStoreError>;
+    async fn get_user_code(&self, user_code: &str) -> Result<DeviceGrant, GrantStoreError>;
+    // Approve or deny the grant of a user code. Fails with `GrantNotFound` unless it is
+    // still pending, so a decision cannot be changed.
+    async fn set_user_code_status(
+        &mut self,
+        user_code: &str,
+        status: DeviceGrantStatus,
+    ) -> Result<(), GrantStoreError>;
+    // Record that the device polled at `now` (see `DeviceGrant::record_poll`), and return the
+    // grant as it was before, so the caller can tell whether the device came too soon
+    async fn poll_device_code(
+        &mut self,
+        device_code: &SecretString,
+        now: DateTime<Utc>,
+    ) -> Result<DeviceGrant, GrantStoreError>;
+    // Fails with `GrantNotFound` if the code was removed in the meantime, so a device code
+    // is only ever redeemed once
+    async fn remove_device_code(
+        &mut self,
+        device_code: &SecretString,
+    ) -> Result<(), GrantStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum GrantStoreError {
+    #[error("Grant not found")]
+    GrantNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for GrantStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::GrantNotFound, Self::GrantNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_match_redirect_uris_exactly() {
+        let client = OAuthClient {
+            id: "client".to_owned(),
+            name: "Client".to_owned(),
+            redirect_uris: vec!["https://app.example.com/callback".to_owned()],
+            secret_hash: None,
+            allowed_scopes: Vec::new(),
+            created_at: Utc::now(),
+        };
+        let rejected = [
+            "https://app.example.com/callback/",
+            "https://app.example.com/callback?next=/",
+            "https://APP.example.com/callback",
+            "http://app.example.com/callback",
+            "https://app.example.com",
+        ];
+
+        assert!(client.allows_redirect_uri("https://app.example.com/callback"));
+        for uri in rejected {
+            assert!(
+                !client.allows_redirect_uri(uri),
+                "Failed for input: {}",
+                uri
+            );
+        }
+    }
+
+    #[test]
+    fn should_verify_client_secrets() {
+        let secret = SecretString::from("s3cret");
+        let public = OAuthClient {
+            id: "public".to_owned(),
+            name: "Public".to_owned(),
+            redirect_uris: Vec::new(),
+            secret_hash: None,
+            allowed_scopes: Vec::new(),
+            created_at: Utc::now(),
+        };
+        let confidential = OAuthClient {
+            id: "confidential".to_owned(),
+            secret_hash: Some(client_secret_hash(&secret)),
+            ..public.clone()
+        };
+
+        assert!(public.verify_secret(None));
+        assert!(!public.verify_secret(Some(&secret)));
+        assert!(confidential.verify_secret(Some(&secret)));
+        assert!(!confidential.verify_secret(Some(&SecretString::from("s3cret "))));
+        assert!(!confidential.verify_secret(None));
+    }
+
+    #[test]
+    fn should_allow_only_listed_scopes() {
+        let client = OAuthClient {
+            id: "client".to_owned(),
+            name: "Client".to_owned(),
+            redirect_uris: Vec::new(),
+            secret_hash: Some(client_secret_hash(&SecretString::from("s3cret"))),
+            allowed_scopes: vec!["reports:read".to_owned(), "reports:write".to_owned()],
+            created_at: Utc::now(),
+        };
+
+        assert!(client.allows_scope("reports:read"));
+        assert!(client.allows_scope("reports:write reports:read"));
+        assert!(!client.allows_scope("reports:read admin"));
+        assert!(!client.allows_scope("reports"));
+    }
+
+    #[test]
+    fn should_slow_down_devices_that_poll_too_soon() {
+        let start = Utc::now();
+        let mut grant = DeviceGrant {
+            client_id: "client".to_owned(),
+            user_code: "BCDFGHJK".to_owned(),
+            scope: None,
+            status: DeviceGrantStatus::Pending,
+            interval_seconds: 5,
+            last_polled_at: None,
+            expires_at: start + chrono::Duration::minutes(10),
+        };
+
+        assert!(!grant.polled_too_soon(start));
+        grant.record_poll(start);
+        assert_eq!(grant.interval_seconds, 5);
+
+        let too_soon = start + chrono::Duration::seconds(4);
+        assert!(grant.polled_too_soon(too_soon));
+        grant.record_poll(too_soon);
+        assert_eq!(grant.interval_seconds, 10);
+
+        // The interval now counts from the last poll, and is longer
+        assert!(grant.polled_too_soon(too_soon + chrono::Duration::seconds(9)));
+        assert!(!grant.polled_too_soon(too_soon + chrono::Duration::seconds(10)));
+    }
+}
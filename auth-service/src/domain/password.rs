@@ -4,6 +4,9 @@ use argon2::{
 };
 use color_eyre::eyre::{eyre, Context, Result};
 use secrecy::{ExposeSecret, SecretString};
+use std::time::Instant;
+
+use crate::utils::metrics::PASSWORD_HASH_DURATION_SECONDS;
 
 #[derive(Clone, Debug, Default)]
 pub struct HashedPassword(SecretString);
@@ -50,11 +53,15 @@ impl HashedPassword {
         let password_candidate = password_candidate.expose_secret().to_string();
         let result = tokio::task::spawn_blocking(move || {
             current_span.in_scope(|| {
+                let start = Instant::now();
                 let expected_password_hash: PasswordHash<'_> = PasswordHash::new(&password_hash)?;
 
-                Argon2::default()
+                let result = Argon2::default()
                     .verify_password(password_candidate.as_bytes(), &expected_password_hash)
-                    .wrap_err("failed to verify password hash")
+                    .wrap_err("failed to verify password hash");
+                metrics::histogram!(PASSWORD_HASH_DURATION_SECONDS, "operation" => "verify")
+                    .record(start.elapsed().as_secs_f64());
+                result
             })
         })
         .await;
@@ -74,6 +81,7 @@ pub async fn compute_password_hash(password: &SecretString) -> Result<SecretStri
     let password = password.expose_secret().to_string();
     let result = tokio::task::spawn_blocking(move || {
         current_span.in_scope(|| {
+            let start = Instant::now();
             let salt: SaltString = SaltString::generate(&mut OsRng);
             let password_hash = Argon2::new(
                 Algorithm::Argon2id,
@@ -82,6 +90,8 @@ pub async fn compute_password_hash(password: &SecretString) -> Result<SecretStri
             )
             .hash_password(password.as_bytes(), &salt)?
             .to_string();
+            metrics::histogram!(PASSWORD_HASH_DURATION_SECONDS, "operation" => "hash")
+                .record(start.elapsed().as_secs_f64());
 
             Ok(SecretString::new(password_hash.into_boxed_str()))
         })
@@ -154,6 +164,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(clippy::let_unit_value, clippy::unit_cmp)]
     async fn can_verify_password_hash() {
         let raw_password = "TestPassword123";
         let salt = SaltString::generate(&mut OsRng);
@@ -0,0 +1,143 @@
+use color_eyre::eyre::Report;
+use thiserror::Error;
+
+use super::UserId;
+
+// Role names and permissions end up in tokens and are compared by downstream services, so
+// they are kept to a small, unambiguous character set
+const MAX_NAME_LENGTH: usize = 64;
+
+// A named set of permissions that can be assigned to users, e.g. `editor` with
+// `articles:write`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Role {
+    pub name: String,
+    // Sorted, without duplicates
+    pub permissions: Vec<String>,
+}
+
+impl Role {
+    // Fails unless the name and every permission are valid, see `is_valid_grant_name`
+    pub fn new(name: &str, permissions: &[String]) -> Result<Self, &'static str> {
+        if !is_valid_grant_name(name) {
+            return Err("invalid role name");
+        }
+        if !permissions
+            .iter()
+            .all(|permission| is_valid_grant_name(permission))
+        {
+            return Err("invalid permission");
+        }
+        let mut permissions = permissions.to_vec();
+        permissions.sort();
+        permissions.dedup();
+        Ok(Self {
+            name: name.to_owned(),
+            permissions,
+        })
+    }
+}
+
+// Letters, digits and `_ - . :`, e.g. `reports:read`
+pub fn is_valid_grant_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
+
+// What a user may do: the names of their roles, and every permission those roles grant.
+// Both are embedded in the user's tokens.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UserGrants {
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+impl UserGrants {
+    pub fn from_roles(roles: &[Role]) -> Self {
+        let mut names: Vec<String> = roles.iter().map(|role| role.name.clone()).collect();
+        names.sort();
+        let mut permissions: Vec<String> = roles
+            .iter()
+            .flat_map(|role| role.permissions.iter().cloned())
+            .collect();
+        permissions.sort();
+        permissions.dedup();
+        Self {
+            roles: names,
+            permissions,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+pub trait RoleStore {
+    // Creates the role, or replaces the permissions of an existing one
+    async fn put_role(&mut self, role: Role) -> Result<(), RoleStoreError>;
+    // By name
+    async fn get_roles(&self) -> Result<Vec<Role>, RoleStoreError>;
+    // Also unassigns the role from everyone who had it
+    async fn delete_role(&mut self, name: &str) -> Result<(), RoleStoreError>;
+    // Assigning a role twice is fine
+    async fn assign_role(&mut self, user_id: UserId, role: &str) -> Result<(), RoleStoreError>;
+    // Fails with `RoleNotFound` unless the user had the role
+    async fn unassign_role(&mut self, user_id: UserId, role: &str) -> Result<(), RoleStoreError>;
+    // By name
+    async fn get_user_roles(&self, user_id: UserId) -> Result<Vec<Role>, RoleStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RoleStoreError {
+    #[error("Role not found")]
+    RoleNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RoleStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::RoleNotFound, Self::RoleNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn should_validate_and_sort_role() {
+        let role = Role::new("editor", &strings(&["b:write", "a:read", "b:write"])).unwrap();
+
+        assert_eq!(role.permissions, strings(&["a:read", "b:write"]));
+        assert!(Role::new("", &[]).is_err());
+        assert!(Role::new("two words", &[]).is_err());
+        assert!(Role::new("editor", &strings(&["a read"])).is_err());
+        assert!(Role::new(&"x".repeat(MAX_NAME_LENGTH + 1), &[]).is_err());
+    }
+
+    #[test]
+    fn should_merge_permissions_of_roles() {
+        let roles = [
+            Role::new("viewer", &strings(&["reports:read"])).unwrap(),
+            Role::new("editor", &strings(&["reports:read", "reports:write"])).unwrap(),
+        ];
+
+        let grants = UserGrants::from_roles(&roles);
+
+        assert_eq!(grants.roles, strings(&["editor", "viewer"]));
+        assert_eq!(
+            grants.permissions,
+            strings(&["reports:read", "reports:write"])
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Report;
+use std::net::IpAddr;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::UserId;
+
+// One issued auth token, so users can see where they are logged in and revoke it
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: UserId,
+    // The OAuth client the token was issued to. First-party sessions have none.
+    pub client_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl Session {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
+
+// Expired sessions are never returned, whether or not the backend has deleted them yet
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, id: Uuid) -> Result<Session, SessionStoreError>;
+    // Newest first
+    async fn get_sessions(&self, user_id: UserId) -> Result<Vec<Session>, SessionStoreError>;
+    async fn touch_session(
+        &mut self,
+        id: Uuid,
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), SessionStoreError>;
+    async fn remove_session(&mut self, id: Uuid) -> Result<(), SessionStoreError>;
+    // Returns how many sessions were removed
+    async fn remove_sessions(&mut self, user_id: UserId) -> Result<u64, SessionStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SessionStoreError {
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for SessionStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SessionNotFound, Self::SessionNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
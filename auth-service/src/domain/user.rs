@@ -1,18 +1,173 @@
-use super::{Email, HashedPassword};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use uuid::Uuid;
+
+use super::{AuthAPIError, Email, HashedPassword};
+
+// Identifies a user for good, unlike their email, which can change. Everything kept about a
+// user is keyed by it, and it is the subject of their tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct UserId(Uuid);
+
+impl UserId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+
+    pub fn parse(id: &str) -> Result<Self> {
+        Uuid::parse_str(id).map(Self).wrap_err("Invalid user ID")
+    }
+
+    pub fn as_uuid(&self) -> Uuid {
+        self.0
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<Uuid> for UserId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+impl fmt::Display for UserId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
 #[derive(Clone, Debug, Default)]
 pub struct User {
+    pub id: UserId,
+    // Unique among users, ignoring case
     pub email: Email,
     pub password: HashedPassword,
     pub requires_2fa: bool,
+    // Bumped to invalidate every token issued to the user so far
+    pub token_generation: i64,
+    // Only active users can log in or use the tokens and API keys they already have
+    pub status: AccountStatus,
+    // Why an admin last changed the status, if they said
+    pub status_reason: Option<String>,
+    pub status_changed_at: DateTime<Utc>,
+    // Set by admins; the user has to choose a new password before they can log in again
+    pub password_reset_required: bool,
 }
 
 impl User {
     pub fn new(email: Email, password: HashedPassword, requires_2fa: bool) -> Self {
         User {
+            id: UserId::new(),
             email,
             password,
             requires_2fa,
+            token_generation: 0,
+            status: AccountStatus::Active,
+            status_reason: None,
+            status_changed_at: Utc::now(),
+            password_reset_required: false,
         }
     }
+
+    pub fn set_status(&mut self, status: AccountStatus, reason: Option<String>) {
+        self.status = status;
+        self.status_reason = reason;
+        self.status_changed_at = Utc::now();
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    Disabled,
+    // Signed up, but not allowed in until an admin lets them
+    PendingVerification,
+    // Soft deleted: the account can be restored until it is purged, once
+    // `ttl.deleted_user_retention_seconds` have passed since
+    ScheduledForDeletion,
+}
+
+impl AccountStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Disabled => "disabled",
+            Self::PendingVerification => "pending_verification",
+            Self::ScheduledForDeletion => "scheduled_for_deletion",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        [
+            Self::Active,
+            Self::Disabled,
+            Self::PendingVerification,
+            Self::ScheduledForDeletion,
+        ]
+        .into_iter()
+        .find(|s| s.as_str() == status)
+    }
+
+    // The error logins of an account in this status fail with
+    pub fn ensure_active(self) -> Result<(), AuthAPIError> {
+        match self {
+            Self::Active => Ok(()),
+            Self::Disabled => Err(AuthAPIError::AccountDisabled),
+            Self::PendingVerification => Err(AuthAPIError::AccountPendingVerification),
+            Self::ScheduledForDeletion => Err(AuthAPIError::AccountScheduledForDeletion),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_user_ids() {
+        let id = UserId::new();
+
+        assert_eq!(UserId::parse(&id.to_string()).unwrap(), id);
+        assert!(UserId::parse("user@example.com").is_err());
+    }
+
+    #[test]
+    fn should_parse_every_status() {
+        for status in [
+            AccountStatus::Active,
+            AccountStatus::Disabled,
+            AccountStatus::PendingVerification,
+            AccountStatus::ScheduledForDeletion,
+        ] {
+            assert_eq!(AccountStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(AccountStatus::parse("deleted"), None);
+    }
+
+    #[test]
+    fn should_only_let_active_accounts_in() {
+        assert!(AccountStatus::Active.ensure_active().is_ok());
+        assert!(matches!(
+            AccountStatus::Disabled.ensure_active(),
+            Err(AuthAPIError::AccountDisabled)
+        ));
+        assert!(matches!(
+            AccountStatus::PendingVerification.ensure_active(),
+            Err(AuthAPIError::AccountPendingVerification)
+        ));
+        assert!(matches!(
+            AccountStatus::ScheduledForDeletion.ensure_active(),
+            Err(AuthAPIError::AccountScheduledForDeletion)
+        ));
+    }
 }
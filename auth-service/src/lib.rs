@@ -1,52 +1,115 @@
 use axum::{
-    http::{Method, StatusCode},
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    http::{
+        header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE, WWW_AUTHENTICATE},
+        HeaderName, HeaderValue, Method, StatusCode,
+    },
+    middleware::{self, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{delete, get, post, put},
     serve::Serve,
     Json, Router,
 };
+#[cfg(feature = "redis")]
 use redis::RedisResult;
 use serde::{Deserialize, Serialize};
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+use sqlx::migrate::Migrator;
+#[cfg(feature = "postgres")]
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::error::Error;
-use tokio::net::TcpListener;
+#[cfg(feature = "sqlite")]
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+#[cfg(feature = "sqlite")]
+use std::str::FromStr;
+use std::{error::Error, net::SocketAddr, time::Duration};
+use tokio::{net::TcpListener, task::JoinHandle};
 use tower_http::{
     cors::CorsLayer,
     services::{ServeDir, ServeFile},
     trace::TraceLayer,
 };
-use utils::tracing::{make_span_with_request_id, on_request, on_response};
+use utils::{
+    metrics::{init_metrics, track_http_metrics},
+    tracing::{
+        make_span_with_request_id, on_request, on_response, propagate_request_id, REQUEST_ID_HEADER,
+    },
+};
 
 use crate::app_state::*;
 use crate::domain::*;
 use crate::routes::{
-    login::login_handler, logout::logout_handler, signup::signup_handler,
-    verify_2fa::verify_2fa_handler, verify_token::verify_token_handler,
+    admin::{
+        admin_logout_all_handler, delete_client_handler, list_clients_handler,
+        register_client_handler,
+    },
+    admin_users::{
+        change_email_handler, delete_user_handler, disable_2fa_handler, disable_user_handler,
+        enable_user_handler, force_password_reset_handler, get_user_handler, reset_2fa_handler,
+        revoke_user_sessions_handler, search_users_handler, set_user_status_handler,
+    },
+    api_keys::{create_api_key_handler, delete_api_key_handler, list_api_keys_handler},
+    authorize::authorize_handler,
+    device::{device_code_handler, device_verification_handler, get_device_verification_handler},
+    health::{healthz_handler, readyz_handler},
+    introspect::introspect_handler,
+    login::login_handler,
+    logout::{logout_all_handler, logout_handler},
+    metrics::metrics_handler,
+    oidc::{end_session_handler, jwks_handler, openid_configuration_handler, userinfo_handler},
+    reset_password::reset_password_handler,
+    revoke::revoke_handler,
+    roles::{
+        assign_role_handler, delete_role_handler, get_user_roles_handler, list_roles_handler,
+        put_role_handler, unassign_role_handler,
+    },
+    sessions::{delete_session_handler, list_sessions_handler},
+    signup::signup_handler,
+    token::token_handler,
+    verify_2fa::verify_2fa_handler,
+    verify_token::verify_token_handler,
 };
 pub mod app_state;
+pub mod cli;
 pub mod domain;
 pub mod routes;
 pub mod services;
+pub mod settings;
 pub mod utils;
 
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<TcpListener, Router, Router>,
+    server: Serve<
+        TcpListener,
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
 }
 
 impl Application {
-    pub async fn build(app_state: AppState, address: &str) -> Result<Self, Box<dyn Error>> {
-        // Allow the app service (running on our local machine) to call the auth service
-        let allowed_origins = ["http://localhost:8000".parse()?];
+    pub async fn build(app_state: AppState) -> Result<Self, Box<dyn Error>> {
+        let server_settings = app_state.settings.server.clone();
+        let allowed_origins = server_settings
+            .cors_allowed_origins
+            .iter()
+            .map(|origin| origin.parse())
+            .collect::<Result<Vec<HeaderValue>, _>>()?;
         let cors = CorsLayer::new()
-            // Allow GET and POST requests
-            .allow_methods([Method::GET, Method::POST])
+            // Allow GET, POST, PUT and DELETE requests
+            .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+            // Allow JSON bodies and bearer tokens
+            .allow_headers([CONTENT_TYPE, AUTHORIZATION])
             // Allow cookies to be included in requests
             .allow_credentials(true)
+            // Let browser clients read the request id to quote it in bug reports
+            .expose_headers([HeaderName::from_static(REQUEST_ID_HEADER)])
             .allow_origin(allowed_origins);
+        init_metrics();
         let assets_dir =
             ServeDir::new("assets").not_found_service(ServeFile::new("assets/index.html"));
         let router = Router::new()
@@ -54,20 +117,92 @@ impl Application {
             .route("/signup", post(signup_handler))
             .route("/login", post(login_handler))
             .route("/verify-2fa", post(verify_2fa_handler))
+            .route("/reset-password", post(reset_password_handler))
             .route("/logout", post(logout_handler))
+            .route("/logout-all", post(logout_all_handler))
+            .route("/admin/logout-all", post(admin_logout_all_handler))
+            .route(
+                "/admin/clients",
+                get(list_clients_handler).post(register_client_handler),
+            )
+            .route("/admin/clients/{id}", delete(delete_client_handler))
+            .route("/admin/roles", get(list_roles_handler))
+            .route(
+                "/admin/roles/{name}",
+                put(put_role_handler).delete(delete_role_handler),
+            )
+            .route("/admin/users", get(search_users_handler))
+            .route(
+                "/admin/users/{user}",
+                get(get_user_handler).delete(delete_user_handler),
+            )
+            .route("/admin/users/{user}/disable", post(disable_user_handler))
+            .route("/admin/users/{user}/enable", post(enable_user_handler))
+            .route("/admin/users/{user}/status", put(set_user_status_handler))
+            .route("/admin/users/{user}/email", put(change_email_handler))
+            .route(
+                "/admin/users/{user}/password-reset",
+                post(force_password_reset_handler),
+            )
+            .route("/admin/users/{user}/2fa/reset", post(reset_2fa_handler))
+            .route("/admin/users/{user}/2fa/disable", post(disable_2fa_handler))
+            .route(
+                "/admin/users/{user}/sessions",
+                delete(revoke_user_sessions_handler),
+            )
+            .route("/admin/users/{user}/roles", get(get_user_roles_handler))
+            .route(
+                "/admin/users/{user}/roles/{role}",
+                put(assign_role_handler).delete(unassign_role_handler),
+            )
             .route("/verify-token", post(verify_token_handler))
+            .route("/introspect", post(introspect_handler))
+            .route("/revoke", post(revoke_handler))
+            .route("/authorize", get(authorize_handler))
+            .route("/token", post(token_handler))
+            .route("/device/code", post(device_code_handler))
+            .route(
+                "/device/verify",
+                get(get_device_verification_handler).post(device_verification_handler),
+            )
+            // The verification page users are sent to from their devices
+            .route_service("/device", ServeFile::new("assets/device.html"))
+            .route(
+                "/.well-known/openid-configuration",
+                get(openid_configuration_handler),
+            )
+            .route("/.well-known/jwks.json", get(jwks_handler))
+            .route("/userinfo", get(userinfo_handler).post(userinfo_handler))
+            .route("/end-session", get(end_session_handler))
+            .route("/sessions", get(list_sessions_handler))
+            .route("/sessions/{id}", delete(delete_session_handler))
+            .route(
+                "/api-keys",
+                get(list_api_keys_handler).post(create_api_key_handler),
+            )
+            .route("/api-keys/{id}", delete(delete_api_key_handler))
+            .route("/healthz", get(healthz_handler))
+            .route("/readyz", get(readyz_handler))
+            .route("/metrics", get(metrics_handler))
             .with_state(app_state)
+            .layer(middleware::from_fn(track_http_metrics))
             .layer(cors) // Add CORS config to our Axum router
             .layer(
                 TraceLayer::new_for_http()
                     .make_span_with(make_span_with_request_id)
                     .on_request(on_request)
                     .on_response(on_response),
-            );
+            )
+            // Outermost, so the request id is in place before the request span is made
+            .layer(middleware::from_fn(propagate_request_id));
 
-        let listener = TcpListener::bind(address).await?;
+        let listener = TcpListener::bind(&server_settings.address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Handlers see the client address, for the audit log
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         // Create a new Application instance and return it
         Ok(Application { server, address })
@@ -87,6 +222,17 @@ pub struct ErrorResponse {
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         log_error_chain(&self);
+        // Clients are told how to authenticate, as RFC 6749 asks for, and which scope they
+        // lacked, as RFC 6750 section 3.1 does
+        let challenge = match self {
+            AuthAPIError::InvalidClient => {
+                Some(HeaderValue::from_static("Basic realm=\"auth-service\""))
+            }
+            AuthAPIError::InsufficientScope => Some(HeaderValue::from_static(
+                "Bearer error=\"insufficient_scope\", scope=\"openid\"",
+            )),
+            _ => None,
+        };
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::IncorrectCredentials => {
@@ -95,6 +241,30 @@ impl IntoResponse for AuthAPIError {
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid auth token"),
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Invalid credentials"),
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
+            AuthAPIError::InvalidClient => (StatusCode::UNAUTHORIZED, "Invalid client credentials"),
+            AuthAPIError::UnauthorizedClient => {
+                (StatusCode::FORBIDDEN, "Client is not allowed to do this")
+            }
+            AuthAPIError::ClientNotFound => (StatusCode::NOT_FOUND, "Client not found"),
+            AuthAPIError::InsufficientScope => {
+                (StatusCode::FORBIDDEN, "Token lacks the required scope")
+            }
+            AuthAPIError::UserCodeNotFound => (StatusCode::NOT_FOUND, "Unknown or expired code"),
+            AuthAPIError::ApiKeyNotFound => (StatusCode::NOT_FOUND, "API key not found"),
+            AuthAPIError::RoleNotFound => (StatusCode::NOT_FOUND, "Role not found"),
+            AuthAPIError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden"),
+            AuthAPIError::AccountDisabled => (StatusCode::FORBIDDEN, "Account disabled"),
+            AuthAPIError::AccountPendingVerification => {
+                (StatusCode::FORBIDDEN, "Account pending verification")
+            }
+            AuthAPIError::AccountScheduledForDeletion => {
+                (StatusCode::FORBIDDEN, "Account scheduled for deletion")
+            }
+            AuthAPIError::PasswordResetRequired => {
+                (StatusCode::FORBIDDEN, "Password reset required")
+            }
             AuthAPIError::UnexpectedError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             }
@@ -102,11 +272,39 @@ impl IntoResponse for AuthAPIError {
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
         });
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let Some(challenge) = challenge {
+            response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+        }
+        response
+    }
+}
+
+// RFC 6749 section 5.2
+#[derive(Serialize, Deserialize)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        log_error_chain(&self);
+        let status = match self {
+            OAuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let body = Json(OAuthErrorResponse {
+            error: self.code().to_owned(),
+            error_description: self.description().map(str::to_owned),
+        });
+        (status, [(CACHE_CONTROL, "no-store")], body).into_response()
     }
 }
 
-fn log_error_chain(e: &(dyn Error + 'static)) {
+pub(crate) fn log_error_chain(e: &(dyn Error + 'static)) {
     let separator =
         "\n-----------------------------------------------------------------------------------\n";
     let mut report = format!("{}{:?}\n", separator, e);
@@ -120,10 +318,50 @@ fn log_error_chain(e: &(dyn Error + 'static)) {
     tracing::error!("{}", report);
 }
 
-pub async fn get_postgres_pool(url: &str) -> Result<PgPool, sqlx::Error> {
-    PgPoolOptions::new().max_connections(5).connect(url).await
+// Applied at startup, and run or reverted on demand by `auth-admin migrate`
+#[cfg(feature = "postgres")]
+pub static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!();
+#[cfg(feature = "sqlite")]
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations-sqlite");
+
+#[cfg(feature = "postgres")]
+pub async fn get_postgres_pool(url: &str, max_connections: u32) -> Result<PgPool, sqlx::Error> {
+    PgPoolOptions::new()
+        .max_connections(max_connections)
+        .connect(url)
+        .await
+}
+
+#[cfg(feature = "sqlite")]
+pub async fn get_sqlite_pool(url: &str, max_connections: u32) -> Result<SqlitePool, sqlx::Error> {
+    // Create the database file on first start so no separate setup step is needed
+    let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+    SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .connect_with(options)
+        .await
+}
+
+// Periodically delete expired entries from stores whose backend keeps them around.
+// The SQL tables index `expires_at`, so each purge is a range scan rather than a full scan.
+pub fn spawn_expired_rows_purge(
+    stores: Vec<ExpiringStoreType>,
+    period: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            for store in &stores {
+                if let Err(e) = store.purge_expired().await {
+                    log_error_chain(e.as_ref());
+                }
+            }
+        }
+    })
 }
 
+#[cfg(feature = "redis")]
 pub fn get_redis_client(redis_hostname: String) -> RedisResult<redis::Client> {
     let redis_url = format!("redis://{}/", redis_hostname);
     redis::Client::open(redis_url)
@@ -0,0 +1,237 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        client_secret_hash, AuditEventKind, AuthAPIError, OAuthClient, OAuthClientStoreError,
+    },
+    utils::{
+        admin::{existing_user, RequireAdmin},
+        audit::{record_audit_event, AuditContext},
+        oauth::{generate_grant_token, normalize_scope, validate_redirect_uri},
+        sessions::end_all_sessions,
+    },
+};
+
+// Log a user out everywhere, e.g. after their account was compromised
+#[tracing::instrument(name = "Admin logout all", skip_all)]
+pub async fn admin_logout_all_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Json(request): Json<AdminLogoutAllRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let actor = request.email.clone();
+    let admin_identity = admin.as_ref().ok().map(|admin| admin.identity.clone());
+    let result = admin_logout_all(&state, admin, request).await;
+    record_audit_event(
+        &state,
+        context.admin_event(
+            AuditEventKind::AdminLogoutAll,
+            admin_identity.as_deref(),
+            Some(&actor),
+            &result,
+        ),
+    )
+    .await;
+    result
+}
+
+async fn admin_logout_all(
+    state: &AppState,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    request: AdminLogoutAllRequest,
+) -> Result<StatusCode, AuthAPIError> {
+    admin?;
+    let user = existing_user(state, request.email).await?;
+    end_all_sessions(state, user.id).await?;
+    Ok(StatusCode::OK)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminLogoutAllRequest {
+    pub email: String,
+}
+
+// A registered OAuth client, as admins see it
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientResponse {
+    pub client_id: String,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub confidential: bool,
+    pub allowed_scopes: Vec<String>,
+    // Only returned when a confidential client is registered, as only its digest is kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<OAuthClient> for ClientResponse {
+    fn from(client: OAuthClient) -> Self {
+        Self {
+            client_id: client.id,
+            name: client.name,
+            redirect_uris: client.redirect_uris,
+            confidential: client.secret_hash.is_some(),
+            allowed_scopes: client.allowed_scopes,
+            client_secret: None,
+            created_at: client.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ClientsResponse {
+    pub clients: Vec<ClientResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterClientRequest {
+    pub name: String,
+    // Confidential clients may leave them out, if they only use client credentials
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    // Confidential clients get a secret, and may also ask for `allowed_scopes` for themselves
+    #[serde(default)]
+    pub confidential: bool,
+    // The scopes users can grant the client at `/authorize`
+    #[serde(default)]
+    pub allowed_scopes: Vec<String>,
+}
+
+#[tracing::instrument(name = "Admin register client", skip_all)]
+pub async fn register_client_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Json(request): Json<RegisterClientRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let admin_identity = admin.as_ref().ok().map(|admin| admin.identity.clone());
+    let result = register_client(&state, admin, request).await;
+    record_audit_event(
+        &state,
+        context.admin_event(
+            AuditEventKind::AdminRegisterClient,
+            admin_identity.as_deref(),
+            None,
+            &result,
+        ),
+    )
+    .await;
+    result.map(|client| (StatusCode::CREATED, Json(client)))
+}
+
+async fn register_client(
+    state: &AppState,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    request: RegisterClientRequest,
+) -> Result<ClientResponse, AuthAPIError> {
+    admin?;
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+    // Public clients can only ever be used through `/authorize`
+    if !request.confidential && request.redirect_uris.is_empty() {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+    for scope in &request.allowed_scopes {
+        if normalize_scope(Some(scope)) != Some(Ok(scope.clone())) || scope.contains(' ') {
+            tracing::info!(scope, "rejected allowed scope");
+            return Err(AuthAPIError::InvalidCredentials);
+        }
+    }
+    for uri in &request.redirect_uris {
+        if let Err(reason) = validate_redirect_uri(uri) {
+            tracing::info!(uri, reason, "rejected redirect URI");
+            return Err(AuthAPIError::InvalidCredentials);
+        }
+    }
+    let secret = request.confidential.then(generate_grant_token);
+    let client = OAuthClient {
+        id: Uuid::new_v4().to_string(),
+        name: name.to_owned(),
+        redirect_uris: request.redirect_uris,
+        secret_hash: secret.as_ref().map(client_secret_hash),
+        allowed_scopes: request.allowed_scopes,
+        created_at: Utc::now(),
+    };
+    state
+        .oauth
+        .clients
+        .write()
+        .await
+        .add_client(client.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(ClientResponse {
+        client_secret: secret.map(|secret| secret.expose_secret().to_owned()),
+        ..client.into()
+    })
+}
+
+#[tracing::instrument(name = "Admin list clients", skip_all)]
+pub async fn list_clients_handler(
+    State(state): State<AppState>,
+    _: RequireAdmin,
+) -> Result<Json<ClientsResponse>, AuthAPIError> {
+    let clients = state
+        .oauth
+        .clients
+        .read()
+        .await
+        .get_clients()
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(Json(ClientsResponse {
+        clients: clients.into_iter().map(ClientResponse::from).collect(),
+    }))
+}
+
+// Codes and refresh tokens issued to the client stop working with it
+#[tracing::instrument(name = "Admin delete client", skip_all)]
+pub async fn delete_client_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    let admin_identity = admin.as_ref().ok().map(|admin| admin.identity.clone());
+    let result = delete_client(&state, admin, &id).await;
+    record_audit_event(
+        &state,
+        context.admin_event(
+            AuditEventKind::AdminDeleteClient,
+            admin_identity.as_deref(),
+            None,
+            &result,
+        ),
+    )
+    .await;
+    result
+}
+
+async fn delete_client(
+    state: &AppState,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    id: &str,
+) -> Result<StatusCode, AuthAPIError> {
+    admin?;
+    match state.oauth.clients.write().await.remove_client(id).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(OAuthClientStoreError::ClientNotFound) => Err(AuthAPIError::ClientNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
@@ -0,0 +1,359 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AccountStatus, AuditEventKind, AuthAPIError, Email, User, UserId, UserStoreError},
+    utils::{
+        accounts::{delete_user, remove_pending_code},
+        admin::{existing_user, RequireAdmin},
+        audit::{record_audit_event, AuditContext},
+        sessions::{end_all_sessions, user_grants},
+    },
+};
+
+const DEFAULT_SEARCH_LIMIT: i64 = 50;
+const MAX_SEARCH_LIMIT: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchUsersQuery {
+    // Part of the email, matched case-insensitively. Everyone matches an empty query.
+    #[serde(default)]
+    pub query: String,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+// The state of an account, as admins see it
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserResponse {
+    pub id: UserId,
+    pub email: String,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    pub status: AccountStatus,
+    pub status_reason: Option<String>,
+    pub status_changed_at: DateTime<Utc>,
+    // Kept for clients written before account statuses, same as a `disabled` status
+    pub disabled: bool,
+    pub password_reset_required: bool,
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email.as_ref().to_owned(),
+            requires_2fa: user.requires_2fa,
+            status: user.status,
+            status_reason: user.status_reason,
+            status_changed_at: user.status_changed_at,
+            disabled: user.status == AccountStatus::Disabled,
+            password_reset_required: user.password_reset_required,
+        }
+    }
+}
+
+// Why the status changed, shown to admins along with it
+#[derive(Debug, Default, Deserialize)]
+pub struct StatusReasonRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetStatusRequest {
+    pub status: AccountStatus,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeEmailRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UsersResponse {
+    pub users: Vec<UserResponse>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserDetailsResponse {
+    #[serde(flatten)]
+    pub user: UserResponse,
+    pub roles: Vec<String>,
+    pub active_sessions: usize,
+    pub api_keys: usize,
+}
+
+// Ordered by email
+#[tracing::instrument(name = "Admin search users", skip_all)]
+pub async fn search_users_handler(
+    State(state): State<AppState>,
+    _: RequireAdmin,
+    Query(query): Query<SearchUsersQuery>,
+) -> Result<Json<UsersResponse>, AuthAPIError> {
+    let limit = query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    if !(1..=MAX_SEARCH_LIMIT).contains(&limit) || offset < 0 {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+    let users = state
+        .user_store
+        .read()
+        .await
+        .search_users(query.query.trim(), limit, offset)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(Json(UsersResponse {
+        users: users.into_iter().map(UserResponse::from).collect(),
+    }))
+}
+
+#[tracing::instrument(name = "Admin get user", skip_all)]
+pub async fn get_user_handler(
+    State(state): State<AppState>,
+    _: RequireAdmin,
+    Path(user): Path<String>,
+) -> Result<Json<UserDetailsResponse>, AuthAPIError> {
+    let user = existing_user(&state, user).await?;
+    let grants = user_grants(&state, user.id)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let active_sessions = state
+        .sessions
+        .read()
+        .await
+        .get_sessions(user.id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .len();
+    let api_keys = state
+        .api_keys
+        .read()
+        .await
+        .get_api_keys(user.id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .len();
+    Ok(Json(UserDetailsResponse {
+        user: user.into(),
+        roles: grants.roles,
+        active_sessions,
+        api_keys,
+    }))
+}
+
+// What an admin can do to an account
+enum UserAction {
+    // Any status but active also ends every session, so the user is locked out at once.
+    // Reactivated users start over with new tokens.
+    SetStatus(AccountStatus, Option<String>),
+    // The user has to choose a new password at their next login, see `/reset-password`
+    ForcePasswordReset,
+    // Discards the code of a pending 2FA login, so the user has to log in again
+    Reset2FA,
+    Disable2FA,
+    RevokeSessions,
+    Delete,
+    // Tokens and sessions keep working, as they name the user by id
+    ChangeEmail(String),
+}
+
+impl UserAction {
+    fn kind(&self) -> AuditEventKind {
+        match self {
+            Self::SetStatus(AccountStatus::Active, _) => AuditEventKind::AdminEnableUser,
+            Self::SetStatus(AccountStatus::Disabled, _) => AuditEventKind::AdminDisableUser,
+            Self::SetStatus(..) => AuditEventKind::AdminSetUserStatus,
+            Self::ForcePasswordReset => AuditEventKind::AdminForcePasswordReset,
+            Self::Reset2FA => AuditEventKind::AdminReset2FA,
+            Self::Disable2FA => AuditEventKind::AdminDisable2FA,
+            Self::RevokeSessions => AuditEventKind::AdminRevokeSessions,
+            Self::Delete => AuditEventKind::AdminDeleteUser,
+            Self::ChangeEmail(_) => AuditEventKind::AdminChangeEmail,
+        }
+    }
+}
+
+// The body is optional
+#[tracing::instrument(name = "Admin disable user", skip_all)]
+pub async fn disable_user_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(user): Path<String>,
+    request: Option<Json<StatusReasonRequest>>,
+) -> Result<StatusCode, AuthAPIError> {
+    let reason = request.and_then(|Json(request)| request.reason);
+    let action = UserAction::SetStatus(AccountStatus::Disabled, reason);
+    user_action_handler(&state, context, admin, user, action).await
+}
+
+// Reactivates an account whatever its status, including one scheduled for deletion
+#[tracing::instrument(name = "Admin enable user", skip_all)]
+pub async fn enable_user_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(user): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    let action = UserAction::SetStatus(AccountStatus::Active, None);
+    user_action_handler(&state, context, admin, user, action).await
+}
+
+#[tracing::instrument(name = "Admin set user status", skip_all)]
+pub async fn set_user_status_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(user): Path<String>,
+    Json(request): Json<SetStatusRequest>,
+) -> Result<StatusCode, AuthAPIError> {
+    let action = UserAction::SetStatus(request.status, request.reason);
+    user_action_handler(&state, context, admin, user, action).await
+}
+
+#[tracing::instrument(name = "Admin force password reset", skip_all)]
+pub async fn force_password_reset_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(user): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    user_action_handler(&state, context, admin, user, UserAction::ForcePasswordReset).await
+}
+
+#[tracing::instrument(name = "Admin reset 2FA", skip_all)]
+pub async fn reset_2fa_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(user): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    user_action_handler(&state, context, admin, user, UserAction::Reset2FA).await
+}
+
+#[tracing::instrument(name = "Admin disable 2FA", skip_all)]
+pub async fn disable_2fa_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(user): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    user_action_handler(&state, context, admin, user, UserAction::Disable2FA).await
+}
+
+#[tracing::instrument(name = "Admin revoke user sessions", skip_all)]
+pub async fn revoke_user_sessions_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(user): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    user_action_handler(&state, context, admin, user, UserAction::RevokeSessions).await
+}
+
+#[tracing::instrument(name = "Admin delete user", skip_all)]
+pub async fn delete_user_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(user): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    user_action_handler(&state, context, admin, user, UserAction::Delete).await
+}
+
+#[tracing::instrument(name = "Admin change user email", skip_all)]
+pub async fn change_email_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(user): Path<String>,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<StatusCode, AuthAPIError> {
+    let action = UserAction::ChangeEmail(request.email);
+    user_action_handler(&state, context, admin, user, action).await
+}
+
+async fn user_action_handler(
+    state: &AppState,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    user: String,
+    action: UserAction,
+) -> Result<StatusCode, AuthAPIError> {
+    let admin_identity = admin.as_ref().ok().map(|admin| admin.identity.clone());
+    let kind = action.kind();
+    let status = match &action {
+        UserAction::SetStatus(status, _) => Some(*status),
+        _ => None,
+    };
+    let result = user_action(state, admin, user.clone(), action).await;
+    let mut event = context.admin_event(kind, admin_identity.as_deref(), Some(&user), &result);
+    if let (AuditEventKind::AdminSetUserStatus, Some(status), Ok(_)) = (kind, status, &result) {
+        // The kind alone does not say which status the account was given
+        event.reason = Some(status.as_str().to_owned());
+    }
+    record_audit_event(state, event).await;
+    result
+}
+
+async fn user_action(
+    state: &AppState,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    user: String,
+    action: UserAction,
+) -> Result<StatusCode, AuthAPIError> {
+    admin?;
+    let mut user = existing_user(state, user).await?;
+    match action {
+        UserAction::SetStatus(status, reason) => {
+            user.set_status(status, reason);
+            update_user(state, &user).await?;
+            if status != AccountStatus::Active {
+                end_all_sessions(state, user.id).await?;
+            }
+        }
+        UserAction::ForcePasswordReset => {
+            user.password_reset_required = true;
+            update_user(state, &user).await?;
+            end_all_sessions(state, user.id).await?;
+            // Only codes sent from now on may be used to choose the new password
+            remove_pending_code(state, user.id).await?;
+        }
+        UserAction::Reset2FA => remove_pending_code(state, user.id).await?,
+        UserAction::Disable2FA => {
+            user.requires_2fa = false;
+            update_user(state, &user).await?;
+            remove_pending_code(state, user.id).await?;
+        }
+        UserAction::RevokeSessions => end_all_sessions(state, user.id).await?,
+        UserAction::Delete => delete_user(state, user.id).await?,
+        UserAction::ChangeEmail(email) => {
+            user.email = Email::parse(email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+            update_user(state, &user).await?;
+        }
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn update_user(state: &AppState, user: &User) -> Result<(), AuthAPIError> {
+    state
+        .user_store
+        .write()
+        .await
+        .update_user(user)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+            UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })
+}
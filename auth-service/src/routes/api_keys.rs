@@ -0,0 +1,233 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{api_key_hash, ApiKey, ApiKeyStoreError, AuditEventKind, AuthAPIError, UserId},
+    utils::{
+        api_keys::generate_api_key,
+        audit::{record_audit_event, AuditContext},
+        auth::{first_party_user, AuthenticatedUser},
+        oauth::normalize_scope,
+        sessions::touch_session,
+    },
+};
+
+// Longer names are more likely a pasted key than a description
+const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scope: Option<String>,
+    // Keys without one are valid until they are revoked
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// A key as shown to its owner, without the key itself
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyResponse {
+    pub id: Uuid,
+    pub name: String,
+    // The first characters of the key
+    pub hint: String,
+    pub scope: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKey> for ApiKeyResponse {
+    fn from(key: ApiKey) -> Self {
+        Self {
+            id: key.id,
+            name: key.name,
+            hint: key.key_hint,
+            scope: key.scope,
+            created_at: key.created_at,
+            expires_at: key.expires_at,
+            last_used_at: key.last_used_at,
+        }
+    }
+}
+
+// The key is only ever shown here, so the caller has to keep it
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyResponse {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeysResponse {
+    pub api_keys: Vec<ApiKeyResponse>,
+}
+
+#[tracing::instrument(name = "Create API key", skip_all)]
+pub async fn create_api_key_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (actor, result) = create_api_key(&state, user, request).await;
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::CreateApiKey, actor.as_deref(), &result),
+    )
+    .await;
+    result.map(|response| (StatusCode::CREATED, Json(response)))
+}
+
+// Also returns the caller, once their token is known to be valid
+async fn create_api_key(
+    state: &AppState,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    request: CreateApiKeyRequest,
+) -> (Option<String>, Result<CreateApiKeyResponse, AuthAPIError>) {
+    let claims = match first_party_user(user) {
+        Ok(user) => user.claims,
+        Err(e) => return (None, Err(e)),
+    };
+    touch_session(state, claims.sid).await;
+    let actor = Some(claims.actor());
+    let result = match claims.user_id() {
+        Ok(user_id) => issue_api_key(state, user_id, request).await,
+        Err(e) => Err(AuthAPIError::UnexpectedError(e)),
+    };
+    (actor, result)
+}
+
+async fn issue_api_key(
+    state: &AppState,
+    user_id: UserId,
+    request: CreateApiKeyRequest,
+) -> Result<CreateApiKeyResponse, AuthAPIError> {
+    let name = request.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+    let scope = match normalize_scope(request.scope.as_deref()) {
+        Some(Ok(scope)) => Some(scope),
+        Some(Err(reason)) => {
+            tracing::info!(reason, "rejected API key scope");
+            return Err(AuthAPIError::InvalidCredentials);
+        }
+        None => None,
+    };
+    let now = Utc::now();
+    if request
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= now)
+    {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let (key, hint) = generate_api_key();
+    let api_key = ApiKey {
+        id: Uuid::new_v4(),
+        user_id,
+        name: name.to_owned(),
+        key_hash: api_key_hash(&key),
+        key_hint: hint,
+        scope,
+        created_at: now,
+        expires_at: request.expires_at,
+        last_used_at: None,
+    };
+    state
+        .api_keys
+        .write()
+        .await
+        .add_api_key(api_key.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(CreateApiKeyResponse {
+        key: key.expose_secret().to_owned(),
+        api_key: api_key.into(),
+    })
+}
+
+#[tracing::instrument(name = "List API keys", skip_all)]
+pub async fn list_api_keys_handler(
+    State(state): State<AppState>,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+) -> Result<Json<ApiKeysResponse>, AuthAPIError> {
+    let claims = first_party_user(user)?.claims;
+    touch_session(&state, claims.sid).await;
+    let user_id = claims.user_id().map_err(AuthAPIError::UnexpectedError)?;
+
+    let api_keys = state
+        .api_keys
+        .read()
+        .await
+        .get_api_keys(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(ApiKeysResponse {
+        api_keys: api_keys.into_iter().map(ApiKeyResponse::from).collect(),
+    }))
+}
+
+#[tracing::instrument(name = "Delete API key", skip_all)]
+pub async fn delete_api_key_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, AuthAPIError> {
+    let (actor, result) = delete_api_key(&state, user, id).await;
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::RevokeApiKey, actor.as_deref(), &result),
+    )
+    .await;
+    result
+}
+
+// Also returns the caller, once their token is known to be valid
+async fn delete_api_key(
+    state: &AppState,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    id: Uuid,
+) -> (Option<String>, Result<StatusCode, AuthAPIError>) {
+    let claims = match first_party_user(user) {
+        Ok(user) => user.claims,
+        Err(e) => return (None, Err(e)),
+    };
+    touch_session(state, claims.sid).await;
+    let actor = Some(claims.actor());
+    let user_id = match claims.user_id() {
+        Ok(user_id) => user_id,
+        Err(e) => return (actor, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    // Keys of other users are reported as missing, so their ids cannot be probed
+    let result = match state
+        .api_keys
+        .write()
+        .await
+        .remove_api_key(user_id, id)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(ApiKeyStoreError::ApiKeyNotFound) => Err(AuthAPIError::ApiKeyNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+    (actor, result)
+}
@@ -0,0 +1,277 @@
+use axum::{
+    extract::{Query, RawQuery, State},
+    response::{IntoResponse, Redirect, Response},
+};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use url::form_urlencoded;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuditEventKind, AuthAPIError, AuthorizationGrant, OAuthClient, OAuthClientStoreError,
+        OAuthError,
+    },
+    utils::{
+        audit::{record_audit_event, AuditContext},
+        auth::{AuthenticatedUser, TokenSource},
+        oauth::{
+            generate_grant_token, is_valid_code_challenge, normalize_scope, with_query_params,
+        },
+    },
+};
+
+// RFC 6749 section 4.1.1, with the PKCE parameters of RFC 7636 section 4.3
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeRequest {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    // OpenID Connect Core 1.0 section 3.1.2.1, echoed in the ID token
+    pub nonce: Option<String>,
+}
+
+// Nonces are stored with the code and copied into the ID token, so their size is bounded
+const MAX_NONCE_LENGTH: usize = 512;
+
+// What became of a request that named a client and one of its redirect URIs
+enum Authorization {
+    // The user is sent to the login page first, which sends them back here afterwards
+    LoginRequired,
+    // The client gets a code, or the reason it did not, at its redirect URI
+    Redirect {
+        redirect_uri: String,
+        result: Result<SecretString, OAuthError>,
+    },
+}
+
+#[tracing::instrument(name = "Authorize", skip_all)]
+pub async fn authorize_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    // Taken as a result, so users who are not logged in yet can be sent to log in
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    RawQuery(query): RawQuery,
+    Query(request): Query<AuthorizeRequest>,
+) -> Result<Response, OAuthError> {
+    let request_state = request.state.clone();
+    let (actor, authorization) = authorize(&state, user, request).await;
+
+    let (redirect_uri, result) = match authorization {
+        // The login itself is audited, and the code issued afterwards
+        Ok(Authorization::LoginRequired) => return Ok(login_redirect(query).into_response()),
+        Ok(Authorization::Redirect {
+            redirect_uri,
+            result,
+        }) => (redirect_uri, result),
+        // Without a redirect URI the client registered, the error can only be shown here
+        Err(e) => {
+            let result: Result<(), &OAuthError> = Err(&e);
+            record_audit_event(
+                &state,
+                context.event(AuditEventKind::Authorize, None, &result),
+            )
+            .await;
+            return Err(e);
+        }
+    };
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::Authorize, actor.as_deref(), &result),
+    )
+    .await;
+
+    let mut params: Vec<(&str, &str)> = Vec::new();
+    match &result {
+        Ok(code) => params.push(("code", code.expose_secret())),
+        Err(e) => {
+            params.push(("error", e.code()));
+            if let Some(description) = e.description() {
+                params.push(("error_description", description));
+            }
+        }
+    }
+    if let Some(request_state) = &request_state {
+        params.push(("state", request_state));
+    }
+    let location =
+        with_query_params(&redirect_uri, &params).map_err(OAuthError::UnexpectedError)?;
+    Ok(Redirect::to(&location).into_response())
+}
+
+// Also returns the user the code is issued for, once they are known
+async fn authorize(
+    state: &AppState,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    request: AuthorizeRequest,
+) -> (Option<String>, Result<Authorization, OAuthError>) {
+    let client = match registered_client(state, &request).await {
+        Ok(client) => client,
+        Err(e) => return (None, Err(e)),
+    };
+    let redirect_uri = request.redirect_uri.clone().unwrap_or_default();
+    let nonce = request.nonce.clone();
+
+    // Problems with the request are reported before the user is asked to log in
+    let (scope, code_challenge) = match validate_request(request, &client) {
+        Ok(validated) => validated,
+        Err(e) => {
+            return (
+                None,
+                Ok(Authorization::Redirect {
+                    redirect_uri,
+                    result: Err(e),
+                }),
+            )
+        }
+    };
+
+    // Only a first-party login in this browser counts. A bearer token, even one issued to
+    // another client, must not be enough to obtain codes.
+    let user = match user {
+        Ok(user) if user.source == TokenSource::Cookie && user.claims.client_id.is_none() => user,
+        _ => return (None, Ok(Authorization::LoginRequired)),
+    };
+    let actor = Some(user.claims.actor());
+    let result = issue_code(
+        state,
+        &user,
+        &client,
+        redirect_uri.clone(),
+        scope,
+        code_challenge,
+        nonce,
+    )
+    .await;
+    (
+        actor,
+        Ok(Authorization::Redirect {
+            redirect_uri,
+            result,
+        }),
+    )
+}
+
+// The client the request is for, provided the redirect URI is one it registered
+async fn registered_client(
+    state: &AppState,
+    request: &AuthorizeRequest,
+) -> Result<OAuthClient, OAuthError> {
+    let client_id = request
+        .client_id
+        .as_deref()
+        .ok_or(OAuthError::InvalidRequest("client_id is missing"))?;
+    let client = match state.oauth.clients.read().await.get_client(client_id).await {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => {
+            return Err(OAuthError::InvalidRequest("client_id is not registered"))
+        }
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+    let redirect_uri = request
+        .redirect_uri
+        .as_deref()
+        .ok_or(OAuthError::InvalidRequest("redirect_uri is missing"))?;
+    match client.allows_redirect_uri(redirect_uri) {
+        true => Ok(client),
+        false => Err(OAuthError::InvalidRequest(
+            "redirect_uri is not registered for this client",
+        )),
+    }
+}
+
+// The scope and PKCE code challenge the code will be issued for
+fn validate_request(
+    request: AuthorizeRequest,
+    client: &OAuthClient,
+) -> Result<(Option<String>, String), OAuthError> {
+    match request.response_type.as_deref() {
+        Some("code") => {}
+        Some(_) => return Err(OAuthError::UnsupportedResponseType),
+        None => return Err(OAuthError::InvalidRequest("response_type is missing")),
+    }
+    let code_challenge = request
+        .code_challenge
+        .ok_or(OAuthError::InvalidRequest("code_challenge is required"))?;
+    if request.code_challenge_method.as_deref() != Some("S256") {
+        return Err(OAuthError::InvalidRequest(
+            "code_challenge_method must be S256",
+        ));
+    }
+    if !is_valid_code_challenge(&code_challenge) {
+        return Err(OAuthError::InvalidRequest("code_challenge is malformed"));
+    }
+    if request
+        .nonce
+        .as_deref()
+        .is_some_and(|nonce| nonce.is_empty() || nonce.len() > MAX_NONCE_LENGTH)
+    {
+        return Err(OAuthError::InvalidRequest("nonce is malformed"));
+    }
+    let scope = normalize_scope(request.scope.as_deref())
+        .transpose()
+        .map_err(|_| OAuthError::InvalidScope)?;
+    // Clients only get the scopes they were registered with
+    if scope
+        .as_deref()
+        .is_some_and(|scope| !client.allows_scope(scope))
+    {
+        return Err(OAuthError::InvalidScope);
+    }
+    Ok((scope, code_challenge))
+}
+
+async fn issue_code(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    client: &OAuthClient,
+    redirect_uri: String,
+    scope: Option<String>,
+    code_challenge: String,
+    nonce: Option<String>,
+) -> Result<SecretString, OAuthError> {
+    let user_id = user.claims.user_id().map_err(OAuthError::UnexpectedError)?;
+    let ttl = chrono::Duration::from_std(state.settings.ttl.authorization_code())
+        .wrap_err("invalid authorization code TTL")
+        .map_err(OAuthError::UnexpectedError)?;
+    let code = generate_grant_token();
+    let grant = AuthorizationGrant {
+        client_id: client.id.clone(),
+        redirect_uri,
+        user_id,
+        scope,
+        code_challenge,
+        nonce,
+        // Tokens from before `iat` was introduced read as issued now
+        auth_time: DateTime::from_timestamp(user.claims.iat as i64, 0)
+            .filter(|_| user.claims.iat > 0)
+            .unwrap_or_else(Utc::now),
+        amr: user.claims.amr.clone(),
+        session_id: user.claims.sid,
+        expires_at: Utc::now() + ttl,
+    };
+    state
+        .oauth
+        .codes
+        .write()
+        .await
+        .add_code(&code, grant)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+    Ok(code)
+}
+
+// The login page sends the user back to `next` once they are logged in
+fn login_redirect(query: Option<String>) -> Redirect {
+    let next = format!("/authorize?{}", query.unwrap_or_default());
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("next", &next)
+        .finish();
+    Redirect::to(&format!("/?{}", query))
+}
@@ -0,0 +1,293 @@
+use axum::{
+    extract::{Query, State},
+    http::{
+        header::{CACHE_CONTROL, PRAGMA},
+        HeaderMap, StatusCode,
+    },
+    response::IntoResponse,
+    Form, Json,
+};
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuditEventKind, AuthAPIError, DeviceGrant, DeviceGrantStatus, GrantStoreError,
+        OAuthClientStoreError, OAuthError,
+    },
+    utils::{
+        audit::{record_audit_event, AuditContext},
+        auth::{AuthenticatedUser, TokenSource},
+        clients::authenticate_oauth_client,
+        oauth::{
+            format_user_code, generate_grant_token, generate_user_code, normalize_scope,
+            normalize_user_code, with_query_params,
+        },
+    },
+};
+
+// RFC 8628 section 3.1. Clients authenticate the way they do at `/token`.
+#[derive(Deserialize)]
+pub struct DeviceCodeRequest {
+    pub client_id: Option<String>,
+    pub client_secret: Option<SecretString>,
+    pub scope: Option<String>,
+}
+
+// RFC 8628 section 3.2
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeviceCodeResponse {
+    pub device_code: String,
+    // Formatted for people, e.g. `BCDF-GHJK`
+    pub user_code: String,
+    pub verification_uri: String,
+    // The verification page with the user code filled in, e.g. for a QR code
+    pub verification_uri_complete: String,
+    // Seconds until both codes expire
+    pub expires_in: u64,
+    // Seconds the device has to wait between polls of `/token`
+    pub interval: u64,
+}
+
+#[tracing::instrument(name = "Device code", skip_all)]
+pub async fn device_code_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    headers: HeaderMap,
+    Form(request): Form<DeviceCodeRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let (actor, result) = issue_device_code(&state, &headers, request).await;
+    record_audit_event(
+        &state,
+        context.event(
+            AuditEventKind::DeviceAuthorization,
+            actor.as_deref(),
+            &result,
+        ),
+    )
+    .await;
+
+    // The device code is as good as a password until it expires, so it is not cached
+    result.map(|response| {
+        (
+            [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+            Json(response),
+        )
+    })
+}
+
+// Also returns the client, once it has authenticated
+async fn issue_device_code(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: DeviceCodeRequest,
+) -> (Option<String>, Result<DeviceCodeResponse, OAuthError>) {
+    let client = match authenticate_oauth_client(
+        state,
+        headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_ref(),
+    )
+    .await
+    {
+        Ok(client) => client,
+        Err(e) => return (None, Err(e)),
+    };
+    let actor = Some(client.id.clone());
+    let scope = match normalize_scope(request.scope.as_deref()).transpose() {
+        Ok(scope) => scope,
+        Err(_) => return (actor, Err(OAuthError::InvalidScope)),
+    };
+    // Clients only get the scopes they were registered with
+    if scope
+        .as_deref()
+        .is_some_and(|scope| !client.allows_scope(scope))
+    {
+        return (actor, Err(OAuthError::InvalidScope));
+    }
+
+    let ttl = &state.settings.ttl;
+    let expires_in =
+        match chrono::Duration::from_std(ttl.device_code()).wrap_err("invalid device code TTL") {
+            Ok(expires_in) => expires_in,
+            Err(e) => return (actor, Err(OAuthError::UnexpectedError(e))),
+        };
+    let device_code = generate_grant_token();
+    let user_code = generate_user_code();
+    let grant = DeviceGrant {
+        client_id: client.id,
+        user_code: user_code.clone(),
+        scope,
+        status: DeviceGrantStatus::Pending,
+        interval_seconds: ttl.device_poll_interval_seconds,
+        last_polled_at: None,
+        expires_at: Utc::now() + expires_in,
+    };
+    if let Err(e) = state
+        .oauth
+        .device_codes
+        .write()
+        .await
+        .add_device_code(&device_code, grant)
+        .await
+    {
+        return (actor, Err(OAuthError::UnexpectedError(e.into())));
+    }
+
+    let user_code = format_user_code(&user_code);
+    let verification_uri = format!(
+        "{}/device",
+        state.settings.oidc.issuer.trim_end_matches('/')
+    );
+    let verification_uri_complete =
+        match with_query_params(&verification_uri, &[("user_code", &user_code)]) {
+            Ok(uri) => uri,
+            Err(e) => return (actor, Err(OAuthError::UnexpectedError(e))),
+        };
+    let response = DeviceCodeResponse {
+        device_code: device_code.expose_secret().to_owned(),
+        user_code,
+        verification_uri,
+        verification_uri_complete,
+        expires_in: ttl.device_code_seconds,
+        interval: ttl.device_poll_interval_seconds,
+    };
+    (actor, Ok(response))
+}
+
+#[derive(Deserialize)]
+pub struct DeviceVerificationQuery {
+    pub user_code: String,
+}
+
+// What the user is asked to approve
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceVerificationResponse {
+    pub client_id: String,
+    pub client_name: String,
+    pub scope: Option<String>,
+}
+
+#[tracing::instrument(name = "Get device verification", skip_all)]
+pub async fn get_device_verification_handler(
+    State(state): State<AppState>,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    Query(query): Query<DeviceVerificationQuery>,
+) -> Result<Json<DeviceVerificationResponse>, AuthAPIError> {
+    first_party_user(user)?;
+    let grant = pending_grant(&state, &query.user_code).await?;
+    let client = match state
+        .oauth
+        .clients
+        .read()
+        .await
+        .get_client(&grant.client_id)
+        .await
+    {
+        Ok(client) => client,
+        // The client was deleted since, so its code is of no use anymore
+        Err(OAuthClientStoreError::ClientNotFound) => return Err(AuthAPIError::UserCodeNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    Ok(Json(DeviceVerificationResponse {
+        client_id: client.id,
+        client_name: client.name,
+        scope: grant.scope,
+    }))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceVerificationRequest {
+    pub user_code: String,
+    pub approve: bool,
+}
+
+#[tracing::instrument(name = "Verify device", skip_all)]
+pub async fn device_verification_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    Json(request): Json<DeviceVerificationRequest>,
+) -> Result<StatusCode, AuthAPIError> {
+    let (actor, result) = verify_device(&state, user, request).await;
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::DeviceApproval, actor.as_deref(), &result),
+    )
+    .await;
+    result
+}
+
+// Also returns the user, once their token is known to be valid
+async fn verify_device(
+    state: &AppState,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    request: DeviceVerificationRequest,
+) -> (Option<String>, Result<StatusCode, AuthAPIError>) {
+    let user = match first_party_user(user) {
+        Ok(user) => user,
+        Err(e) => return (None, Err(e)),
+    };
+    let actor = Some(user.claims.actor());
+    let status = match request.approve {
+        true => match user.claims.user_id() {
+            Ok(user_id) => DeviceGrantStatus::Approved(user_id),
+            Err(e) => return (actor, Err(AuthAPIError::UnexpectedError(e))),
+        },
+        false => DeviceGrantStatus::Denied,
+    };
+    let Some(user_code) = normalize_user_code(&request.user_code) else {
+        return (actor, Err(AuthAPIError::UserCodeNotFound));
+    };
+    let result = match state
+        .oauth
+        .device_codes
+        .write()
+        .await
+        .set_user_code_status(&user_code, status)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(GrantStoreError::GrantNotFound) => Err(AuthAPIError::UserCodeNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+    (actor, result)
+}
+
+// Only a first-party login in this browser may approve devices, like at `/authorize`. A
+// bearer token, even one issued to another client, must not be enough. Users without one
+// are told to log in with a 401, which the verification page sends to the login page.
+fn first_party_user(
+    user: Result<AuthenticatedUser, AuthAPIError>,
+) -> Result<AuthenticatedUser, AuthAPIError> {
+    match user {
+        Ok(user) if user.source == TokenSource::Cookie && user.claims.client_id.is_none() => {
+            Ok(user)
+        }
+        Err(AuthAPIError::UnexpectedError(e)) => Err(AuthAPIError::UnexpectedError(e)),
+        _ => Err(AuthAPIError::InvalidToken),
+    }
+}
+
+// The grant of a user code as typed, while it still waits for the user's decision
+async fn pending_grant(state: &AppState, user_code: &str) -> Result<DeviceGrant, AuthAPIError> {
+    let user_code = normalize_user_code(user_code).ok_or(AuthAPIError::UserCodeNotFound)?;
+    match state
+        .oauth
+        .device_codes
+        .read()
+        .await
+        .get_user_code(&user_code)
+        .await
+    {
+        Ok(grant) if grant.status == DeviceGrantStatus::Pending => Ok(grant),
+        Ok(_) | Err(GrantStoreError::GrantNotFound) => Err(AuthAPIError::UserCodeNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
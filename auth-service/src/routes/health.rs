@@ -0,0 +1,51 @@
+use axum::{extract::State, http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    app_state::AppState,
+    domain::{DependencyState, DependencyStatus},
+    services::health_checks::run_health_checks,
+};
+
+#[derive(Deserialize, Serialize)]
+pub struct HealthResponse {
+    pub status: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ReadinessResponse {
+    pub status: String,
+    pub checks: BTreeMap<String, DependencyStatus>,
+}
+
+// Liveness: the process is up and serving requests, regardless of its dependencies
+pub async fn healthz_handler() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok".to_owned(),
+    })
+}
+
+// Readiness: every configured dependency answered within the health check timeout
+#[tracing::instrument(name = "Readiness check", skip_all)]
+pub async fn readyz_handler(
+    State(state): State<AppState>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let checks = run_health_checks(&state.health_checks, state.settings.health.timeout()).await;
+    let ready = checks
+        .values()
+        .all(|check| check.status == DependencyState::Up);
+
+    let (status_code, status) = match ready {
+        true => (StatusCode::OK, "ready"),
+        false => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+    };
+    let body = ReadinessResponse {
+        status: status.to_owned(),
+        checks: checks
+            .into_iter()
+            .map(|(name, status)| (name.to_owned(), status))
+            .collect(),
+    };
+    (status_code, Json(body))
+}
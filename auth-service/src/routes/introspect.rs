@@ -0,0 +1,116 @@
+use axum::{extract::State, Form, Json};
+use chrono::{DateTime, Utc};
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuthAPIError},
+    utils::{
+        api_keys::{is_api_key, validate_api_key},
+        audit::{record_audit_event, AuditContext},
+        auth::validate_token,
+        clients::AuthenticatedClient,
+        sessions::touch_session,
+    },
+};
+
+#[derive(Deserialize)]
+pub struct IntrospectRequest {
+    pub token: SecretString,
+    // Only access tokens can be introspected. Refresh tokens are reported inactive, so the
+    // hint is accepted but not needed.
+    pub token_type_hint: Option<String>,
+}
+
+// RFC 7662 section 2.2. Inactive tokens are described by `active` alone, so callers
+// learn nothing about why a token was rejected.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct IntrospectResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    // The user's email, for tokens and API keys of users
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+}
+
+#[tracing::instrument(name = "Introspect token", skip_all)]
+pub async fn introspect_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    client: Result<AuthenticatedClient, AuthAPIError>,
+    Form(request): Form<IntrospectRequest>,
+) -> Result<Json<IntrospectResponse>, AuthAPIError> {
+    let result = introspect(&state, client, request).await;
+    let actor = match &result {
+        Ok(Json(response)) => response.username.clone().or_else(|| response.sub.clone()),
+        Err(_) => None,
+    };
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::Introspect, actor.as_deref(), &result),
+    )
+    .await;
+    result
+}
+
+async fn introspect(
+    state: &AppState,
+    client: Result<AuthenticatedClient, AuthAPIError>,
+    request: IntrospectRequest,
+) -> Result<Json<IntrospectResponse>, AuthAPIError> {
+    client?;
+    if is_api_key(&request.token) {
+        return Ok(Json(introspect_api_key(state, &request.token).await));
+    }
+    let banned_tokens = state.banned_tokens.clone();
+    let user_store = state.user_store.clone();
+    let clients = state.oauth.clients.clone();
+    let Ok(claims) = validate_token(&request.token, banned_tokens, user_store, clients).await
+    else {
+        return Ok(Json(IntrospectResponse::default()));
+    };
+    touch_session(state, claims.sid).await;
+
+    Ok(Json(IntrospectResponse {
+        active: true,
+        sub: Some(claims.sub),
+        username: claims.email,
+        exp: Some(claims.exp),
+        // Tokens from before `iat` was added have none
+        iat: (claims.iat > 0).then_some(claims.iat),
+        scope: claims.scope,
+        client_id: claims.client_id,
+        token_type: Some("Bearer".to_owned()),
+    }))
+}
+
+// API keys are described like the tokens of their owner. They are not JWTs, so they have
+// no `exp` unless they were given an expiry.
+async fn introspect_api_key(state: &AppState, key: &SecretString) -> IntrospectResponse {
+    let Ok((api_key, owner)) = validate_api_key(state, key).await else {
+        return IntrospectResponse::default();
+    };
+    let timestamp = |at: DateTime<Utc>| at.timestamp().try_into().ok();
+    IntrospectResponse {
+        active: true,
+        sub: Some(owner.id.to_string()),
+        username: Some(owner.email.as_ref().to_owned()),
+        exp: api_key.expires_at.and_then(timestamp),
+        iat: timestamp(api_key.created_at),
+        scope: api_key.scope,
+        client_id: None,
+        token_type: Some("ApiKey".to_owned()),
+    }
+}
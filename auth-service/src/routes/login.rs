@@ -1,18 +1,58 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use axum_extra::extract::CookieJar;
+use axum_extra::extract::{cookie::Cookie, CookieJar};
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{AuditEventKind, AuthAPIError, Email, LoginAttemptId, TwoFACode, User},
+    utils::{
+        audit::{record_audit_event, AuditContext},
+        auth::PASSWORD_AMR,
+        metrics::{error_outcome, LOGINS_TOTAL, TWO_FA_CODES_SENT_TOTAL},
+        sessions::start_session,
+    },
 };
 
 #[derive(Deserialize)]
 pub struct LoginRequest {
     pub email: String,
     pub password: SecretString,
+    #[serde(rename = "tokenDelivery", default)]
+    pub token_delivery: TokenDelivery,
+}
+
+// How the token is handed to the client once the login is complete. Browsers keep it in
+// an HTTP-only cookie; apps and CLIs that send it as a bearer token ask for it in the body.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenDelivery {
+    #[default]
+    Cookie,
+    Body,
+}
+
+impl TokenDelivery {
+    // Hand the token in `auth_cookie` to the client, returning the body to send it in, if any
+    pub fn deliver(
+        self,
+        jar: CookieJar,
+        auth_cookie: Cookie<'static>,
+        ttl: Duration,
+    ) -> (CookieJar, Option<TokenResponse>) {
+        match self {
+            Self::Cookie => (jar.add(auth_cookie), None),
+            Self::Body => (
+                jar,
+                Some(TokenResponse {
+                    token: auth_cookie.value().to_owned(),
+                    token_type: "Bearer".to_owned(),
+                    expires_in: ttl.as_secs(),
+                }),
+            ),
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -20,6 +60,19 @@ pub struct LoginRequest {
 pub enum LoginResponse {
     RegularAuth,
     TwoFactorAuth(TwoFactorAuthResponse),
+    TokenAuth(TokenResponse),
+    // The code sent by email is for `/reset-password`, not `/verify-2fa`
+    PasswordReset(TwoFactorAuthResponse),
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct TokenResponse {
+    pub token: String,
+    #[serde(rename = "tokenType")]
+    pub token_type: String,
+    // Seconds until the token expires
+    #[serde(rename = "expiresIn")]
+    pub expires_in: u64,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -32,9 +85,38 @@ pub struct TwoFactorAuthResponse {
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login_handler(
     State(state): State<AppState>,
+    context: AuditContext,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let actor = request.email.clone();
+    let (jar, result) = login(&state, &context, jar, request).await;
+    let outcome = match &result {
+        Ok((_, Json(LoginResponse::RegularAuth | LoginResponse::TokenAuth(_)))) => "success",
+        Ok((_, Json(LoginResponse::TwoFactorAuth(_)))) => "2fa_required",
+        Ok((_, Json(LoginResponse::PasswordReset(_)))) => "password_reset_required",
+        Err(e) => error_outcome(e),
+    };
+    metrics::counter!(LOGINS_TOTAL, "outcome" => outcome).increment(1);
+
+    let mut event = context.event(AuditEventKind::Login, Some(&actor), &result);
+    if matches!(outcome, "2fa_required" | "password_reset_required") {
+        // The password was right, but the login is only complete once the code is verified
+        event.reason = Some(outcome.to_owned());
+    }
+    record_audit_event(&state, event).await;
+    (jar, result)
+}
+
+async fn login(
+    state: &AppState,
+    context: &AuditContext,
+    jar: CookieJar,
+    request: LoginRequest,
+) -> (
+    CookieJar,
+    Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
+) {
     let Ok(email) = Email::parse(request.email) else {
         return (jar, Err(AuthAPIError::InvalidCredentials));
     };
@@ -52,74 +134,122 @@ pub async fn login_handler(
         Ok(user) => user,
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
+    // `/reset-password` locks the codes before the users, so the users cannot stay locked
+    drop(user_store);
 
+    if let Err(e) = user.status.ensure_active() {
+        return (jar, Err(e));
+    }
+    if user.password_reset_required {
+        return handle_password_reset(&user, state, jar).await;
+    }
     match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+        true => handle_2fa(&user, state, jar).await,
+        false => handle_no_2fa(&user, request.token_delivery, state, context, jar).await,
     }
 }
 
 #[tracing::instrument(name = "handle login with 2FA", skip_all)]
 async fn handle_2fa(
-    email: &Email,
+    user: &User,
     state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
+    let login_attempt_id = match send_login_code(user, state, "2FA Code").await {
+        Ok(id) => id,
+        Err(e) => return (jar, Err(e)),
+    };
+    (
+        jar,
+        Ok((
+            StatusCode::PARTIAL_CONTENT,
+            Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
+                message: "2FA required".to_owned(),
+                login_attempt_id: login_attempt_id.as_ref().expose_secret().to_string(),
+            })),
+        )),
+    )
+}
+
+// The user has to prove they still own their email before they choose a new password
+#[tracing::instrument(name = "handle login with password reset", skip_all)]
+async fn handle_password_reset(
+    user: &User,
+    state: &AppState,
+    jar: CookieJar,
+) -> (
+    CookieJar,
+    Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
+) {
+    let login_attempt_id = match send_login_code(user, state, "Password reset code").await {
+        Ok(id) => id,
+        Err(e) => return (jar, Err(e)),
+    };
+    (
+        jar,
+        Ok((
+            StatusCode::PARTIAL_CONTENT,
+            Json(LoginResponse::PasswordReset(TwoFactorAuthResponse {
+                message: "Password reset required".to_owned(),
+                login_attempt_id: login_attempt_id.as_ref().expose_secret().to_string(),
+            })),
+        )),
+    )
+}
+
+// Email a new code for the login attempt, replacing any earlier one
+async fn send_login_code(
+    user: &User,
+    state: &AppState,
+    subject: &str,
+) -> Result<LoginAttemptId, AuthAPIError> {
     let login_attempt_id = LoginAttemptId::default();
     let two_fa_code = TwoFACode::default();
 
-    if let Err(e) = state
+    state
         .two_fa_codes
         .write()
         .await
-        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
+        .add_code(user.id, login_attempt_id.clone(), two_fa_code.clone())
         .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
-    }
-
-    if let Err(e) = state
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    state
         .email_client
         .read()
         .await
-        .send_email(email, "2FA Code", two_fa_code.as_ref().expose_secret())
+        .send_email(&user.email, subject, two_fa_code.as_ref().expose_secret())
         .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError(e)));
-    }
+        .map_err(AuthAPIError::UnexpectedError)?;
+    metrics::counter!(TWO_FA_CODES_SENT_TOTAL).increment(1);
 
-    (
-        jar,
-        Ok((
-            StatusCode::PARTIAL_CONTENT,
-            Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
-                message: "2FA required".to_owned(),
-                login_attempt_id: login_attempt_id.as_ref().expose_secret().to_string(),
-            })),
-        )),
-    )
+    Ok(login_attempt_id)
 }
 
 #[tracing::instrument(name = "handle login without 2FA", skip_all)]
 async fn handle_no_2fa(
-    email: &Email,
+    user: &User,
+    token_delivery: TokenDelivery,
+    state: &AppState,
+    context: &AuditContext,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let auth_cookie = match generate_auth_cookie(email) {
+    let auth_cookie = match start_session(state, user, context, PASSWORD_AMR).await {
         Ok(cookie) => cookie,
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    let ttl = state.settings.ttl.token();
+    let (updated_jar, body) = token_delivery.deliver(jar, auth_cookie, ttl);
+    let response = match body {
+        Some(body) => LoginResponse::TokenAuth(body),
+        None => LoginResponse::RegularAuth,
+    };
 
-    (
-        updated_jar,
-        Ok((StatusCode::OK, Json(LoginResponse::RegularAuth))),
-    )
+    (updated_jar, Ok((StatusCode::OK, Json(response))))
 }
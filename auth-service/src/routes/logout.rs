@@ -1,27 +1,45 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use axum_extra::extract::{cookie, CookieJar};
-use secrecy::SecretString;
+use axum_extra::extract::CookieJar;
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{AuditEventKind, AuthAPIError},
+    utils::{
+        audit::{record_audit_event, AuditContext},
+        auth::{first_party_user, AuthenticatedUser},
+        metrics::TOKENS_BANNED_TOTAL,
+        sessions::{end_all_sessions, end_session},
+    },
 };
 
 #[tracing::instrument(name = "Logout", skip_all)]
 pub async fn logout_handler(
     State(state): State<AppState>,
+    context: AuditContext,
+    user: Result<AuthenticatedUser, AuthAPIError>,
     jar: CookieJar,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
-    let Some(cookie) = jar.get(JWT_COOKIE_NAME) else {
-        return (jar, Err(AuthAPIError::MissingToken));
-    };
+    let (actor, jar, result) = logout(&state, user, jar).await;
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::Logout, actor.as_deref(), &result),
+    )
+    .await;
+    (jar, result)
+}
 
-    let token = SecretString::new(cookie.value().to_owned().into_boxed_str());
-    let banned_tokens = state.banned_tokens.clone();
-    let Ok(_claims) = validate_token(&token, banned_tokens).await else {
-        return (jar, Err(AuthAPIError::InvalidToken));
+// Also returns the user the token belonged to, once the token is known to be valid
+async fn logout(
+    state: &AppState,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    jar: CookieJar,
+) -> (Option<String>, CookieJar, Result<StatusCode, AuthAPIError>) {
+    let user = match user {
+        Ok(user) => user,
+        Err(e) => return (None, jar, Err(e)),
     };
+    let actor = Some(user.claims.actor());
+    let token = user.token.clone();
     if let Err(e) = state
         .banned_tokens
         .write()
@@ -29,14 +47,57 @@ pub async fn logout_handler(
         .add_token(token.to_owned())
         .await
     {
-        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        return (actor, jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
-    let jar = jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME));
+    let jar = user.clear_cookie(jar);
     let mut token_store = state.banned_tokens.write().await;
     if let Err(e) = token_store.add_token(token).await {
-        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        return (actor, jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+    metrics::counter!(TOKENS_BANNED_TOTAL).increment(1);
+    if let Err(e) = end_session(state, user.claims.sid).await {
+        return (actor, jar, Err(e));
+    }
+
+    (actor, jar, Ok(StatusCode::OK))
+}
+
+#[tracing::instrument(name = "Logout all", skip_all)]
+pub async fn logout_all_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (actor, jar, result) = logout_all(&state, user, jar).await;
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::LogoutAll, actor.as_deref(), &result),
+    )
+    .await;
+    (jar, result)
+}
+
+// Invalidates every token of the caller, not only the one the request was made with
+async fn logout_all(
+    state: &AppState,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    jar: CookieJar,
+) -> (Option<String>, CookieJar, Result<StatusCode, AuthAPIError>) {
+    let user = match first_party_user(user) {
+        Ok(user) => user,
+        Err(e) => return (None, jar, Err(e)),
+    };
+    let actor = Some(user.claims.actor());
+    let user_id = match user.claims.user_id() {
+        Ok(user_id) => user_id,
+        Err(e) => return (actor, jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+    if let Err(e) = end_all_sessions(state, user_id).await {
+        return (actor, jar, Err(e));
     }
 
-    (jar, Ok(StatusCode::OK))
+    let jar = user.clear_cookie(jar);
+    (actor, jar, Ok(StatusCode::OK))
 }
@@ -0,0 +1,14 @@
+use axum::{http::header, response::IntoResponse};
+
+use crate::utils::metrics::init_metrics;
+
+// Prometheus text exposition of every metric recorded so far
+pub async fn metrics_handler() -> impl IntoResponse {
+    let handle = init_metrics();
+    // Drain histogram samples and expire idle metrics before rendering
+    handle.run_upkeep();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+}
@@ -1,5 +1,19 @@
+pub mod admin;
+pub mod admin_users;
+pub mod api_keys;
+pub mod authorize;
+pub mod device;
+pub mod health;
+pub mod introspect;
 pub mod login;
 pub mod logout;
+pub mod metrics;
+pub mod oidc;
+pub mod reset_password;
+pub mod revoke;
+pub mod roles;
+pub mod sessions;
 pub mod signup;
+pub mod token;
 pub mod verify_2fa;
 pub mod verify_token;
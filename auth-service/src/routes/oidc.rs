@@ -0,0 +1,296 @@
+use axum::{
+    extract::{Query, State},
+    response::Redirect,
+    Json,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use jsonwebtoken::jwk::JwkSet;
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuditEventKind, AuthAPIError, OAuthClientStoreError, OAuthError, SessionStoreError, UserId,
+        UserStoreError,
+    },
+    routes::token::DEVICE_CODE_GRANT_TYPE,
+    utils::{
+        audit::{record_audit_event, AuditContext},
+        auth::{access_token_jwk, decode_token, AuthenticatedUser},
+        constants::JWT_COOKIE_NAME,
+        oauth::with_query_params,
+        oidc::{has_scope, IdTokenClaims, EMAIL_SCOPE, OPENID_SCOPE},
+        sessions::{end_client_sessions, revoke_session},
+    },
+};
+
+// OpenID Connect Discovery 1.0 section 3
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub end_session_endpoint: String,
+    pub revocation_endpoint: String,
+    pub introspection_endpoint: String,
+    // RFC 8628 section 4
+    pub device_authorization_endpoint: String,
+    pub response_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub scopes_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+#[tracing::instrument(name = "OpenID configuration", skip_all)]
+pub async fn openid_configuration_handler(State(state): State<AppState>) -> Json<ProviderMetadata> {
+    let issuer = &state.settings.oidc.issuer;
+    let endpoint = |path: &str| format!("{}{}", issuer.trim_end_matches('/'), path);
+    Json(ProviderMetadata {
+        issuer: issuer.clone(),
+        authorization_endpoint: endpoint("/authorize"),
+        token_endpoint: endpoint("/token"),
+        userinfo_endpoint: endpoint("/userinfo"),
+        jwks_uri: endpoint("/.well-known/jwks.json"),
+        end_session_endpoint: endpoint("/end-session"),
+        revocation_endpoint: endpoint("/revoke"),
+        introspection_endpoint: endpoint("/introspect"),
+        device_authorization_endpoint: endpoint("/device/code"),
+        response_types_supported: strings(&["code"]),
+        subject_types_supported: strings(&["public"]),
+        id_token_signing_alg_values_supported: strings(&["ES256"]),
+        scopes_supported: strings(&[OPENID_SCOPE, EMAIL_SCOPE]),
+        // Public clients prove themselves with PKCE, confidential ones also with their secret
+        token_endpoint_auth_methods_supported: strings(&[
+            "none",
+            "client_secret_basic",
+            "client_secret_post",
+        ]),
+        code_challenge_methods_supported: strings(&["S256"]),
+        grant_types_supported: strings(&[
+            "authorization_code",
+            "refresh_token",
+            "client_credentials",
+            DEVICE_CODE_GRANT_TYPE,
+        ]),
+        claims_supported: strings(&[
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "auth_time",
+            "nonce",
+            "amr",
+            "sid",
+            "email",
+        ]),
+    })
+}
+
+// The public keys ID tokens and access tokens are signed with
+#[tracing::instrument(name = "JWKS", skip_all)]
+pub async fn jwks_handler(State(state): State<AppState>) -> Json<JwkSet> {
+    let mut jwks = state.id_token_keys.jwks();
+    jwks.keys.push(access_token_jwk());
+    Json(jwks)
+}
+
+// OpenID Connect Core 1.0 section 5.3.2
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UserInfoResponse {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+// Only access tokens issued to a client for the `openid` scope are accepted. The email is
+// the user's current one, which may have changed since the token was issued.
+#[tracing::instrument(name = "User info", skip_all)]
+pub async fn userinfo_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<UserInfoResponse>, AuthAPIError> {
+    let claims = user.claims;
+    if claims.client_id.is_none() || !has_scope(claims.scope.as_deref(), OPENID_SCOPE) {
+        return Err(AuthAPIError::InsufficientScope);
+    }
+    let email = match has_scope(claims.scope.as_deref(), EMAIL_SCOPE) {
+        true => {
+            // Clients acting on their own have no email
+            let user_id = claims
+                .user_id()
+                .map_err(|_| AuthAPIError::InsufficientScope)?;
+            match state.user_store.read().await.get_user_by_id(user_id).await {
+                Ok(user) => Some(user.email.as_ref().to_owned()),
+                Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+                Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+            }
+        }
+        false => None,
+    };
+    Ok(Json(UserInfoResponse {
+        sub: claims.sub,
+        email,
+    }))
+}
+
+// OpenID Connect RP-Initiated Logout 1.0 section 2
+#[derive(Debug, Deserialize)]
+pub struct EndSessionRequest {
+    pub id_token_hint: Option<String>,
+    pub post_logout_redirect_uri: Option<String>,
+    pub state: Option<String>,
+    pub client_id: Option<String>,
+}
+
+// Log the user out of the session the ID token was issued from, and revoke the tokens the
+// client holds for them, then send them back to the client
+#[tracing::instrument(name = "End session", skip_all)]
+pub async fn end_session_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    jar: CookieJar,
+    Query(request): Query<EndSessionRequest>,
+) -> (CookieJar, Result<Redirect, OAuthError>) {
+    let (actor, jar, result) = end_session(&state, jar, request).await;
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::EndSession, actor.as_deref(), &result),
+    )
+    .await;
+    (jar, result)
+}
+
+// Also returns the user the ID token was issued for, once it is known to be genuine
+async fn end_session(
+    state: &AppState,
+    jar: CookieJar,
+    request: EndSessionRequest,
+) -> (Option<String>, CookieJar, Result<Redirect, OAuthError>) {
+    let Some(hint) = request.id_token_hint.clone() else {
+        return (
+            None,
+            jar,
+            Err(OAuthError::InvalidRequest("id_token_hint is required")),
+        );
+    };
+    // A hint is honoured for as long as a refresh token issued along with it lasts
+    let claims = match state.id_token_keys.verify_hint(
+        &hint,
+        &state.settings.oidc.issuer,
+        state.settings.ttl.refresh_token(),
+    ) {
+        Ok(claims) => claims,
+        Err(e) => {
+            tracing::info!(error = %e, "rejected ID token hint");
+            return (
+                None,
+                jar,
+                Err(OAuthError::InvalidRequest("id_token_hint is invalid")),
+            );
+        }
+    };
+    // By id, as the user's email may have changed since, or the user may be gone
+    let actor = Some(claims.sub.clone());
+
+    let location = match post_logout_location(state, &claims, request).await {
+        Ok(location) => location,
+        Err(e) => return (actor, jar, Err(e)),
+    };
+    if let Err(e) = end_sessions(state, &claims).await {
+        return (actor, jar, Err(e));
+    }
+    // The browser's login is the one that ended, unless it has changed since
+    let logged_in_here = jar
+        .get(JWT_COOKIE_NAME)
+        .and_then(|cookie| decode_token(&SecretString::from(cookie.value().to_owned())).ok())
+        .is_some_and(|cookie_claims| cookie_claims.sid == claims.sid);
+    let jar = match logged_in_here {
+        true => jar.remove(Cookie::build(JWT_COOKIE_NAME).path("/")),
+        false => jar,
+    };
+    (actor, jar, Ok(Redirect::to(&location)))
+}
+
+// Where the user goes afterwards: the client's post-logout redirect URI, which has to be one
+// of its redirect URIs, or the login page
+async fn post_logout_location(
+    state: &AppState,
+    claims: &IdTokenClaims,
+    request: EndSessionRequest,
+) -> Result<String, OAuthError> {
+    if request
+        .client_id
+        .as_deref()
+        .is_some_and(|client_id| client_id != claims.aud)
+    {
+        return Err(OAuthError::InvalidRequest(
+            "client_id does not match id_token_hint",
+        ));
+    }
+    let Some(uri) = request.post_logout_redirect_uri else {
+        return Ok("/".to_owned());
+    };
+    let client = match state
+        .oauth
+        .clients
+        .read()
+        .await
+        .get_client(&claims.aud)
+        .await
+    {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => {
+            return Err(OAuthError::InvalidRequest("client is no longer registered"))
+        }
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+    if !client.allows_redirect_uri(&uri) {
+        return Err(OAuthError::InvalidRequest(
+            "post_logout_redirect_uri is not registered for this client",
+        ));
+    }
+    match request.state {
+        Some(state) => {
+            with_query_params(&uri, &[("state", &state)]).map_err(OAuthError::UnexpectedError)
+        }
+        None => Ok(uri),
+    }
+}
+
+// End the login session the ID token was issued from, and everything the client holds
+async fn end_sessions(state: &AppState, claims: &IdTokenClaims) -> Result<(), OAuthError> {
+    let user_id = UserId::parse(&claims.sub).map_err(OAuthError::UnexpectedError)?;
+    let session = state.sessions.read().await.get_session(claims.sid).await;
+    match session {
+        Ok(session) if session.user_id == user_id => revoke_session(state, session)
+            .await
+            .map_err(|e| OAuthError::UnexpectedError(e.into()))?,
+        // Gone already when the user logged out some other way
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => {}
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    }
+    end_client_sessions(state, user_id, &claims.aud)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+    state
+        .oauth
+        .refresh_tokens
+        .write()
+        .await
+        .remove_refresh_tokens(&claims.aud, user_id)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+    Ok(())
+}
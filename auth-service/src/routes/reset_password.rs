@@ -0,0 +1,92 @@
+use axum::{extract::State, http::StatusCode, Json};
+use secrecy::SecretString;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuditEventKind, AuthAPIError, Email, HashedPassword, LoginAttemptId, TwoFACode,
+        UserStoreError,
+    },
+    utils::audit::{record_audit_event, AuditContext},
+};
+
+// Choose a new password after an admin forced a reset. Logging in with the old password
+// sends the code, and the user logs in with the new one afterwards.
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: SecretString,
+}
+
+#[tracing::instrument(name = "Reset password", skip_all)]
+pub async fn reset_password_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<StatusCode, AuthAPIError> {
+    let actor = request.email.clone();
+    let result = reset_password(&state, request).await;
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::ResetPassword, Some(&actor), &result),
+    )
+    .await;
+    result
+}
+
+async fn reset_password(
+    state: &AppState,
+    request: ResetPasswordRequest,
+) -> Result<StatusCode, AuthAPIError> {
+    let Ok(email) = Email::parse(request.email) else {
+        return Err(AuthAPIError::InvalidCredentials);
+    };
+    let Ok(login_attempt_id) = LoginAttemptId::parse(request.login_attempt_id) else {
+        return Err(AuthAPIError::InvalidCredentials);
+    };
+    let Ok(two_fa_code) = TwoFACode::parse(request.two_fa_code) else {
+        return Err(AuthAPIError::InvalidCredentials);
+    };
+    let Ok(password) = HashedPassword::parse(request.new_password).await else {
+        return Err(AuthAPIError::InvalidCredentials);
+    };
+
+    let mut two_fa_codes = state.two_fa_codes.write().await;
+    let mut user_store = state.user_store.write().await;
+    let mut user = match user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+    let Ok(code_tuple) = two_fa_codes.get_code(user.id).await else {
+        return Err(AuthAPIError::IncorrectCredentials);
+    };
+    if !(login_attempt_id == code_tuple.0 && two_fa_code == code_tuple.1) {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    user.status.ensure_active()?;
+    // The code was sent for a 2FA login
+    if !user.password_reset_required {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+    user.password = password;
+    user.password_reset_required = false;
+    user_store
+        .update_user(&user)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    drop(user_store);
+
+    two_fa_codes
+        .remove_code(user.id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(StatusCode::OK)
+}
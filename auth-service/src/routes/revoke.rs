@@ -0,0 +1,168 @@
+use axum::{extract::State, http::StatusCode, Form};
+use secrecy::SecretString;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuthAPIError, GrantStoreError, OAuthClientStoreError},
+    utils::{
+        admin::RequireAdmin,
+        audit::{record_audit_event, AuditContext},
+        auth::decode_token,
+        clients::AuthenticatedClient,
+        sessions::revoke_token,
+    },
+};
+
+#[derive(Deserialize)]
+pub struct RevokeRequest {
+    pub token: SecretString,
+    // `access_token` or `refresh_token`. Access tokens are JWTs and refresh tokens are not,
+    // so the hint is accepted but not needed.
+    pub token_type_hint: Option<String>,
+    // Public OAuth clients identify themselves with their id alone, confidential ones add
+    // their secret
+    pub client_id: Option<String>,
+    pub client_secret: Option<SecretString>,
+}
+
+// Who asked for the revocation
+enum Caller {
+    // With the admin's identity
+    Admin(String),
+    Client(String),
+    OAuthClient(String),
+}
+
+impl Caller {
+    // Admins send their token as a bearer token, configured clients their credentials with
+    // HTTP Basic, and OAuth clients their `client_id` (and `client_secret`) in the form
+    async fn authenticate(
+        state: &AppState,
+        admin: Result<RequireAdmin, AuthAPIError>,
+        client: Result<AuthenticatedClient, AuthAPIError>,
+        request: &RevokeRequest,
+    ) -> Result<Self, AuthAPIError> {
+        match (admin, client, request.client_id.as_deref()) {
+            (Ok(admin), _, _) => Ok(Self::Admin(admin.identity)),
+            (_, Ok(client), _) => Ok(Self::Client(client.id)),
+            (Err(AuthAPIError::MissingToken), Err(_), Some(id)) => {
+                match state.oauth.clients.read().await.get_client(id).await {
+                    Ok(client) if client.verify_secret(request.client_secret.as_ref()) => {
+                        Ok(Self::OAuthClient(client.id))
+                    }
+                    Ok(_) | Err(OAuthClientStoreError::ClientNotFound) => {
+                        Err(AuthAPIError::InvalidClient)
+                    }
+                    Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+                }
+            }
+            // No bearer token at all, so the client's error is the relevant one
+            (Err(AuthAPIError::MissingToken), Err(e), None) => Err(e),
+            (Err(e), _, _) => Err(e),
+        }
+    }
+
+    // Admins may revoke any token. Configured clients may revoke first-party tokens and
+    // their own, OAuth clients only their own.
+    fn may_revoke(&self, owner: Option<&str>) -> bool {
+        match (self, owner) {
+            (Self::Admin(_), _) => true,
+            (Self::Client(_), None) => true,
+            (Self::Client(id) | Self::OAuthClient(id), Some(owner)) => id == owner,
+            (Self::OAuthClient(_), None) => false,
+        }
+    }
+}
+
+// RFC 7009. Tokens that are invalid, expired or already revoked are reported as revoked,
+// so callers learn nothing about them.
+#[tracing::instrument(name = "Revoke token", skip_all)]
+pub async fn revoke_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    // Taken as results so rejected attempts are audited too
+    admin: Result<RequireAdmin, AuthAPIError>,
+    client: Result<AuthenticatedClient, AuthAPIError>,
+    Form(request): Form<RevokeRequest>,
+) -> Result<StatusCode, AuthAPIError> {
+    let caller = Caller::authenticate(&state, admin, client, &request).await;
+    let admin_identity = match &caller {
+        Ok(Caller::Admin(identity)) => Some(identity.clone()),
+        _ => None,
+    };
+    let (actor, result) = revoke(&state, caller, request).await;
+    record_audit_event(
+        &state,
+        context.admin_event(
+            AuditEventKind::RevokeToken,
+            admin_identity.as_deref(),
+            actor.as_deref(),
+            &result,
+        ),
+    )
+    .await;
+    result
+}
+
+// Also returns the user the token belongs to, once it is known to be one of ours
+async fn revoke(
+    state: &AppState,
+    caller: Result<Caller, AuthAPIError>,
+    request: RevokeRequest,
+) -> (Option<String>, Result<StatusCode, AuthAPIError>) {
+    let caller = match caller {
+        Ok(caller) => caller,
+        Err(e) => return (None, Err(e)),
+    };
+    let Ok(claims) = decode_token(&request.token) else {
+        return revoke_refresh_token(state, &caller, request.token).await;
+    };
+    let actor = Some(claims.actor());
+
+    if !caller.may_revoke(claims.client_id.as_deref()) {
+        return (actor, Err(AuthAPIError::UnauthorizedClient));
+    }
+    match revoke_token(state, request.token, claims.sid).await {
+        Ok(()) => (actor, Ok(StatusCode::OK)),
+        Err(e) => (actor, Err(e)),
+    }
+}
+
+// Access tokens issued with the refresh token stay valid until they expire
+async fn revoke_refresh_token(
+    state: &AppState,
+    caller: &Caller,
+    token: SecretString,
+) -> (Option<String>, Result<StatusCode, AuthAPIError>) {
+    let refresh_tokens = state.oauth.refresh_tokens.clone();
+    let grant = match refresh_tokens.read().await.get_refresh_token(&token).await {
+        Ok(grant) => grant,
+        Err(GrantStoreError::GrantNotFound) => return (None, Ok(StatusCode::OK)),
+        Err(e) => return (None, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+    // Refresh tokens only know their user by id, which stands in if the user is gone
+    let actor = match state
+        .user_store
+        .read()
+        .await
+        .get_user_by_id(grant.user_id)
+        .await
+    {
+        Ok(user) => Some(user.email.as_ref().to_owned()),
+        Err(_) => Some(grant.user_id.to_string()),
+    };
+
+    if !caller.may_revoke(Some(&grant.client_id)) {
+        return (actor, Err(AuthAPIError::UnauthorizedClient));
+    }
+    let removed = refresh_tokens
+        .write()
+        .await
+        .remove_refresh_token(&token)
+        .await;
+    match removed {
+        Ok(()) | Err(GrantStoreError::GrantNotFound) => (actor, Ok(StatusCode::OK)),
+        Err(e) => (actor, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+}
@@ -0,0 +1,241 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuthAPIError, Role, RoleStoreError},
+    utils::{
+        admin::{existing_user, RequireAdmin},
+        audit::{record_audit_event, AuditContext},
+        sessions::user_grants,
+    },
+};
+
+// Role changes only reach a user's tokens when new ones are issued, at their next login
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RoleResponse {
+    pub name: String,
+    pub permissions: Vec<String>,
+}
+
+impl From<Role> for RoleResponse {
+    fn from(role: Role) -> Self {
+        Self {
+            name: role.name,
+            permissions: role.permissions,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RolesResponse {
+    pub roles: Vec<RoleResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PutRoleRequest {
+    pub permissions: Vec<String>,
+}
+
+// The roles of a user and the permissions they grant, as they would be put in a new token
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UserRolesResponse {
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+#[tracing::instrument(name = "Admin list roles", skip_all)]
+pub async fn list_roles_handler(
+    State(state): State<AppState>,
+    _: RequireAdmin,
+) -> Result<Json<RolesResponse>, AuthAPIError> {
+    let roles = state
+        .roles
+        .read()
+        .await
+        .get_roles()
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(Json(RolesResponse {
+        roles: roles.into_iter().map(RoleResponse::from).collect(),
+    }))
+}
+
+// Create a role, or replace the permissions of an existing one
+#[tracing::instrument(name = "Admin put role", skip_all)]
+pub async fn put_role_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(name): Path<String>,
+    Json(request): Json<PutRoleRequest>,
+) -> Result<Json<RoleResponse>, AuthAPIError> {
+    let admin_identity = admin.as_ref().ok().map(|admin| admin.identity.clone());
+    let result = put_role(&state, admin, &name, request).await;
+    record_audit_event(
+        &state,
+        context.admin_event(
+            AuditEventKind::AdminPutRole,
+            admin_identity.as_deref(),
+            None,
+            &result,
+        ),
+    )
+    .await;
+    result
+}
+
+async fn put_role(
+    state: &AppState,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    name: &str,
+    request: PutRoleRequest,
+) -> Result<Json<RoleResponse>, AuthAPIError> {
+    admin?;
+    let role = match Role::new(name, &request.permissions) {
+        Ok(role) => role,
+        Err(reason) => {
+            tracing::info!(reason, "rejected role");
+            return Err(AuthAPIError::InvalidCredentials);
+        }
+    };
+    state
+        .roles
+        .write()
+        .await
+        .put_role(role.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(Json(role.into()))
+}
+
+// The role is taken away from everyone who had it
+#[tracing::instrument(name = "Admin delete role", skip_all)]
+pub async fn delete_role_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, AuthAPIError> {
+    let admin_identity = admin.as_ref().ok().map(|admin| admin.identity.clone());
+    let result = delete_role(&state, admin, &name).await;
+    record_audit_event(
+        &state,
+        context.admin_event(
+            AuditEventKind::AdminDeleteRole,
+            admin_identity.as_deref(),
+            None,
+            &result,
+        ),
+    )
+    .await;
+    result
+}
+
+async fn delete_role(
+    state: &AppState,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    name: &str,
+) -> Result<StatusCode, AuthAPIError> {
+    admin?;
+    match state.roles.write().await.delete_role(name).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(RoleStoreError::RoleNotFound) => Err(AuthAPIError::RoleNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[tracing::instrument(name = "Admin get user roles", skip_all)]
+pub async fn get_user_roles_handler(
+    State(state): State<AppState>,
+    _: RequireAdmin,
+    Path(user): Path<String>,
+) -> Result<Json<UserRolesResponse>, AuthAPIError> {
+    let user = existing_user(&state, user).await?;
+    let grants = user_grants(&state, user.id)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+    Ok(Json(UserRolesResponse {
+        roles: grants.roles,
+        permissions: grants.permissions,
+    }))
+}
+
+#[tracing::instrument(name = "Admin assign role", skip_all)]
+pub async fn assign_role_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path((user, role)): Path<(String, String)>,
+) -> Result<StatusCode, AuthAPIError> {
+    let admin_identity = admin.as_ref().ok().map(|admin| admin.identity.clone());
+    let result = assign_role(&state, admin, user.clone(), &role).await;
+    record_audit_event(
+        &state,
+        context.admin_event(
+            AuditEventKind::AdminAssignRole,
+            admin_identity.as_deref(),
+            Some(&user),
+            &result,
+        ),
+    )
+    .await;
+    result
+}
+
+async fn assign_role(
+    state: &AppState,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    user: String,
+    role: &str,
+) -> Result<StatusCode, AuthAPIError> {
+    admin?;
+    let user = existing_user(state, user).await?;
+    match state.roles.write().await.assign_role(user.id, role).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(RoleStoreError::RoleNotFound) => Err(AuthAPIError::RoleNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[tracing::instrument(name = "Admin unassign role", skip_all)]
+pub async fn unassign_role_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    Path((user, role)): Path<(String, String)>,
+) -> Result<StatusCode, AuthAPIError> {
+    let admin_identity = admin.as_ref().ok().map(|admin| admin.identity.clone());
+    let result = unassign_role(&state, admin, user.clone(), &role).await;
+    record_audit_event(
+        &state,
+        context.admin_event(
+            AuditEventKind::AdminUnassignRole,
+            admin_identity.as_deref(),
+            Some(&user),
+            &result,
+        ),
+    )
+    .await;
+    result
+}
+
+async fn unassign_role(
+    state: &AppState,
+    admin: Result<RequireAdmin, AuthAPIError>,
+    user: String,
+    role: &str,
+) -> Result<StatusCode, AuthAPIError> {
+    admin?;
+    let user = existing_user(state, user).await?;
+    match state.roles.write().await.unassign_role(user.id, role).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(RoleStoreError::RoleNotFound) => Err(AuthAPIError::RoleNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
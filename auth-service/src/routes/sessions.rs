@@ -0,0 +1,130 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuthAPIError, Session, SessionStoreError},
+    utils::{
+        audit::{record_audit_event, AuditContext},
+        auth::{first_party_user, AuthenticatedUser},
+        sessions::{revoke_session, touch_session},
+    },
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+// A session as shown to its owner, without the token
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    // Whether this is the session the request was made with
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current_id: Uuid) -> Self {
+        Self {
+            id: session.id,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            expires_at: session.expires_at,
+            ip: session.ip,
+            user_agent: session.user_agent,
+            current: session.id == current_id,
+        }
+    }
+}
+
+#[tracing::instrument(name = "List sessions", skip_all)]
+pub async fn list_sessions_handler(
+    State(state): State<AppState>,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+) -> Result<Json<SessionsResponse>, AuthAPIError> {
+    let claims = first_party_user(user)?.claims;
+    touch_session(&state, claims.sid).await;
+    let user_id = claims.user_id().map_err(AuthAPIError::UnexpectedError)?;
+
+    let sessions = state
+        .sessions
+        .read()
+        .await
+        .get_sessions(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(SessionsResponse {
+        sessions: sessions
+            .into_iter()
+            .map(|session| SessionResponse::new(session, claims.sid))
+            .collect(),
+    }))
+}
+
+#[tracing::instrument(name = "Delete session", skip_all)]
+pub async fn delete_session_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    jar: CookieJar,
+    Path(id): Path<Uuid>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (actor, jar, result) = delete_session(&state, user, jar, id).await;
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::RevokeSession, actor.as_deref(), &result),
+    )
+    .await;
+    (jar, result)
+}
+
+// Also returns the caller, once their token is known to be valid
+async fn delete_session(
+    state: &AppState,
+    user: Result<AuthenticatedUser, AuthAPIError>,
+    jar: CookieJar,
+    id: Uuid,
+) -> (Option<String>, CookieJar, Result<StatusCode, AuthAPIError>) {
+    let user = match first_party_user(user) {
+        Ok(user) => user,
+        Err(e) => return (None, jar, Err(e)),
+    };
+    let claims = &user.claims;
+    let actor = Some(claims.actor());
+
+    // Sessions of other users are reported as missing, so their ids cannot be probed
+    let session = match state.sessions.read().await.get_session(id).await {
+        Ok(session) if claims.user_id().is_ok_and(|id| id == session.user_id) => session,
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => {
+            return (actor, jar, Err(AuthAPIError::SessionNotFound))
+        }
+        Err(e) => return (actor, jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+    if let Err(e) = revoke_session(state, session).await {
+        return (actor, jar, Err(e));
+    }
+
+    // Revoking the current session logs the caller out
+    let jar = match id == claims.sid {
+        true => user.clear_cookie(jar),
+        false => jar,
+    };
+    (actor, jar, Ok(StatusCode::NO_CONTENT))
+}
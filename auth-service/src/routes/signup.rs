@@ -4,14 +4,30 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, HashedPassword, User},
+    domain::{AuditEventKind, AuthAPIError, Email, HashedPassword, User},
+    utils::audit::{record_audit_event, AuditContext},
 };
 
 #[tracing::instrument(name = "Signup", skip_all)]
 pub async fn signup_handler(
     State(state): State<AppState>,
+    context: AuditContext,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
+    let actor = request.email.clone();
+    let result = signup(&state, request).await;
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::Signup, Some(&actor), &result),
+    )
+    .await;
+    result
+}
+
+async fn signup(
+    state: &AppState,
+    request: SignupRequest,
+) -> Result<(StatusCode, Json<SignupResponse>), AuthAPIError> {
     let Ok(email) = Email::parse(request.email) else {
         return Err(AuthAPIError::InvalidCredentials);
     };
@@ -0,0 +1,436 @@
+use axum::{
+    extract::State,
+    http::{
+        header::{CACHE_CONTROL, PRAGMA},
+        HeaderMap,
+    },
+    response::IntoResponse,
+    Form, Json,
+};
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// RFC 8628 section 3.4
+pub const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AccountStatus, AuditEventKind, AuthorizationGrant, DeviceGrantStatus, GrantStoreError,
+        OAuthClient, OAuthError, RefreshGrant, User, UserId, UserStoreError,
+    },
+    utils::{
+        audit::{record_audit_event, AuditContext},
+        auth::generate_service_token,
+        clients::authenticate_oauth_client,
+        metrics::{ErrorOutcome, OAUTH_TOKEN_REQUESTS_TOTAL},
+        oauth::{generate_grant_token, normalize_scope, scope_within, verify_code_verifier},
+        oidc::{has_scope, IdTokenClaims, EMAIL_SCOPE, OPENID_SCOPE},
+        sessions::start_client_session,
+    },
+};
+
+// RFC 6749 sections 4.1.3, 4.4.2 and 6, with the PKCE verifier of RFC 7636 section 4.5
+// and the device code of RFC 8628 section 3.4.
+// Public clients identify themselves with `client_id` alone. Confidential clients add
+// `client_secret`, or send both with HTTP Basic instead.
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<SecretString>,
+    pub code: Option<SecretString>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<SecretString>,
+    pub refresh_token: Option<SecretString>,
+    pub scope: Option<String>,
+    pub device_code: Option<SecretString>,
+}
+
+// RFC 6749 section 5.1
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AccessTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    // Seconds until the access token expires
+    pub expires_in: u64,
+    // Clients acting on their own get none, as they can simply ask again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // OpenID Connect Core 1.0 section 3.1.3.3: only when a code with the `openid` scope
+    // is redeemed, never on refresh
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+#[tracing::instrument(name = "Token", skip_all)]
+pub async fn token_handler(
+    State(state): State<AppState>,
+    context: AuditContext,
+    headers: HeaderMap,
+    Form(request): Form<TokenRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    // Only known grant types become metric labels
+    let grant_type = match request.grant_type.as_deref() {
+        Some("authorization_code") => "authorization_code",
+        Some("refresh_token") => "refresh_token",
+        Some("client_credentials") => "client_credentials",
+        Some(DEVICE_CODE_GRANT_TYPE) => "device_code",
+        Some(_) => "unsupported",
+        None => "missing",
+    };
+    let (actor, result) = match grant_type {
+        "missing" => (
+            None,
+            Err(OAuthError::InvalidRequest("grant_type is missing")),
+        ),
+        "unsupported" => (None, Err(OAuthError::UnsupportedGrantType)),
+        _ => match authenticate_oauth_client(
+            &state,
+            &headers,
+            request.client_id.as_deref(),
+            request.client_secret.as_ref(),
+        )
+        .await
+        {
+            Err(e) => (None, Err(e)),
+            Ok(client) => match grant_type {
+                "authorization_code" => redeem_code(&state, &context, client, request).await,
+                "refresh_token" => refresh(&state, &context, client, request).await,
+                "device_code" => redeem_device_code(&state, &context, client, request).await,
+                _ => client_credentials(&state, client, request),
+            },
+        },
+    };
+    let outcome = match &result {
+        Ok(_) => "success",
+        Err(e) => e.outcome(),
+    };
+    metrics::counter!(OAUTH_TOKEN_REQUESTS_TOTAL, "grant_type" => grant_type, "outcome" => outcome)
+        .increment(1);
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::OAuthToken, actor.as_deref(), &result),
+    )
+    .await;
+
+    // RFC 6749 section 5.1: responses with tokens must not be cached
+    result.map(|response| {
+        (
+            [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+            Json(response),
+        )
+    })
+}
+
+// Exchange an authorization code for tokens. Also returns the user the code was issued for.
+async fn redeem_code(
+    state: &AppState,
+    context: &AuditContext,
+    client: OAuthClient,
+    request: TokenRequest,
+) -> (Option<String>, Result<AccessTokenResponse, OAuthError>) {
+    let (Some(code), Some(code_verifier)) = (request.code, request.code_verifier) else {
+        return (
+            None,
+            Err(OAuthError::InvalidRequest(
+                "code and code_verifier are required",
+            )),
+        );
+    };
+    // Taking the code uses it up, so it cannot be retried with another verifier
+    let grant = match state.oauth.codes.write().await.take_code(&code).await {
+        Ok(grant) => grant,
+        Err(GrantStoreError::GrantNotFound) => return (None, Err(OAuthError::InvalidGrant)),
+        Err(e) => return (None, Err(OAuthError::UnexpectedError(e.into()))),
+    };
+    let (actor, user) = grant_owner(state, grant.user_id).await;
+    let actor = Some(actor);
+
+    if grant.client_id != client.id
+        || request.redirect_uri.as_deref() != Some(grant.redirect_uri.as_str())
+        || !verify_code_verifier(&code_verifier, &grant.code_challenge)
+    {
+        return (actor, Err(OAuthError::InvalidGrant));
+    }
+    let user = match user {
+        Ok(user) => user,
+        Err(e) => return (actor, Err(e)),
+    };
+    let scope = grant.scope.clone();
+    let id_token = match has_scope(scope.as_deref(), OPENID_SCOPE) {
+        true => match id_token(state, grant, &user) {
+            Ok(id_token) => Some(id_token),
+            Err(e) => return (actor, Err(e)),
+        },
+        false => None,
+    };
+    let result = issue_tokens(state, context, &user, &client, scope.clone(), scope)
+        .await
+        .map(|response| AccessTokenResponse {
+            id_token,
+            ..response
+        });
+    (actor, result)
+}
+
+// An ID token for the user and login session the code was issued from
+fn id_token(
+    state: &AppState,
+    grant: AuthorizationGrant,
+    user: &User,
+) -> Result<String, OAuthError> {
+    let now = Utc::now().timestamp() as usize;
+    let email =
+        has_scope(grant.scope.as_deref(), EMAIL_SCOPE).then(|| user.email.as_ref().to_owned());
+    let claims = IdTokenClaims {
+        iss: state.settings.oidc.issuer.clone(),
+        sub: user.id.to_string(),
+        aud: grant.client_id,
+        exp: now + state.settings.ttl.token().as_secs() as usize,
+        iat: now,
+        auth_time: grant.auth_time.timestamp() as usize,
+        amr: grant.amr,
+        nonce: grant.nonce,
+        sid: grant.session_id,
+        email,
+    };
+    state
+        .id_token_keys
+        .sign(&claims)
+        .map_err(OAuthError::UnexpectedError)
+}
+
+// Exchange a refresh token for new tokens. The refresh token is rotated: it is used up,
+// and a new one is issued for the same scope. Also returns the user it was issued for.
+async fn refresh(
+    state: &AppState,
+    context: &AuditContext,
+    client: OAuthClient,
+    request: TokenRequest,
+) -> (Option<String>, Result<AccessTokenResponse, OAuthError>) {
+    let Some(refresh_token) = request.refresh_token else {
+        return (
+            None,
+            Err(OAuthError::InvalidRequest("refresh_token is required")),
+        );
+    };
+    let refresh_tokens = state.oauth.refresh_tokens.clone();
+    let grant = match refresh_tokens
+        .read()
+        .await
+        .get_refresh_token(&refresh_token)
+        .await
+    {
+        Ok(grant) => grant,
+        Err(GrantStoreError::GrantNotFound) => return (None, Err(OAuthError::InvalidGrant)),
+        Err(e) => return (None, Err(OAuthError::UnexpectedError(e.into()))),
+    };
+    let (actor, user) = grant_owner(state, grant.user_id).await;
+    let actor = Some(actor);
+    if grant.client_id != client.id {
+        return (actor, Err(OAuthError::InvalidGrant));
+    }
+
+    // The new access token may be limited to part of the scope that was granted
+    let scope = match normalize_scope(request.scope.as_deref()) {
+        None => grant.scope.clone(),
+        Some(Ok(scope)) if scope_within(&scope, grant.scope.as_deref()) => Some(scope),
+        Some(_) => return (actor, Err(OAuthError::InvalidScope)),
+    };
+    let user = match user {
+        Ok(user) => user,
+        Err(e) => return (actor, Err(e)),
+    };
+    if user.token_generation != grant.generation {
+        return (actor, Err(OAuthError::InvalidGrant));
+    }
+    // Of two requests with the same refresh token, only the one that removes it succeeds
+    match refresh_tokens
+        .write()
+        .await
+        .remove_refresh_token(&refresh_token)
+        .await
+    {
+        Ok(()) => {}
+        Err(GrantStoreError::GrantNotFound) => return (actor, Err(OAuthError::InvalidGrant)),
+        Err(e) => return (actor, Err(OAuthError::UnexpectedError(e.into()))),
+    }
+    let result = issue_tokens(state, context, &user, &client, scope, grant.scope).await;
+    (actor, result)
+}
+
+// Issue a token to a confidential client for itself (RFC 6749 section 4.4). Without a
+// `scope`, it gets every scope it is allowed. Also returns the client, as the actor.
+fn client_credentials(
+    state: &AppState,
+    client: OAuthClient,
+    request: TokenRequest,
+) -> (Option<String>, Result<AccessTokenResponse, OAuthError>) {
+    let actor = Some(client.id.clone());
+    if !client.is_confidential() {
+        return (actor, Err(OAuthError::UnauthorizedClient));
+    }
+    let scope = match normalize_scope(request.scope.as_deref()) {
+        None if client.allowed_scopes.is_empty() => None,
+        None => Some(client.allowed_scopes.join(" ")),
+        Some(Ok(scope)) if client.allows_scope(&scope) => Some(scope),
+        Some(_) => return (actor, Err(OAuthError::InvalidScope)),
+    };
+    let ttl = state.settings.ttl.token();
+    let issuer = &state.settings.oidc.issuer;
+    let result = generate_service_token(issuer, &client.id, Uuid::new_v4(), ttl, scope.as_deref())
+        .map(|access_token| AccessTokenResponse {
+            access_token: access_token.expose_secret().to_owned(),
+            token_type: "Bearer".to_owned(),
+            expires_in: ttl.as_secs(),
+            refresh_token: None,
+            scope,
+            id_token: None,
+        })
+        .map_err(OAuthError::UnexpectedError);
+    (actor, result)
+}
+
+// Exchange a device code for tokens, once the user approved it (RFC 8628 section 3.5).
+// Until then the device is told to keep polling, at its interval. Also returns the user
+// who approved it.
+async fn redeem_device_code(
+    state: &AppState,
+    context: &AuditContext,
+    client: OAuthClient,
+    request: TokenRequest,
+) -> (Option<String>, Result<AccessTokenResponse, OAuthError>) {
+    let Some(device_code) = request.device_code else {
+        return (
+            None,
+            Err(OAuthError::InvalidRequest("device_code is required")),
+        );
+    };
+    let device_codes = state.oauth.device_codes.clone();
+    let now = Utc::now();
+    let grant = match device_codes
+        .write()
+        .await
+        .poll_device_code(&device_code, now)
+        .await
+    {
+        Ok(grant) => grant,
+        Err(GrantStoreError::GrantNotFound) => return (None, Err(OAuthError::InvalidGrant)),
+        Err(e) => return (None, Err(OAuthError::UnexpectedError(e.into()))),
+    };
+    if grant.client_id != client.id {
+        return (None, Err(OAuthError::InvalidGrant));
+    }
+    if grant.polled_too_soon(now) {
+        return (None, Err(OAuthError::SlowDown));
+    }
+    let user_id = match grant.status {
+        DeviceGrantStatus::Pending => return (None, Err(OAuthError::AuthorizationPending)),
+        DeviceGrantStatus::Approved(user_id) => user_id,
+        DeviceGrantStatus::Denied => {
+            // The device learns of the denial once, then the code is gone
+            if let Err(e) = device_codes
+                .write()
+                .await
+                .remove_device_code(&device_code)
+                .await
+            {
+                if e != GrantStoreError::GrantNotFound {
+                    return (None, Err(OAuthError::UnexpectedError(e.into())));
+                }
+            }
+            return (None, Err(OAuthError::AccessDenied));
+        }
+    };
+    let (actor, user) = grant_owner(state, user_id).await;
+    let actor = Some(actor);
+
+    // Of two polls that find the grant approved, only the one that removes it succeeds
+    match device_codes
+        .write()
+        .await
+        .remove_device_code(&device_code)
+        .await
+    {
+        Ok(()) => {}
+        Err(GrantStoreError::GrantNotFound) => return (actor, Err(OAuthError::InvalidGrant)),
+        Err(e) => return (actor, Err(OAuthError::UnexpectedError(e.into()))),
+    }
+    let user = match user {
+        Ok(user) => user,
+        Err(e) => return (actor, Err(e)),
+    };
+    let scope = grant.scope;
+    let result = issue_tokens(state, context, &user, &client, scope.clone(), scope).await;
+    (actor, result)
+}
+
+// The user a grant was issued for, along with who to record the attempt as: their email,
+// or their id once they are gone. Users deleted, disabled or scheduled for deletion since
+// the grant was issued cannot get tokens.
+async fn grant_owner(state: &AppState, user_id: UserId) -> (String, Result<User, OAuthError>) {
+    match state.user_store.read().await.get_user_by_id(user_id).await {
+        Ok(user) => {
+            let actor = user.email.as_ref().to_owned();
+            match user.status {
+                AccountStatus::Active => (actor, Ok(user)),
+                _ => (actor, Err(OAuthError::InvalidGrant)),
+            }
+        }
+        Err(UserStoreError::UserNotFound) => (user_id.to_string(), Err(OAuthError::InvalidGrant)),
+        Err(e) => (
+            user_id.to_string(),
+            Err(OAuthError::UnexpectedError(e.into())),
+        ),
+    }
+}
+
+// Issue an access token for `scope` and a refresh token for `refresh_scope`
+async fn issue_tokens(
+    state: &AppState,
+    context: &AuditContext,
+    user: &User,
+    client: &OAuthClient,
+    scope: Option<String>,
+    refresh_scope: Option<String>,
+) -> Result<AccessTokenResponse, OAuthError> {
+    let ttl = &state.settings.ttl;
+    let access_token = start_client_session(state, user, context, &client.id, scope.as_deref())
+        .await
+        .map_err(OAuthError::UnexpectedError)?;
+
+    let refresh_ttl = chrono::Duration::from_std(ttl.refresh_token())
+        .wrap_err("invalid refresh token TTL")
+        .map_err(OAuthError::UnexpectedError)?;
+    let refresh_token = generate_grant_token();
+    let grant = RefreshGrant {
+        client_id: client.id.clone(),
+        user_id: user.id,
+        scope: refresh_scope,
+        generation: user.token_generation,
+        expires_at: Utc::now() + refresh_ttl,
+    };
+    state
+        .oauth
+        .refresh_tokens
+        .write()
+        .await
+        .add_refresh_token(&refresh_token, grant)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    Ok(AccessTokenResponse {
+        access_token: access_token.expose_secret().to_owned(),
+        token_type: "Bearer".to_owned(),
+        expires_in: ttl.token().as_secs(),
+        refresh_token: Some(refresh_token.expose_secret().to_owned()),
+        scope,
+        id_token: None,
+    })
+}
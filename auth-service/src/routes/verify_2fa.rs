@@ -1,11 +1,22 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
 use axum_extra::extract::CookieJar;
 use serde::Deserialize;
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{AuditEventKind, AuthAPIError, Email, LoginAttemptId, TwoFACode, UserStoreError},
+    routes::login::TokenDelivery,
+    utils::{
+        audit::{record_audit_event, AuditContext},
+        auth::TWO_FACTOR_AMR,
+        metrics::TWO_FA_VERIFICATIONS_TOTAL,
+        sessions::start_session,
+    },
 };
 
 #[derive(Deserialize)]
@@ -15,14 +26,39 @@ pub struct Verify2FARequest {
     pub login_attempt_id: String,
     #[serde(rename = "2FACode")]
     pub two_fa_code: String,
+    #[serde(rename = "tokenDelivery", default)]
+    pub token_delivery: TokenDelivery,
 }
 
 #[tracing::instrument(name = "Verify 2FA", skip_all)]
 pub async fn verify_2fa_handler(
     State(state): State<AppState>,
+    context: AuditContext,
     jar: CookieJar,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let actor = request.email.clone();
+    let (jar, result) = verify_2fa(&state, &context, jar, request).await;
+    let outcome = match &result {
+        Ok(_) => "success",
+        Err(AuthAPIError::UnexpectedError(_)) => "error",
+        Err(_) => "failed",
+    };
+    metrics::counter!(TWO_FA_VERIFICATIONS_TOTAL, "outcome" => outcome).increment(1);
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::Verify2FA, Some(&actor), &result),
+    )
+    .await;
+    (jar, result)
+}
+
+async fn verify_2fa(
+    state: &AppState,
+    context: &AuditContext,
+    jar: CookieJar,
+    request: Verify2FARequest,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
     let email = match Email::parse(request.email.clone()) {
         Ok(address) => address,
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
@@ -36,21 +72,39 @@ pub async fn verify_2fa_handler(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
     let mut two_fa_codes = state.two_fa_codes.write().await;
-    let code_tuple = match two_fa_codes.get_code(&email).await {
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+    let code_tuple = match two_fa_codes.get_code(user.id).await {
         Ok(code) => code,
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
     if !(login_attempt_id == code_tuple.0 && two_fa_code == code_tuple.1) {
         return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
-    match two_fa_codes.remove_code(&email).await {
+    // An admin may have changed the account since the code was sent. A code sent for a
+    // password reset is kept for `/reset-password`.
+    if let Err(e) = user.status.ensure_active() {
+        return (jar, Err(e));
+    }
+    if user.password_reset_required {
+        return (jar, Err(AuthAPIError::PasswordResetRequired));
+    }
+    match two_fa_codes.remove_code(user.id).await {
         Ok(_) => (),
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
     }
-    let cookie = match generate_auth_cookie(&email) {
+    let cookie = match start_session(state, &user, context, TWO_FACTOR_AMR).await {
         Ok(cookie) => cookie,
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
     };
-    let updated_jar = jar.add(cookie);
-    (updated_jar, Ok(StatusCode::OK.into_response()))
+    let ttl = state.settings.ttl.token();
+    let (updated_jar, body) = request.token_delivery.deliver(jar, cookie, ttl);
+    let response = match body {
+        Some(body) => Json(body).into_response(),
+        None => StatusCode::OK.into_response(),
+    };
+    (updated_jar, Ok(response))
 }
@@ -1,26 +1,118 @@
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{extract::State, http::HeaderMap, Json};
+use chrono::{DateTime, Utc};
 use secrecy::SecretString;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::validate_token};
+use crate::{
+    app_state::AppState,
+    domain::{AuditEventKind, AuthAPIError},
+    utils::{
+        api_keys::{api_key_header, is_api_key, validate_api_key},
+        audit::{record_audit_event, AuditContext},
+        auth::{validate_token, AuthToken},
+        sessions::touch_session,
+    },
+};
 
 #[derive(Deserialize)]
 pub struct VerifyTokenRequest {
     pub token: SecretString,
 }
 
-#[derive(Deserialize, Serialize)]
+// Who a valid token or API key belongs to, so services behind auth-service need not read
+// the credential themselves
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ValidateTokenResponse {
-    pub message: String,
+    // The user's id, or the client id for tokens of clients acting on their own
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    // API keys have no session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
+    // API keys without an expiry have none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // Only first-party tokens carry roles and permissions
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
 }
 
 #[tracing::instrument(name = "Verify JWT Token", skip_all)]
 pub async fn verify_token_handler(
     State(state): State<AppState>,
-    Json(request): Json<VerifyTokenRequest>,
-) -> Result<StatusCode, AuthAPIError> {
-    match validate_token(&request.token, state.banned_tokens.clone()).await {
-        Ok(_) => Ok(StatusCode::OK),
-        Err(_) => Err(AuthAPIError::InvalidToken),
+    context: AuditContext,
+    headers: HeaderMap,
+    caller_token: Result<AuthToken, AuthAPIError>,
+    request: Option<Json<VerifyTokenRequest>>,
+) -> Result<Json<ValidateTokenResponse>, AuthAPIError> {
+    // A token in the body is checked on behalf of someone else, so it wins over the
+    // caller's own API key, bearer header or cookie
+    let token = match (request, api_key_header(&headers)) {
+        (Some(Json(request)), _) => Ok(request.token),
+        (None, Some(key)) => Ok(SecretString::from(key)),
+        (None, None) => caller_token.map(|caller_token| caller_token.token),
+    };
+    let (actor, result) = verify_token(&state, token).await;
+    record_audit_event(
+        &state,
+        context.event(AuditEventKind::VerifyToken, actor.as_deref(), &result),
+    )
+    .await;
+    result
+}
+
+// Also returns the user the token belongs to, once it is known to be valid
+async fn verify_token(
+    state: &AppState,
+    token: Result<SecretString, AuthAPIError>,
+) -> (
+    Option<String>,
+    Result<Json<ValidateTokenResponse>, AuthAPIError>,
+) {
+    let token = match token {
+        Ok(token) => token,
+        Err(e) => return (None, Err(e)),
+    };
+    if is_api_key(&token) {
+        return match validate_api_key(state, &token).await {
+            Ok((api_key, owner)) => {
+                let timestamp = |at: DateTime<Utc>| at.timestamp().try_into().ok();
+                let response = ValidateTokenResponse {
+                    sub: owner.id.to_string(),
+                    email: Some(owner.email.as_ref().to_owned()),
+                    sid: None,
+                    exp: api_key.expires_at.and_then(timestamp),
+                    scope: api_key.scope,
+                    roles: Vec::new(),
+                    permissions: Vec::new(),
+                };
+                (response.email.clone(), Ok(Json(response)))
+            }
+            Err(_) => (None, Err(AuthAPIError::InvalidToken)),
+        };
+    }
+    let banned_tokens = state.banned_tokens.clone();
+    let user_store = state.user_store.clone();
+    let clients = state.oauth.clients.clone();
+    match validate_token(&token, banned_tokens, user_store, clients).await {
+        Ok(claims) => {
+            touch_session(state, claims.sid).await;
+            let actor = Some(claims.actor());
+            let response = ValidateTokenResponse {
+                sub: claims.sub,
+                email: claims.email,
+                sid: Some(claims.sid),
+                exp: Some(claims.exp),
+                scope: claims.scope,
+                roles: claims.roles,
+                permissions: claims.permissions,
+            };
+            (actor, Ok(Json(response)))
+        }
+        Err(_) => (None, Err(AuthAPIError::InvalidToken)),
     }
 }
@@ -0,0 +1,145 @@
+// Tests every implementation of a store runs, so they all behave the same. Each store's
+// own tests call these with a fresh, empty store.
+
+use secrecy::SecretString;
+
+use crate::domain::{
+    data_stores::{
+        BannedTokenStore, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+    },
+    UserId,
+};
+
+pub async fn should_add_valid_code_to_2fa_store(mut store: impl TwoFACodeStore) {
+    let user_id = UserId::new();
+    let login_attempt_id = LoginAttemptId::default();
+    let code = TwoFACode::default();
+
+    let result = store
+        .add_code(user_id, login_attempt_id.clone(), code.clone())
+        .await;
+
+    assert!(result.is_ok());
+    assert_eq!(
+        store.get_code(user_id).await.unwrap(),
+        (login_attempt_id, code)
+    );
+}
+
+pub async fn should_replace_existing_code_in_2fa_store(mut store: impl TwoFACodeStore) {
+    let user_id = UserId::new();
+    store
+        .add_code(user_id, LoginAttemptId::default(), TwoFACode::default())
+        .await
+        .unwrap();
+    let login_attempt_id = LoginAttemptId::default();
+    let code = TwoFACode::default();
+
+    let result = store
+        .add_code(user_id, login_attempt_id.clone(), code.clone())
+        .await;
+
+    assert!(result.is_ok());
+    assert_eq!(
+        store.get_code(user_id).await.unwrap(),
+        (login_attempt_id, code)
+    );
+}
+
+pub async fn should_remove_matching_code_from_2fa_store(mut store: impl TwoFACodeStore) {
+    let user_id = UserId::new();
+    store
+        .add_code(user_id, LoginAttemptId::default(), TwoFACode::default())
+        .await
+        .unwrap();
+
+    let result = store.remove_code(user_id).await;
+
+    assert!(result.is_ok());
+    assert_eq!(
+        store.get_code(user_id).await.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+}
+
+pub async fn should_not_remove_missing_code_from_2fa_store(mut store: impl TwoFACodeStore) {
+    let stored_user_id = UserId::new();
+    let attempted_user_id = UserId::new();
+    store
+        .add_code(
+            stored_user_id,
+            LoginAttemptId::default(),
+            TwoFACode::default(),
+        )
+        .await
+        .unwrap();
+
+    let result = store.remove_code(attempted_user_id).await;
+
+    assert_eq!(
+        result.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+}
+
+pub async fn should_get_matching_code_from_2fa_store(mut store: impl TwoFACodeStore) {
+    let user_id = UserId::new();
+    let login_attempt_id = LoginAttemptId::default();
+    let code = TwoFACode::default();
+    store
+        .add_code(user_id, login_attempt_id.clone(), code.clone())
+        .await
+        .unwrap();
+
+    let result = store.get_code(user_id).await;
+
+    assert_eq!(result.unwrap(), (login_attempt_id, code));
+}
+
+pub async fn should_not_get_missing_code_from_2fa_store(mut store: impl TwoFACodeStore) {
+    let stored_user_id = UserId::new();
+    let attempted_user_id = UserId::new();
+    store
+        .add_code(
+            stored_user_id,
+            LoginAttemptId::default(),
+            TwoFACode::default(),
+        )
+        .await
+        .unwrap();
+
+    let result = store.get_code(attempted_user_id).await;
+
+    assert_eq!(
+        result.unwrap_err(),
+        TwoFACodeStoreError::LoginAttemptIdNotFound
+    );
+}
+
+pub async fn should_add_banned_token(mut store: impl BannedTokenStore) {
+    let token = SecretString::new("foobar".to_owned().into_boxed_str());
+
+    let result = store.add_token(token.clone()).await;
+
+    assert!(result.is_ok());
+    assert!(store.check_token(&token).await.unwrap());
+}
+
+pub async fn should_add_banned_token_twice(mut store: impl BannedTokenStore) {
+    let token = SecretString::new("foobar".to_owned().into_boxed_str());
+    store.add_token(token.clone()).await.unwrap();
+
+    let result = store.add_token(token.clone()).await;
+
+    assert!(result.is_ok());
+    assert!(store.check_token(&token).await.unwrap());
+}
+
+pub async fn should_only_contain_banned_tokens(mut store: impl BannedTokenStore) {
+    let token = SecretString::new("foobar".to_owned().into_boxed_str());
+    let other = SecretString::new("other".to_owned().into_boxed_str());
+    store.add_token(token.clone()).await.unwrap();
+
+    assert!(store.check_token(&token).await.unwrap());
+    assert!(!store.check_token(&other).await.unwrap());
+}
@@ -2,28 +2,28 @@ use std::collections::HashMap;
 
 use crate::domain::{
     data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    email::Email,
+    UserId,
 };
 
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<UserId, (LoginAttemptId, TwoFACode)>,
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(
         &mut self,
-        email: Email,
+        user_id: UserId,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        self.codes.insert(user_id, (login_attempt_id, code));
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        match self.codes.remove(email) {
+    async fn remove_code(&mut self, user_id: UserId) -> Result<(), TwoFACodeStoreError> {
+        match self.codes.remove(&user_id) {
             Some(_) => Ok(()),
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
@@ -31,9 +31,9 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
 
     async fn get_code(
         &self,
-        email: &Email,
+        user_id: UserId,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        match self.codes.get(email) {
+        match self.codes.get(&user_id) {
             Some(result) => Ok(result.clone()),
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
@@ -43,88 +43,40 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use fake::{faker::internet::en::SafeEmail, Fake};
+    use crate::services::data_stores::contract_tests;
 
     #[tokio::test]
     async fn should_add_valid_code_to_2fa_store() {
-        let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(SafeEmail().fake()).unwrap();
-        let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
+        contract_tests::should_add_valid_code_to_2fa_store(HashmapTwoFACodeStore::default()).await;
+    }
 
-        let result = store
-            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+    #[tokio::test]
+    async fn should_replace_existing_code_in_2fa_store() {
+        contract_tests::should_replace_existing_code_in_2fa_store(HashmapTwoFACodeStore::default())
             .await;
-
-        assert!(result.is_ok());
-        assert_eq!(store.codes.get(&email), Some(&(login_attempt_id, code)))
     }
 
     #[tokio::test]
     async fn should_remove_matching_code_from_2fa_store() {
-        let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(SafeEmail().fake()).unwrap();
-        let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
-        store.codes.insert(email.clone(), (login_attempt_id, code));
-
-        let result = store.remove_code(&email).await;
-
-        assert!(result.is_ok());
-        assert_eq!(store.codes.get(&email), None);
+        contract_tests::should_remove_matching_code_from_2fa_store(HashmapTwoFACodeStore::default()).await;
     }
 
     #[tokio::test]
     async fn should_not_remove_missing_code_from_2fa_store() {
-        let mut store = HashmapTwoFACodeStore::default();
-        let stored_email = Email::parse(SafeEmail().fake()).unwrap();
-        let attempted_email = Email::parse(SafeEmail().fake()).unwrap();
-        let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
-        store
-            .codes
-            .insert(stored_email, (login_attempt_id.clone(), code.clone()));
-
-        let result = store.remove_code(&attempted_email).await;
-
-        assert!(result.is_err());
-        assert_eq!(
-            result.unwrap_err(),
-            TwoFACodeStoreError::LoginAttemptIdNotFound
-        );
+        contract_tests::should_not_remove_missing_code_from_2fa_store(
+            HashmapTwoFACodeStore::default(),
+        )
+        .await;
     }
 
     #[tokio::test]
     async fn should_get_matching_code_from_2fa_store() {
-        let mut store = HashmapTwoFACodeStore::default();
-        let email = Email::parse(SafeEmail().fake()).unwrap();
-        let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
-        store
-            .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
-
-        let result = store.get_code(&email).await;
-
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), (login_attempt_id, code));
+        contract_tests::should_get_matching_code_from_2fa_store(HashmapTwoFACodeStore::default())
+            .await;
     }
 
     #[tokio::test]
     async fn should_not_get_missing_code_from_2fa_store() {
-        let mut store = HashmapTwoFACodeStore::default();
-        let stored_email = Email::parse(SafeEmail().fake()).unwrap();
-        let attempted_email = Email::parse(SafeEmail().fake()).unwrap();
-        let login_attempt_id = LoginAttemptId::default();
-        let code = TwoFACode::default();
-        store.codes.insert(stored_email, (login_attempt_id, code));
-
-        let result = store.get_code(&attempted_email).await;
-
-        assert!(result.is_err());
-        assert_eq!(
-            result.unwrap_err(),
-            TwoFACodeStoreError::LoginAttemptIdNotFound
-        );
+        contract_tests::should_not_get_missing_code_from_2fa_store(HashmapTwoFACodeStore::default()).await;
     }
 }
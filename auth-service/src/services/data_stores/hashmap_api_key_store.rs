@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+use std::{cmp::Reverse, collections::HashMap};
+use uuid::Uuid;
+
+use crate::domain::{ApiKey, ApiKeyStore, ApiKeyStoreError, UserId};
+
+#[derive(Default)]
+pub struct HashmapApiKeyStore {
+    keys: HashMap<Uuid, ApiKey>,
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for HashmapApiKeyStore {
+    async fn add_api_key(&mut self, key: ApiKey) -> Result<(), ApiKeyStoreError> {
+        // Nothing purges this store, so expired keys are dropped whenever one is added
+        let now = Utc::now();
+        self.keys.retain(|_, key| !key.is_expired(now));
+        self.keys.insert(key.id, key);
+        Ok(())
+    }
+
+    async fn get_api_key(&self, key_hash: &str) -> Result<ApiKey, ApiKeyStoreError> {
+        let now = Utc::now();
+        self.keys
+            .values()
+            .find(|key| key.key_hash == key_hash && !key.is_expired(now))
+            .cloned()
+            .ok_or(ApiKeyStoreError::ApiKeyNotFound)
+    }
+
+    async fn get_api_keys(&self, user_id: UserId) -> Result<Vec<ApiKey>, ApiKeyStoreError> {
+        let now = Utc::now();
+        let mut keys: Vec<ApiKey> = self
+            .keys
+            .values()
+            .filter(|key| key.user_id == user_id && !key.is_expired(now))
+            .cloned()
+            .collect();
+        keys.sort_by_key(|key| Reverse(key.created_at));
+        Ok(keys)
+    }
+
+    async fn touch_api_key(
+        &mut self,
+        id: Uuid,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), ApiKeyStoreError> {
+        match self.keys.get_mut(&id) {
+            Some(key) if !key.is_expired(Utc::now()) => {
+                key.last_used_at = Some(used_at);
+                Ok(())
+            }
+            _ => Err(ApiKeyStoreError::ApiKeyNotFound),
+        }
+    }
+
+    async fn remove_api_key(&mut self, user_id: UserId, id: Uuid) -> Result<(), ApiKeyStoreError> {
+        match self.keys.get(&id) {
+            Some(key) if key.user_id == user_id => {
+                self.keys.remove(&id);
+                Ok(())
+            }
+            _ => Err(ApiKeyStoreError::ApiKeyNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn key(user_id: UserId, key_hash: &str, created_at: DateTime<Utc>) -> ApiKey {
+        ApiKey {
+            id: Uuid::new_v4(),
+            user_id,
+            name: "deploy script".to_owned(),
+            key_hash: key_hash.to_owned(),
+            key_hint: "ak_abcd".to_owned(),
+            scope: None,
+            created_at,
+            expires_at: None,
+            last_used_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_find_unexpired_keys_by_hash() {
+        let mut store = HashmapApiKeyStore::default();
+        let user = UserId::new();
+        let now = Utc::now();
+        let active = key(user, "active", now);
+        let expired = ApiKey {
+            expires_at: Some(now - Duration::minutes(1)),
+            ..key(user, "expired", now)
+        };
+        store.add_api_key(active.clone()).await.unwrap();
+        store.add_api_key(expired).await.unwrap();
+
+        assert_eq!(store.get_api_key("active").await.unwrap(), active);
+        assert_eq!(
+            store.get_api_key("expired").await.unwrap_err(),
+            ApiKeyStoreError::ApiKeyNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn should_list_own_keys_newest_first() {
+        let mut store = HashmapApiKeyStore::default();
+        let user = UserId::new();
+        let now = Utc::now();
+        let older = key(user, "older", now - Duration::minutes(2));
+        let newer = key(user, "newer", now);
+        let other = key(UserId::new(), "other", now);
+        for key in [older.clone(), newer.clone(), other] {
+            store.add_api_key(key).await.unwrap();
+        }
+
+        let ids: Vec<Uuid> = store
+            .get_api_keys(user)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|key| key.id)
+            .collect();
+
+        assert_eq!(ids, vec![newer.id, older.id]);
+    }
+
+    #[tokio::test]
+    async fn should_touch_and_remove_only_own_key() {
+        let mut store = HashmapApiKeyStore::default();
+        let user = UserId::new();
+        let key = key(user, "hash", Utc::now());
+        store.add_api_key(key.clone()).await.unwrap();
+        let used_at = key.created_at + Duration::minutes(1);
+
+        store.touch_api_key(key.id, used_at).await.unwrap();
+
+        assert_eq!(
+            store.get_api_key("hash").await.unwrap().last_used_at,
+            Some(used_at)
+        );
+        assert_eq!(
+            store
+                .remove_api_key(UserId::new(), key.id)
+                .await
+                .unwrap_err(),
+            ApiKeyStoreError::ApiKeyNotFound
+        );
+        store.remove_api_key(user, key.id).await.unwrap();
+        assert_eq!(
+            store.get_api_key("hash").await.unwrap_err(),
+            ApiKeyStoreError::ApiKeyNotFound
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::Utc;
+use secrecy::SecretString;
+use std::collections::HashMap;
+
+use crate::domain::{grant_key, AuthorizationCodeStore, AuthorizationGrant, GrantStoreError};
+
+#[derive(Default)]
+pub struct HashmapAuthorizationCodeStore {
+    codes: HashMap<String, AuthorizationGrant>,
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for HashmapAuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: &SecretString,
+        grant: AuthorizationGrant,
+    ) -> Result<(), GrantStoreError> {
+        // Nothing purges this store, so expired codes are dropped whenever one is added
+        let now = Utc::now();
+        self.codes.retain(|_, grant| !grant.is_expired(now));
+        self.codes.insert(grant_key(code), grant);
+        Ok(())
+    }
+
+    async fn take_code(
+        &mut self,
+        code: &SecretString,
+    ) -> Result<AuthorizationGrant, GrantStoreError> {
+        self.codes
+            .remove(&grant_key(code))
+            .filter(|grant| !grant.is_expired(Utc::now()))
+            .ok_or(GrantStoreError::GrantNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::UserId;
+    use chrono::{DateTime, Duration};
+    use uuid::Uuid;
+
+    fn grant(expires_at: DateTime<Utc>) -> AuthorizationGrant {
+        AuthorizationGrant {
+            client_id: "client".to_owned(),
+            redirect_uri: "https://app.example.com/callback".to_owned(),
+            user_id: UserId::new(),
+            scope: Some("profile".to_owned()),
+            code_challenge: "challenge".to_owned(),
+            nonce: None,
+            auth_time: Utc::now(),
+            amr: vec!["pwd".to_owned()],
+            session_id: Uuid::new_v4(),
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_redeem_code_only_once() {
+        let mut store = HashmapAuthorizationCodeStore::default();
+        let code = SecretString::from("code");
+        let grant = grant(Utc::now() + Duration::minutes(1));
+        store.add_code(&code, grant.clone()).await.unwrap();
+
+        assert_eq!(store.take_code(&code).await.unwrap(), grant);
+        assert_eq!(
+            store.take_code(&code).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_redeem_expired_code() {
+        let mut store = HashmapAuthorizationCodeStore::default();
+        let code = SecretString::from("code");
+        store
+            .add_code(&code, grant(Utc::now() - Duration::seconds(1)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.take_code(&code).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+    }
+}
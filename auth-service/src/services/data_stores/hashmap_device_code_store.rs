@@ -0,0 +1,211 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::eyre;
+use secrecy::SecretString;
+use std::collections::HashMap;
+
+use crate::domain::{grant_key, DeviceCodeStore, DeviceGrant, DeviceGrantStatus, GrantStoreError};
+
+#[derive(Default)]
+pub struct HashmapDeviceCodeStore {
+    grants: HashMap<String, DeviceGrant>,
+}
+
+impl HashmapDeviceCodeStore {
+    fn get_grant_mut(&mut self, key: &str) -> Result<&mut DeviceGrant, GrantStoreError> {
+        self.grants
+            .get_mut(key)
+            .filter(|grant| !grant.is_expired(Utc::now()))
+            .ok_or(GrantStoreError::GrantNotFound)
+    }
+
+    fn user_code_key(&self, user_code: &str) -> Option<String> {
+        let now = Utc::now();
+        self.grants
+            .iter()
+            .find(|(_, grant)| grant.user_code == user_code && !grant.is_expired(now))
+            .map(|(key, _)| key.clone())
+    }
+}
+
+#[async_trait::async_trait]
+impl DeviceCodeStore for HashmapDeviceCodeStore {
+    async fn add_device_code(
+        &mut self,
+        device_code: &SecretString,
+        grant: DeviceGrant,
+    ) -> Result<(), GrantStoreError> {
+        // Nothing purges this store, so expired codes are dropped whenever one is added
+        let now = Utc::now();
+        self.grants.retain(|_, grant| !grant.is_expired(now));
+        if self.user_code_key(&grant.user_code).is_some() {
+            return Err(GrantStoreError::UnexpectedError(eyre!(
+                "user code is already in use"
+            )));
+        }
+        self.grants.insert(grant_key(device_code), grant);
+        Ok(())
+    }
+
+    async fn get_user_code(&self, user_code: &str) -> Result<DeviceGrant, GrantStoreError> {
+        self.user_code_key(user_code)
+            .and_then(|key| self.grants.get(&key))
+            .cloned()
+            .ok_or(GrantStoreError::GrantNotFound)
+    }
+
+    async fn set_user_code_status(
+        &mut self,
+        user_code: &str,
+        status: DeviceGrantStatus,
+    ) -> Result<(), GrantStoreError> {
+        let key = self
+            .user_code_key(user_code)
+            .ok_or(GrantStoreError::GrantNotFound)?;
+        let grant = self.get_grant_mut(&key)?;
+        if grant.status != DeviceGrantStatus::Pending {
+            return Err(GrantStoreError::GrantNotFound);
+        }
+        grant.status = status;
+        Ok(())
+    }
+
+    async fn poll_device_code(
+        &mut self,
+        device_code: &SecretString,
+        now: DateTime<Utc>,
+    ) -> Result<DeviceGrant, GrantStoreError> {
+        let grant = self.get_grant_mut(&grant_key(device_code))?;
+        let before = grant.clone();
+        grant.record_poll(now);
+        Ok(before)
+    }
+
+    async fn remove_device_code(
+        &mut self,
+        device_code: &SecretString,
+    ) -> Result<(), GrantStoreError> {
+        match self.grants.remove(&grant_key(device_code)) {
+            Some(_) => Ok(()),
+            None => Err(GrantStoreError::GrantNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::UserId;
+    use chrono::Duration;
+
+    fn grant(user_code: &str, expires_at: DateTime<Utc>) -> DeviceGrant {
+        DeviceGrant {
+            client_id: "client".to_owned(),
+            user_code: user_code.to_owned(),
+            scope: Some("profile".to_owned()),
+            status: DeviceGrantStatus::Pending,
+            interval_seconds: 5,
+            last_polled_at: None,
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_approve_user_code_once() {
+        let mut store = HashmapDeviceCodeStore::default();
+        let device_code = SecretString::from("device-code");
+        let grant = grant("BCDFGHJK", Utc::now() + Duration::minutes(10));
+        store
+            .add_device_code(&device_code, grant.clone())
+            .await
+            .unwrap();
+        assert_eq!(store.get_user_code("BCDFGHJK").await.unwrap(), grant);
+
+        let user_id = UserId::new();
+        store
+            .set_user_code_status("BCDFGHJK", DeviceGrantStatus::Approved(user_id))
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .set_user_code_status("BCDFGHJK", DeviceGrantStatus::Denied)
+                .await
+                .unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+
+        let polled = store
+            .poll_device_code(&device_code, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(polled.status, DeviceGrantStatus::Approved(user_id));
+        assert_eq!(polled.last_polled_at, None);
+        store.remove_device_code(&device_code).await.unwrap();
+        assert_eq!(
+            store.remove_device_code(&device_code).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn should_record_polls() {
+        let mut store = HashmapDeviceCodeStore::default();
+        let device_code = SecretString::from("device-code");
+        store
+            .add_device_code(
+                &device_code,
+                grant("BCDFGHJK", Utc::now() + Duration::minutes(10)),
+            )
+            .await
+            .unwrap();
+        let now = Utc::now();
+
+        store.poll_device_code(&device_code, now).await.unwrap();
+        let polled = store.poll_device_code(&device_code, now).await.unwrap();
+
+        assert_eq!(polled.last_polled_at, Some(now));
+        assert!(polled.polled_too_soon(now));
+        let polled = store.poll_device_code(&device_code, now).await.unwrap();
+        assert_eq!(polled.interval_seconds, 10);
+    }
+
+    #[tokio::test]
+    async fn should_not_return_expired_codes() {
+        let mut store = HashmapDeviceCodeStore::default();
+        let device_code = SecretString::from("device-code");
+        store
+            .add_device_code(
+                &device_code,
+                grant("BCDFGHJK", Utc::now() - Duration::seconds(1)),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_user_code("BCDFGHJK").await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+        assert_eq!(
+            store
+                .poll_device_code(&device_code, Utc::now())
+                .await
+                .unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn should_reject_user_code_in_use() {
+        let mut store = HashmapDeviceCodeStore::default();
+        let expires_at = Utc::now() + Duration::minutes(10);
+        store
+            .add_device_code(&SecretString::from("first"), grant("BCDFGHJK", expires_at))
+            .await
+            .unwrap();
+
+        let result = store
+            .add_device_code(&SecretString::from("second"), grant("BCDFGHJK", expires_at))
+            .await;
+
+        assert!(matches!(result, Err(GrantStoreError::UnexpectedError(_))));
+    }
+}
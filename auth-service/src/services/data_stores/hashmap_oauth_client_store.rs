@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use crate::domain::{OAuthClient, OAuthClientStore, OAuthClientStoreError};
+
+#[derive(Default)]
+pub struct HashmapOAuthClientStore {
+    clients: HashMap<String, OAuthClient>,
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for HashmapOAuthClientStore {
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError> {
+        self.clients.insert(client.id.clone(), client);
+        Ok(())
+    }
+
+    async fn get_client(&self, id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        self.clients
+            .get(id)
+            .cloned()
+            .ok_or(OAuthClientStoreError::ClientNotFound)
+    }
+
+    async fn get_clients(&self) -> Result<Vec<OAuthClient>, OAuthClientStoreError> {
+        let mut clients: Vec<OAuthClient> = self.clients.values().cloned().collect();
+        clients.sort_by_key(|client| client.created_at);
+        Ok(clients)
+    }
+
+    async fn remove_client(&mut self, id: &str) -> Result<(), OAuthClientStoreError> {
+        match self.clients.remove(id) {
+            Some(_) => Ok(()),
+            None => Err(OAuthClientStoreError::ClientNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, Utc};
+
+    fn client(id: &str, created_at: DateTime<Utc>) -> OAuthClient {
+        OAuthClient {
+            id: id.to_owned(),
+            name: "Client".to_owned(),
+            redirect_uris: vec!["https://app.example.com/callback".to_owned()],
+            secret_hash: None,
+            allowed_scopes: Vec::new(),
+            created_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_add_list_and_remove_clients() {
+        let mut store = HashmapOAuthClientStore::default();
+        let now = Utc::now();
+        let newer = client("newer", now);
+        let older = client("older", now - Duration::minutes(1));
+        store.add_client(newer.clone()).await.unwrap();
+        store.add_client(older.clone()).await.unwrap();
+
+        assert_eq!(store.get_client("newer").await.unwrap(), newer);
+        assert_eq!(store.get_clients().await.unwrap(), vec![older, newer]);
+        assert!(store.remove_client("newer").await.is_ok());
+        assert_eq!(
+            store.get_client("newer").await.unwrap_err(),
+            OAuthClientStoreError::ClientNotFound
+        );
+        assert_eq!(
+            store.remove_client("newer").await.unwrap_err(),
+            OAuthClientStoreError::ClientNotFound
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use chrono::Utc;
+use secrecy::SecretString;
+use std::collections::HashMap;
+
+use crate::domain::{grant_key, GrantStoreError, RefreshGrant, RefreshTokenStore, UserId};
+
+#[derive(Default)]
+pub struct HashmapRefreshTokenStore {
+    tokens: HashMap<String, RefreshGrant>,
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn add_refresh_token(
+        &mut self,
+        token: &SecretString,
+        grant: RefreshGrant,
+    ) -> Result<(), GrantStoreError> {
+        // Nothing purges this store, so expired tokens are dropped whenever one is added
+        let now = Utc::now();
+        self.tokens.retain(|_, grant| !grant.is_expired(now));
+        self.tokens.insert(grant_key(token), grant);
+        Ok(())
+    }
+
+    async fn get_refresh_token(
+        &self,
+        token: &SecretString,
+    ) -> Result<RefreshGrant, GrantStoreError> {
+        self.tokens
+            .get(&grant_key(token))
+            .filter(|grant| !grant.is_expired(Utc::now()))
+            .cloned()
+            .ok_or(GrantStoreError::GrantNotFound)
+    }
+
+    async fn remove_refresh_token(&mut self, token: &SecretString) -> Result<(), GrantStoreError> {
+        match self.tokens.remove(&grant_key(token)) {
+            Some(_) => Ok(()),
+            None => Err(GrantStoreError::GrantNotFound),
+        }
+    }
+
+    async fn remove_refresh_tokens(
+        &mut self,
+        client_id: &str,
+        user_id: UserId,
+    ) -> Result<u64, GrantStoreError> {
+        let before = self.tokens.len();
+        self.tokens
+            .retain(|_, grant| grant.client_id != client_id || grant.user_id != user_id);
+        Ok((before - self.tokens.len()) as u64)
+    }
+
+    async fn remove_user_refresh_tokens(
+        &mut self,
+        user_id: UserId,
+    ) -> Result<u64, GrantStoreError> {
+        let before = self.tokens.len();
+        self.tokens.retain(|_, grant| grant.user_id != user_id);
+        Ok((before - self.tokens.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration};
+    use uuid::Uuid;
+
+    // The user every grant of these tests is for
+    fn user_id() -> UserId {
+        UserId::from(Uuid::from_u128(1))
+    }
+
+    fn grant(expires_at: DateTime<Utc>) -> RefreshGrant {
+        RefreshGrant {
+            client_id: "client".to_owned(),
+            user_id: user_id(),
+            scope: None,
+            generation: 0,
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_add_get_and_remove_refresh_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let token = SecretString::from("token");
+        let grant = grant(Utc::now() + Duration::days(1));
+        store
+            .add_refresh_token(&token, grant.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_refresh_token(&token).await.unwrap(), grant);
+        assert!(store.remove_refresh_token(&token).await.is_ok());
+        assert_eq!(
+            store.get_refresh_token(&token).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+        assert_eq!(
+            store.remove_refresh_token(&token).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_return_expired_refresh_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let token = SecretString::from("token");
+        store
+            .add_refresh_token(&token, grant(Utc::now() - Duration::seconds(1)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_refresh_token(&token).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn should_remove_refresh_tokens_of_client_and_user() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let expires_at = Utc::now() + Duration::days(1);
+        let other_client = RefreshGrant {
+            client_id: "other".to_owned(),
+            ..grant(expires_at)
+        };
+        for (token, grant) in [
+            ("first", grant(expires_at)),
+            ("second", grant(expires_at)),
+            ("other", other_client),
+        ] {
+            store
+                .add_refresh_token(&SecretString::from(token), grant)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            store
+                .remove_refresh_tokens("client", user_id())
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(store
+            .get_refresh_token(&SecretString::from("other"))
+            .await
+            .is_ok());
+        assert_eq!(
+            store.remove_user_refresh_tokens(user_id()).await.unwrap(),
+            1
+        );
+        assert_eq!(
+            store.remove_user_refresh_tokens(user_id()).await.unwrap(),
+            0
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::domain::{Role, RoleStore, RoleStoreError, UserId};
+
+#[derive(Default)]
+pub struct HashmapRoleStore {
+    // Ordered, so roles are listed by name
+    roles: BTreeMap<String, Role>,
+    assignments: HashMap<UserId, BTreeSet<String>>,
+}
+
+#[async_trait::async_trait]
+impl RoleStore for HashmapRoleStore {
+    async fn put_role(&mut self, role: Role) -> Result<(), RoleStoreError> {
+        self.roles.insert(role.name.clone(), role);
+        Ok(())
+    }
+
+    async fn get_roles(&self) -> Result<Vec<Role>, RoleStoreError> {
+        Ok(self.roles.values().cloned().collect())
+    }
+
+    async fn delete_role(&mut self, name: &str) -> Result<(), RoleStoreError> {
+        self.roles
+            .remove(name)
+            .ok_or(RoleStoreError::RoleNotFound)?;
+        for roles in self.assignments.values_mut() {
+            roles.remove(name);
+        }
+        Ok(())
+    }
+
+    async fn assign_role(&mut self, user_id: UserId, role: &str) -> Result<(), RoleStoreError> {
+        if !self.roles.contains_key(role) {
+            return Err(RoleStoreError::RoleNotFound);
+        }
+        self.assignments
+            .entry(user_id)
+            .or_default()
+            .insert(role.to_owned());
+        Ok(())
+    }
+
+    async fn unassign_role(&mut self, user_id: UserId, role: &str) -> Result<(), RoleStoreError> {
+        let removed = self
+            .assignments
+            .get_mut(&user_id)
+            .is_some_and(|roles| roles.remove(role));
+        match removed {
+            true => Ok(()),
+            false => Err(RoleStoreError::RoleNotFound),
+        }
+    }
+
+    async fn get_user_roles(&self, user_id: UserId) -> Result<Vec<Role>, RoleStoreError> {
+        Ok(self
+            .assignments
+            .get(&user_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|name| self.roles.get(name).cloned())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(name: &str, permissions: &[&str]) -> Role {
+        Role {
+            name: name.to_owned(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_replace_role_permissions() {
+        let mut store = HashmapRoleStore::default();
+        store.put_role(role("viewer", &["a:read"])).await.unwrap();
+        store.put_role(role("editor", &["a:write"])).await.unwrap();
+        store.put_role(role("viewer", &["b:read"])).await.unwrap();
+
+        assert_eq!(
+            store.get_roles().await.unwrap(),
+            vec![role("editor", &["a:write"]), role("viewer", &["b:read"])]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_assign_and_unassign_roles() {
+        let mut store = HashmapRoleStore::default();
+        let user = UserId::new();
+        store.put_role(role("viewer", &["a:read"])).await.unwrap();
+
+        assert_eq!(
+            store.assign_role(user, "missing").await.unwrap_err(),
+            RoleStoreError::RoleNotFound
+        );
+        store.assign_role(user, "viewer").await.unwrap();
+        store.assign_role(user, "viewer").await.unwrap();
+        assert_eq!(
+            store.get_user_roles(user).await.unwrap(),
+            vec![role("viewer", &["a:read"])]
+        );
+
+        store.unassign_role(user, "viewer").await.unwrap();
+        assert_eq!(
+            store.unassign_role(user, "viewer").await.unwrap_err(),
+            RoleStoreError::RoleNotFound
+        );
+        assert!(store.get_user_roles(user).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_unassign_deleted_role() {
+        let mut store = HashmapRoleStore::default();
+        let user = UserId::new();
+        store.put_role(role("viewer", &["a:read"])).await.unwrap();
+        store.assign_role(user, "viewer").await.unwrap();
+
+        store.delete_role("viewer").await.unwrap();
+
+        assert!(store.get_user_roles(user).await.unwrap().is_empty());
+        assert_eq!(
+            store.delete_role("viewer").await.unwrap_err(),
+            RoleStoreError::RoleNotFound
+        );
+    }
+}
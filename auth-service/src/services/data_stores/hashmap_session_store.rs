@@ -0,0 +1,137 @@
+use chrono::{DateTime, Utc};
+use std::{cmp::Reverse, collections::HashMap};
+use uuid::Uuid;
+
+use crate::domain::{Session, SessionStore, SessionStoreError, UserId};
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: HashMap<Uuid, Session>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        // Nothing purges this store, so expired sessions are dropped whenever one is added
+        let now = Utc::now();
+        self.sessions.retain(|_, session| !session.is_expired(now));
+        self.sessions.insert(session.id, session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: Uuid) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .get(&id)
+            .filter(|session| !session.is_expired(Utc::now()))
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn get_sessions(&self, user_id: UserId) -> Result<Vec<Session>, SessionStoreError> {
+        let now = Utc::now();
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .values()
+            .filter(|session| session.user_id == user_id && !session.is_expired(now))
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| Reverse(session.created_at));
+        Ok(sessions)
+    }
+
+    async fn touch_session(
+        &mut self,
+        id: Uuid,
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), SessionStoreError> {
+        match self.sessions.get_mut(&id) {
+            Some(session) if !session.is_expired(Utc::now()) => {
+                session.last_seen_at = seen_at;
+                Ok(())
+            }
+            _ => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn remove_session(&mut self, id: Uuid) -> Result<(), SessionStoreError> {
+        match self.sessions.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn remove_sessions(&mut self, user_id: UserId) -> Result<u64, SessionStoreError> {
+        let before = self.sessions.len();
+        self.sessions
+            .retain(|_, session| session.user_id != user_id);
+        Ok((before - self.sessions.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn session(user_id: UserId, created_at: DateTime<Utc>) -> Session {
+        Session {
+            id: Uuid::new_v4(),
+            user_id,
+            client_id: None,
+            created_at,
+            last_seen_at: created_at,
+            expires_at: created_at + Duration::minutes(10),
+            ip: None,
+            user_agent: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_list_own_unexpired_sessions_newest_first() {
+        let mut store = HashmapSessionStore::default();
+        let user = UserId::new();
+        let other = UserId::new();
+        let now = Utc::now();
+        let older = session(user, now - Duration::minutes(2));
+        let newer = session(user, now);
+        let expired = session(user, now - Duration::minutes(20));
+        for session in [older.clone(), newer.clone(), expired, session(other, now)] {
+            store.add_session(session).await.unwrap();
+        }
+
+        let ids: Vec<Uuid> = store
+            .get_sessions(user)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|session| session.id)
+            .collect();
+
+        assert_eq!(ids, vec![newer.id, older.id]);
+    }
+
+    #[tokio::test]
+    async fn should_touch_and_remove_session() {
+        let mut store = HashmapSessionStore::default();
+        let user = UserId::new();
+        let session = session(user, Utc::now());
+        store.add_session(session.clone()).await.unwrap();
+        let seen_at = session.created_at + Duration::minutes(1);
+
+        store.touch_session(session.id, seen_at).await.unwrap();
+
+        assert_eq!(
+            store.get_session(session.id).await.unwrap().last_seen_at,
+            seen_at
+        );
+        assert!(store.remove_session(session.id).await.is_ok());
+        assert_eq!(
+            store.get_session(session.id).await.unwrap_err(),
+            SessionStoreError::SessionNotFound
+        );
+        assert_eq!(
+            store.remove_session(session.id).await.unwrap_err(),
+            SessionStoreError::SessionNotFound
+        );
+    }
+}
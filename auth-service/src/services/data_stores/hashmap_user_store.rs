@@ -1,14 +1,15 @@
+use chrono::{DateTime, Utc};
 use secrecy::SecretString;
 use std::collections::HashMap;
 
 use crate::{
-    domain::{User, UserStoreError},
+    domain::{AccountStatus, User, UserId, UserStoreError},
     Email, UserStore,
 };
 
 #[derive(Default)]
 pub struct HashmapUserStore {
-    users: HashMap<Email, User>,
+    users: HashMap<UserId, User>,
 }
 
 impl HashmapUserStore {
@@ -17,20 +18,37 @@ impl HashmapUserStore {
             users: HashMap::new(),
         }
     }
+
+    fn find_by_email(&self, email: &Email) -> Option<&User> {
+        self.users
+            .values()
+            .find(|user| same_email(&user.email, email))
+    }
+}
+
+fn same_email(a: &Email, b: &Email) -> bool {
+    a.as_ref().to_lowercase() == b.as_ref().to_lowercase()
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        if self.users.contains_key(&user.email) {
+        if self.users.contains_key(&user.id) || self.find_by_email(&user.email).is_some() {
             return Err(UserStoreError::UserAlreadyExists);
         }
-        self.users.insert(user.email.clone(), user);
+        self.users.insert(user.id, user);
         Ok(())
     }
 
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        match self.users.get(email) {
+        match self.find_by_email(email) {
+            Some(user) => Ok(user.clone()),
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn get_user_by_id(&self, id: UserId) -> Result<User, UserStoreError> {
+        match self.users.get(&id) {
             Some(user) => Ok(user.clone()),
             None => Err(UserStoreError::UserNotFound),
         }
@@ -41,7 +59,7 @@ impl UserStore for HashmapUserStore {
         email: &Email,
         raw_password: &SecretString,
     ) -> Result<(), UserStoreError> {
-        match self.users.get(email) {
+        match self.find_by_email(email) {
             Some(user) => user
                 .password
                 .verify_raw_password(raw_password)
@@ -50,6 +68,79 @@ impl UserStore for HashmapUserStore {
             None => Err(UserStoreError::InvalidCredentials),
         }
     }
+
+    async fn bump_token_generation(&mut self, id: UserId) -> Result<i64, UserStoreError> {
+        match self.users.get_mut(&id) {
+            Some(user) => {
+                user.token_generation += 1;
+                Ok(user.token_generation)
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn search_users(
+        &self,
+        query: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<User>, UserStoreError> {
+        let query = query.to_lowercase();
+        let mut users: Vec<&User> = self
+            .users
+            .values()
+            .filter(|user| user.email.as_ref().to_lowercase().contains(&query))
+            .collect();
+        users.sort_by(|a, b| a.email.as_ref().cmp(b.email.as_ref()));
+        Ok(users
+            .into_iter()
+            .skip(offset.try_into().unwrap_or_default())
+            .take(limit.try_into().unwrap_or_default())
+            .cloned()
+            .collect())
+    }
+
+    async fn update_user(&mut self, user: &User) -> Result<(), UserStoreError> {
+        if self
+            .find_by_email(&user.email)
+            .is_some_and(|other| other.id != user.id)
+        {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+        match self.users.get_mut(&user.id) {
+            Some(stored) => {
+                *stored = User {
+                    token_generation: stored.token_generation,
+                    ..user.clone()
+                };
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn delete_user(&mut self, id: UserId) -> Result<(), UserStoreError> {
+        match self.users.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn users_scheduled_for_deletion(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<UserId>, UserStoreError> {
+        let mut users: Vec<&User> = self
+            .users
+            .values()
+            .filter(|user| {
+                user.status == AccountStatus::ScheduledForDeletion
+                    && user.status_changed_at < before
+            })
+            .collect();
+        users.sort_by_key(|user| user.status_changed_at);
+        Ok(users.into_iter().map(|user| user.id).collect())
+    }
 }
 
 #[cfg(test)]
@@ -71,9 +162,15 @@ mod tests {
             .await
             .unwrap();
         let user = User {
+            id: UserId::new(),
             email,
             password,
             requires_2fa: true,
+            token_generation: 0,
+            status: AccountStatus::Active,
+            status_reason: None,
+            status_changed_at: Utc::now(),
+            password_reset_required: false,
         };
         let mut store = HashmapUserStore::new();
 
@@ -90,17 +187,30 @@ mod tests {
             .await
             .unwrap();
         let user = User {
+            id: UserId::new(),
             email: email.clone(),
             password: password.clone(),
             requires_2fa: true,
+            token_generation: 0,
+            status: AccountStatus::Active,
+            status_reason: None,
+            status_changed_at: Utc::now(),
+            password_reset_required: false,
         };
         let mut store = HashmapUserStore::new();
+        let id = UserId::new();
         store.users.insert(
-            email.clone(),
+            id,
             User {
+                id,
                 email,
                 password,
                 requires_2fa: true,
+                token_generation: 0,
+                status: AccountStatus::Active,
+                status_reason: None,
+                status_changed_at: Utc::now(),
+                password_reset_required: false,
             },
         );
 
@@ -110,6 +220,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(clippy::bool_assert_comparison)]
     async fn should_get_existing_user() {
         let email = Email::parse(SafeEmail().fake()).unwrap();
         let fake: String = FakePassword(10..12).fake();
@@ -118,11 +229,17 @@ mod tests {
             .unwrap();
         let mut store = HashmapUserStore::new();
         let user = User {
+            id: UserId::new(),
             email: email.clone(),
             password,
             requires_2fa: true,
+            token_generation: 0,
+            status: AccountStatus::Active,
+            status_reason: None,
+            status_changed_at: Utc::now(),
+            password_reset_required: false,
         };
-        store.users.insert(email.clone(), user.clone());
+        store.users.insert(user.id, user.clone());
 
         let result = store.get_user(&email).await;
 
@@ -138,11 +255,17 @@ mod tests {
             .unwrap();
         let mut store = HashmapUserStore::new();
         let user = User {
+            id: UserId::new(),
             email: email.clone(),
             password,
             requires_2fa: true,
+            token_generation: 0,
+            status: AccountStatus::Active,
+            status_reason: None,
+            status_changed_at: Utc::now(),
+            password_reset_required: false,
         };
-        store.users.insert(email.clone(), user.clone());
+        store.users.insert(user.id, user.clone());
 
         let result = store
             .get_user(&Email::parse("unknown@example.com".to_owned()).unwrap())
@@ -160,11 +283,17 @@ mod tests {
             .unwrap();
         let mut store = HashmapUserStore::new();
         let user = User {
+            id: UserId::new(),
             email: email.clone(),
             password,
             requires_2fa: true,
+            token_generation: 0,
+            status: AccountStatus::Active,
+            status_reason: None,
+            status_changed_at: Utc::now(),
+            password_reset_required: false,
         };
-        store.users.insert(email.clone(), user.clone());
+        store.users.insert(user.id, user.clone());
 
         let actual_email = Email::parse(SafeEmail().fake()).unwrap();
         let result = store
@@ -186,11 +315,17 @@ mod tests {
             .unwrap();
         let mut store = HashmapUserStore::new();
         let user = User {
+            id: UserId::new(),
             email: email.clone(),
             password,
             requires_2fa: true,
+            token_generation: 0,
+            status: AccountStatus::Active,
+            status_reason: None,
+            status_changed_at: Utc::now(),
+            password_reset_required: false,
         };
-        store.users.insert(email.clone(), user.clone());
+        store.users.insert(user.id, user.clone());
 
         let result = store
             .validate_user(
@@ -201,4 +336,141 @@ mod tests {
 
         assert_eq!(result.unwrap_err(), UserStoreError::InvalidCredentials);
     }
+
+    fn user(address: &str) -> User {
+        User {
+            email: Email::parse(address.to_owned()).unwrap(),
+            ..User::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn should_search_users_by_email() {
+        let mut store = HashmapUserStore::new();
+        for address in ["bob@example.com", "alice@example.com", "carol@other.org"] {
+            store.add_user(user(address)).await.unwrap();
+        }
+
+        let emails = |users: Vec<User>| -> Vec<String> {
+            users
+                .into_iter()
+                .map(|user| user.email.as_ref().to_owned())
+                .collect()
+        };
+        assert_eq!(
+            emails(store.search_users("EXAMPLE", 10, 0).await.unwrap()),
+            ["alice@example.com", "bob@example.com"]
+        );
+        assert_eq!(
+            emails(store.search_users("", 1, 1).await.unwrap()),
+            ["bob@example.com"]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_update_and_delete_user() {
+        let mut store = HashmapUserStore::new();
+        let mut user = user("user@example.com");
+        store.add_user(user.clone()).await.unwrap();
+        store.bump_token_generation(user.id).await.unwrap();
+
+        user.set_status(AccountStatus::Disabled, Some("Chargeback".to_owned()));
+        user.requires_2fa = true;
+        store.update_user(&user).await.unwrap();
+
+        let stored = store.get_user(&user.email).await.unwrap();
+        assert_eq!(stored.status, AccountStatus::Disabled);
+        assert_eq!(stored.status_reason.as_deref(), Some("Chargeback"));
+        assert!(stored.requires_2fa);
+        assert_eq!(stored.token_generation, 1);
+
+        store.delete_user(user.id).await.unwrap();
+        assert_eq!(
+            store.update_user(&user).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+        assert_eq!(
+            store.delete_user(user.id).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn should_list_users_scheduled_for_deletion_before_a_time() {
+        let mut store = HashmapUserStore::new();
+        let now = Utc::now();
+        for (address, status, days_ago) in [
+            ("old@example.com", AccountStatus::ScheduledForDeletion, 40),
+            ("older@example.com", AccountStatus::ScheduledForDeletion, 50),
+            ("recent@example.com", AccountStatus::ScheduledForDeletion, 1),
+            ("disabled@example.com", AccountStatus::Disabled, 40),
+        ] {
+            let mut user = user(address);
+            user.status = status;
+            user.status_changed_at = now - chrono::Duration::days(days_ago);
+            store.add_user(user).await.unwrap();
+        }
+
+        let mut emails = Vec::new();
+        for id in store
+            .users_scheduled_for_deletion(now - chrono::Duration::days(30))
+            .await
+            .unwrap()
+        {
+            let user = store.get_user_by_id(id).await.unwrap();
+            emails.push(user.email.as_ref().to_owned());
+        }
+        assert_eq!(emails, ["older@example.com", "old@example.com"]);
+    }
+
+    #[tokio::test]
+    async fn should_match_emails_ignoring_case() {
+        let mut store = HashmapUserStore::new();
+        let user = user("User@Example.com");
+        store.add_user(user.clone()).await.unwrap();
+
+        let found = store
+            .get_user(&Email::parse("user@EXAMPLE.com".to_owned()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(found.id, user.id);
+        assert_eq!(
+            store
+                .add_user(self::user("USER@example.com"))
+                .await
+                .unwrap_err(),
+            UserStoreError::UserAlreadyExists
+        );
+    }
+
+    #[tokio::test]
+    async fn should_change_email_of_user() {
+        let mut store = HashmapUserStore::new();
+        let mut user = user("old@example.com");
+        store.add_user(user.clone()).await.unwrap();
+        store
+            .add_user(self::user("taken@example.com"))
+            .await
+            .unwrap();
+
+        user.email = Email::parse("new@example.com".to_owned()).unwrap();
+        store.update_user(&user).await.unwrap();
+
+        assert_eq!(
+            store.get_user_by_id(user.id).await.unwrap().email,
+            user.email
+        );
+        assert_eq!(
+            store
+                .get_user(&Email::parse("old@example.com".to_owned()).unwrap())
+                .await
+                .unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+        user.email = Email::parse("Taken@example.com".to_owned()).unwrap();
+        assert_eq!(
+            store.update_user(&user).await.unwrap_err(),
+            UserStoreError::UserAlreadyExists
+        );
+    }
 }
@@ -23,28 +23,20 @@ impl BannedTokenStore for HashsetBannedTokenStore {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::services::data_stores::contract_tests;
 
     #[tokio::test]
     async fn test_add_token() {
-        let mut test_store = HashsetBannedTokenStore::default();
-        let token = SecretString::new("foobar".to_owned().into_boxed_str());
-
-        let result = test_store.add_token(token.clone()).await;
+        contract_tests::should_add_banned_token(HashsetBannedTokenStore::default()).await;
+    }
 
-        assert!(result.is_ok());
-        assert!(test_store.token_store.contains(token.expose_secret()));
+    #[tokio::test]
+    async fn test_add_token_twice() {
+        contract_tests::should_add_banned_token_twice(HashsetBannedTokenStore::default()).await;
     }
 
     #[tokio::test]
     async fn test_contains_token() {
-        let mut test_store = HashsetBannedTokenStore::default();
-        let token = SecretString::new("foobar".to_owned().into_boxed_str());
-        test_store
-            .token_store
-            .insert(token.expose_secret().to_string());
-
-        let result = test_store.check_token(&token).await;
-
-        assert!(result.unwrap());
+        contract_tests::should_only_contain_banned_tokens(HashsetBannedTokenStore::default()).await;
     }
 }
@@ -0,0 +1,518 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Result;
+use secrecy::SecretString;
+use std::time::Instant;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        ApiKey, ApiKeyStore, ApiKeyStoreError, AuditEntry, AuditEvent, AuditLog, AuditLogError,
+        AuthorizationCodeStore, AuthorizationGrant, BannedTokenStore, BannedTokenStoreError,
+        ChainVerification, DeviceCodeStore, DeviceGrant, DeviceGrantStatus, Email, ExpiringStore,
+        GrantStoreError, LoginAttemptId, OAuthClient, OAuthClientStore, OAuthClientStoreError,
+        RefreshGrant, RefreshTokenStore, Role, RoleStore, RoleStoreError, Session, SessionStore,
+        SessionStoreError, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, User, UserId, UserStore,
+        UserStoreError,
+    },
+    utils::metrics::STORE_OPERATION_DURATION_SECONDS,
+};
+
+// Wraps any store and records the latency of each operation, labelled by store, backend,
+// operation and whether it succeeded
+pub struct MeteredStore<S> {
+    inner: S,
+    store: &'static str,
+    backend: &'static str,
+}
+
+impl<S> MeteredStore<S> {
+    pub fn new(inner: S, store: &'static str, backend: &'static str) -> Self {
+        Self {
+            inner,
+            store,
+            backend,
+        }
+    }
+
+    fn record<T, E>(&self, operation: &'static str, start: Instant, result: &Result<T, E>) {
+        let outcome = match result {
+            Ok(_) => "ok",
+            Err(_) => "error",
+        };
+        metrics::histogram!(
+            STORE_OPERATION_DURATION_SECONDS,
+            "store" => self.store,
+            "backend" => self.backend,
+            "operation" => operation,
+            "outcome" => outcome,
+        )
+        .record(start.elapsed().as_secs_f64());
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: ExpiringStore + Send + Sync> ExpiringStore for MeteredStore<S> {
+    async fn purge_expired(&self) -> Result<u64> {
+        let start = Instant::now();
+        let result = self.inner.purge_expired().await;
+        self.record("purge_expired", start, &result);
+        result
+    }
+}
+
+// Lets the purge task share the store the request handlers use, behind the same lock
+#[async_trait::async_trait]
+impl<S: ExpiringStore + Send + Sync> ExpiringStore for RwLock<S> {
+    async fn purge_expired(&self) -> Result<u64> {
+        self.read().await.purge_expired().await
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: UserStore + Send + Sync> UserStore for MeteredStore<S> {
+    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+        let start = Instant::now();
+        let result = self.inner.add_user(user).await;
+        self.record("add_user", start, &result);
+        result
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_user(email).await;
+        self.record("get_user", start, &result);
+        result
+    }
+
+    async fn get_user_by_id(&self, id: UserId) -> Result<User, UserStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_user_by_id(id).await;
+        self.record("get_user_by_id", start, &result);
+        result
+    }
+
+    async fn validate_user(
+        &self,
+        email: &Email,
+        raw_password: &SecretString,
+    ) -> Result<(), UserStoreError> {
+        let start = Instant::now();
+        let result = self.inner.validate_user(email, raw_password).await;
+        self.record("validate_user", start, &result);
+        result
+    }
+
+    async fn bump_token_generation(&mut self, id: UserId) -> Result<i64, UserStoreError> {
+        let start = Instant::now();
+        let result = self.inner.bump_token_generation(id).await;
+        self.record("bump_token_generation", start, &result);
+        result
+    }
+
+    async fn search_users(
+        &self,
+        query: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<User>, UserStoreError> {
+        let start = Instant::now();
+        let result = self.inner.search_users(query, limit, offset).await;
+        self.record("search_users", start, &result);
+        result
+    }
+
+    async fn update_user(&mut self, user: &User) -> Result<(), UserStoreError> {
+        let start = Instant::now();
+        let result = self.inner.update_user(user).await;
+        self.record("update_user", start, &result);
+        result
+    }
+
+    async fn delete_user(&mut self, id: UserId) -> Result<(), UserStoreError> {
+        let start = Instant::now();
+        let result = self.inner.delete_user(id).await;
+        self.record("delete_user", start, &result);
+        result
+    }
+
+    async fn users_scheduled_for_deletion(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<UserId>, UserStoreError> {
+        let start = Instant::now();
+        let result = self.inner.users_scheduled_for_deletion(before).await;
+        self.record("users_scheduled_for_deletion", start, &result);
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: BannedTokenStore + Send + Sync> BannedTokenStore for MeteredStore<S> {
+    async fn add_token(&mut self, token: SecretString) -> Result<(), BannedTokenStoreError> {
+        let start = Instant::now();
+        let result = self.inner.add_token(token).await;
+        self.record("add_token", start, &result);
+        result
+    }
+
+    async fn check_token(&self, token: &SecretString) -> Result<bool, BannedTokenStoreError> {
+        let start = Instant::now();
+        let result = self.inner.check_token(token).await;
+        self.record("check_token", start, &result);
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: TwoFACodeStore + Send + Sync> TwoFACodeStore for MeteredStore<S> {
+    async fn add_code(
+        &mut self,
+        user_id: UserId,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let start = Instant::now();
+        let result = self.inner.add_code(user_id, login_attempt_id, code).await;
+        self.record("add_code", start, &result);
+        result
+    }
+
+    async fn remove_code(&mut self, user_id: UserId) -> Result<(), TwoFACodeStoreError> {
+        let start = Instant::now();
+        let result = self.inner.remove_code(user_id).await;
+        self.record("remove_code", start, &result);
+        result
+    }
+
+    async fn get_code(
+        &self,
+        user_id: UserId,
+    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_code(user_id).await;
+        self.record("get_code", start, &result);
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: AuditLog + Send + Sync> AuditLog for MeteredStore<S> {
+    async fn append(&mut self, event: AuditEvent) -> Result<(), AuditLogError> {
+        let start = Instant::now();
+        let result = self.inner.append(event).await;
+        self.record("append", start, &result);
+        result
+    }
+
+    async fn entries(&self, after_id: i64, limit: i64) -> Result<Vec<AuditEntry>, AuditLogError> {
+        let start = Instant::now();
+        let result = self.inner.entries(after_id, limit).await;
+        self.record("entries", start, &result);
+        result
+    }
+
+    // Delegated as a whole, so the inner log's own verification (if any) is used
+    async fn verify(&self) -> Result<ChainVerification, AuditLogError> {
+        let start = Instant::now();
+        let result = self.inner.verify().await;
+        self.record("verify", start, &result);
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: SessionStore + Send + Sync> SessionStore for MeteredStore<S> {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        let start = Instant::now();
+        let result = self.inner.add_session(session).await;
+        self.record("add_session", start, &result);
+        result
+    }
+
+    async fn get_session(&self, id: Uuid) -> Result<Session, SessionStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_session(id).await;
+        self.record("get_session", start, &result);
+        result
+    }
+
+    async fn get_sessions(&self, user_id: UserId) -> Result<Vec<Session>, SessionStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_sessions(user_id).await;
+        self.record("get_sessions", start, &result);
+        result
+    }
+
+    async fn touch_session(
+        &mut self,
+        id: Uuid,
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), SessionStoreError> {
+        let start = Instant::now();
+        let result = self.inner.touch_session(id, seen_at).await;
+        self.record("touch_session", start, &result);
+        result
+    }
+
+    async fn remove_session(&mut self, id: Uuid) -> Result<(), SessionStoreError> {
+        let start = Instant::now();
+        let result = self.inner.remove_session(id).await;
+        self.record("remove_session", start, &result);
+        result
+    }
+
+    async fn remove_sessions(&mut self, user_id: UserId) -> Result<u64, SessionStoreError> {
+        let start = Instant::now();
+        let result = self.inner.remove_sessions(user_id).await;
+        self.record("remove_sessions", start, &result);
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: RoleStore + Send + Sync> RoleStore for MeteredStore<S> {
+    async fn put_role(&mut self, role: Role) -> Result<(), RoleStoreError> {
+        let start = Instant::now();
+        let result = self.inner.put_role(role).await;
+        self.record("put_role", start, &result);
+        result
+    }
+
+    async fn get_roles(&self) -> Result<Vec<Role>, RoleStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_roles().await;
+        self.record("get_roles", start, &result);
+        result
+    }
+
+    async fn delete_role(&mut self, name: &str) -> Result<(), RoleStoreError> {
+        let start = Instant::now();
+        let result = self.inner.delete_role(name).await;
+        self.record("delete_role", start, &result);
+        result
+    }
+
+    async fn assign_role(&mut self, user_id: UserId, role: &str) -> Result<(), RoleStoreError> {
+        let start = Instant::now();
+        let result = self.inner.assign_role(user_id, role).await;
+        self.record("assign_role", start, &result);
+        result
+    }
+
+    async fn unassign_role(&mut self, user_id: UserId, role: &str) -> Result<(), RoleStoreError> {
+        let start = Instant::now();
+        let result = self.inner.unassign_role(user_id, role).await;
+        self.record("unassign_role", start, &result);
+        result
+    }
+
+    async fn get_user_roles(&self, user_id: UserId) -> Result<Vec<Role>, RoleStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_user_roles(user_id).await;
+        self.record("get_user_roles", start, &result);
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: ApiKeyStore + Send + Sync> ApiKeyStore for MeteredStore<S> {
+    async fn add_api_key(&mut self, key: ApiKey) -> Result<(), ApiKeyStoreError> {
+        let start = Instant::now();
+        let result = self.inner.add_api_key(key).await;
+        self.record("add_api_key", start, &result);
+        result
+    }
+
+    async fn get_api_key(&self, key_hash: &str) -> Result<ApiKey, ApiKeyStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_api_key(key_hash).await;
+        self.record("get_api_key", start, &result);
+        result
+    }
+
+    async fn get_api_keys(&self, user_id: UserId) -> Result<Vec<ApiKey>, ApiKeyStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_api_keys(user_id).await;
+        self.record("get_api_keys", start, &result);
+        result
+    }
+
+    async fn touch_api_key(
+        &mut self,
+        id: Uuid,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), ApiKeyStoreError> {
+        let start = Instant::now();
+        let result = self.inner.touch_api_key(id, used_at).await;
+        self.record("touch_api_key", start, &result);
+        result
+    }
+
+    async fn remove_api_key(&mut self, user_id: UserId, id: Uuid) -> Result<(), ApiKeyStoreError> {
+        let start = Instant::now();
+        let result = self.inner.remove_api_key(user_id, id).await;
+        self.record("remove_api_key", start, &result);
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: OAuthClientStore + Send + Sync> OAuthClientStore for MeteredStore<S> {
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError> {
+        let start = Instant::now();
+        let result = self.inner.add_client(client).await;
+        self.record("add_client", start, &result);
+        result
+    }
+
+    async fn get_client(&self, id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_client(id).await;
+        self.record("get_client", start, &result);
+        result
+    }
+
+    async fn get_clients(&self) -> Result<Vec<OAuthClient>, OAuthClientStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_clients().await;
+        self.record("get_clients", start, &result);
+        result
+    }
+
+    async fn remove_client(&mut self, id: &str) -> Result<(), OAuthClientStoreError> {
+        let start = Instant::now();
+        let result = self.inner.remove_client(id).await;
+        self.record("remove_client", start, &result);
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: AuthorizationCodeStore + Send + Sync> AuthorizationCodeStore for MeteredStore<S> {
+    async fn add_code(
+        &mut self,
+        code: &SecretString,
+        grant: AuthorizationGrant,
+    ) -> Result<(), GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.add_code(code, grant).await;
+        self.record("add_code", start, &result);
+        result
+    }
+
+    async fn take_code(
+        &mut self,
+        code: &SecretString,
+    ) -> Result<AuthorizationGrant, GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.take_code(code).await;
+        self.record("take_code", start, &result);
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: RefreshTokenStore + Send + Sync> RefreshTokenStore for MeteredStore<S> {
+    async fn add_refresh_token(
+        &mut self,
+        token: &SecretString,
+        grant: RefreshGrant,
+    ) -> Result<(), GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.add_refresh_token(token, grant).await;
+        self.record("add_refresh_token", start, &result);
+        result
+    }
+
+    async fn get_refresh_token(
+        &self,
+        token: &SecretString,
+    ) -> Result<RefreshGrant, GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_refresh_token(token).await;
+        self.record("get_refresh_token", start, &result);
+        result
+    }
+
+    async fn remove_refresh_token(&mut self, token: &SecretString) -> Result<(), GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.remove_refresh_token(token).await;
+        self.record("remove_refresh_token", start, &result);
+        result
+    }
+
+    async fn remove_refresh_tokens(
+        &mut self,
+        client_id: &str,
+        user_id: UserId,
+    ) -> Result<u64, GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.remove_refresh_tokens(client_id, user_id).await;
+        self.record("remove_refresh_tokens", start, &result);
+        result
+    }
+
+    async fn remove_user_refresh_tokens(
+        &mut self,
+        user_id: UserId,
+    ) -> Result<u64, GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.remove_user_refresh_tokens(user_id).await;
+        self.record("remove_user_refresh_tokens", start, &result);
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: DeviceCodeStore + Send + Sync> DeviceCodeStore for MeteredStore<S> {
+    async fn add_device_code(
+        &mut self,
+        device_code: &SecretString,
+        grant: DeviceGrant,
+    ) -> Result<(), GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.add_device_code(device_code, grant).await;
+        self.record("add_device_code", start, &result);
+        result
+    }
+
+    async fn get_user_code(&self, user_code: &str) -> Result<DeviceGrant, GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.get_user_code(user_code).await;
+        self.record("get_user_code", start, &result);
+        result
+    }
+
+    async fn set_user_code_status(
+        &mut self,
+        user_code: &str,
+        status: DeviceGrantStatus,
+    ) -> Result<(), GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.set_user_code_status(user_code, status).await;
+        self.record("set_user_code_status", start, &result);
+        result
+    }
+
+    async fn poll_device_code(
+        &mut self,
+        device_code: &SecretString,
+        now: DateTime<Utc>,
+    ) -> Result<DeviceGrant, GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.poll_device_code(device_code, now).await;
+        self.record("poll_device_code", start, &result);
+        result
+    }
+
+    async fn remove_device_code(
+        &mut self,
+        device_code: &SecretString,
+    ) -> Result<(), GrantStoreError> {
+        let start = Instant::now();
+        let result = self.inner.remove_device_code(device_code).await;
+        self.record("remove_device_code", start, &result);
+        result
+    }
+}
@@ -6,12 +6,13 @@ pub struct MockEmailClient;
 #[async_trait::async_trait]
 impl EmailClient for MockEmailClient {
     async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()> {
-        // Simply log the details to stdout
+        // Simply log the details to stdout. The content carries the 2FA code, so only its
+        // length is logged.
         tracing::debug!(
-            "Sending email to {} with subject: {} and content: {}",
-            recipient.as_ref(),
+            recipient = recipient.as_ref(),
             subject,
-            content,
+            content_length = content.len(),
+            "Sending email"
         );
         Ok(())
     }
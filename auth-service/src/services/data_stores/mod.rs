@@ -1,7 +1,53 @@
+#[cfg(test)]
+mod contract_tests;
 pub mod hashmap_2fa_code_store;
+pub mod hashmap_api_key_store;
+pub mod hashmap_authorization_code_store;
+pub mod hashmap_device_code_store;
+pub mod hashmap_oauth_client_store;
+pub mod hashmap_refresh_token_store;
+pub mod hashmap_role_store;
+pub mod hashmap_session_store;
 pub mod hashmap_user_store;
 pub mod hashset_banned_token_store;
+pub mod metered_store;
 pub mod mock_email_client;
+#[cfg(feature = "postgres")]
 pub mod postgrep_user_store;
+#[cfg(feature = "postgres")]
+pub mod postgres_api_key_store;
+#[cfg(feature = "postgres")]
+pub mod postgres_audit_log;
+#[cfg(feature = "postgres")]
+pub mod postgres_authorization_code_store;
+#[cfg(feature = "postgres")]
+pub mod postgres_banned_token_store;
+#[cfg(feature = "postgres")]
+pub mod postgres_oauth_client_store;
+#[cfg(feature = "postgres")]
+pub mod postgres_refresh_token_store;
+#[cfg(feature = "postgres")]
+pub mod postgres_role_store;
+#[cfg(feature = "postgres")]
+pub mod postgres_session_store;
+#[cfg(feature = "postgres")]
+pub mod postgres_two_fa_code_store;
+#[cfg(feature = "redis")]
 pub mod redis_banned_token_store;
+#[cfg(feature = "redis")]
+pub mod redis_device_code_store;
+#[cfg(feature = "redis")]
 pub mod redis_two_fa_code_store;
+#[cfg(feature = "smtp")]
+pub mod smtp_email_client;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_api_key_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_banned_token_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_session_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_two_fa_code_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_user_store;
+pub mod vec_audit_log;
@@ -1,10 +1,12 @@
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::{eyre, Result};
 use secrecy::{ExposeSecret, SecretString};
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::domain::{
     data_stores::{UserStore, UserStoreError},
-    Email, HashedPassword, User,
+    AccountStatus, Email, HashedPassword, User, UserId,
 };
 
 pub struct PostgresUserStore {
@@ -17,50 +19,107 @@ impl PostgresUserStore {
     }
 }
 
+struct UserRow {
+    id: Uuid,
+    email: String,
+    password_hash: String,
+    requires_2fa: bool,
+    token_generation: i64,
+    status: String,
+    status_reason: Option<String>,
+    status_changed_at: DateTime<Utc>,
+    password_reset_required: bool,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: row.id.into(),
+            email: Email::parse(row.email)
+                .map_err(|e| UserStoreError::UnexpectedError(eyre!(e)))?,
+            password: HashedPassword::parse_password_hash(SecretString::new(
+                row.password_hash.into_boxed_str(),
+            ))
+            .map_err(|e| UserStoreError::UnexpectedError(eyre!(e)))?,
+            requires_2fa: row.requires_2fa,
+            token_generation: row.token_generation,
+            status: AccountStatus::parse(&row.status).ok_or_else(|| {
+                UserStoreError::UnexpectedError(eyre!("unknown account status {}", row.status))
+            })?,
+            status_reason: row.status_reason,
+            status_changed_at: row.status_changed_at,
+            password_reset_required: row.password_reset_required,
+        })
+    }
+}
+
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
     #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
         sqlx::query!(
             r#"
-            insert into users (email, password_hash, requires_2fa)
-            values ($1, $2, $3)
+            insert into users (id, email, password_hash, requires_2fa, status, status_reason,
+                status_changed_at)
+            values ($1, $2, $3, $4, $5, $6, $7)
             "#,
+            user.id.as_uuid(),
             user.email.as_ref(),
             &user.password.as_ref().expose_secret(),
-            user.requires_2fa
+            user.requires_2fa,
+            user.status.as_str(),
+            user.status_reason,
+            user.status_changed_at
         )
         .execute(&self.pool)
         .await
-        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                UserStoreError::UserAlreadyExists
+            }
+            e => UserStoreError::UnexpectedError(e.into()),
+        })?;
 
         Ok(())
     }
 
     #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        sqlx::query!(
+        sqlx::query_as!(
+            UserRow,
             r#"
-            select email, password_hash, requires_2fa
+            select id, email, password_hash, requires_2fa, token_generation, status,
+                status_reason, status_changed_at, password_reset_required
             from users
-            where email = $1
+            where lower(email) = lower($1)
             "#,
             email.as_ref()
         )
         .fetch_optional(&self.pool)
         .await
         .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
-        .map(|row| {
-            Ok(User {
-                email: Email::parse(row.email)
-                    .map_err(|e| UserStoreError::UnexpectedError(eyre!(e)))?,
-                password: HashedPassword::parse_password_hash(SecretString::new(
-                    row.password_hash.into_boxed_str(),
-                ))
-                .map_err(|e| UserStoreError::UnexpectedError(eyre!(e)))?,
-                requires_2fa: row.requires_2fa,
-            })
-        })
+        .map(User::try_from)
+        .ok_or(UserStoreError::UserNotFound)?
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id from PostgreSQL", skip_all)]
+    async fn get_user_by_id(&self, id: UserId) -> Result<User, UserStoreError> {
+        sqlx::query_as!(
+            UserRow,
+            r#"
+            select id, email, password_hash, requires_2fa, token_generation, status,
+                status_reason, status_changed_at, password_reset_required
+            from users
+            where id = $1
+            "#,
+            id.as_uuid()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .map(User::try_from)
         .ok_or(UserStoreError::UserNotFound)?
     }
 
@@ -76,4 +135,117 @@ impl UserStore for PostgresUserStore {
             .await
             .map_err(|_| UserStoreError::InvalidCredentials)
     }
+
+    #[tracing::instrument(name = "Bumping token generation in PostgreSQL", skip_all)]
+    async fn bump_token_generation(&mut self, id: UserId) -> Result<i64, UserStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            update users
+            set token_generation = token_generation + 1
+            where id = $1
+            returning token_generation
+            "#,
+            id.as_uuid()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)
+    }
+
+    #[tracing::instrument(name = "Searching users in PostgreSQL", skip_all)]
+    async fn search_users(
+        &self,
+        query: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<User>, UserStoreError> {
+        // `strpos` rather than `like`, so `%` and `_` in the query are matched literally
+        sqlx::query_as!(
+            UserRow,
+            r#"
+            select id, email, password_hash, requires_2fa, token_generation, status,
+                status_reason, status_changed_at, password_reset_required
+            from users
+            where strpos(lower(email), lower($1)) > 0
+            order by email
+            limit $2 offset $3
+            "#,
+            query,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(User::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Updating user in PostgreSQL", skip_all)]
+    async fn update_user(&mut self, user: &User) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            update users
+            set email = $2, password_hash = $3, requires_2fa = $4, status = $5,
+                status_reason = $6, status_changed_at = $7, password_reset_required = $8
+            where id = $1
+            "#,
+            user.id.as_uuid(),
+            user.email.as_ref(),
+            &user.password.as_ref().expose_secret(),
+            user.requires_2fa,
+            user.status.as_str(),
+            user.status_reason,
+            user.status_changed_at,
+            user.password_reset_required
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                UserStoreError::UserAlreadyExists
+            }
+            e => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Deleting user from PostgreSQL", skip_all)]
+    async fn delete_user(&mut self, id: UserId) -> Result<(), UserStoreError> {
+        let result = sqlx::query!("delete from users where id = $1", id.as_uuid())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Listing users scheduled for deletion in PostgreSQL", skip_all)]
+    async fn users_scheduled_for_deletion(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<UserId>, UserStoreError> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            select id
+            from users
+            where status = 'scheduled_for_deletion' and status_changed_at < $1
+            order by status_changed_at
+            "#,
+            before
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        Ok(ids.into_iter().map(UserId::from).collect())
+    }
 }
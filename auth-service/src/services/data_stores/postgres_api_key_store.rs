@@ -0,0 +1,275 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{data_stores::ExpiringStore, ApiKey, ApiKeyStore, ApiKeyStoreError, UserId};
+
+pub struct PostgresApiKeyStore {
+    pool: PgPool,
+}
+
+impl PostgresApiKeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for PostgresApiKeyStore {
+    // Delete keys past their expiry. Keys without one are kept until they are revoked.
+    #[tracing::instrument(name = "Purging expired API keys from PostgreSQL", skip_all)]
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            delete from api_keys
+            where expires_at <= now()
+            "#
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to purge expired API keys")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+struct ApiKeyRow {
+    id: Uuid,
+    user_id: Uuid,
+    name: String,
+    key_hash: String,
+    key_hint: String,
+    scope: Option<String>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<ApiKeyRow> for ApiKey {
+    type Error = ApiKeyStoreError;
+
+    fn try_from(row: ApiKeyRow) -> Result<Self, Self::Error> {
+        Ok(ApiKey {
+            id: row.id,
+            user_id: row.user_id.into(),
+            name: row.name,
+            key_hash: row.key_hash,
+            key_hint: row.key_hint,
+            scope: row.scope,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+            last_used_at: row.last_used_at,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for PostgresApiKeyStore {
+    #[tracing::instrument(name = "Adding API key to PostgreSQL", skip_all)]
+    async fn add_api_key(&mut self, key: ApiKey) -> Result<(), ApiKeyStoreError> {
+        sqlx::query!(
+            r#"
+            insert into api_keys
+                (id, user_id, name, key_hash, key_hint, scope, created_at, expires_at, last_used_at)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            key.id,
+            key.user_id.as_uuid(),
+            key.name,
+            key.key_hash,
+            key.key_hint,
+            key.scope,
+            key.created_at,
+            key.expires_at,
+            key.last_used_at,
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert API key into PostgreSQL")
+        .map_err(ApiKeyStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving API key from PostgreSQL", skip_all)]
+    async fn get_api_key(&self, key_hash: &str) -> Result<ApiKey, ApiKeyStoreError> {
+        sqlx::query_as!(
+            ApiKeyRow,
+            r#"
+            select id, user_id, name, key_hash, key_hint, scope, created_at, expires_at,
+                last_used_at
+            from api_keys
+            where key_hash = $1 and (expires_at is null or expires_at > now())
+            "#,
+            key_hash
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to retrieve API key from PostgreSQL")
+        .map_err(ApiKeyStoreError::UnexpectedError)?
+        .ok_or(ApiKeyStoreError::ApiKeyNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Retrieving user API keys from PostgreSQL", skip_all)]
+    async fn get_api_keys(&self, user_id: UserId) -> Result<Vec<ApiKey>, ApiKeyStoreError> {
+        sqlx::query_as!(
+            ApiKeyRow,
+            r#"
+            select id, user_id, name, key_hash, key_hint, scope, created_at, expires_at,
+                last_used_at
+            from api_keys
+            where user_id = $1 and (expires_at is null or expires_at > now())
+            order by created_at desc
+            "#,
+            user_id.as_uuid()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to retrieve user API keys from PostgreSQL")
+        .map_err(ApiKeyStoreError::UnexpectedError)?
+        .into_iter()
+        .map(ApiKey::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Updating API key last used time in PostgreSQL", skip_all)]
+    async fn touch_api_key(
+        &mut self,
+        id: Uuid,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), ApiKeyStoreError> {
+        let result = sqlx::query!(
+            r#"
+            update api_keys
+            set last_used_at = $2
+            where id = $1 and (expires_at is null or expires_at > now())
+            "#,
+            id,
+            used_at
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to update API key in PostgreSQL")
+        .map_err(ApiKeyStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(ApiKeyStoreError::ApiKeyNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Removing API key from PostgreSQL", skip_all)]
+    async fn remove_api_key(&mut self, user_id: UserId, id: Uuid) -> Result<(), ApiKeyStoreError> {
+        let result = sqlx::query!(
+            "delete from api_keys where id = $1 and user_id = $2",
+            id,
+            user_id.as_uuid()
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete API key from PostgreSQL")
+        .map_err(ApiKeyStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(ApiKeyStoreError::ApiKeyNotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    // Postgres keeps microseconds, so the keys read back compare equal
+    fn key(user_id: UserId, key_hash: &str, created_at: DateTime<Utc>) -> ApiKey {
+        let created_at = DateTime::from_timestamp_micros(created_at.timestamp_micros()).unwrap();
+        ApiKey {
+            id: Uuid::new_v4(),
+            user_id,
+            name: "deploy script".to_owned(),
+            key_hash: key_hash.to_owned(),
+            key_hint: "ak_abcd".to_owned(),
+            scope: Some("reports:read".to_owned()),
+            created_at,
+            expires_at: Some(created_at + Duration::days(30)),
+            last_used_at: None,
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_add_and_get_api_key(pool: PgPool) {
+        let mut store = PostgresApiKeyStore::new(pool);
+        let key = key(UserId::new(), "hash", Utc::now());
+
+        store.add_api_key(key.clone()).await.unwrap();
+
+        assert_eq!(store.get_api_key("hash").await.unwrap(), key);
+        assert_eq!(
+            store.get_api_key("other").await.unwrap_err(),
+            ApiKeyStoreError::ApiKeyNotFound
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_get_api_keys_of_user_newest_first(pool: PgPool) {
+        let mut store = PostgresApiKeyStore::new(pool);
+        let user = UserId::new();
+        let now = Utc::now();
+        let older = key(user, "older", now - Duration::minutes(2));
+        let newer = ApiKey {
+            expires_at: None,
+            ..key(user, "newer", now)
+        };
+        let expired = ApiKey {
+            expires_at: Some(now - Duration::minutes(1)),
+            ..key(user, "expired", now)
+        };
+        let other = key(UserId::new(), "other", now);
+        for key in [older.clone(), newer.clone(), expired, other] {
+            store.add_api_key(key).await.unwrap();
+        }
+
+        let ids: Vec<Uuid> = store
+            .get_api_keys(user)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|key| key.id)
+            .collect();
+
+        assert_eq!(ids, vec![newer.id, older.id]);
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+    }
+
+    #[sqlx::test]
+    async fn test_touch_and_remove_api_key(pool: PgPool) {
+        let mut store = PostgresApiKeyStore::new(pool);
+        let user = UserId::new();
+        let key = key(user, "hash", Utc::now());
+        store.add_api_key(key.clone()).await.unwrap();
+        let used_at = key.created_at + Duration::minutes(1);
+
+        store.touch_api_key(key.id, used_at).await.unwrap();
+
+        assert_eq!(
+            store.get_api_key("hash").await.unwrap().last_used_at,
+            Some(used_at)
+        );
+        assert_eq!(
+            store
+                .remove_api_key(UserId::new(), key.id)
+                .await
+                .unwrap_err(),
+            ApiKeyStoreError::ApiKeyNotFound
+        );
+        store.remove_api_key(user, key.id).await.unwrap();
+        assert_eq!(
+            store.touch_api_key(key.id, used_at).await.unwrap_err(),
+            ApiKeyStoreError::ApiKeyNotFound
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use color_eyre::eyre::Context;
+use sqlx::PgPool;
+
+use crate::domain::{
+    AuditEntry, AuditEvent, AuditEventKind, AuditLog, AuditLogError, AuditOutcome, GENESIS_HASH,
+};
+
+pub struct PostgresAuditLog {
+    pool: PgPool,
+}
+
+impl PostgresAuditLog {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLog for PostgresAuditLog {
+    #[tracing::instrument(name = "Appending audit entry to PostgreSQL", skip_all)]
+    async fn append(&mut self, event: AuditEvent) -> Result<(), AuditLogError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .wrap_err("failed to start audit log transaction")
+            .map_err(AuditLogError::UnexpectedError)?;
+        // Appends from every instance are serialized, so each one chains onto the latest entry.
+        // Reads are not blocked.
+        sqlx::query!("lock table audit_log in share row exclusive mode")
+            .execute(&mut *transaction)
+            .await
+            .wrap_err("failed to lock audit log")
+            .map_err(AuditLogError::UnexpectedError)?;
+        let prev_hash = sqlx::query_scalar!("select hash from audit_log order by id desc limit 1")
+            .fetch_optional(&mut *transaction)
+            .await
+            .wrap_err("failed to read latest audit entry")
+            .map_err(AuditLogError::UnexpectedError)?
+            .unwrap_or_else(|| GENESIS_HASH.to_owned());
+
+        // The id is assigned by the database and is not part of the hash
+        let entry = AuditEntry::chain(0, event, prev_hash);
+        let event = &entry.event;
+        sqlx::query!(
+            r#"
+            insert into audit_log
+                (recorded_at, event, actor, admin, ip, user_agent, outcome, reason, prev_hash, hash)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            entry.recorded_at,
+            event.kind.as_str(),
+            event.actor,
+            event.admin,
+            event.ip.map(|ip| ip.to_string()),
+            event.user_agent,
+            event.outcome.as_str(),
+            event.reason,
+            entry.prev_hash,
+            entry.hash,
+        )
+        .execute(&mut *transaction)
+        .await
+        .wrap_err("failed to insert audit entry into PostgreSQL")
+        .map_err(AuditLogError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .wrap_err("failed to commit audit entry")
+            .map_err(AuditLogError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Reading audit entries from PostgreSQL", skip_all)]
+    async fn entries(&self, after_id: i64, limit: i64) -> Result<Vec<AuditEntry>, AuditLogError> {
+        let rows = sqlx::query!(
+            r#"
+            select id, recorded_at, event, actor, admin, ip, user_agent, outcome, reason,
+                prev_hash, hash
+            from audit_log
+            where id > $1
+            order by id
+            limit $2
+            "#,
+            after_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to read audit entries from PostgreSQL")
+        .map_err(AuditLogError::UnexpectedError)?;
+
+        rows.into_iter()
+            .map(|row| {
+                let corrupt = || AuditLogError::CorruptEntry(row.id);
+                let ip = match &row.ip {
+                    Some(ip) => Some(ip.parse().map_err(|_| corrupt())?),
+                    None => None,
+                };
+                Ok(AuditEntry {
+                    id: row.id,
+                    recorded_at: row.recorded_at,
+                    event: AuditEvent {
+                        kind: AuditEventKind::parse(&row.event).ok_or_else(corrupt)?,
+                        actor: row.actor,
+                        admin: row.admin,
+                        ip,
+                        user_agent: row.user_agent,
+                        outcome: AuditOutcome::parse(&row.outcome).ok_or_else(corrupt)?,
+                        reason: row.reason,
+                    },
+                    prev_hash: row.prev_hash,
+                    hash: row.hash,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ChainProblem, ChainVerification};
+
+    fn event(kind: AuditEventKind, outcome: AuditOutcome) -> AuditEvent {
+        AuditEvent {
+            kind,
+            actor: Some("user@example.com".to_owned()),
+            admin: None,
+            ip: Some("203.0.113.7".parse().unwrap()),
+            user_agent: Some("curl/8.0".to_owned()),
+            outcome,
+            reason: None,
+        }
+    }
+
+    async fn audit_log_with_entries(pool: PgPool) -> PostgresAuditLog {
+        let mut audit_log = PostgresAuditLog::new(pool);
+        for (kind, outcome) in [
+            (AuditEventKind::Signup, AuditOutcome::Success),
+            (AuditEventKind::Login, AuditOutcome::Failure),
+            (AuditEventKind::Login, AuditOutcome::Success),
+        ] {
+            audit_log.append(event(kind, outcome)).await.unwrap();
+        }
+        audit_log
+    }
+
+    // Tampering means getting around the append-only triggers first
+    async fn tamper(pool: &PgPool, statement: &str) {
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("alter table audit_log disable trigger user")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query(statement).execute(&mut *conn).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_append_and_read_entries(pool: PgPool) {
+        let audit_log = audit_log_with_entries(pool).await;
+
+        let entries = audit_log.entries(0, 10).await.unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(entries[1].prev_hash, entries[0].hash);
+        assert_eq!(
+            entries[1].event,
+            event(AuditEventKind::Login, AuditOutcome::Failure)
+        );
+        assert_eq!(
+            audit_log.entries(entries[1].id, 10).await.unwrap(),
+            entries[2..]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_verify_intact_chain(pool: PgPool) {
+        let audit_log = audit_log_with_entries(pool).await;
+
+        let result = audit_log.verify().await.unwrap();
+
+        assert!(matches!(
+            result,
+            ChainVerification::Intact { entries: 3, .. }
+        ));
+    }
+
+    #[sqlx::test]
+    async fn test_reject_update_and_delete(pool: PgPool) {
+        audit_log_with_entries(pool.clone()).await;
+
+        let update = sqlx::query("update audit_log set outcome = 'success'")
+            .execute(&pool)
+            .await;
+        let delete = sqlx::query("delete from audit_log").execute(&pool).await;
+
+        assert!(update.is_err());
+        assert!(delete.is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_verify_detects_modified_entry(pool: PgPool) {
+        let audit_log = audit_log_with_entries(pool.clone()).await;
+        let id = audit_log.entries(0, 10).await.unwrap()[1].id;
+
+        tamper(
+            &pool,
+            &format!("update audit_log set outcome = 'success' where id = {}", id),
+        )
+        .await;
+
+        assert_eq!(
+            audit_log.verify().await.unwrap(),
+            ChainVerification::Broken {
+                entry_id: id,
+                problem: ChainProblem::Modified
+            }
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_verify_detects_deleted_entry(pool: PgPool) {
+        let audit_log = audit_log_with_entries(pool.clone()).await;
+        let entries = audit_log.entries(0, 10).await.unwrap();
+
+        tamper(
+            &pool,
+            &format!("delete from audit_log where id = {}", entries[1].id),
+        )
+        .await;
+
+        assert_eq!(
+            audit_log.verify().await.unwrap(),
+            ChainVerification::Broken {
+                entry_id: entries[2].id,
+                problem: ChainProblem::Unlinked
+            }
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_verify_reports_unreadable_entry(pool: PgPool) {
+        let audit_log = audit_log_with_entries(pool.clone()).await;
+        let id = audit_log.entries(0, 10).await.unwrap()[0].id;
+
+        tamper(
+            &pool,
+            &format!("update audit_log set event = 'sudo' where id = {}", id),
+        )
+        .await;
+
+        assert_eq!(
+            audit_log.verify().await.unwrap(),
+            ChainVerification::Broken {
+                entry_id: id,
+                problem: ChainProblem::Modified
+            }
+        );
+    }
+}
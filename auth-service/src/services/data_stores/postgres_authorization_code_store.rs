@@ -0,0 +1,217 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use secrecy::SecretString;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::ExpiringStore, grant_key, AuthorizationCodeStore, AuthorizationGrant,
+    GrantStoreError,
+};
+
+pub struct PostgresAuthorizationCodeStore {
+    pool: PgPool,
+}
+
+impl PostgresAuthorizationCodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for PostgresAuthorizationCodeStore {
+    // Delete authorization codes that were never redeemed
+    #[tracing::instrument(name = "Purging expired authorization codes from PostgreSQL", skip_all)]
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            delete from authorization_codes
+            where expires_at <= now()
+            "#
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to purge expired authorization codes")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+struct AuthorizationCodeRow {
+    client_id: String,
+    redirect_uri: String,
+    user_id: Uuid,
+    scope: Option<String>,
+    code_challenge: String,
+    nonce: Option<String>,
+    auth_time: DateTime<Utc>,
+    amr: Vec<String>,
+    session_id: Uuid,
+    expires_at: DateTime<Utc>,
+}
+
+impl TryFrom<AuthorizationCodeRow> for AuthorizationGrant {
+    type Error = GrantStoreError;
+
+    fn try_from(row: AuthorizationCodeRow) -> Result<Self, Self::Error> {
+        Ok(AuthorizationGrant {
+            client_id: row.client_id,
+            redirect_uri: row.redirect_uri,
+            user_id: row.user_id.into(),
+            scope: row.scope,
+            code_challenge: row.code_challenge,
+            nonce: row.nonce,
+            auth_time: row.auth_time,
+            amr: row.amr,
+            session_id: row.session_id,
+            expires_at: row.expires_at,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for PostgresAuthorizationCodeStore {
+    #[tracing::instrument(name = "Adding authorization code to PostgreSQL", skip_all)]
+    async fn add_code(
+        &mut self,
+        code: &SecretString,
+        grant: AuthorizationGrant,
+    ) -> Result<(), GrantStoreError> {
+        sqlx::query!(
+            r#"
+            insert into authorization_codes (
+                code_hash, client_id, redirect_uri, user_id, scope, code_challenge,
+                nonce, auth_time, amr, session_id, expires_at
+            )
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            grant_key(code),
+            grant.client_id,
+            grant.redirect_uri,
+            grant.user_id.as_uuid(),
+            grant.scope,
+            grant.code_challenge,
+            grant.nonce,
+            grant.auth_time,
+            &grant.amr,
+            grant.session_id,
+            grant.expires_at,
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert authorization code into PostgreSQL")
+        .map_err(GrantStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    // Deleting and returning in one statement keeps a code from being redeemed twice
+    #[tracing::instrument(name = "Redeeming authorization code from PostgreSQL", skip_all)]
+    async fn take_code(
+        &mut self,
+        code: &SecretString,
+    ) -> Result<AuthorizationGrant, GrantStoreError> {
+        let row = sqlx::query_as!(
+            AuthorizationCodeRow,
+            r#"
+            delete from authorization_codes
+            where code_hash = $1
+            returning client_id, redirect_uri, user_id, scope, code_challenge,
+                nonce, auth_time, amr, session_id, expires_at
+            "#,
+            grant_key(code)
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to redeem authorization code from PostgreSQL")
+        .map_err(GrantStoreError::UnexpectedError)?
+        .ok_or(GrantStoreError::GrantNotFound)?;
+
+        let grant = AuthorizationGrant::try_from(row)?;
+        match grant.is_expired(Utc::now()) {
+            true => Err(GrantStoreError::GrantNotFound),
+            false => Ok(grant),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::{OAuthClient, OAuthClientStore, UserId},
+        services::data_stores::postgres_oauth_client_store::PostgresOAuthClientStore,
+    };
+    use chrono::Duration;
+
+    // Codes reference their client, so one has to exist first
+    async fn store(pool: PgPool) -> PostgresAuthorizationCodeStore {
+        PostgresOAuthClientStore::new(pool.clone())
+            .add_client(OAuthClient {
+                id: "client".to_owned(),
+                name: "Client".to_owned(),
+                redirect_uris: vec!["https://app.example.com/callback".to_owned()],
+                secret_hash: None,
+                allowed_scopes: Vec::new(),
+                created_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        PostgresAuthorizationCodeStore::new(pool)
+    }
+
+    fn grant(expires_at: DateTime<Utc>) -> AuthorizationGrant {
+        let expires_at = DateTime::from_timestamp_micros(expires_at.timestamp_micros()).unwrap();
+        let auth_time = DateTime::from_timestamp_micros(Utc::now().timestamp_micros()).unwrap();
+        AuthorizationGrant {
+            client_id: "client".to_owned(),
+            redirect_uri: "https://app.example.com/callback".to_owned(),
+            user_id: UserId::new(),
+            scope: Some("profile".to_owned()),
+            code_challenge: "challenge".to_owned(),
+            nonce: Some("nonce".to_owned()),
+            auth_time,
+            amr: vec!["pwd".to_owned()],
+            session_id: Uuid::new_v4(),
+            expires_at,
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_redeem_code_only_once(pool: PgPool) {
+        let mut store = store(pool).await;
+        let code = SecretString::from("code");
+        let grant = grant(Utc::now() + Duration::minutes(1));
+        store.add_code(&code, grant.clone()).await.unwrap();
+
+        assert_eq!(store.take_code(&code).await.unwrap(), grant);
+        assert_eq!(
+            store.take_code(&code).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_purge_expired_codes(pool: PgPool) {
+        let mut store = store(pool).await;
+        let expired = SecretString::from("expired");
+        store
+            .add_code(&expired, grant(Utc::now() - Duration::seconds(1)))
+            .await
+            .unwrap();
+        store
+            .add_code(
+                &SecretString::from("valid"),
+                grant(Utc::now() + Duration::minutes(1)),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+        assert_eq!(
+            store.take_code(&expired).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+    }
+}
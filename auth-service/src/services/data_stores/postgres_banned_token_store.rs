@@ -0,0 +1,128 @@
+use chrono::Utc;
+use color_eyre::eyre::{Context, Result};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError, ExpiringStore};
+
+pub struct PostgresBannedTokenStore {
+    pool: PgPool,
+    // Tokens only need to stay banned for as long as they would otherwise be valid
+    ttl: Duration,
+}
+
+impl PostgresBannedTokenStore {
+    pub fn new(pool: PgPool, ttl: Duration) -> Self {
+        Self { pool, ttl }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for PostgresBannedTokenStore {
+    // Delete banned tokens that have outlived the JWT TTL and can no longer be presented
+    #[tracing::instrument(name = "Purging expired banned tokens from PostgreSQL", skip_all)]
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            delete from banned_tokens
+            where expires_at <= now()
+            "#
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to purge expired banned tokens")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for PostgresBannedTokenStore {
+    #[tracing::instrument(name = "Adding banned token to PostgreSQL", skip_all)]
+    async fn add_token(&mut self, token: SecretString) -> Result<(), BannedTokenStoreError> {
+        let delta = chrono::Duration::from_std(self.ttl)
+            .wrap_err("failed to create token TTL time delta")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+        let expires_at = Utc::now() + delta;
+
+        sqlx::query!(
+            r#"
+            insert into banned_tokens (token, expires_at)
+            values ($1, $2)
+            on conflict (token) do nothing
+            "#,
+            token.expose_secret(),
+            expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert banned token into PostgreSQL")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking banned token in PostgreSQL", skip_all)]
+    async fn check_token(&self, token: &SecretString) -> Result<bool, BannedTokenStoreError> {
+        let is_banned = sqlx::query_scalar!(
+            r#"
+            select exists(
+                select 1 from banned_tokens
+                where token = $1 and expires_at > now()
+            ) as "exists!"
+            "#,
+            token.expose_secret()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to check if token exists in PostgreSQL")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(is_banned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::data_stores::contract_tests;
+
+    const TTL: Duration = Duration::from_secs(600);
+
+    #[sqlx::test]
+    async fn test_add_token(pool: PgPool) {
+        contract_tests::should_add_banned_token(PostgresBannedTokenStore::new(pool, TTL)).await;
+    }
+
+    #[sqlx::test]
+    async fn test_add_token_twice(pool: PgPool) {
+        contract_tests::should_add_banned_token_twice(PostgresBannedTokenStore::new(pool, TTL))
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn test_contains_token(pool: PgPool) {
+        contract_tests::should_only_contain_banned_tokens(PostgresBannedTokenStore::new(pool, TTL))
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn test_purge_expired_tokens(pool: PgPool) {
+        let mut test_store = PostgresBannedTokenStore::new(pool.clone(), TTL);
+        let live = SecretString::new("live".to_owned().into_boxed_str());
+        test_store.add_token(live.clone()).await.unwrap();
+        sqlx::query("insert into banned_tokens (token, expires_at) values ($1, now() - interval '1 second')")
+            .bind("expired")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let expired = SecretString::new("expired".to_owned().into_boxed_str());
+        assert!(!test_store.check_token(&expired).await.unwrap());
+        let purged = test_store.purge_expired().await.unwrap();
+
+        assert_eq!(purged, 1);
+        assert!(test_store.check_token(&live).await.unwrap());
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Context;
+use sqlx::PgPool;
+
+use crate::domain::{OAuthClient, OAuthClientStore, OAuthClientStoreError};
+
+pub struct PostgresOAuthClientStore {
+    pool: PgPool,
+}
+
+impl PostgresOAuthClientStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+struct OAuthClientRow {
+    id: String,
+    name: String,
+    redirect_uris: Vec<String>,
+    secret_hash: Option<String>,
+    allowed_scopes: Vec<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<OAuthClientRow> for OAuthClient {
+    fn from(row: OAuthClientRow) -> Self {
+        OAuthClient {
+            id: row.id,
+            name: row.name,
+            redirect_uris: row.redirect_uris,
+            secret_hash: row.secret_hash,
+            allowed_scopes: row.allowed_scopes,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for PostgresOAuthClientStore {
+    #[tracing::instrument(name = "Adding OAuth client to PostgreSQL", skip_all)]
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError> {
+        sqlx::query!(
+            r#"
+            insert into oauth_clients
+                (id, name, redirect_uris, secret_hash, allowed_scopes, created_at)
+            values ($1, $2, $3, $4, $5, $6)
+            "#,
+            client.id,
+            client.name,
+            &client.redirect_uris,
+            client.secret_hash,
+            &client.allowed_scopes,
+            client.created_at,
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert OAuth client into PostgreSQL")
+        .map_err(OAuthClientStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving OAuth client from PostgreSQL", skip_all)]
+    async fn get_client(&self, id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        sqlx::query_as!(
+            OAuthClientRow,
+            r#"
+            select id, name, redirect_uris, secret_hash, allowed_scopes, created_at
+            from oauth_clients
+            where id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to retrieve OAuth client from PostgreSQL")
+        .map_err(OAuthClientStoreError::UnexpectedError)?
+        .map(OAuthClient::from)
+        .ok_or(OAuthClientStoreError::ClientNotFound)
+    }
+
+    #[tracing::instrument(name = "Retrieving OAuth clients from PostgreSQL", skip_all)]
+    async fn get_clients(&self) -> Result<Vec<OAuthClient>, OAuthClientStoreError> {
+        let rows = sqlx::query_as!(
+            OAuthClientRow,
+            r#"
+            select id, name, redirect_uris, secret_hash, allowed_scopes, created_at
+            from oauth_clients
+            order by created_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to retrieve OAuth clients from PostgreSQL")
+        .map_err(OAuthClientStoreError::UnexpectedError)?;
+
+        Ok(rows.into_iter().map(OAuthClient::from).collect())
+    }
+
+    // Codes and refresh tokens issued to the client are deleted along with it
+    #[tracing::instrument(name = "Removing OAuth client from PostgreSQL", skip_all)]
+    async fn remove_client(&mut self, id: &str) -> Result<(), OAuthClientStoreError> {
+        let result = sqlx::query!("delete from oauth_clients where id = $1", id)
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete OAuth client from PostgreSQL")
+            .map_err(OAuthClientStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(OAuthClientStoreError::ClientNotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    // Postgres keeps microseconds, so the clients read back compare equal
+    fn client(id: &str, created_at: DateTime<Utc>) -> OAuthClient {
+        let created_at = DateTime::from_timestamp_micros(created_at.timestamp_micros()).unwrap();
+        OAuthClient {
+            id: id.to_owned(),
+            name: "Client".to_owned(),
+            redirect_uris: vec![
+                "https://app.example.com/callback".to_owned(),
+                "http://127.0.0.1:8000/callback".to_owned(),
+            ],
+            secret_hash: None,
+            allowed_scopes: Vec::new(),
+            created_at,
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_add_and_get_clients(pool: PgPool) {
+        let mut store = PostgresOAuthClientStore::new(pool);
+        let now = Utc::now();
+        let newer = client("newer", now);
+        let older = client("older", now - Duration::minutes(1));
+        store.add_client(newer.clone()).await.unwrap();
+        store.add_client(older.clone()).await.unwrap();
+
+        assert_eq!(store.get_client("newer").await.unwrap(), newer);
+        assert_eq!(store.get_clients().await.unwrap(), vec![older, newer]);
+    }
+
+    #[sqlx::test]
+    async fn test_add_and_get_confidential_client(pool: PgPool) {
+        let mut store = PostgresOAuthClientStore::new(pool);
+        let confidential = OAuthClient {
+            redirect_uris: Vec::new(),
+            secret_hash: Some("digest".to_owned()),
+            allowed_scopes: vec!["reports:read".to_owned()],
+            ..client("service", Utc::now())
+        };
+        store.add_client(confidential.clone()).await.unwrap();
+
+        assert_eq!(store.get_client("service").await.unwrap(), confidential);
+    }
+
+    #[sqlx::test]
+    async fn test_remove_client(pool: PgPool) {
+        let mut store = PostgresOAuthClientStore::new(pool);
+        store
+            .add_client(client("client", Utc::now()))
+            .await
+            .unwrap();
+
+        assert!(store.remove_client("client").await.is_ok());
+        assert_eq!(
+            store.get_client("client").await.unwrap_err(),
+            OAuthClientStoreError::ClientNotFound
+        );
+        assert_eq!(
+            store.remove_client("client").await.unwrap_err(),
+            OAuthClientStoreError::ClientNotFound
+        );
+    }
+}
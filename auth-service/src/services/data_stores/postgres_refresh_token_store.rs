@@ -0,0 +1,274 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use secrecy::SecretString;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::ExpiringStore, grant_key, GrantStoreError, RefreshGrant, RefreshTokenStore, UserId,
+};
+
+pub struct PostgresRefreshTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresRefreshTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for PostgresRefreshTokenStore {
+    // Delete refresh tokens that were never used up
+    #[tracing::instrument(name = "Purging expired refresh tokens from PostgreSQL", skip_all)]
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            delete from refresh_tokens
+            where expires_at <= now()
+            "#
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to purge expired refresh tokens")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+struct RefreshTokenRow {
+    client_id: String,
+    user_id: Uuid,
+    scope: Option<String>,
+    generation: i64,
+    expires_at: DateTime<Utc>,
+}
+
+impl TryFrom<RefreshTokenRow> for RefreshGrant {
+    type Error = GrantStoreError;
+
+    fn try_from(row: RefreshTokenRow) -> Result<Self, Self::Error> {
+        Ok(RefreshGrant {
+            client_id: row.client_id,
+            user_id: row.user_id.into(),
+            scope: row.scope,
+            generation: row.generation,
+            expires_at: row.expires_at,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for PostgresRefreshTokenStore {
+    #[tracing::instrument(name = "Adding refresh token to PostgreSQL", skip_all)]
+    async fn add_refresh_token(
+        &mut self,
+        token: &SecretString,
+        grant: RefreshGrant,
+    ) -> Result<(), GrantStoreError> {
+        sqlx::query!(
+            r#"
+            insert into refresh_tokens
+                (token_hash, client_id, user_id, scope, generation, expires_at)
+            values ($1, $2, $3, $4, $5, $6)
+            "#,
+            grant_key(token),
+            grant.client_id,
+            grant.user_id.as_uuid(),
+            grant.scope,
+            grant.generation,
+            grant.expires_at,
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert refresh token into PostgreSQL")
+        .map_err(GrantStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving refresh token from PostgreSQL", skip_all)]
+    async fn get_refresh_token(
+        &self,
+        token: &SecretString,
+    ) -> Result<RefreshGrant, GrantStoreError> {
+        sqlx::query_as!(
+            RefreshTokenRow,
+            r#"
+            select client_id, user_id, scope, generation, expires_at
+            from refresh_tokens
+            where token_hash = $1 and expires_at > now()
+            "#,
+            grant_key(token)
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to retrieve refresh token from PostgreSQL")
+        .map_err(GrantStoreError::UnexpectedError)?
+        .ok_or(GrantStoreError::GrantNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Removing refresh token from PostgreSQL", skip_all)]
+    async fn remove_refresh_token(&mut self, token: &SecretString) -> Result<(), GrantStoreError> {
+        let result = sqlx::query!(
+            "delete from refresh_tokens where token_hash = $1",
+            grant_key(token)
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete refresh token from PostgreSQL")
+        .map_err(GrantStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(GrantStoreError::GrantNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Removing refresh tokens of client from PostgreSQL", skip_all)]
+    async fn remove_refresh_tokens(
+        &mut self,
+        client_id: &str,
+        user_id: UserId,
+    ) -> Result<u64, GrantStoreError> {
+        let result = sqlx::query!(
+            "delete from refresh_tokens where client_id = $1 and user_id = $2",
+            client_id,
+            user_id.as_uuid()
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete refresh tokens from PostgreSQL")
+        .map_err(GrantStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Removing refresh tokens of user from PostgreSQL", skip_all)]
+    async fn remove_user_refresh_tokens(
+        &mut self,
+        user_id: UserId,
+    ) -> Result<u64, GrantStoreError> {
+        let result = sqlx::query!(
+            "delete from refresh_tokens where user_id = $1",
+            user_id.as_uuid()
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete refresh tokens from PostgreSQL")
+        .map_err(GrantStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::{OAuthClient, OAuthClientStore},
+        services::data_stores::postgres_oauth_client_store::PostgresOAuthClientStore,
+    };
+    use chrono::Duration;
+
+    // The user every grant of these tests is for
+    fn user_id() -> UserId {
+        UserId::from(Uuid::from_u128(1))
+    }
+
+    // Refresh tokens reference their client, so one has to exist first
+    async fn store(pool: PgPool) -> PostgresRefreshTokenStore {
+        PostgresOAuthClientStore::new(pool.clone())
+            .add_client(OAuthClient {
+                id: "client".to_owned(),
+                name: "Client".to_owned(),
+                redirect_uris: vec!["https://app.example.com/callback".to_owned()],
+                secret_hash: None,
+                allowed_scopes: Vec::new(),
+                created_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        PostgresRefreshTokenStore::new(pool)
+    }
+
+    fn grant(expires_at: DateTime<Utc>) -> RefreshGrant {
+        let expires_at = DateTime::from_timestamp_micros(expires_at.timestamp_micros()).unwrap();
+        RefreshGrant {
+            client_id: "client".to_owned(),
+            user_id: user_id(),
+            scope: None,
+            generation: 3,
+            expires_at,
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_add_get_and_remove_refresh_token(pool: PgPool) {
+        let mut store = store(pool).await;
+        let token = SecretString::from("token");
+        let grant = grant(Utc::now() + Duration::days(1));
+        store
+            .add_refresh_token(&token, grant.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_refresh_token(&token).await.unwrap(), grant);
+        assert!(store.remove_refresh_token(&token).await.is_ok());
+        assert_eq!(
+            store.get_refresh_token(&token).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+        assert_eq!(
+            store.remove_refresh_token(&token).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_remove_refresh_tokens_of_client_and_user(pool: PgPool) {
+        let mut store = store(pool).await;
+        let expires_at = Utc::now() + Duration::days(1);
+        store
+            .add_refresh_token(&SecretString::from("first"), grant(expires_at))
+            .await
+            .unwrap();
+        store
+            .add_refresh_token(&SecretString::from("second"), grant(expires_at))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store
+                .remove_refresh_tokens("client", UserId::new())
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            store
+                .remove_refresh_tokens("client", user_id())
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_purge_expired_refresh_tokens(pool: PgPool) {
+        let mut store = store(pool).await;
+        let expired = SecretString::from("expired");
+        store
+            .add_refresh_token(&expired, grant(Utc::now() - Duration::seconds(1)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_refresh_token(&expired).await.unwrap_err(),
+            GrantStoreError::GrantNotFound
+        );
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,265 @@
+use color_eyre::eyre::Context;
+use sqlx::PgPool;
+
+use crate::domain::{Role, RoleStore, RoleStoreError, UserId};
+
+pub struct PostgresRoleStore {
+    pool: PgPool,
+}
+
+impl PostgresRoleStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    async fn role_exists(&self, name: &str) -> Result<bool, RoleStoreError> {
+        sqlx::query_scalar!(
+            r#"select exists(select 1 from roles where name = $1) as "exists!""#,
+            name
+        )
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to look up role in PostgreSQL")
+        .map_err(RoleStoreError::UnexpectedError)
+    }
+}
+
+struct RoleRow {
+    name: String,
+    permissions: Vec<String>,
+}
+
+impl From<RoleRow> for Role {
+    fn from(row: RoleRow) -> Self {
+        Role {
+            name: row.name,
+            permissions: row.permissions,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RoleStore for PostgresRoleStore {
+    #[tracing::instrument(name = "Putting role into PostgreSQL", skip_all)]
+    async fn put_role(&mut self, role: Role) -> Result<(), RoleStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .wrap_err("failed to start role transaction")
+            .map_err(RoleStoreError::UnexpectedError)?;
+        sqlx::query!(
+            "insert into roles (name) values ($1) on conflict (name) do nothing",
+            role.name
+        )
+        .execute(&mut *transaction)
+        .await
+        .wrap_err("failed to insert role into PostgreSQL")
+        .map_err(RoleStoreError::UnexpectedError)?;
+        sqlx::query!("delete from role_permissions where role = $1", role.name)
+            .execute(&mut *transaction)
+            .await
+            .wrap_err("failed to delete role permissions from PostgreSQL")
+            .map_err(RoleStoreError::UnexpectedError)?;
+        sqlx::query!(
+            r#"
+            insert into role_permissions (role, permission)
+            select $1, permission from unnest($2::text[]) as permission
+            "#,
+            role.name,
+            &role.permissions,
+        )
+        .execute(&mut *transaction)
+        .await
+        .wrap_err("failed to insert role permissions into PostgreSQL")
+        .map_err(RoleStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .wrap_err("failed to commit role")
+            .map_err(RoleStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Retrieving roles from PostgreSQL", skip_all)]
+    async fn get_roles(&self) -> Result<Vec<Role>, RoleStoreError> {
+        let rows = sqlx::query_as!(
+            RoleRow,
+            r#"
+            select roles.name,
+                coalesce(
+                    array_agg(permission order by permission)
+                        filter (where permission is not null),
+                    '{}'
+                ) as "permissions!"
+            from roles
+            left join role_permissions on role_permissions.role = roles.name
+            group by roles.name
+            order by roles.name
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to retrieve roles from PostgreSQL")
+        .map_err(RoleStoreError::UnexpectedError)?;
+
+        Ok(rows.into_iter().map(Role::from).collect())
+    }
+
+    // Assignments and permissions of the role are deleted along with it
+    #[tracing::instrument(name = "Deleting role from PostgreSQL", skip_all)]
+    async fn delete_role(&mut self, name: &str) -> Result<(), RoleStoreError> {
+        let result = sqlx::query!("delete from roles where name = $1", name)
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete role from PostgreSQL")
+            .map_err(RoleStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(RoleStoreError::RoleNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Assigning role in PostgreSQL", skip_all)]
+    async fn assign_role(&mut self, user_id: UserId, role: &str) -> Result<(), RoleStoreError> {
+        let result = sqlx::query!(
+            r#"
+            insert into user_roles (user_id, role)
+            select $1, name from roles where name = $2
+            on conflict (user_id, role) do nothing
+            "#,
+            user_id.as_uuid(),
+            role
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to assign role in PostgreSQL")
+        .map_err(RoleStoreError::UnexpectedError)?;
+
+        // Nothing is inserted either when the role is missing or when it was already assigned
+        match result.rows_affected() > 0 || self.role_exists(role).await? {
+            true => Ok(()),
+            false => Err(RoleStoreError::RoleNotFound),
+        }
+    }
+
+    #[tracing::instrument(name = "Unassigning role in PostgreSQL", skip_all)]
+    async fn unassign_role(&mut self, user_id: UserId, role: &str) -> Result<(), RoleStoreError> {
+        let result = sqlx::query!(
+            "delete from user_roles where user_id = $1 and role = $2",
+            user_id.as_uuid(),
+            role
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to unassign role in PostgreSQL")
+        .map_err(RoleStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(RoleStoreError::RoleNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Retrieving user roles from PostgreSQL", skip_all)]
+    async fn get_user_roles(&self, user_id: UserId) -> Result<Vec<Role>, RoleStoreError> {
+        let rows = sqlx::query_as!(
+            RoleRow,
+            r#"
+            select user_roles.role as name,
+                coalesce(
+                    array_agg(permission order by permission)
+                        filter (where permission is not null),
+                    '{}'
+                ) as "permissions!"
+            from user_roles
+            left join role_permissions on role_permissions.role = user_roles.role
+            where user_roles.user_id = $1
+            group by user_roles.role
+            order by user_roles.role
+            "#,
+            user_id.as_uuid()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to retrieve user roles from PostgreSQL")
+        .map_err(RoleStoreError::UnexpectedError)?;
+
+        Ok(rows.into_iter().map(Role::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(name: &str, permissions: &[&str]) -> Role {
+        Role {
+            name: name.to_owned(),
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_put_and_get_roles(pool: PgPool) {
+        let mut store = PostgresRoleStore::new(pool);
+        store.put_role(role("viewer", &["a:read"])).await.unwrap();
+        store.put_role(role("empty", &[])).await.unwrap();
+        store
+            .put_role(role("viewer", &["b:read", "c:read"]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_roles().await.unwrap(),
+            vec![role("empty", &[]), role("viewer", &["b:read", "c:read"])]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_assign_and_unassign_roles(pool: PgPool) {
+        let mut store = PostgresRoleStore::new(pool);
+        let user = UserId::new();
+        store.put_role(role("viewer", &["a:read"])).await.unwrap();
+        store.put_role(role("editor", &["a:write"])).await.unwrap();
+
+        assert_eq!(
+            store.assign_role(user, "missing").await.unwrap_err(),
+            RoleStoreError::RoleNotFound
+        );
+        store.assign_role(user, "viewer").await.unwrap();
+        store.assign_role(user, "viewer").await.unwrap();
+        store.assign_role(user, "editor").await.unwrap();
+        assert_eq!(
+            store.get_user_roles(user).await.unwrap(),
+            vec![role("editor", &["a:write"]), role("viewer", &["a:read"])]
+        );
+
+        store.unassign_role(user, "viewer").await.unwrap();
+        assert_eq!(
+            store.unassign_role(user, "viewer").await.unwrap_err(),
+            RoleStoreError::RoleNotFound
+        );
+        assert_eq!(
+            store.get_user_roles(user).await.unwrap(),
+            vec![role("editor", &["a:write"])]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_delete_role_unassigns_it(pool: PgPool) {
+        let mut store = PostgresRoleStore::new(pool);
+        let user = UserId::new();
+        store.put_role(role("viewer", &["a:read"])).await.unwrap();
+        store.assign_role(user, "viewer").await.unwrap();
+
+        store.delete_role("viewer").await.unwrap();
+
+        assert!(store.get_user_roles(user).await.unwrap().is_empty());
+        assert_eq!(
+            store.delete_role("viewer").await.unwrap_err(),
+            RoleStoreError::RoleNotFound
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{data_stores::ExpiringStore, Session, SessionStore, SessionStoreError, UserId};
+
+pub struct PostgresSessionStore {
+    pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for PostgresSessionStore {
+    // Delete sessions whose token has expired
+    #[tracing::instrument(name = "Purging expired sessions from PostgreSQL", skip_all)]
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            delete from sessions
+            where expires_at <= now()
+            "#
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to purge expired sessions")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+struct SessionRow {
+    id: Uuid,
+    user_id: Uuid,
+    client_id: Option<String>,
+    created_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl TryFrom<SessionRow> for Session {
+    type Error = SessionStoreError;
+
+    fn try_from(row: SessionRow) -> Result<Self, Self::Error> {
+        let ip = match row.ip {
+            Some(ip) => Some(
+                ip.parse()
+                    .map_err(|_| eyre!("invalid IP address in session {}", row.id))
+                    .map_err(SessionStoreError::UnexpectedError)?,
+            ),
+            None => None,
+        };
+        Ok(Session {
+            id: row.id,
+            user_id: row.user_id.into(),
+            client_id: row.client_id,
+            created_at: row.created_at,
+            last_seen_at: row.last_seen_at,
+            expires_at: row.expires_at,
+            ip,
+            user_agent: row.user_agent,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for PostgresSessionStore {
+    #[tracing::instrument(name = "Adding session to PostgreSQL", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            r#"
+            insert into sessions
+                (id, user_id, client_id, created_at, last_seen_at, expires_at, ip, user_agent)
+            values ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            session.id,
+            session.user_id.as_uuid(),
+            session.client_id,
+            session.created_at,
+            session.last_seen_at,
+            session.expires_at,
+            session.ip.map(|ip| ip.to_string()),
+            session.user_agent,
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert session into PostgreSQL")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving session from PostgreSQL", skip_all)]
+    async fn get_session(&self, id: Uuid) -> Result<Session, SessionStoreError> {
+        sqlx::query_as!(
+            SessionRow,
+            r#"
+            select id, user_id, client_id, created_at, last_seen_at, expires_at, ip, user_agent
+            from sessions
+            where id = $1 and expires_at > now()
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to retrieve session from PostgreSQL")
+        .map_err(SessionStoreError::UnexpectedError)?
+        .ok_or(SessionStoreError::SessionNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Retrieving user sessions from PostgreSQL", skip_all)]
+    async fn get_sessions(&self, user_id: UserId) -> Result<Vec<Session>, SessionStoreError> {
+        sqlx::query_as!(
+            SessionRow,
+            r#"
+            select id, user_id, client_id, created_at, last_seen_at, expires_at, ip, user_agent
+            from sessions
+            where user_id = $1 and expires_at > now()
+            order by created_at desc
+            "#,
+            user_id.as_uuid()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to retrieve user sessions from PostgreSQL")
+        .map_err(SessionStoreError::UnexpectedError)?
+        .into_iter()
+        .map(Session::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Updating session last seen time in PostgreSQL", skip_all)]
+    async fn touch_session(
+        &mut self,
+        id: Uuid,
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"
+            update sessions
+            set last_seen_at = $2
+            where id = $1 and expires_at > now()
+            "#,
+            id,
+            seen_at
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to update session in PostgreSQL")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(SessionStoreError::SessionNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Removing session from PostgreSQL", skip_all)]
+    async fn remove_session(&mut self, id: Uuid) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!("delete from sessions where id = $1", id)
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete session from PostgreSQL")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(SessionStoreError::SessionNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Removing user sessions from PostgreSQL", skip_all)]
+    async fn remove_sessions(&mut self, user_id: UserId) -> Result<u64, SessionStoreError> {
+        let result = sqlx::query!("delete from sessions where user_id = $1", user_id.as_uuid())
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete user sessions from PostgreSQL")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    // Postgres keeps microseconds, so the sessions read back compare equal
+    fn session(user_id: UserId, created_at: DateTime<Utc>) -> Session {
+        let created_at = DateTime::from_timestamp_micros(created_at.timestamp_micros()).unwrap();
+        Session {
+            id: Uuid::new_v4(),
+            user_id,
+            client_id: Some("client".to_owned()),
+            created_at,
+            last_seen_at: created_at,
+            expires_at: created_at + Duration::minutes(10),
+            ip: Some("203.0.113.7".parse().unwrap()),
+            user_agent: Some("curl/8.0".to_owned()),
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_add_and_get_session(pool: PgPool) {
+        let mut store = PostgresSessionStore::new(pool);
+        let session = session(UserId::new(), Utc::now());
+
+        store.add_session(session.clone()).await.unwrap();
+        let stored = store.get_session(session.id).await.unwrap();
+
+        assert_eq!(stored.user_id, session.user_id);
+        assert_eq!(stored.client_id, session.client_id);
+        assert_eq!(stored.created_at, session.created_at);
+        assert_eq!(stored.ip, session.ip);
+        assert_eq!(stored.user_agent, session.user_agent);
+    }
+
+    #[sqlx::test]
+    async fn test_get_sessions_of_user_newest_first(pool: PgPool) {
+        let mut store = PostgresSessionStore::new(pool);
+        let user = UserId::new();
+        let now = Utc::now();
+        let older = session(user, now - Duration::minutes(2));
+        let newer = session(user, now);
+        let expired = session(user, now - Duration::minutes(20));
+        let other = session(UserId::new(), now);
+        for session in [older.clone(), newer.clone(), expired, other] {
+            store.add_session(session).await.unwrap();
+        }
+
+        let ids: Vec<Uuid> = store
+            .get_sessions(user)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|session| session.id)
+            .collect();
+
+        assert_eq!(ids, vec![newer.id, older.id]);
+    }
+
+    #[sqlx::test]
+    async fn test_touch_and_remove_session(pool: PgPool) {
+        let mut store = PostgresSessionStore::new(pool);
+        let session = session(UserId::new(), Utc::now());
+        store.add_session(session.clone()).await.unwrap();
+        let seen_at = session.created_at + Duration::minutes(1);
+
+        store.touch_session(session.id, seen_at).await.unwrap();
+
+        assert_eq!(
+            store.get_session(session.id).await.unwrap().last_seen_at,
+            seen_at
+        );
+        assert!(store.remove_session(session.id).await.is_ok());
+        assert_eq!(
+            store.get_session(session.id).await.unwrap_err(),
+            SessionStoreError::SessionNotFound
+        );
+        assert_eq!(
+            store.touch_session(session.id, seen_at).await.unwrap_err(),
+            SessionStoreError::SessionNotFound
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_remove_sessions_of_user(pool: PgPool) {
+        let mut store = PostgresSessionStore::new(pool);
+        let user = UserId::new();
+        let other = session(UserId::new(), Utc::now());
+        for session in [session(user, Utc::now()), session(user, Utc::now())] {
+            store.add_session(session).await.unwrap();
+        }
+        store.add_session(other.clone()).await.unwrap();
+
+        assert_eq!(store.remove_sessions(user).await.unwrap(), 2);
+        assert!(store.get_sessions(user).await.unwrap().is_empty());
+        assert!(store.get_session(other.id).await.is_ok());
+    }
+
+    #[sqlx::test]
+    async fn test_purge_expired_sessions(pool: PgPool) {
+        let mut store = PostgresSessionStore::new(pool);
+        let user = UserId::new();
+        let expired = session(user, Utc::now() - Duration::minutes(20));
+        store.add_session(expired.clone()).await.unwrap();
+        store.add_session(session(user, Utc::now())).await.unwrap();
+
+        assert_eq!(
+            store.get_session(expired.id).await.unwrap_err(),
+            SessionStoreError::SessionNotFound
+        );
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+    }
+}
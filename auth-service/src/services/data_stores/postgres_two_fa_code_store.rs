@@ -0,0 +1,198 @@
+use chrono::Utc;
+use color_eyre::eyre::{Context, Result};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::domain::{
+    data_stores::{ExpiringStore, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    UserId,
+};
+
+pub struct PostgresTwoFACodeStore {
+    pool: PgPool,
+    ttl: Duration,
+}
+
+impl PostgresTwoFACodeStore {
+    pub fn new(pool: PgPool, ttl: Duration) -> Self {
+        Self { pool, ttl }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for PostgresTwoFACodeStore {
+    // Delete 2FA codes whose login attempt has expired
+    #[tracing::instrument(name = "Purging expired 2FA codes from PostgreSQL", skip_all)]
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            delete from two_fa_codes
+            where expires_at <= now()
+            "#
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to purge expired 2FA codes")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for PostgresTwoFACodeStore {
+    #[tracing::instrument(name = "Adding 2FA code to PostgreSQL", skip_all)]
+    async fn add_code(
+        &mut self,
+        user_id: UserId,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let delta = chrono::Duration::from_std(self.ttl)
+            .wrap_err("failed to create 2FA code TTL time delta")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let expires_at = Utc::now() + delta;
+
+        sqlx::query!(
+            r#"
+            insert into two_fa_codes (user_id, login_attempt_id, code, expires_at)
+            values ($1, $2, $3, $4)
+            on conflict (user_id) do update
+            set login_attempt_id = excluded.login_attempt_id,
+                code = excluded.code,
+                expires_at = excluded.expires_at
+            "#,
+            user_id.as_uuid(),
+            login_attempt_id.as_ref().expose_secret(),
+            code.as_ref().expose_secret(),
+            expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to set 2FA code in PostgreSQL")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing 2FA code from PostgreSQL", skip_all)]
+    async fn remove_code(&mut self, user_id: UserId) -> Result<(), TwoFACodeStoreError> {
+        let result = sqlx::query!(
+            r#"
+            delete from two_fa_codes
+            where user_id = $1
+            "#,
+            user_id.as_uuid()
+        )
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete 2FA code from PostgreSQL")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Retrieving 2FA code from PostgreSQL", skip_all)]
+    async fn get_code(
+        &self,
+        user_id: UserId,
+    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        let row = sqlx::query!(
+            r#"
+            select login_attempt_id, code
+            from two_fa_codes
+            where user_id = $1 and expires_at > now()
+            "#,
+            user_id.as_uuid()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to get 2FA code from PostgreSQL")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let login_attempt_id = LoginAttemptId::parse(row.login_attempt_id)
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let code = TwoFACode::parse(row.code).map_err(TwoFACodeStoreError::UnexpectedError)?;
+        Ok((login_attempt_id, code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::data_stores::contract_tests;
+
+    const TTL: Duration = Duration::from_secs(600);
+
+    #[sqlx::test]
+    async fn should_add_valid_code_to_2fa_store(pool: PgPool) {
+        contract_tests::should_add_valid_code_to_2fa_store(PostgresTwoFACodeStore::new(pool, TTL))
+            .await;
+    }
+
+    #[sqlx::test]
+    async fn should_replace_existing_code_in_2fa_store(pool: PgPool) {
+        contract_tests::should_replace_existing_code_in_2fa_store(PostgresTwoFACodeStore::new(
+            pool, TTL,
+        ))
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn should_remove_matching_code_from_2fa_store(pool: PgPool) {
+        contract_tests::should_remove_matching_code_from_2fa_store(PostgresTwoFACodeStore::new(
+            pool, TTL,
+        ))
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn should_not_remove_missing_code_from_2fa_store(pool: PgPool) {
+        contract_tests::should_not_remove_missing_code_from_2fa_store(PostgresTwoFACodeStore::new(
+            pool, TTL,
+        ))
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn should_get_matching_code_from_2fa_store(pool: PgPool) {
+        contract_tests::should_get_matching_code_from_2fa_store(PostgresTwoFACodeStore::new(
+            pool, TTL,
+        ))
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn should_not_get_missing_code_from_2fa_store(pool: PgPool) {
+        contract_tests::should_not_get_missing_code_from_2fa_store(PostgresTwoFACodeStore::new(
+            pool, TTL,
+        ))
+        .await;
+    }
+
+    #[sqlx::test]
+    async fn should_not_get_or_keep_expired_code(pool: PgPool) {
+        let store = PostgresTwoFACodeStore::new(pool.clone(), TTL);
+        let user_id = UserId::new();
+        sqlx::query(
+            "insert into two_fa_codes (user_id, login_attempt_id, code, expires_at)
+             values ($1, $2, $3, now() - interval '1 second')",
+        )
+        .bind(user_id.as_uuid())
+        .bind(LoginAttemptId::default().as_ref().expose_secret())
+        .bind(TwoFACode::default().as_ref().expose_secret())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            store.get_code(user_id).await.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+    }
+}
@@ -1,21 +1,20 @@
 use color_eyre::eyre::Context;
 use redis::{Commands, Connection};
 use secrecy::{ExposeSecret, SecretString};
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tokio::sync::RwLock;
 
-use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
-    utils::auth::TOKEN_TTL_SECONDS,
-};
+use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError};
 
 pub struct RedisBannedTokenStore {
     conn: Arc<RwLock<Connection>>,
+    // Tokens only need to stay banned for as long as they would otherwise be valid
+    ttl: Duration,
 }
 
 impl RedisBannedTokenStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    pub fn new(conn: Arc<RwLock<Connection>>, ttl: Duration) -> Self {
+        Self { conn, ttl }
     }
 }
 
@@ -24,10 +23,7 @@ impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument(name = "add token", skip_all)]
     async fn add_token(&mut self, token: SecretString) -> Result<(), BannedTokenStoreError> {
         let key = get_key(token.expose_secret());
-        let ttl: u64 = TOKEN_TTL_SECONDS
-            .try_into()
-            .wrap_err("failed to cast TOKEN_TTL_SECONDS to u64")
-            .map_err(BannedTokenStoreError::UnexpectedError)?;
+        let ttl = self.ttl.as_secs();
         let value = true;
         let _: () = self
             .conn
@@ -0,0 +1,235 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context};
+use redis::{Commands, Connection};
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    grant_key, DeviceCodeStore, DeviceGrant, DeviceGrantStatus, GrantStoreError, UserId,
+};
+
+// Each grant is kept under its device code's digest, and its user code points to that key.
+// Both expire along with the grant. The connection is held across the reads and writes of
+// an operation, so operations of this process do not interleave.
+pub struct RedisDeviceCodeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisDeviceCodeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl DeviceCodeStore for RedisDeviceCodeStore {
+    #[tracing::instrument(name = "add device code", skip_all)]
+    async fn add_device_code(
+        &mut self,
+        device_code: &SecretString,
+        grant: DeviceGrant,
+    ) -> Result<(), GrantStoreError> {
+        // A grant that has already expired would never be returned anyway
+        let Some(ttl) = seconds_left(&grant, Utc::now()) else {
+            return Ok(());
+        };
+        let key = get_device_code_key(device_code);
+        let user_code_key = get_user_code_key(&grant.user_code);
+        let mut conn = self.conn.write().await;
+        let in_use: bool = conn
+            .exists(&user_code_key)
+            .wrap_err("failed to check user code in Redis")
+            .map_err(GrantStoreError::UnexpectedError)?;
+        if in_use {
+            return Err(GrantStoreError::UnexpectedError(eyre!(
+                "user code is already in use"
+            )));
+        }
+        set_grant(&mut conn, &key, &grant, ttl)?;
+        let _: () = conn
+            .set_ex(&user_code_key, &key, ttl)
+            .wrap_err("failed to set user code in Redis")
+            .map_err(GrantStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "get user code", skip_all)]
+    async fn get_user_code(&self, user_code: &str) -> Result<DeviceGrant, GrantStoreError> {
+        let mut conn = self.conn.write().await;
+        let key = get_key_of_user_code(&mut conn, user_code)?;
+        get_grant(&mut conn, &key)
+    }
+
+    #[tracing::instrument(name = "set user code status", skip_all)]
+    async fn set_user_code_status(
+        &mut self,
+        user_code: &str,
+        status: DeviceGrantStatus,
+    ) -> Result<(), GrantStoreError> {
+        let mut conn = self.conn.write().await;
+        let key = get_key_of_user_code(&mut conn, user_code)?;
+        let mut grant = get_grant(&mut conn, &key)?;
+        if grant.status != DeviceGrantStatus::Pending {
+            return Err(GrantStoreError::GrantNotFound);
+        }
+        grant.status = status;
+        let ttl = seconds_left(&grant, Utc::now()).ok_or(GrantStoreError::GrantNotFound)?;
+        set_grant(&mut conn, &key, &grant, ttl)
+    }
+
+    #[tracing::instrument(name = "poll device code", skip_all)]
+    async fn poll_device_code(
+        &mut self,
+        device_code: &SecretString,
+        now: DateTime<Utc>,
+    ) -> Result<DeviceGrant, GrantStoreError> {
+        let key = get_device_code_key(device_code);
+        let mut conn = self.conn.write().await;
+        let before = get_grant(&mut conn, &key)?;
+        let mut grant = before.clone();
+        grant.record_poll(now);
+        let ttl = seconds_left(&grant, now).ok_or(GrantStoreError::GrantNotFound)?;
+        set_grant(&mut conn, &key, &grant, ttl)?;
+        Ok(before)
+    }
+
+    #[tracing::instrument(name = "remove device code", skip_all)]
+    async fn remove_device_code(
+        &mut self,
+        device_code: &SecretString,
+    ) -> Result<(), GrantStoreError> {
+        let key = get_device_code_key(device_code);
+        let mut conn = self.conn.write().await;
+        // Read first, to find the user code that has to go too
+        let grant = get_grant(&mut conn, &key)?;
+        let removed: u64 = conn
+            .del(&key)
+            .wrap_err("failed to delete device code from Redis")
+            .map_err(GrantStoreError::UnexpectedError)?;
+        let _: () = conn
+            .del(get_user_code_key(&grant.user_code))
+            .wrap_err("failed to delete user code from Redis")
+            .map_err(GrantStoreError::UnexpectedError)?;
+        match removed {
+            0 => Err(GrantStoreError::GrantNotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Redis expires keys after whole seconds, so what is left is rounded up. `None` once the
+// grant has expired.
+fn seconds_left(grant: &DeviceGrant, now: DateTime<Utc>) -> Option<u64> {
+    let left = (grant.expires_at - now).num_milliseconds();
+    (left > 0).then(|| (left as u64).div_ceil(1000))
+}
+
+fn get_key_of_user_code(conn: &mut Connection, user_code: &str) -> Result<String, GrantStoreError> {
+    let key: Option<String> = conn
+        .get(get_user_code_key(user_code))
+        .wrap_err("failed to get user code from Redis")
+        .map_err(GrantStoreError::UnexpectedError)?;
+    key.ok_or(GrantStoreError::GrantNotFound)
+}
+
+fn get_grant(conn: &mut Connection, key: &str) -> Result<DeviceGrant, GrantStoreError> {
+    let value: Option<String> = conn
+        .get(key)
+        .wrap_err("failed to get device code from Redis")
+        .map_err(GrantStoreError::UnexpectedError)?;
+    let value = value.ok_or(GrantStoreError::GrantNotFound)?;
+    let stored: StoredDeviceGrant = serde_json::from_str(&value)
+        .wrap_err("failed to deserialize device grant")
+        .map_err(GrantStoreError::UnexpectedError)?;
+    let grant: DeviceGrant = stored.try_into()?;
+    match grant.is_expired(Utc::now()) {
+        true => Err(GrantStoreError::GrantNotFound),
+        false => Ok(grant),
+    }
+}
+
+fn set_grant(
+    conn: &mut Connection,
+    key: &str,
+    grant: &DeviceGrant,
+    ttl: u64,
+) -> Result<(), GrantStoreError> {
+    let value = serde_json::to_string(&StoredDeviceGrant::from(grant.clone()))
+        .wrap_err("failed to serialize device grant")
+        .map_err(GrantStoreError::UnexpectedError)?;
+    let _: () = conn
+        .set_ex(key, value, ttl)
+        .wrap_err("failed to set device code in Redis")
+        .map_err(GrantStoreError::UnexpectedError)?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredDeviceGrant {
+    client_id: String,
+    user_code: String,
+    scope: Option<String>,
+    // The id of the user who approved the grant
+    approved_by: Option<String>,
+    denied: bool,
+    interval_seconds: u64,
+    last_polled_at: Option<DateTime<Utc>>,
+    expires_at: DateTime<Utc>,
+}
+
+impl From<DeviceGrant> for StoredDeviceGrant {
+    fn from(grant: DeviceGrant) -> Self {
+        let (approved_by, denied) = match grant.status {
+            DeviceGrantStatus::Pending => (None, false),
+            DeviceGrantStatus::Approved(user_id) => (Some(user_id.to_string()), false),
+            DeviceGrantStatus::Denied => (None, true),
+        };
+        Self {
+            client_id: grant.client_id,
+            user_code: grant.user_code,
+            scope: grant.scope,
+            approved_by,
+            denied,
+            interval_seconds: grant.interval_seconds,
+            last_polled_at: grant.last_polled_at,
+            expires_at: grant.expires_at,
+        }
+    }
+}
+
+impl TryFrom<StoredDeviceGrant> for DeviceGrant {
+    type Error = GrantStoreError;
+
+    fn try_from(stored: StoredDeviceGrant) -> Result<Self, Self::Error> {
+        let status = match (stored.approved_by, stored.denied) {
+            (Some(user_id), _) => DeviceGrantStatus::Approved(
+                UserId::parse(&user_id).map_err(GrantStoreError::UnexpectedError)?,
+            ),
+            (None, true) => DeviceGrantStatus::Denied,
+            (None, false) => DeviceGrantStatus::Pending,
+        };
+        Ok(Self {
+            client_id: stored.client_id,
+            user_code: stored.user_code,
+            scope: stored.scope,
+            status,
+            interval_seconds: stored.interval_seconds,
+            last_polled_at: stored.last_polled_at,
+            expires_at: stored.expires_at,
+        })
+    }
+}
+
+const DEVICE_CODE_KEY_PREFIX: &str = "device_code:";
+const USER_CODE_KEY_PREFIX: &str = "user_code:";
+
+// Device codes are kept by their digest, like the other OAuth grants
+fn get_device_code_key(device_code: &SecretString) -> String {
+    format!("{}{}", DEVICE_CODE_KEY_PREFIX, grant_key(device_code))
+}
+
+fn get_user_code_key(user_code: &str) -> String {
+    format!("{}{}", USER_CODE_KEY_PREFIX, user_code)
+}
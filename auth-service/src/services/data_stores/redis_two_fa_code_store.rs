@@ -2,21 +2,22 @@ use color_eyre::eyre::Context;
 use redis::{Commands, Connection};
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tokio::sync::RwLock;
 
 use crate::domain::{
     data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    Email,
+    UserId,
 };
 
 pub struct RedisTwoFACodeStore {
     conn: Arc<RwLock<Connection>>,
+    ttl: Duration,
 }
 
 impl RedisTwoFACodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    pub fn new(conn: Arc<RwLock<Connection>>, ttl: Duration) -> Self {
+        Self { conn, ttl }
     }
 }
 
@@ -25,11 +26,11 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
     #[tracing::instrument(name = "add 2FA code", skip_all)]
     async fn add_code(
         &mut self,
-        email: Email,
+        user_id: UserId,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(&email);
+        let key = get_key(user_id);
         let value = TwoFATuple(
             login_attempt_id.as_ref().expose_secret().to_string(),
             code.as_ref().expose_secret().to_string(),
@@ -41,15 +42,15 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
             .conn
             .write()
             .await
-            .set_ex(key, tuple_str, TEN_MINUTES_IN_SECONDS)
+            .set_ex(key, tuple_str, self.ttl.as_secs())
             .wrap_err("failed to set 2FA code in Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
         Ok(())
     }
 
     #[tracing::instrument(name = "remove 2FA code", skip_all)]
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(email);
+    async fn remove_code(&mut self, user_id: UserId) -> Result<(), TwoFACodeStoreError> {
+        let key = get_key(user_id);
         let _: () = self
             .conn
             .write()
@@ -63,9 +64,9 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
     #[tracing::instrument(name = "get 2FA code", skip_all)]
     async fn get_code(
         &self,
-        email: &Email,
+        user_id: UserId,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        let key = get_key(email);
+        let key = get_key(user_id);
         let code_str = self
             .conn
             .write()
@@ -85,9 +86,8 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
 #[derive(Serialize, Deserialize)]
 struct TwoFATuple(pub String, pub String);
 
-const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
 
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref())
+fn get_key(user_id: UserId) -> String {
+    format!("{}{}", TWO_FA_CODE_PREFIX, user_id)
 }
@@ -0,0 +1,109 @@
+use color_eyre::eyre::{Context, Result};
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use secrecy::ExposeSecret;
+
+use crate::{
+    domain::{Email, EmailClient},
+    settings::SmtpSettings,
+};
+
+pub struct SmtpEmailClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: Mailbox,
+}
+
+impl SmtpEmailClient {
+    pub fn new(settings: &SmtpSettings) -> Result<Self> {
+        let sender = settings
+            .sender
+            .parse()
+            .wrap_err("failed to parse SMTP sender address")?;
+        // Upgrade the connection with STARTTLS before authenticating
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)
+            .wrap_err("failed to create SMTP transport")?
+            .port(settings.port);
+        if !settings.username.is_empty() {
+            transport = transport.credentials(Credentials::new(
+                settings.username.clone(),
+                settings.password.expose_secret().to_owned(),
+            ));
+        }
+
+        Ok(Self {
+            transport: transport.build(),
+            sender,
+        })
+    }
+
+    // The underlying transport, for checking that the SMTP server is reachable
+    pub fn transport(&self) -> AsyncSmtpTransport<Tokio1Executor> {
+        self.transport.clone()
+    }
+
+    fn build_message(&self, recipient: &Email, subject: &str, content: &str) -> Result<Message> {
+        let recipient = recipient
+            .as_ref()
+            .parse()
+            .wrap_err("failed to parse recipient address")?;
+        Message::builder()
+            .from(self.sender.clone())
+            .to(recipient)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(content.to_owned())
+            .wrap_err("failed to build email message")
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    #[tracing::instrument(name = "Sending email over SMTP", skip_all)]
+    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()> {
+        let message = self.build_message(recipient, subject, content)?;
+        self.transport
+            .send(message)
+            .await
+            .wrap_err("failed to send email over SMTP")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(sender: &str) -> SmtpSettings {
+        SmtpSettings {
+            host: "smtp.example.com".to_owned(),
+            sender: sender.to_owned(),
+            ..SmtpSettings::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn should_build_plain_text_message() {
+        let client = SmtpEmailClient::new(&settings("Auth <auth@example.com>")).unwrap();
+        let recipient = Email::parse("user@example.com".to_owned()).unwrap();
+
+        let message = client
+            .build_message(&recipient, "2FA Code", "123456")
+            .unwrap();
+
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("From: Auth <auth@example.com>"));
+        assert!(formatted.contains("To: user@example.com"));
+        assert!(formatted.contains("Subject: 2FA Code"));
+        assert!(formatted.ends_with("123456"));
+    }
+
+    #[tokio::test]
+    async fn should_reject_invalid_sender() {
+        let result = SmtpEmailClient::new(&settings("not an address"));
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+use crate::domain::{data_stores::ExpiringStore, ApiKey, ApiKeyStore, ApiKeyStoreError, UserId};
+
+pub struct SqliteApiKeyStore {
+    pool: SqlitePool,
+}
+
+impl SqliteApiKeyStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for SqliteApiKeyStore {
+    // Delete keys past their expiry. Keys without one are kept until they are revoked.
+    #[tracing::instrument(name = "Purging expired API keys from SQLite", skip_all)]
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            delete from api_keys
+            where expires_at <= $1
+            "#,
+        )
+        .bind(Utc::now().timestamp_micros())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to purge expired API keys")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[derive(FromRow)]
+struct ApiKeyRow {
+    id: String,
+    user_id: String,
+    name: String,
+    key_hash: String,
+    key_hint: String,
+    scope: Option<String>,
+    created_at: i64,
+    expires_at: Option<i64>,
+    last_used_at: Option<i64>,
+}
+
+impl TryFrom<ApiKeyRow> for ApiKey {
+    type Error = ApiKeyStoreError;
+
+    fn try_from(row: ApiKeyRow) -> Result<Self, Self::Error> {
+        let corrupt = |field: &str| {
+            ApiKeyStoreError::UnexpectedError(eyre!("invalid {} in API key {}", field, row.id))
+        };
+        let timestamp = |micros: i64, field: &str| {
+            DateTime::from_timestamp_micros(micros).ok_or_else(|| corrupt(field))
+        };
+        Ok(ApiKey {
+            id: row.id.parse().map_err(|_| corrupt("id"))?,
+            user_id: UserId::parse(&row.user_id).map_err(ApiKeyStoreError::UnexpectedError)?,
+            name: row.name.clone(),
+            key_hash: row.key_hash.clone(),
+            key_hint: row.key_hint.clone(),
+            scope: row.scope.clone(),
+            created_at: timestamp(row.created_at, "created_at")?,
+            expires_at: row
+                .expires_at
+                .map(|micros| timestamp(micros, "expires_at"))
+                .transpose()?,
+            last_used_at: row
+                .last_used_at
+                .map(|micros| timestamp(micros, "last_used_at"))
+                .transpose()?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiKeyStore for SqliteApiKeyStore {
+    #[tracing::instrument(name = "Adding API key to SQLite", skip_all)]
+    async fn add_api_key(&mut self, key: ApiKey) -> Result<(), ApiKeyStoreError> {
+        sqlx::query(
+            r#"
+            insert into api_keys
+                (id, user_id, name, key_hash, key_hint, scope, created_at, expires_at, last_used_at)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(key.id.to_string())
+        .bind(key.user_id.to_string())
+        .bind(key.name)
+        .bind(key.key_hash)
+        .bind(key.key_hint)
+        .bind(key.scope)
+        .bind(key.created_at.timestamp_micros())
+        .bind(key.expires_at.map(|at| at.timestamp_micros()))
+        .bind(key.last_used_at.map(|at| at.timestamp_micros()))
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert API key into SQLite")
+        .map_err(ApiKeyStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving API key from SQLite", skip_all)]
+    async fn get_api_key(&self, key_hash: &str) -> Result<ApiKey, ApiKeyStoreError> {
+        sqlx::query_as::<_, ApiKeyRow>(
+            r#"
+            select id, user_id, name, key_hash, key_hint, scope, created_at, expires_at,
+                last_used_at
+            from api_keys
+            where key_hash = $1 and (expires_at is null or expires_at > $2)
+            "#,
+        )
+        .bind(key_hash)
+        .bind(Utc::now().timestamp_micros())
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to retrieve API key from SQLite")
+        .map_err(ApiKeyStoreError::UnexpectedError)?
+        .ok_or(ApiKeyStoreError::ApiKeyNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Retrieving user API keys from SQLite", skip_all)]
+    async fn get_api_keys(&self, user_id: UserId) -> Result<Vec<ApiKey>, ApiKeyStoreError> {
+        sqlx::query_as::<_, ApiKeyRow>(
+            r#"
+            select id, user_id, name, key_hash, key_hint, scope, created_at, expires_at,
+                last_used_at
+            from api_keys
+            where user_id = $1 and (expires_at is null or expires_at > $2)
+            order by created_at desc
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(Utc::now().timestamp_micros())
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to retrieve user API keys from SQLite")
+        .map_err(ApiKeyStoreError::UnexpectedError)?
+        .into_iter()
+        .map(ApiKey::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Updating API key last used time in SQLite", skip_all)]
+    async fn touch_api_key(
+        &mut self,
+        id: Uuid,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), ApiKeyStoreError> {
+        let result = sqlx::query(
+            r#"
+            update api_keys
+            set last_used_at = $2
+            where id = $1 and (expires_at is null or expires_at > $3)
+            "#,
+        )
+        .bind(id.to_string())
+        .bind(used_at.timestamp_micros())
+        .bind(Utc::now().timestamp_micros())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to update API key in SQLite")
+        .map_err(ApiKeyStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(ApiKeyStoreError::ApiKeyNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Removing API key from SQLite", skip_all)]
+    async fn remove_api_key(&mut self, user_id: UserId, id: Uuid) -> Result<(), ApiKeyStoreError> {
+        let result = sqlx::query("delete from api_keys where id = $1 and user_id = $2")
+            .bind(id.to_string())
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete API key from SQLite")
+            .map_err(ApiKeyStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(ApiKeyStoreError::ApiKeyNotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    // Only microseconds are stored, so the keys read back compare equal
+    fn key(user_id: UserId, key_hash: &str, created_at: DateTime<Utc>) -> ApiKey {
+        let created_at = DateTime::from_timestamp_micros(created_at.timestamp_micros()).unwrap();
+        ApiKey {
+            id: Uuid::new_v4(),
+            user_id,
+            name: "deploy script".to_owned(),
+            key_hash: key_hash.to_owned(),
+            key_hint: "ak_abcd".to_owned(),
+            scope: None,
+            created_at,
+            expires_at: Some(created_at + Duration::days(30)),
+            last_used_at: None,
+        }
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_add_and_get_api_key(pool: SqlitePool) {
+        let mut store = SqliteApiKeyStore::new(pool);
+        let key = ApiKey {
+            expires_at: None,
+            ..key(UserId::new(), "hash", Utc::now())
+        };
+
+        store.add_api_key(key.clone()).await.unwrap();
+
+        assert_eq!(store.get_api_key("hash").await.unwrap(), key);
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_list_unexpired_api_keys_of_user(pool: SqlitePool) {
+        let mut store = SqliteApiKeyStore::new(pool);
+        let user = UserId::new();
+        let now = Utc::now();
+        let older = key(user, "older", now - Duration::minutes(2));
+        let newer = key(user, "newer", now);
+        let expired = ApiKey {
+            expires_at: Some(now - Duration::minutes(1)),
+            ..key(user, "expired", now)
+        };
+        let other = key(UserId::new(), "other", now);
+        for key in [older.clone(), newer.clone(), expired, other] {
+            store.add_api_key(key).await.unwrap();
+        }
+
+        let ids: Vec<Uuid> = store
+            .get_api_keys(user)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|key| key.id)
+            .collect();
+
+        assert_eq!(ids, vec![newer.id, older.id]);
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_touch_and_remove_api_key(pool: SqlitePool) {
+        let mut store = SqliteApiKeyStore::new(pool);
+        let user = UserId::new();
+        let key = key(user, "hash", Utc::now());
+        store.add_api_key(key.clone()).await.unwrap();
+        let used_at = key.created_at + Duration::minutes(1);
+
+        store.touch_api_key(key.id, used_at).await.unwrap();
+
+        assert_eq!(
+            store.get_api_key("hash").await.unwrap().last_used_at,
+            Some(used_at)
+        );
+        assert_eq!(
+            store
+                .remove_api_key(UserId::new(), key.id)
+                .await
+                .unwrap_err(),
+            ApiKeyStoreError::ApiKeyNotFound
+        );
+        store.remove_api_key(user, key.id).await.unwrap();
+        assert_eq!(
+            store.remove_api_key(user, key.id).await.unwrap_err(),
+            ApiKeyStoreError::ApiKeyNotFound
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::Utc;
+use color_eyre::eyre::{Context, Result};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::SqlitePool;
+use std::time::Duration;
+
+use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError, ExpiringStore};
+
+pub struct SqliteBannedTokenStore {
+    pool: SqlitePool,
+    // Tokens only need to stay banned for as long as they would otherwise be valid
+    ttl: Duration,
+}
+
+impl SqliteBannedTokenStore {
+    pub fn new(pool: SqlitePool, ttl: Duration) -> Self {
+        Self { pool, ttl }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for SqliteBannedTokenStore {
+    // Delete banned tokens that have outlived the JWT TTL and can no longer be presented
+    #[tracing::instrument(name = "Purging expired banned tokens from SQLite", skip_all)]
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            delete from banned_tokens
+            where expires_at <= $1
+            "#,
+        )
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to purge expired banned tokens")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for SqliteBannedTokenStore {
+    #[tracing::instrument(name = "Adding banned token to SQLite", skip_all)]
+    async fn add_token(&mut self, token: SecretString) -> Result<(), BannedTokenStoreError> {
+        let expires_at = Utc::now().timestamp() + self.ttl.as_secs() as i64;
+
+        sqlx::query(
+            r#"
+            insert into banned_tokens (token, expires_at)
+            values ($1, $2)
+            on conflict (token) do nothing
+            "#,
+        )
+        .bind(token.expose_secret())
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert banned token into SQLite")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking banned token in SQLite", skip_all)]
+    async fn check_token(&self, token: &SecretString) -> Result<bool, BannedTokenStoreError> {
+        let is_banned: bool = sqlx::query_scalar(
+            r#"
+            select exists(
+                select 1 from banned_tokens
+                where token = $1 and expires_at > $2
+            )
+            "#,
+        )
+        .bind(token.expose_secret())
+        .bind(Utc::now().timestamp())
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("failed to check if token exists in SQLite")
+        .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(is_banned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(600);
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn test_add_token(pool: SqlitePool) {
+        let mut test_store = SqliteBannedTokenStore::new(pool, TTL);
+        let token = SecretString::new("foobar".to_owned().into_boxed_str());
+
+        let result = test_store.add_token(token.clone()).await;
+
+        assert!(result.is_ok());
+        assert!(test_store.check_token(&token).await.unwrap());
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn test_add_token_twice(pool: SqlitePool) {
+        let mut test_store = SqliteBannedTokenStore::new(pool, TTL);
+        let token = SecretString::new("foobar".to_owned().into_boxed_str());
+        test_store.add_token(token.clone()).await.unwrap();
+
+        let result = test_store.add_token(token).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn test_contains_token(pool: SqlitePool) {
+        let mut test_store = SqliteBannedTokenStore::new(pool, TTL);
+        let token = SecretString::new("foobar".to_owned().into_boxed_str());
+        let other = SecretString::new("other".to_owned().into_boxed_str());
+        test_store.add_token(token.clone()).await.unwrap();
+
+        assert!(test_store.check_token(&token).await.unwrap());
+        assert!(!test_store.check_token(&other).await.unwrap());
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn test_purge_expired_tokens(pool: SqlitePool) {
+        let mut test_store = SqliteBannedTokenStore::new(pool.clone(), TTL);
+        let live = SecretString::new("live".to_owned().into_boxed_str());
+        test_store.add_token(live.clone()).await.unwrap();
+        sqlx::query("insert into banned_tokens (token, expires_at) values ($1, $2)")
+            .bind("expired")
+            .bind(Utc::now().timestamp() - 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let expired = SecretString::new("expired".to_owned().into_boxed_str());
+        assert!(!test_store.check_token(&expired).await.unwrap());
+        let purged = test_store.purge_expired().await.unwrap();
+
+        assert_eq!(purged, 1);
+        assert!(test_store.check_token(&live).await.unwrap());
+    }
+}
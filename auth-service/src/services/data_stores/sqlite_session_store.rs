@@ -0,0 +1,277 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+use crate::domain::{data_stores::ExpiringStore, Session, SessionStore, SessionStoreError, UserId};
+
+pub struct SqliteSessionStore {
+    pool: SqlitePool,
+}
+
+impl SqliteSessionStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for SqliteSessionStore {
+    // Delete sessions whose token has expired
+    #[tracing::instrument(name = "Purging expired sessions from SQLite", skip_all)]
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            delete from sessions
+            where expires_at <= $1
+            "#,
+        )
+        .bind(Utc::now().timestamp_micros())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to purge expired sessions")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[derive(FromRow)]
+struct SessionRow {
+    id: String,
+    user_id: String,
+    client_id: Option<String>,
+    created_at: i64,
+    last_seen_at: i64,
+    expires_at: i64,
+    ip: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl TryFrom<SessionRow> for Session {
+    type Error = SessionStoreError;
+
+    fn try_from(row: SessionRow) -> Result<Self, Self::Error> {
+        let corrupt = |field: &str| {
+            SessionStoreError::UnexpectedError(eyre!("invalid {} in session {}", field, row.id))
+        };
+        let timestamp = |micros: i64, field: &str| {
+            DateTime::from_timestamp_micros(micros).ok_or_else(|| corrupt(field))
+        };
+        let ip = match &row.ip {
+            Some(ip) => Some(ip.parse().map_err(|_| corrupt("ip"))?),
+            None => None,
+        };
+        Ok(Session {
+            id: row.id.parse().map_err(|_| corrupt("id"))?,
+            user_id: UserId::parse(&row.user_id).map_err(SessionStoreError::UnexpectedError)?,
+            client_id: row.client_id.clone(),
+            created_at: timestamp(row.created_at, "created_at")?,
+            last_seen_at: timestamp(row.last_seen_at, "last_seen_at")?,
+            expires_at: timestamp(row.expires_at, "expires_at")?,
+            ip,
+            user_agent: row.user_agent.clone(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for SqliteSessionStore {
+    #[tracing::instrument(name = "Adding session to SQLite", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        sqlx::query(
+            r#"
+            insert into sessions
+                (id, user_id, client_id, created_at, last_seen_at, expires_at, ip, user_agent)
+            values ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(session.id.to_string())
+        .bind(session.user_id.to_string())
+        .bind(session.client_id)
+        .bind(session.created_at.timestamp_micros())
+        .bind(session.last_seen_at.timestamp_micros())
+        .bind(session.expires_at.timestamp_micros())
+        .bind(session.ip.map(|ip| ip.to_string()))
+        .bind(session.user_agent)
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to insert session into SQLite")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving session from SQLite", skip_all)]
+    async fn get_session(&self, id: Uuid) -> Result<Session, SessionStoreError> {
+        sqlx::query_as::<_, SessionRow>(
+            r#"
+            select id, user_id, client_id, created_at, last_seen_at, expires_at, ip, user_agent
+            from sessions
+            where id = $1 and expires_at > $2
+            "#,
+        )
+        .bind(id.to_string())
+        .bind(Utc::now().timestamp_micros())
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to retrieve session from SQLite")
+        .map_err(SessionStoreError::UnexpectedError)?
+        .ok_or(SessionStoreError::SessionNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Retrieving user sessions from SQLite", skip_all)]
+    async fn get_sessions(&self, user_id: UserId) -> Result<Vec<Session>, SessionStoreError> {
+        sqlx::query_as::<_, SessionRow>(
+            r#"
+            select id, user_id, client_id, created_at, last_seen_at, expires_at, ip, user_agent
+            from sessions
+            where user_id = $1 and expires_at > $2
+            order by created_at desc
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(Utc::now().timestamp_micros())
+        .fetch_all(&self.pool)
+        .await
+        .wrap_err("failed to retrieve user sessions from SQLite")
+        .map_err(SessionStoreError::UnexpectedError)?
+        .into_iter()
+        .map(Session::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Updating session last seen time in SQLite", skip_all)]
+    async fn touch_session(
+        &mut self,
+        id: Uuid,
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), SessionStoreError> {
+        let result = sqlx::query(
+            r#"
+            update sessions
+            set last_seen_at = $2
+            where id = $1 and expires_at > $3
+            "#,
+        )
+        .bind(id.to_string())
+        .bind(seen_at.timestamp_micros())
+        .bind(Utc::now().timestamp_micros())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to update session in SQLite")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(SessionStoreError::SessionNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Removing session from SQLite", skip_all)]
+    async fn remove_session(&mut self, id: Uuid) -> Result<(), SessionStoreError> {
+        let result = sqlx::query("delete from sessions where id = $1")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete session from SQLite")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(SessionStoreError::SessionNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Removing user sessions from SQLite", skip_all)]
+    async fn remove_sessions(&mut self, user_id: UserId) -> Result<u64, SessionStoreError> {
+        let result = sqlx::query("delete from sessions where user_id = $1")
+            .bind(user_id.to_string())
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to delete user sessions from SQLite")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    // Only microseconds are stored, so the sessions read back compare equal
+    fn session(user_id: UserId, created_at: DateTime<Utc>) -> Session {
+        let created_at = DateTime::from_timestamp_micros(created_at.timestamp_micros()).unwrap();
+        Session {
+            id: Uuid::new_v4(),
+            user_id,
+            client_id: Some("client".to_owned()),
+            created_at,
+            last_seen_at: created_at,
+            expires_at: created_at + Duration::minutes(10),
+            ip: Some("2001:db8::1".parse().unwrap()),
+            user_agent: None,
+        }
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_add_and_get_session(pool: SqlitePool) {
+        let mut store = SqliteSessionStore::new(pool);
+        let session = session(UserId::new(), Utc::now());
+
+        store.add_session(session.clone()).await.unwrap();
+        let stored = store.get_session(session.id).await.unwrap();
+
+        assert_eq!(stored.id, session.id);
+        assert_eq!(stored.client_id, session.client_id);
+        assert_eq!(stored.expires_at, session.expires_at);
+        assert_eq!(stored.ip, session.ip);
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_list_unexpired_sessions_of_user(pool: SqlitePool) {
+        let mut store = SqliteSessionStore::new(pool);
+        let user = UserId::new();
+        let now = Utc::now();
+        let older = session(user, now - Duration::minutes(2));
+        let newer = session(user, now);
+        let expired = session(user, now - Duration::minutes(20));
+        let other = session(UserId::new(), now);
+        for session in [older.clone(), newer.clone(), expired, other] {
+            store.add_session(session).await.unwrap();
+        }
+
+        let ids: Vec<Uuid> = store
+            .get_sessions(user)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|session| session.id)
+            .collect();
+
+        assert_eq!(ids, vec![newer.id, older.id]);
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_touch_and_remove_session(pool: SqlitePool) {
+        let mut store = SqliteSessionStore::new(pool);
+        let session = session(UserId::new(), Utc::now());
+        store.add_session(session.clone()).await.unwrap();
+        let seen_at = session.created_at + Duration::minutes(1);
+
+        store.touch_session(session.id, seen_at).await.unwrap();
+
+        assert_eq!(
+            store.get_session(session.id).await.unwrap().last_seen_at,
+            seen_at
+        );
+        assert!(store.remove_session(session.id).await.is_ok());
+        assert_eq!(
+            store.remove_session(session.id).await.unwrap_err(),
+            SessionStoreError::SessionNotFound
+        );
+    }
+}
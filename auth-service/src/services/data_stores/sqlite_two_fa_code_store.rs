@@ -0,0 +1,228 @@
+use chrono::Utc;
+use color_eyre::eyre::{Context, Result};
+use secrecy::ExposeSecret;
+use sqlx::{FromRow, SqlitePool};
+use std::time::Duration;
+
+use crate::domain::{
+    data_stores::{ExpiringStore, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    UserId,
+};
+
+pub struct SqliteTwoFACodeStore {
+    pool: SqlitePool,
+    ttl: Duration,
+}
+
+impl SqliteTwoFACodeStore {
+    pub fn new(pool: SqlitePool, ttl: Duration) -> Self {
+        Self { pool, ttl }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExpiringStore for SqliteTwoFACodeStore {
+    // Delete 2FA codes whose login attempt has expired
+    #[tracing::instrument(name = "Purging expired 2FA codes from SQLite", skip_all)]
+    async fn purge_expired(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            delete from two_fa_codes
+            where expires_at <= $1
+            "#,
+        )
+        .bind(Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to purge expired 2FA codes")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[derive(FromRow)]
+struct TwoFACodeRow {
+    login_attempt_id: String,
+    code: String,
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for SqliteTwoFACodeStore {
+    #[tracing::instrument(name = "Adding 2FA code to SQLite", skip_all)]
+    async fn add_code(
+        &mut self,
+        user_id: UserId,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let expires_at = Utc::now().timestamp() + self.ttl.as_secs() as i64;
+
+        sqlx::query(
+            r#"
+            insert into two_fa_codes (user_id, login_attempt_id, code, expires_at)
+            values ($1, $2, $3, $4)
+            on conflict (user_id) do update
+            set login_attempt_id = excluded.login_attempt_id,
+                code = excluded.code,
+                expires_at = excluded.expires_at
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(login_attempt_id.as_ref().expose_secret())
+        .bind(code.as_ref().expose_secret())
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to set 2FA code in SQLite")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing 2FA code from SQLite", skip_all)]
+    async fn remove_code(&mut self, user_id: UserId) -> Result<(), TwoFACodeStoreError> {
+        let result = sqlx::query(
+            r#"
+            delete from two_fa_codes
+            where user_id = $1
+            "#,
+        )
+        .bind(user_id.to_string())
+        .execute(&self.pool)
+        .await
+        .wrap_err("failed to delete 2FA code from SQLite")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        match result.rows_affected() {
+            0 => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Retrieving 2FA code from SQLite", skip_all)]
+    async fn get_code(
+        &self,
+        user_id: UserId,
+    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        let row = sqlx::query_as::<_, TwoFACodeRow>(
+            r#"
+            select login_attempt_id, code
+            from two_fa_codes
+            where user_id = $1 and expires_at > $2
+            "#,
+        )
+        .bind(user_id.to_string())
+        .bind(Utc::now().timestamp())
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("failed to get 2FA code from SQLite")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        let login_attempt_id = LoginAttemptId::parse(row.login_attempt_id)
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let code = TwoFACode::parse(row.code).map_err(TwoFACodeStoreError::UnexpectedError)?;
+        Ok((login_attempt_id, code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(600);
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_add_valid_code_to_2fa_store(pool: SqlitePool) {
+        let mut store = SqliteTwoFACodeStore::new(pool, TTL);
+        let user_id = UserId::new();
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+
+        let result = store
+            .add_code(user_id, login_attempt_id.clone(), code.clone())
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            store.get_code(user_id).await.unwrap(),
+            (login_attempt_id, code)
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_replace_existing_code_in_2fa_store(pool: SqlitePool) {
+        let mut store = SqliteTwoFACodeStore::new(pool, TTL);
+        let user_id = UserId::new();
+        store
+            .add_code(user_id, LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+
+        let result = store
+            .add_code(user_id, login_attempt_id.clone(), code.clone())
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            store.get_code(user_id).await.unwrap(),
+            (login_attempt_id, code)
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_remove_matching_code_from_2fa_store(pool: SqlitePool) {
+        let mut store = SqliteTwoFACodeStore::new(pool, TTL);
+        let user_id = UserId::new();
+        store
+            .add_code(user_id, LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+
+        let result = store.remove_code(user_id).await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            store.get_code(user_id).await.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_not_remove_missing_code_from_2fa_store(pool: SqlitePool) {
+        let mut store = SqliteTwoFACodeStore::new(pool, TTL);
+        let attempted_user_id = UserId::new();
+
+        let result = store.remove_code(attempted_user_id).await;
+
+        assert_eq!(
+            result.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_not_get_or_keep_expired_code(pool: SqlitePool) {
+        let store = SqliteTwoFACodeStore::new(pool.clone(), TTL);
+        let user_id = UserId::new();
+        sqlx::query(
+            "insert into two_fa_codes (user_id, login_attempt_id, code, expires_at)
+             values ($1, $2, $3, $4)",
+        )
+        .bind(user_id.to_string())
+        .bind(LoginAttemptId::default().as_ref().expose_secret())
+        .bind(TwoFACode::default().as_ref().expose_secret())
+        .bind(Utc::now().timestamp() - 1)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            store.get_code(user_id).await.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+        assert_eq!(store.purge_expired().await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,504 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{FromRow, SqlitePool};
+
+use crate::domain::{
+    data_stores::{UserStore, UserStoreError},
+    AccountStatus, Email, HashedPassword, User, UserId,
+};
+
+pub struct SqliteUserStore {
+    pool: SqlitePool,
+}
+
+impl SqliteUserStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(FromRow)]
+struct UserRow {
+    id: String,
+    email: String,
+    password_hash: String,
+    requires_2fa: bool,
+    token_generation: i64,
+    status: String,
+    status_reason: Option<String>,
+    // Unix timestamp in microseconds
+    status_changed_at: i64,
+    password_reset_required: bool,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: UserId::parse(&row.id).map_err(UserStoreError::UnexpectedError)?,
+            email: Email::parse(row.email)
+                .map_err(|e| UserStoreError::UnexpectedError(eyre!(e)))?,
+            password: HashedPassword::parse_password_hash(SecretString::new(
+                row.password_hash.into_boxed_str(),
+            ))
+            .map_err(|e| UserStoreError::UnexpectedError(eyre!(e)))?,
+            requires_2fa: row.requires_2fa,
+            token_generation: row.token_generation,
+            status: AccountStatus::parse(&row.status).ok_or_else(|| {
+                UserStoreError::UnexpectedError(eyre!("unknown account status {}", row.status))
+            })?,
+            status_reason: row.status_reason,
+            status_changed_at: DateTime::from_timestamp_micros(row.status_changed_at).ok_or_else(
+                || UserStoreError::UnexpectedError(eyre!("corrupt status_changed_at")),
+            )?,
+            password_reset_required: row.password_reset_required,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for SqliteUserStore {
+    #[tracing::instrument(name = "Adding user to SQLite", skip_all)]
+    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+        sqlx::query(
+            r#"
+            insert into users (id, email, password_hash, requires_2fa, status, status_reason,
+                status_changed_at)
+            values ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(user.id.to_string())
+        .bind(user.email.as_ref())
+        .bind(user.password.as_ref().expose_secret())
+        .bind(user.requires_2fa)
+        .bind(user.status.as_str())
+        .bind(&user.status_reason)
+        .bind(user.status_changed_at.timestamp_micros())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                UserStoreError::UserAlreadyExists
+            }
+            e => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving user from SQLite", skip_all)]
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        sqlx::query_as::<_, UserRow>(
+            r#"
+            select id, email, password_hash, requires_2fa, token_generation, status,
+                status_reason, status_changed_at, password_reset_required
+            from users
+            where lower(email) = lower($1)
+            "#,
+        )
+        .bind(email.as_ref())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .map(User::try_from)
+        .ok_or(UserStoreError::UserNotFound)?
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id from SQLite", skip_all)]
+    async fn get_user_by_id(&self, id: UserId) -> Result<User, UserStoreError> {
+        sqlx::query_as::<_, UserRow>(
+            r#"
+            select id, email, password_hash, requires_2fa, token_generation, status,
+                status_reason, status_changed_at, password_reset_required
+            from users
+            where id = $1
+            "#,
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .map(User::try_from)
+        .ok_or(UserStoreError::UserNotFound)?
+    }
+
+    #[tracing::instrument(name = "Validating user credentials in SQLite", skip_all)]
+    async fn validate_user(
+        &self,
+        email: &Email,
+        raw_password: &SecretString,
+    ) -> Result<(), UserStoreError> {
+        let user: User = self.get_user(email).await?;
+        user.password
+            .verify_raw_password(raw_password)
+            .await
+            .map_err(|_| UserStoreError::InvalidCredentials)
+    }
+
+    #[tracing::instrument(name = "Bumping token generation in SQLite", skip_all)]
+    async fn bump_token_generation(&mut self, id: UserId) -> Result<i64, UserStoreError> {
+        sqlx::query_scalar::<_, i64>(
+            r#"
+            update users
+            set token_generation = token_generation + 1
+            where id = $1
+            returning token_generation
+            "#,
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)
+    }
+
+    #[tracing::instrument(name = "Searching users in SQLite", skip_all)]
+    async fn search_users(
+        &self,
+        query: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<User>, UserStoreError> {
+        // `instr` rather than `like`, so `%` and `_` in the query are matched literally
+        sqlx::query_as::<_, UserRow>(
+            r#"
+            select id, email, password_hash, requires_2fa, token_generation, status,
+                status_reason, status_changed_at, password_reset_required
+            from users
+            where instr(lower(email), lower($1)) > 0
+            order by email
+            limit $2 offset $3
+            "#,
+        )
+        .bind(query)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(User::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Updating user in SQLite", skip_all)]
+    async fn update_user(&mut self, user: &User) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            r#"
+            update users
+            set email = $2, password_hash = $3, requires_2fa = $4, status = $5,
+                status_reason = $6, status_changed_at = $7, password_reset_required = $8
+            where id = $1
+            "#,
+        )
+        .bind(user.id.to_string())
+        .bind(user.email.as_ref())
+        .bind(user.password.as_ref().expose_secret())
+        .bind(user.requires_2fa)
+        .bind(user.status.as_str())
+        .bind(&user.status_reason)
+        .bind(user.status_changed_at.timestamp_micros())
+        .bind(user.password_reset_required)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_error) if db_error.is_unique_violation() => {
+                UserStoreError::UserAlreadyExists
+            }
+            e => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Deleting user from SQLite", skip_all)]
+    async fn delete_user(&mut self, id: UserId) -> Result<(), UserStoreError> {
+        let result = sqlx::query("delete from users where id = $1")
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(UserStoreError::UserNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Listing users scheduled for deletion in SQLite", skip_all)]
+    async fn users_scheduled_for_deletion(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<Vec<UserId>, UserStoreError> {
+        sqlx::query_scalar::<_, String>(
+            r#"
+            select id
+            from users
+            where status = 'scheduled_for_deletion' and status_changed_at < $1
+            order by status_changed_at
+            "#,
+        )
+        .bind(before.timestamp_micros())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(|id| UserId::parse(&id).map_err(UserStoreError::UnexpectedError))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fake::{
+        faker::internet::en::{Password as FakePassword, SafeEmail},
+        Fake,
+    };
+
+    use super::*;
+
+    async fn fake_user() -> (User, SecretString) {
+        let email = Email::parse(SafeEmail().fake()).unwrap();
+        let fake: String = FakePassword(10..12).fake();
+        let raw_password = SecretString::new(fake.into_boxed_str());
+        let password = HashedPassword::parse(raw_password.clone()).await.unwrap();
+        (User::new(email, password, true), raw_password)
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_add_unique_user(pool: SqlitePool) {
+        let (user, _) = fake_user().await;
+        let mut store = SqliteUserStore::new(pool);
+
+        let result = store.add_user(user).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_refuse_to_add_duplicate_user(pool: SqlitePool) {
+        let (user, _) = fake_user().await;
+        let mut store = SqliteUserStore::new(pool);
+        store.add_user(user.clone()).await.unwrap();
+
+        let result = store.add_user(user).await;
+
+        assert_eq!(result.unwrap_err(), UserStoreError::UserAlreadyExists);
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_get_existing_user(pool: SqlitePool) {
+        let (user, _) = fake_user().await;
+        let mut store = SqliteUserStore::new(pool);
+        store.add_user(user.clone()).await.unwrap();
+
+        let result = store.get_user(&user.email).await.unwrap();
+
+        assert_eq!(result.email, user.email);
+        assert_eq!(result.password, user.password);
+        assert!(result.requires_2fa);
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_refuse_to_get_missing_user(pool: SqlitePool) {
+        let (user, _) = fake_user().await;
+        let mut store = SqliteUserStore::new(pool);
+        store.add_user(user).await.unwrap();
+
+        let result = store
+            .get_user(&Email::parse("unknown@example.com".to_owned()).unwrap())
+            .await;
+
+        assert_eq!(result.unwrap_err(), UserStoreError::UserNotFound);
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_validate_user_with_correct_creds(pool: SqlitePool) {
+        let (user, raw_password) = fake_user().await;
+        let mut store = SqliteUserStore::new(pool);
+        store.add_user(user.clone()).await.unwrap();
+
+        let result = store.validate_user(&user.email, &raw_password).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_refuse_to_validate_user_with_incorrect_creds(pool: SqlitePool) {
+        let (user, _) = fake_user().await;
+        let mut store = SqliteUserStore::new(pool);
+        store.add_user(user.clone()).await.unwrap();
+
+        let result = store
+            .validate_user(
+                &user.email,
+                &SecretString::new("password".to_string().into_boxed_str()),
+            )
+            .await;
+
+        assert_eq!(result.unwrap_err(), UserStoreError::InvalidCredentials);
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_bump_token_generation(pool: SqlitePool) {
+        let (user, _) = fake_user().await;
+        let mut store = SqliteUserStore::new(pool);
+        store.add_user(user.clone()).await.unwrap();
+
+        assert_eq!(store.bump_token_generation(user.id).await.unwrap(), 1);
+        assert_eq!(store.bump_token_generation(user.id).await.unwrap(), 2);
+        assert_eq!(
+            store.get_user(&user.email).await.unwrap().token_generation,
+            2
+        );
+        assert_eq!(
+            store
+                .bump_token_generation(UserId::new())
+                .await
+                .unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_search_users_by_email(pool: SqlitePool) {
+        let mut store = SqliteUserStore::new(pool);
+        for address in ["bob@example.com", "alice@example.com", "carol@other.org"] {
+            let (mut user, _) = fake_user().await;
+            user.email = Email::parse(address.to_owned()).unwrap();
+            store.add_user(user).await.unwrap();
+        }
+
+        let emails = |users: Vec<User>| -> Vec<String> {
+            users
+                .into_iter()
+                .map(|user| user.email.as_ref().to_owned())
+                .collect()
+        };
+        assert_eq!(
+            emails(store.search_users("EXAMPLE", 10, 0).await.unwrap()),
+            ["alice@example.com", "bob@example.com"]
+        );
+        assert_eq!(
+            emails(store.search_users("", 1, 1).await.unwrap()),
+            ["bob@example.com"]
+        );
+        assert!(store.search_users("%", 10, 0).await.unwrap().is_empty());
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_update_and_delete_user(pool: SqlitePool) {
+        let (mut user, _) = fake_user().await;
+        let mut store = SqliteUserStore::new(pool);
+        store.add_user(user.clone()).await.unwrap();
+        store.bump_token_generation(user.id).await.unwrap();
+
+        user.set_status(AccountStatus::Disabled, Some("Chargeback".to_owned()));
+        user.password_reset_required = true;
+        user.requires_2fa = false;
+        store.update_user(&user).await.unwrap();
+
+        let stored = store.get_user(&user.email).await.unwrap();
+        assert_eq!(stored.status, AccountStatus::Disabled);
+        assert_eq!(stored.status_reason.as_deref(), Some("Chargeback"));
+        assert_eq!(
+            stored.status_changed_at.timestamp_micros(),
+            user.status_changed_at.timestamp_micros()
+        );
+        assert!(stored.password_reset_required);
+        assert!(!stored.requires_2fa);
+        assert_eq!(stored.token_generation, 1);
+
+        store.delete_user(user.id).await.unwrap();
+        assert_eq!(
+            store.update_user(&user).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+        assert_eq!(
+            store.delete_user(user.id).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_list_users_scheduled_for_deletion_before_a_time(pool: SqlitePool) {
+        let mut store = SqliteUserStore::new(pool);
+        let now = Utc::now();
+        for (address, status, days_ago) in [
+            ("old@example.com", AccountStatus::ScheduledForDeletion, 40),
+            ("older@example.com", AccountStatus::ScheduledForDeletion, 50),
+            ("recent@example.com", AccountStatus::ScheduledForDeletion, 1),
+            ("disabled@example.com", AccountStatus::Disabled, 40),
+        ] {
+            let (mut user, _) = fake_user().await;
+            user.email = Email::parse(address.to_owned()).unwrap();
+            user.status = status;
+            user.status_changed_at = now - chrono::Duration::days(days_ago);
+            store.add_user(user).await.unwrap();
+        }
+
+        let mut emails = Vec::new();
+        for id in store
+            .users_scheduled_for_deletion(now - chrono::Duration::days(30))
+            .await
+            .unwrap()
+        {
+            let user = store.get_user_by_id(id).await.unwrap();
+            emails.push(user.email.as_ref().to_owned());
+        }
+        assert_eq!(emails, ["older@example.com", "old@example.com"]);
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_match_emails_ignoring_case(pool: SqlitePool) {
+        let (mut user, _) = fake_user().await;
+        user.email = Email::parse("User@Example.com".to_owned()).unwrap();
+        let mut store = SqliteUserStore::new(pool);
+        store.add_user(user.clone()).await.unwrap();
+
+        let found = store
+            .get_user(&Email::parse("user@EXAMPLE.com".to_owned()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(found.id, user.id);
+        assert_eq!(found.email, user.email);
+        let (mut other, _) = fake_user().await;
+        other.email = Email::parse("USER@example.com".to_owned()).unwrap();
+        assert_eq!(
+            store.add_user(other).await.unwrap_err(),
+            UserStoreError::UserAlreadyExists
+        );
+    }
+
+    #[sqlx::test(migrations = "./migrations-sqlite")]
+    async fn should_change_email_of_user(pool: SqlitePool) {
+        let (mut user, _) = fake_user().await;
+        let (taken, _) = fake_user().await;
+        let mut store = SqliteUserStore::new(pool);
+        store.add_user(user.clone()).await.unwrap();
+        store.add_user(taken.clone()).await.unwrap();
+        let old_email = user.email.clone();
+
+        user.email = Email::parse("new@example.com".to_owned()).unwrap();
+        store.update_user(&user).await.unwrap();
+
+        assert_eq!(
+            store.get_user_by_id(user.id).await.unwrap().email,
+            user.email
+        );
+        assert_eq!(
+            store.get_user(&old_email).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+        user.email = taken.email;
+        assert_eq!(
+            store.update_user(&user).await.unwrap_err(),
+            UserStoreError::UserAlreadyExists
+        );
+    }
+}
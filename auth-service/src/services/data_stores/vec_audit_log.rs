@@ -0,0 +1,94 @@
+use crate::domain::{AuditEntry, AuditEvent, AuditLog, AuditLogError, GENESIS_HASH};
+
+#[derive(Default)]
+pub struct VecAuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+#[async_trait::async_trait]
+impl AuditLog for VecAuditLog {
+    async fn append(&mut self, event: AuditEvent) -> Result<(), AuditLogError> {
+        let prev_hash = self
+            .entries
+            .last()
+            .map(|entry| entry.hash.clone())
+            .unwrap_or_else(|| GENESIS_HASH.to_owned());
+        let id = self.entries.len() as i64 + 1;
+        self.entries.push(AuditEntry::chain(id, event, prev_hash));
+        Ok(())
+    }
+
+    async fn entries(&self, after_id: i64, limit: i64) -> Result<Vec<AuditEntry>, AuditLogError> {
+        Ok(self
+            .entries
+            .iter()
+            .filter(|entry| entry.id > after_id)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{AuditEventKind, AuditOutcome, ChainProblem, ChainVerification};
+
+    fn event(kind: AuditEventKind) -> AuditEvent {
+        AuditEvent {
+            kind,
+            actor: Some("user@example.com".to_owned()),
+            admin: None,
+            ip: None,
+            user_agent: None,
+            outcome: AuditOutcome::Success,
+            reason: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_append_chains_entries() {
+        let mut audit_log = VecAuditLog::default();
+
+        audit_log
+            .append(event(AuditEventKind::Signup))
+            .await
+            .unwrap();
+        audit_log
+            .append(event(AuditEventKind::Login))
+            .await
+            .unwrap();
+
+        let entries = audit_log.entries(0, 10).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(entries[1].prev_hash, entries[0].hash);
+        assert_eq!(audit_log.entries(1, 10).await.unwrap(), entries[1..]);
+    }
+
+    #[tokio::test]
+    async fn test_verify_detects_deleted_entry() {
+        let mut audit_log = VecAuditLog::default();
+        for kind in [
+            AuditEventKind::Signup,
+            AuditEventKind::Login,
+            AuditEventKind::Logout,
+        ] {
+            audit_log.append(event(kind)).await.unwrap();
+        }
+        assert!(matches!(
+            audit_log.verify().await.unwrap(),
+            ChainVerification::Intact { entries: 3, .. }
+        ));
+
+        audit_log.entries.remove(1);
+
+        assert_eq!(
+            audit_log.verify().await.unwrap(),
+            ChainVerification::Broken {
+                entry_id: 3,
+                problem: ChainProblem::Unlinked
+            }
+        );
+    }
+}
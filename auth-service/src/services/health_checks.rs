@@ -0,0 +1,242 @@
+#[cfg(any(
+    feature = "postgres",
+    feature = "redis",
+    feature = "sqlite",
+    feature = "smtp"
+))]
+use color_eyre::eyre::{Context, Result};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tokio::task::JoinSet;
+
+use crate::domain::{DependencyStatus, HealthCheck};
+
+pub type HealthCheckType = Arc<dyn HealthCheck + Send + Sync>;
+
+// Run every check concurrently, giving each one at most `timeout` to succeed
+#[tracing::instrument(name = "Running health checks", skip_all)]
+pub async fn run_health_checks(
+    checks: &[HealthCheckType],
+    timeout: Duration,
+) -> BTreeMap<&'static str, DependencyStatus> {
+    let mut set = JoinSet::new();
+    for check in checks {
+        let check = check.clone();
+        set.spawn(async move {
+            let status = match tokio::time::timeout(timeout, check.check()).await {
+                Ok(Ok(())) => DependencyStatus::up(),
+                Ok(Err(e)) => {
+                    tracing::warn!("{} health check failed: {:?}", check.name(), e);
+                    DependencyStatus::down(e.to_string())
+                }
+                Err(_) => DependencyStatus::down(format!("timed out after {:?}", timeout)),
+            };
+            (check.name(), status)
+        });
+    }
+
+    let mut statuses = BTreeMap::new();
+    while let Some(result) = set.join_next().await {
+        match result {
+            Ok((name, status)) => {
+                statuses.insert(name, status);
+            }
+            Err(e) => tracing::error!("health check task failed: {}", e),
+        }
+    }
+    statuses
+}
+
+#[cfg(feature = "postgres")]
+pub struct PostgresHealthCheck {
+    pool: sqlx::PgPool,
+}
+
+#[cfg(feature = "postgres")]
+impl PostgresHealthCheck {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[cfg(feature = "postgres")]
+#[async_trait::async_trait]
+impl HealthCheck for PostgresHealthCheck {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn check(&self) -> Result<()> {
+        sqlx::query("select 1")
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to query PostgreSQL")?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub struct SqliteHealthCheck {
+    pool: sqlx::SqlitePool,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteHealthCheck {
+    pub fn new(pool: sqlx::SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[async_trait::async_trait]
+impl HealthCheck for SqliteHealthCheck {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn check(&self) -> Result<()> {
+        sqlx::query("select 1")
+            .execute(&self.pool)
+            .await
+            .wrap_err("failed to query SQLite")?;
+        Ok(())
+    }
+}
+
+// Pings Redis over a separate connection, so a probe never waits on the stores' shared one
+#[cfg(feature = "redis")]
+pub struct RedisHealthCheck {
+    client: redis::Client,
+    timeout: Duration,
+}
+
+#[cfg(feature = "redis")]
+impl RedisHealthCheck {
+    pub fn new(client: redis::Client, timeout: Duration) -> Self {
+        Self { client, timeout }
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait::async_trait]
+impl HealthCheck for RedisHealthCheck {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn check(&self) -> Result<()> {
+        let client = self.client.clone();
+        let timeout = self.timeout;
+        // The Redis connection is blocking, so keep it off the async worker threads
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let mut conn = client
+                .get_connection_with_timeout(timeout)
+                .wrap_err("failed to connect to Redis")?;
+            conn.set_read_timeout(Some(timeout))
+                .wrap_err("failed to set Redis read timeout")?;
+            let _: String = redis::cmd("PING")
+                .query(&mut conn)
+                .wrap_err("failed to ping Redis")?;
+            Ok(())
+        })
+        .await
+        .wrap_err("Redis health check task failed")?
+    }
+}
+
+#[cfg(feature = "smtp")]
+pub struct SmtpHealthCheck {
+    transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>,
+}
+
+#[cfg(feature = "smtp")]
+impl SmtpHealthCheck {
+    pub fn new(transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>) -> Self {
+        Self { transport }
+    }
+}
+
+#[cfg(feature = "smtp")]
+#[async_trait::async_trait]
+impl HealthCheck for SmtpHealthCheck {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn check(&self) -> Result<()> {
+        let connected = self
+            .transport
+            .test_connection()
+            .await
+            .wrap_err("failed to connect to SMTP server")?;
+        match connected {
+            true => Ok(()),
+            false => Err(color_eyre::eyre::eyre!("SMTP server did not accept NOOP")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::DependencyState;
+    use color_eyre::eyre::{eyre, Result};
+
+    struct FakeCheck {
+        name: &'static str,
+        delay: Duration,
+        healthy: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl HealthCheck for FakeCheck {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn check(&self) -> Result<()> {
+            tokio::time::sleep(self.delay).await;
+            match self.healthy {
+                true => Ok(()),
+                false => Err(eyre!("connection refused")),
+            }
+        }
+    }
+
+    fn fake(name: &'static str, delay_ms: u64, healthy: bool) -> HealthCheckType {
+        Arc::new(FakeCheck {
+            name,
+            delay: Duration::from_millis(delay_ms),
+            healthy,
+        })
+    }
+
+    #[tokio::test]
+    async fn should_report_status_of_each_dependency() {
+        let checks = [
+            fake("postgres", 0, true),
+            fake("redis", 0, false),
+            fake("smtp", 1_000, true),
+        ];
+
+        let statuses = run_health_checks(&checks, Duration::from_millis(100)).await;
+
+        assert_eq!(statuses["postgres"], DependencyStatus::up());
+        assert_eq!(
+            statuses["redis"],
+            DependencyStatus::down("connection refused")
+        );
+        assert_eq!(statuses["smtp"].status, DependencyState::Down);
+        assert!(statuses["smtp"]
+            .error
+            .as_ref()
+            .unwrap()
+            .starts_with("timed out"));
+    }
+
+    #[tokio::test]
+    async fn should_report_nothing_without_dependencies() {
+        let statuses = run_health_checks(&[], Duration::from_millis(100)).await;
+
+        assert!(statuses.is_empty());
+    }
+}
@@ -1 +1,2 @@
 pub mod data_stores;
+pub mod health_checks;
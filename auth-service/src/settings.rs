@@ -0,0 +1,1152 @@
+use ::config::{Config, Environment, File, FileFormat, Source};
+use axum::http::HeaderValue;
+use dotenvy::dotenv;
+use p256::{pkcs8::DecodePrivateKey, SecretKey};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use std::{collections::HashMap, env as std_env, net::SocketAddr, time::Duration};
+use thiserror::Error;
+use url::Url;
+
+use crate::utils::constants::{env, DEFAULT_CONFIG_FILE, DEFAULT_REDIS_HOSTNAME};
+
+// Prefix for environment overrides, e.g. AUTH__SERVER__ADDRESS=127.0.0.1:3000
+const ENV_PREFIX: &str = "AUTH";
+const ENV_SEPARATOR: &str = "__";
+// Admin tokens and client secrets have to be long enough that guessing them is hopeless
+const MIN_SECRET_LENGTH: usize = 32;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub redis: RedisSettings,
+    pub smtp: SmtpSettings,
+    pub stores: StoreSettings,
+    pub ttl: TtlSettings,
+    pub health: HealthSettings,
+    pub logging: LoggingSettings,
+    pub tracing: TracingSettings,
+    pub admin: AdminSettings,
+    pub clients: Vec<ClientSettings>,
+    pub oidc: OidcSettings,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    pub address: String,
+    pub cors_allowed_origins: Vec<String>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            // Listen on all the configured network interfaces
+            address: "0.0.0.0:3000".to_owned(),
+            // Allow the app service (running on our local machine) to call the auth service
+            cors_allowed_origins: vec!["http://localhost:8000".to_owned()],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct DatabaseSettings {
+    pub url: SecretString,
+    pub max_connections: u32,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        Self {
+            url: SecretString::from(""),
+            max_connections: 5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RedisSettings {
+    pub host_name: String,
+}
+
+impl Default for RedisSettings {
+    fn default() -> Self {
+        Self {
+            host_name: DEFAULT_REDIS_HOSTNAME.to_owned(),
+        }
+    }
+}
+
+// Only used when `stores.email_client = "smtp"`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: SecretString,
+    // Address the 2FA emails are sent from, e.g. "Auth Service <auth@example.com>"
+    pub sender: String,
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 587,
+            username: String::new(),
+            password: SecretString::from(""),
+            sender: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct StoreSettings {
+    pub users: UserStoreBackend,
+    pub banned_tokens: TokenStoreBackend,
+    pub two_fa_codes: TokenStoreBackend,
+    pub email_client: EmailClientBackend,
+    pub audit_log: AuditLogBackend,
+    pub sessions: UserStoreBackend,
+    // OAuth clients, authorization codes and refresh tokens
+    pub oauth: OAuthStoreBackend,
+    // OAuth device codes, which only live for minutes
+    pub device_codes: DeviceCodeStoreBackend,
+    // Personal API keys, which live until they expire or are revoked
+    pub api_keys: UserStoreBackend,
+    // Roles, their permissions and who they are assigned to
+    pub roles: RoleStoreBackend,
+}
+
+impl Default for StoreSettings {
+    fn default() -> Self {
+        Self {
+            users: UserStoreBackend::Postgres,
+            banned_tokens: TokenStoreBackend::Redis,
+            two_fa_codes: TokenStoreBackend::Redis,
+            email_client: EmailClientBackend::Mock,
+            audit_log: AuditLogBackend::Postgres,
+            sessions: UserStoreBackend::Postgres,
+            oauth: OAuthStoreBackend::Postgres,
+            device_codes: DeviceCodeStoreBackend::Redis,
+            api_keys: UserStoreBackend::Postgres,
+            roles: RoleStoreBackend::Postgres,
+        }
+    }
+}
+
+impl StoreSettings {
+    // Every store kept in memory, for running without any external service
+    pub fn in_memory() -> Self {
+        Self {
+            users: UserStoreBackend::Memory,
+            banned_tokens: TokenStoreBackend::Memory,
+            two_fa_codes: TokenStoreBackend::Memory,
+            email_client: EmailClientBackend::Mock,
+            audit_log: AuditLogBackend::Memory,
+            sessions: UserStoreBackend::Memory,
+            oauth: OAuthStoreBackend::Memory,
+            device_codes: DeviceCodeStoreBackend::Memory,
+            api_keys: UserStoreBackend::Memory,
+            roles: RoleStoreBackend::Memory,
+        }
+    }
+
+    pub fn uses_database(&self, kind: DatabaseKind) -> bool {
+        self.users.database() == Some(kind)
+            || self.banned_tokens.database() == Some(kind)
+            || self.two_fa_codes.database() == Some(kind)
+            || self.audit_log.database() == Some(kind)
+            || self.sessions.database() == Some(kind)
+            || self.oauth.database() == Some(kind)
+            || self.api_keys.database() == Some(kind)
+            || self.roles.database() == Some(kind)
+    }
+
+    pub fn uses_redis(&self) -> bool {
+        self.banned_tokens == TokenStoreBackend::Redis
+            || self.two_fa_codes == TokenStoreBackend::Redis
+            || self.device_codes == DeviceCodeStoreBackend::Redis
+    }
+
+    // The (key, cargo feature) pairs of the selected backends that need one
+    fn required_features(&self) -> Vec<(&'static str, &'static str)> {
+        [
+            ("stores.users", self.users.feature()),
+            ("stores.banned_tokens", self.banned_tokens.feature()),
+            ("stores.two_fa_codes", self.two_fa_codes.feature()),
+            ("stores.email_client", self.email_client.feature()),
+            ("stores.audit_log", self.audit_log.feature()),
+            ("stores.sessions", self.sessions.feature()),
+            ("stores.oauth", self.oauth.feature()),
+            ("stores.device_codes", self.device_codes.feature()),
+            ("stores.api_keys", self.api_keys.feature()),
+            ("stores.roles", self.roles.feature()),
+        ]
+        .into_iter()
+        .filter_map(|(key, feature)| feature.map(|feature| (key, feature)))
+        .collect()
+    }
+
+    // The (key, backend) pairs that need a SQL database, for error messages
+    fn database_users(&self) -> Vec<(&'static str, DatabaseKind)> {
+        [
+            ("stores.users", self.users.database()),
+            ("stores.banned_tokens", self.banned_tokens.database()),
+            ("stores.two_fa_codes", self.two_fa_codes.database()),
+            ("stores.audit_log", self.audit_log.database()),
+            ("stores.sessions", self.sessions.database()),
+            ("stores.oauth", self.oauth.database()),
+            ("stores.api_keys", self.api_keys.database()),
+            ("stores.roles", self.roles.database()),
+        ]
+        .into_iter()
+        .filter_map(|(key, kind)| kind.map(|kind| (key, kind)))
+        .collect()
+    }
+}
+
+// Backends for the long-lived user, session and API key stores
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UserStoreBackend {
+    Postgres,
+    Sqlite,
+    Memory,
+}
+
+impl UserStoreBackend {
+    fn feature(self) -> Option<&'static str> {
+        self.database().map(DatabaseKind::name)
+    }
+
+    fn database(self) -> Option<DatabaseKind> {
+        match self {
+            Self::Postgres => Some(DatabaseKind::Postgres),
+            Self::Sqlite => Some(DatabaseKind::Sqlite),
+            Self::Memory => None,
+        }
+    }
+}
+
+// Backends for the short-lived banned token and 2FA code stores
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStoreBackend {
+    Redis,
+    Postgres,
+    Sqlite,
+    Memory,
+}
+
+impl TokenStoreBackend {
+    fn feature(self) -> Option<&'static str> {
+        match self {
+            Self::Redis => Some("redis"),
+            _ => self.database().map(DatabaseKind::name),
+        }
+    }
+
+    fn database(self) -> Option<DatabaseKind> {
+        match self {
+            Self::Postgres => Some(DatabaseKind::Postgres),
+            Self::Sqlite => Some(DatabaseKind::Sqlite),
+            Self::Redis | Self::Memory => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailClientBackend {
+    Mock,
+    Smtp,
+}
+
+impl EmailClientBackend {
+    fn feature(self) -> Option<&'static str> {
+        match self {
+            Self::Mock => None,
+            Self::Smtp => Some("smtp"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditLogBackend {
+    Postgres,
+    // Lost on restart, for tests and local development
+    Memory,
+}
+
+impl AuditLogBackend {
+    fn feature(self) -> Option<&'static str> {
+        self.database().map(DatabaseKind::name)
+    }
+
+    fn database(self) -> Option<DatabaseKind> {
+        match self {
+            Self::Postgres => Some(DatabaseKind::Postgres),
+            Self::Memory => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OAuthStoreBackend {
+    Postgres,
+    // Registered clients are lost on restart, for tests and local development
+    Memory,
+}
+
+impl OAuthStoreBackend {
+    fn feature(self) -> Option<&'static str> {
+        self.database().map(DatabaseKind::name)
+    }
+
+    fn database(self) -> Option<DatabaseKind> {
+        match self {
+            Self::Postgres => Some(DatabaseKind::Postgres),
+            Self::Memory => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RoleStoreBackend {
+    Postgres,
+    // Roles are lost on restart, for tests and local development
+    Memory,
+}
+
+impl RoleStoreBackend {
+    fn feature(self) -> Option<&'static str> {
+        self.database().map(DatabaseKind::name)
+    }
+
+    fn database(self) -> Option<DatabaseKind> {
+        match self {
+            Self::Postgres => Some(DatabaseKind::Postgres),
+            Self::Memory => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceCodeStoreBackend {
+    Redis,
+    // Pending device logins are lost on restart, for tests and local development
+    Memory,
+}
+
+impl DeviceCodeStoreBackend {
+    fn feature(self) -> Option<&'static str> {
+        match self {
+            Self::Redis => Some("redis"),
+            Self::Memory => None,
+        }
+    }
+}
+
+// Whether the cargo feature of the same name was enabled for this build
+fn feature_enabled(feature: &str) -> bool {
+    (feature == "postgres" && cfg!(feature = "postgres"))
+        || (feature == "redis" && cfg!(feature = "redis"))
+        || (feature == "sqlite" && cfg!(feature = "sqlite"))
+        || (feature == "smtp" && cfg!(feature = "smtp"))
+        || (feature == "otlp" && cfg!(feature = "otlp"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatabaseKind {
+    Postgres,
+    Sqlite,
+}
+
+impl DatabaseKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Postgres => "postgres",
+            Self::Sqlite => "sqlite",
+        }
+    }
+
+    fn accepts_url(self, url: &str) -> bool {
+        match self {
+            Self::Postgres => url.starts_with("postgres://") || url.starts_with("postgresql://"),
+            Self::Sqlite => url.starts_with("sqlite:"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TtlSettings {
+    // How long a JWT auth token (and therefore its ban) stays valid
+    pub token_seconds: u64,
+    pub two_fa_code_seconds: u64,
+    // How long an OAuth client has to redeem an authorization code at `/token`
+    pub authorization_code_seconds: u64,
+    pub refresh_token_seconds: u64,
+    // How long a device has to be approved once it asked for a code at `/device/code`
+    pub device_code_seconds: u64,
+    // How long a device waits between polls of `/token` while it waits for approval
+    pub device_poll_interval_seconds: u64,
+    // How often expired rows are deleted from SQL-backed token and 2FA stores
+    pub purge_interval_seconds: u64,
+    // How long accounts scheduled for deletion can be restored before they are purged
+    pub deleted_user_retention_seconds: u64,
+}
+
+impl Default for TtlSettings {
+    fn default() -> Self {
+        Self {
+            token_seconds: 600,
+            two_fa_code_seconds: 600,
+            authorization_code_seconds: 60,
+            refresh_token_seconds: 30 * 24 * 60 * 60,
+            device_code_seconds: 600,
+            device_poll_interval_seconds: 5,
+            purge_interval_seconds: 60,
+            deleted_user_retention_seconds: 30 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl TtlSettings {
+    pub fn token(&self) -> Duration {
+        Duration::from_secs(self.token_seconds)
+    }
+
+    pub fn two_fa_code(&self) -> Duration {
+        Duration::from_secs(self.two_fa_code_seconds)
+    }
+
+    pub fn authorization_code(&self) -> Duration {
+        Duration::from_secs(self.authorization_code_seconds)
+    }
+
+    pub fn refresh_token(&self) -> Duration {
+        Duration::from_secs(self.refresh_token_seconds)
+    }
+
+    pub fn device_code(&self) -> Duration {
+        Duration::from_secs(self.device_code_seconds)
+    }
+
+    pub fn purge_interval(&self) -> Duration {
+        Duration::from_secs(self.purge_interval_seconds)
+    }
+
+    pub fn deleted_user_retention(&self) -> Duration {
+        Duration::from_secs(self.deleted_user_retention_seconds)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct HealthSettings {
+    // How long `/readyz` waits for each dependency before reporting it as down
+    pub timeout_ms: u64,
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        Self { timeout_ms: 2_000 }
+    }
+}
+
+impl HealthSettings {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LoggingSettings {
+    pub format: LogFormat,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    // Human-readable, one line per event
+    #[default]
+    Compact,
+    // One JSON object per event, with the fields of every enclosing span
+    Json,
+}
+
+// Where finished spans are exported to, on top of the log output
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TracingSettings {
+    pub exporter: TraceExporter,
+    // Reported as `service.name` on every exported span
+    pub service_name: String,
+    // OTLP/HTTP collector endpoint, only used when `exporter = "otlp"`
+    pub otlp_endpoint: String,
+    // Spans are appended here as JSON lines, only used when `exporter = "file"`
+    pub file_path: String,
+}
+
+impl Default for TracingSettings {
+    fn default() -> Self {
+        Self {
+            exporter: TraceExporter::None,
+            service_name: "auth-service".to_owned(),
+            otlp_endpoint: "http://localhost:4318/v1/traces".to_owned(),
+            file_path: "traces.jsonl".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceExporter {
+    None,
+    Otlp,
+    Stdout,
+    File,
+}
+
+impl TraceExporter {
+    fn feature(self) -> Option<&'static str> {
+        match self {
+            Self::Otlp => Some("otlp"),
+            Self::None | Self::Stdout | Self::File => None,
+        }
+    }
+}
+
+// The `/admin` routes are disabled while no token is set
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AdminSettings {
+    // Sent by admin clients as `Authorization: Bearer <token>`
+    pub token: SecretString,
+}
+
+impl Default for AdminSettings {
+    fn default() -> Self {
+        Self {
+            token: SecretString::from(""),
+        }
+    }
+}
+
+impl AdminSettings {
+    pub fn enabled(&self) -> bool {
+        !self.token.expose_secret().is_empty()
+    }
+}
+
+// OpenID Connect. ID tokens are signed with ES256, so relying parties can check them
+// against `/.well-known/jwks.json` without sharing a secret.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct OidcSettings {
+    // The `iss` of ID tokens, and the base URL of the endpoints in the discovery document
+    pub issuer: String,
+    // A P-256 private key in PKCS#8 PEM. When empty, a key is generated at startup, and
+    // ID tokens issued before a restart can no longer be verified.
+    pub signing_key: SecretString,
+}
+
+impl Default for OidcSettings {
+    fn default() -> Self {
+        Self {
+            issuer: "http://localhost:3000".to_owned(),
+            signing_key: SecretString::from(""),
+        }
+    }
+}
+
+// A service allowed to call `/introspect` and `/revoke`, authenticated with HTTP Basic
+#[derive(Clone, Debug, Deserialize)]
+pub struct ClientSettings {
+    pub id: String,
+    pub secret: SecretString,
+}
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("failed to load configuration: {0}")]
+    Load(#[from] ::config::ConfigError),
+    #[error("invalid configuration for `{key}`: {reason}")]
+    Invalid { key: &'static str, reason: String },
+}
+
+impl Settings {
+    // Load settings from the TOML config file, then apply environment overrides, then validate.
+    // The file is `config.toml` unless AUTH_CONFIG_FILE names another one.
+    pub fn load() -> Result<Self, SettingsError> {
+        dotenv().ok();
+        let vars: HashMap<String, String> = std_env::vars().collect();
+        let file = match vars.get(env::CONFIG_FILE_ENV_VAR) {
+            Some(path) => File::new(path, FileFormat::Toml).required(true),
+            None => File::new(DEFAULT_CONFIG_FILE, FileFormat::Toml).required(false),
+        };
+        Self::build(file, vars)
+    }
+
+    fn build<S>(file: S, vars: HashMap<String, String>) -> Result<Self, SettingsError>
+    where
+        S: Source + Send + Sync + 'static,
+    {
+        let settings: Settings = Config::builder()
+            .add_source(file)
+            .add_source(
+                Environment::with_prefix(ENV_PREFIX)
+                    .separator(ENV_SEPARATOR)
+                    .list_separator(",")
+                    .with_list_parse_key("server.cors_allowed_origins")
+                    .try_parsing(true)
+                    .source(Some(vars.clone().into_iter().collect())),
+            )
+            // Variables the service has always read take precedence over everything else
+            .set_override_option("database.url", vars.get(env::DATABASE_URL_ENV_VAR).cloned())?
+            .set_override_option(
+                "redis.host_name",
+                vars.get(env::REDIS_HOST_NAME_ENV_VAR).cloned(),
+            )?
+            .build()?
+            .try_deserialize()?;
+
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if let Err(e) = self.server.address.parse::<SocketAddr>() {
+            return Err(invalid("server.address", e.to_string()));
+        }
+        for origin in &self.server.cors_allowed_origins {
+            if !(origin.starts_with("http://") || origin.starts_with("https://"))
+                || origin.parse::<HeaderValue>().is_err()
+            {
+                return Err(invalid(
+                    "server.cors_allowed_origins",
+                    format!("'{}' is not an http(s) origin", origin),
+                ));
+            }
+        }
+        if self.database.max_connections == 0 {
+            return Err(invalid("database.max_connections", "must be at least 1"));
+        }
+        if self.ttl.token_seconds == 0 {
+            return Err(invalid("ttl.token_seconds", "must be at least 1"));
+        }
+        if self.ttl.two_fa_code_seconds == 0 {
+            return Err(invalid("ttl.two_fa_code_seconds", "must be at least 1"));
+        }
+        if self.ttl.authorization_code_seconds == 0 {
+            return Err(invalid(
+                "ttl.authorization_code_seconds",
+                "must be at least 1",
+            ));
+        }
+        if self.ttl.refresh_token_seconds == 0 {
+            return Err(invalid("ttl.refresh_token_seconds", "must be at least 1"));
+        }
+        if self.ttl.device_code_seconds == 0 {
+            return Err(invalid("ttl.device_code_seconds", "must be at least 1"));
+        }
+        if self.ttl.device_poll_interval_seconds == 0 {
+            return Err(invalid(
+                "ttl.device_poll_interval_seconds",
+                "must be at least 1",
+            ));
+        }
+        if self.ttl.purge_interval_seconds == 0 {
+            return Err(invalid("ttl.purge_interval_seconds", "must be at least 1"));
+        }
+        if self.health.timeout_ms == 0 {
+            return Err(invalid("health.timeout_ms", "must be at least 1"));
+        }
+        if self.tracing.exporter != TraceExporter::None && self.tracing.service_name.is_empty() {
+            return Err(invalid(
+                "tracing.service_name",
+                "must be set to export spans",
+            ));
+        }
+        if self.tracing.exporter == TraceExporter::Otlp && self.tracing.otlp_endpoint.is_empty() {
+            return Err(invalid(
+                "tracing.otlp_endpoint",
+                "must be set to export spans over OTLP",
+            ));
+        }
+        if self.tracing.exporter == TraceExporter::File && self.tracing.file_path.is_empty() {
+            return Err(invalid(
+                "tracing.file_path",
+                "must be set to export spans to a file",
+            ));
+        }
+        if self.admin.enabled() && self.admin.token.expose_secret().len() < MIN_SECRET_LENGTH {
+            return Err(invalid(
+                "admin.token",
+                format!("must be at least {} characters", MIN_SECRET_LENGTH),
+            ));
+        }
+        for (i, client) in self.clients.iter().enumerate() {
+            // HTTP Basic credentials are split at the first colon
+            if client.id.is_empty() || client.id.contains(':') {
+                return Err(invalid("clients.id", "must be non-empty and without ':'"));
+            }
+            if self.clients[..i].iter().any(|other| other.id == client.id) {
+                return Err(invalid(
+                    "clients.id",
+                    format!("'{}' is used more than once", client.id),
+                ));
+            }
+            if client.secret.expose_secret().len() < MIN_SECRET_LENGTH {
+                return Err(invalid(
+                    "clients.secret",
+                    format!("must be at least {} characters", MIN_SECRET_LENGTH),
+                ));
+            }
+        }
+        match Url::parse(&self.oidc.issuer) {
+            Ok(url)
+                if matches!(url.scheme(), "http" | "https")
+                    && url.query().is_none()
+                    && url.fragment().is_none() => {}
+            _ => {
+                return Err(invalid(
+                    "oidc.issuer",
+                    "must be an http(s) URL without query or fragment",
+                ))
+            }
+        }
+        let signing_key = self.oidc.signing_key.expose_secret();
+        if !signing_key.is_empty() && SecretKey::from_pkcs8_pem(signing_key).is_err() {
+            return Err(invalid(
+                "oidc.signing_key",
+                "must be a P-256 private key in PKCS#8 PEM",
+            ));
+        }
+
+        let tracing_feature = self
+            .tracing
+            .exporter
+            .feature()
+            .map(|feature| ("tracing.exporter", feature));
+        for (key, feature) in self
+            .stores
+            .required_features()
+            .into_iter()
+            .chain(tracing_feature)
+        {
+            if !feature_enabled(feature) {
+                return Err(invalid(
+                    key,
+                    format!(
+                        "{} support was not compiled in, rebuild with `--features {}`",
+                        feature, feature
+                    ),
+                ));
+            }
+        }
+        if self.stores.uses_redis() && self.redis.host_name.is_empty() {
+            return Err(invalid(
+                "redis.host_name",
+                "must be set to use Redis stores",
+            ));
+        }
+
+        if self.stores.email_client == EmailClientBackend::Smtp {
+            if self.smtp.host.is_empty() {
+                return Err(invalid("smtp.host", "must be set to send email over SMTP"));
+            }
+            if self.smtp.sender.is_empty() {
+                return Err(invalid(
+                    "smtp.sender",
+                    "must be set to send email over SMTP",
+                ));
+            }
+        }
+
+        let url = self.database.url.expose_secret();
+        let database_users = self.stores.database_users();
+        if let Some((first_key, first_kind)) = database_users.first() {
+            if let Some((key, kind)) = database_users.iter().find(|(_, k)| k != first_kind) {
+                return Err(invalid(
+                    key,
+                    format!(
+                        "{} = {} and {} = {} need different databases, but only one database.url can be configured",
+                        first_key,
+                        first_kind.name(),
+                        key,
+                        kind.name()
+                    ),
+                ));
+            }
+            if url.is_empty() {
+                return Err(invalid(
+                    "database.url",
+                    format!(
+                        "must be set (or {}) because {} = {}",
+                        env::DATABASE_URL_ENV_VAR,
+                        first_key,
+                        first_kind.name()
+                    ),
+                ));
+            }
+            if !first_kind.accepts_url(url) {
+                return Err(invalid(
+                    "database.url",
+                    format!(
+                        "is not a {} URL, but {} = {}",
+                        first_kind.name(),
+                        first_key,
+                        first_kind.name()
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid(key: &'static str, reason: impl Into<String>) -> SettingsError {
+    SettingsError::Invalid {
+        key,
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(toml: &str, vars: &[(&str, &str)]) -> Result<Settings, SettingsError> {
+        let vars = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Settings::build(File::from_str(toml, FileFormat::Toml), vars)
+    }
+
+    #[test]
+    #[cfg(all(feature = "postgres", feature = "redis"))]
+    fn should_use_defaults_when_only_database_url_is_set() {
+        let settings = build("", &[("DATABASE_URL", "postgres://localhost:5432")]).unwrap();
+
+        assert_eq!(settings.server.address, "0.0.0.0:3000");
+        assert_eq!(
+            settings.server.cors_allowed_origins,
+            vec!["http://localhost:8000"]
+        );
+        assert_eq!(settings.database.max_connections, 5);
+        assert_eq!(settings.redis.host_name, DEFAULT_REDIS_HOSTNAME);
+        assert_eq!(settings.stores.users, UserStoreBackend::Postgres);
+        assert_eq!(settings.stores.banned_tokens, TokenStoreBackend::Redis);
+        assert_eq!(settings.stores.two_fa_codes, TokenStoreBackend::Redis);
+        assert_eq!(settings.stores.audit_log, AuditLogBackend::Postgres);
+        assert_eq!(settings.stores.sessions, UserStoreBackend::Postgres);
+        assert_eq!(settings.stores.oauth, OAuthStoreBackend::Postgres);
+        assert_eq!(settings.stores.device_codes, DeviceCodeStoreBackend::Redis);
+        assert_eq!(settings.stores.api_keys, UserStoreBackend::Postgres);
+        assert_eq!(settings.stores.roles, RoleStoreBackend::Postgres);
+        assert_eq!(settings.ttl.token(), Duration::from_secs(600));
+        assert_eq!(settings.ttl.device_code(), Duration::from_secs(600));
+        assert_eq!(
+            settings.ttl.deleted_user_retention(),
+            Duration::from_secs(30 * 24 * 60 * 60)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn should_read_backends_from_file() {
+        let toml = r#"
+            [database]
+            url = "sqlite://auth.db"
+            max_connections = 1
+
+            [stores]
+            users = "sqlite"
+            banned_tokens = "sqlite"
+            two_fa_codes = "memory"
+            audit_log = "memory"
+            sessions = "sqlite"
+            oauth = "memory"
+            device_codes = "memory"
+            api_keys = "memory"
+            roles = "memory"
+        "#;
+
+        let settings = build(toml, &[]).unwrap();
+
+        assert_eq!(settings.database.url.expose_secret(), "sqlite://auth.db");
+        assert_eq!(settings.database.max_connections, 1);
+        assert_eq!(settings.stores.users, UserStoreBackend::Sqlite);
+        assert_eq!(settings.stores.banned_tokens, TokenStoreBackend::Sqlite);
+        assert_eq!(settings.stores.two_fa_codes, TokenStoreBackend::Memory);
+        assert_eq!(settings.stores.audit_log, AuditLogBackend::Memory);
+        assert_eq!(settings.stores.sessions, UserStoreBackend::Sqlite);
+        assert_eq!(settings.stores.oauth, OAuthStoreBackend::Memory);
+        assert_eq!(settings.stores.device_codes, DeviceCodeStoreBackend::Memory);
+        assert_eq!(settings.stores.api_keys, UserStoreBackend::Memory);
+        assert_eq!(settings.stores.roles, RoleStoreBackend::Memory);
+    }
+
+    #[test]
+    fn should_let_environment_override_file() {
+        let toml = r#"
+            [server]
+            address = "127.0.0.1:3000"
+
+            [stores]
+            users = "memory"
+            banned_tokens = "memory"
+            two_fa_codes = "memory"
+            audit_log = "memory"
+            sessions = "memory"
+            oauth = "memory"
+            device_codes = "memory"
+            api_keys = "memory"
+            roles = "memory"
+        "#;
+        let vars = [
+            ("AUTH__SERVER__ADDRESS", "127.0.0.1:4000"),
+            (
+                "AUTH__SERVER__CORS_ALLOWED_ORIGINS",
+                "http://localhost:8000,https://example.com",
+            ),
+            ("AUTH__TTL__TOKEN_SECONDS", "60"),
+            ("REDIS_HOST_NAME", "cache"),
+        ];
+
+        let settings = build(toml, &vars).unwrap();
+
+        assert_eq!(settings.server.address, "127.0.0.1:4000");
+        assert_eq!(
+            settings.server.cors_allowed_origins,
+            vec!["http://localhost:8000", "https://example.com"]
+        );
+        assert_eq!(settings.ttl.token_seconds, 60);
+        assert_eq!(settings.redis.host_name, "cache");
+    }
+
+    #[test]
+    fn should_reject_unknown_backend() {
+        let result = build("[stores]\nusers = \"mysql\"", &[]);
+
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("mysql"), "{}", message);
+    }
+
+    #[test]
+    #[cfg(all(feature = "postgres", feature = "redis"))]
+    fn should_reject_missing_database_url() {
+        let result = build("", &[]);
+
+        assert!(matches!(
+            result.unwrap_err(),
+            SettingsError::Invalid {
+                key: "database.url",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "postgres", feature = "redis"))]
+    fn should_reject_database_url_of_wrong_kind() {
+        let result = build("", &[("DATABASE_URL", "sqlite://auth.db")]);
+
+        assert!(matches!(
+            result.unwrap_err(),
+            SettingsError::Invalid {
+                key: "database.url",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "postgres", feature = "sqlite"))]
+    fn should_reject_mixed_databases() {
+        let toml = "[stores]\nusers = \"postgres\"\nbanned_tokens = \"sqlite\"";
+
+        let result = build(toml, &[("DATABASE_URL", "postgres://localhost:5432")]);
+
+        assert!(matches!(
+            result.unwrap_err(),
+            SettingsError::Invalid {
+                key: "stores.banned_tokens",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(not(feature = "smtp"))]
+    fn should_reject_backend_that_was_not_compiled_in() {
+        let toml = r#"
+            [stores]
+            users = "memory"
+            banned_tokens = "memory"
+            two_fa_codes = "memory"
+            audit_log = "memory"
+            sessions = "memory"
+            oauth = "memory"
+            device_codes = "memory"
+            api_keys = "memory"
+            roles = "memory"
+            email_client = "smtp"
+        "#;
+
+        let result = build(toml, &[]);
+
+        assert!(matches!(
+            result.unwrap_err(),
+            SettingsError::Invalid {
+                key: "stores.email_client",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(not(feature = "otlp"))]
+    fn should_reject_otlp_exporter_that_was_not_compiled_in() {
+        let toml = r#"
+            [stores]
+            users = "memory"
+            banned_tokens = "memory"
+            two_fa_codes = "memory"
+            audit_log = "memory"
+            sessions = "memory"
+            oauth = "memory"
+            device_codes = "memory"
+            api_keys = "memory"
+            roles = "memory"
+
+            [tracing]
+            exporter = "otlp"
+        "#;
+
+        let result = build(toml, &[]);
+
+        assert!(matches!(
+            result.unwrap_err(),
+            SettingsError::Invalid {
+                key: "tracing.exporter",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn should_read_file_trace_exporter() {
+        let toml = r#"
+            [stores]
+            users = "memory"
+            banned_tokens = "memory"
+            two_fa_codes = "memory"
+            audit_log = "memory"
+            sessions = "memory"
+            oauth = "memory"
+            device_codes = "memory"
+            api_keys = "memory"
+            roles = "memory"
+
+            [tracing]
+            exporter = "file"
+            file_path = "/tmp/spans.jsonl"
+        "#;
+
+        let settings = build(toml, &[("AUTH__TRACING__SERVICE_NAME", "auth-staging")]).unwrap();
+
+        assert_eq!(settings.tracing.exporter, TraceExporter::File);
+        assert_eq!(settings.tracing.file_path, "/tmp/spans.jsonl");
+        assert_eq!(settings.tracing.service_name, "auth-staging");
+        assert_eq!(settings.logging.format, LogFormat::Compact);
+    }
+
+    #[test]
+    fn should_read_log_format_from_environment() {
+        let toml = r#"
+            [stores]
+            users = "memory"
+            banned_tokens = "memory"
+            two_fa_codes = "memory"
+            audit_log = "memory"
+            sessions = "memory"
+            oauth = "memory"
+            device_codes = "memory"
+            api_keys = "memory"
+            roles = "memory"
+        "#;
+
+        let settings = build(toml, &[("AUTH__LOGGING__FORMAT", "json")]).unwrap();
+
+        assert_eq!(settings.logging.format, LogFormat::Json);
+    }
+
+    #[test]
+    fn should_reject_invalid_server_settings() {
+        let vars = [("DATABASE_URL", "postgres://localhost:5432")];
+        let test_cases = [
+            ("[server]\naddress = \"localhost\"", "server.address"),
+            (
+                "[server]\ncors_allowed_origins = [\"localhost:8000\"]",
+                "server.cors_allowed_origins",
+            ),
+            (
+                "[database]\nmax_connections = 0",
+                "database.max_connections",
+            ),
+            ("[ttl]\ntoken_seconds = 0", "ttl.token_seconds"),
+            (
+                "[ttl]\nrefresh_token_seconds = 0",
+                "ttl.refresh_token_seconds",
+            ),
+            (
+                "[ttl]\ndevice_poll_interval_seconds = 0",
+                "ttl.device_poll_interval_seconds",
+            ),
+            (
+                "[tracing]\nexporter = \"file\"\nfile_path = \"\"",
+                "tracing.file_path",
+            ),
+            ("[admin]\ntoken = \"too-short\"", "admin.token"),
+            (
+                "[[clients]]\nid = \"a:b\"\nsecret = \"0123456789abcdef0123456789abcdef\"",
+                "clients.id",
+            ),
+            (
+                "[[clients]]\nid = \"app\"\nsecret = \"too-short\"",
+                "clients.secret",
+            ),
+            ("[oidc]\nissuer = \"auth.example.com\"", "oidc.issuer"),
+            (
+                "[oidc]\nissuer = \"https://auth.example.com/?tenant=1\"",
+                "oidc.issuer",
+            ),
+            ("[oidc]\nsigning_key = \"not-a-key\"", "oidc.signing_key"),
+        ];
+        for (toml, expected_key) in test_cases {
+            match build(toml, &vars) {
+                Err(SettingsError::Invalid { key, .. }) => {
+                    assert_eq!(key, expected_key, "Failed for input: {}", toml)
+                }
+                other => panic!("Expected invalid {}, got {:?}", expected_key, other),
+            }
+        }
+    }
+}
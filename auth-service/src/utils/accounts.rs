@@ -0,0 +1,205 @@
+use chrono::Utc;
+use color_eyre::eyre::{Context, Result};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AccountStatus, AuditEventKind, AuthAPIError, TwoFACodeStoreError, UserId, UserStoreError,
+    },
+    log_error_chain,
+    utils::{
+        audit::{record_audit_event, AuditContext},
+        sessions::revoke_session,
+    },
+};
+
+// Discard the code of a pending 2FA login or password reset, if there is one
+pub async fn remove_pending_code(state: &AppState, user_id: UserId) -> Result<(), AuthAPIError> {
+    match state.two_fa_codes.write().await.remove_code(user_id).await {
+        Ok(()) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => Ok(()),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Everything kept for the user goes with them, and the tokens of their sessions are banned
+pub async fn delete_user(state: &AppState, user_id: UserId) -> Result<(), AuthAPIError> {
+    let sessions = state
+        .sessions
+        .read()
+        .await
+        .get_sessions(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    for session in sessions {
+        revoke_session(state, session).await?;
+    }
+    state
+        .oauth
+        .refresh_tokens
+        .write()
+        .await
+        .remove_user_refresh_tokens(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let mut api_keys = state.api_keys.write().await;
+    let keys = api_keys
+        .get_api_keys(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    for key in keys {
+        api_keys
+            .remove_api_key(user_id, key.id)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    }
+    drop(api_keys);
+
+    let mut roles = state.roles.write().await;
+    let user_roles = roles
+        .get_user_roles(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    for role in user_roles {
+        roles
+            .unassign_role(user_id, &role.name)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    }
+    drop(roles);
+
+    remove_pending_code(state, user_id).await?;
+    state
+        .user_store
+        .write()
+        .await
+        .delete_user(user_id)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })
+}
+
+// Delete the accounts whose retention ran out since they were scheduled for deletion,
+// returning how many went
+#[tracing::instrument(name = "Purge deleted users", skip_all)]
+pub async fn purge_deleted_users(state: &AppState) -> Result<u64> {
+    let retention = chrono::Duration::from_std(state.settings.ttl.deleted_user_retention())
+        .wrap_err("invalid deleted user retention")?;
+    let user_ids = state
+        .user_store
+        .read()
+        .await
+        .users_scheduled_for_deletion(Utc::now() - retention)
+        .await
+        .wrap_err("failed to list users scheduled for deletion")?;
+
+    let mut purged = 0;
+    for user_id in user_ids {
+        // An admin may have restored the account since it was listed
+        let user = match state.user_store.read().await.get_user_by_id(user_id).await {
+            Ok(user) if user.status == AccountStatus::ScheduledForDeletion => user,
+            _ => continue,
+        };
+        let result = delete_user(state, user_id).await;
+        record_audit_event(
+            state,
+            AuditContext::default().event(
+                AuditEventKind::PurgeUser,
+                Some(user.email.as_ref()),
+                &result,
+            ),
+        )
+        .await;
+        match result {
+            Ok(()) => purged += 1,
+            // Deleted by an admin in the meantime
+            Err(AuthAPIError::UserNotFound) => (),
+            // The others still go, and this one is tried again next time
+            Err(e) => log_error_chain(&e),
+        }
+    }
+    Ok(purged)
+}
+
+// Periodically purge the accounts scheduled for deletion whose retention ran out
+pub fn spawn_deleted_users_purge(state: AppState, period: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if let Err(e) = purge_deleted_users(&state).await {
+                log_error_chain(e.as_ref());
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::SecretString;
+
+    use super::*;
+    use crate::{
+        domain::{Email, HashedPassword, User},
+        settings::{Settings, StoreSettings},
+    };
+
+    async fn state() -> AppState {
+        AppState::from_settings(Settings {
+            stores: StoreSettings::in_memory(),
+            ..Settings::default()
+        })
+        .await
+        .unwrap()
+    }
+
+    async fn add_user(state: &AppState, address: &str, status: AccountStatus, days_ago: i64) {
+        let password = HashedPassword::parse(SecretString::from("password123"))
+            .await
+            .unwrap();
+        let mut user = User::new(Email::parse(address.to_owned()).unwrap(), password, false);
+        user.status = status;
+        user.status_changed_at = Utc::now() - chrono::Duration::days(days_ago);
+        state.user_store.write().await.add_user(user).await.unwrap();
+    }
+
+    async fn exists(state: &AppState, address: &str) -> bool {
+        let email = Email::parse(address.to_owned()).unwrap();
+        state.user_store.read().await.get_user(&email).await.is_ok()
+    }
+
+    #[tokio::test]
+    async fn should_only_purge_users_past_their_retention() {
+        let state = state().await;
+        add_user(
+            &state,
+            "gone@example.com",
+            AccountStatus::ScheduledForDeletion,
+            31,
+        )
+        .await;
+        add_user(
+            &state,
+            "kept@example.com",
+            AccountStatus::ScheduledForDeletion,
+            29,
+        )
+        .await;
+        add_user(&state, "disabled@example.com", AccountStatus::Disabled, 31).await;
+
+        assert_eq!(purge_deleted_users(&state).await.unwrap(), 1);
+
+        assert!(!exists(&state, "gone@example.com").await);
+        assert!(exists(&state, "kept@example.com").await);
+        assert!(exists(&state, "disabled@example.com").await);
+        let entries = state.audit_log.read().await.entries(0, 100).await.unwrap();
+        assert!(entries.iter().any(|entry| {
+            entry.event.kind == AuditEventKind::PurgeUser
+                && entry.event.actor.as_deref() == Some("gone@example.com")
+        }));
+    }
+}
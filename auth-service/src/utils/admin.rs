@@ -0,0 +1,74 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use secrecy::SecretString;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, User, UserId, UserStoreError},
+    utils::auth::{bearer_token, secrets_match, validate_token},
+};
+
+// The permission that makes a user an admin, see `RequireAdmin`
+pub const ADMIN_PERMISSION: &str = "admin";
+// What requests made with the admin token are recorded as, since it belongs to no one
+pub const ADMIN_TOKEN_IDENTITY: &str = "admin-token";
+// What changes made with the `auth-admin` binary are recorded as
+pub const CLI_IDENTITY: &str = "cli";
+
+// Proof that the request came from an admin: it carried either the admin token from the
+// settings, or the token of a user who was granted the `admin` permission
+#[derive(Debug)]
+pub struct RequireAdmin {
+    // Who the admin is, for the audit log: the user's id, which unlike their email never
+    // changes, or `ADMIN_TOKEN_IDENTITY`
+    pub identity: String,
+}
+
+impl FromRequestParts<AppState> for RequireAdmin {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(&parts.headers).ok_or(AuthAPIError::MissingToken)?;
+
+        let admin = &state.settings.admin;
+        if admin.enabled() && secrets_match(token, &admin.token) {
+            return Ok(Self {
+                identity: ADMIN_TOKEN_IDENTITY.to_owned(),
+            });
+        }
+        let claims = validate_token(
+            &SecretString::from(token),
+            state.banned_tokens.clone(),
+            state.user_store.clone(),
+            state.oauth.clients.clone(),
+        )
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+        // Only first-party tokens carry permissions
+        match claims.permissions.iter().any(|p| p == ADMIN_PERMISSION) {
+            true => Ok(Self {
+                identity: claims.sub,
+            }),
+            false => Err(AuthAPIError::Forbidden),
+        }
+    }
+}
+
+// The user an admin route's path names, by id or by email
+pub async fn existing_user(state: &AppState, user: String) -> Result<User, AuthAPIError> {
+    let user_store = state.user_store.read().await;
+    let result = match UserId::parse(&user) {
+        Ok(id) => user_store.get_user_by_id(id).await,
+        Err(_) => {
+            let email = Email::parse(user).map_err(|_| AuthAPIError::InvalidCredentials)?;
+            user_store.get_user(&email).await
+        }
+    };
+    match result {
+        Ok(user) => Ok(user),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::UserNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
@@ -0,0 +1,105 @@
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::{
+    app_state::AppState,
+    domain::{api_key_hash, AccountStatus, ApiKey, ApiKeyStoreError, User, API_KEY_PREFIX},
+    log_error_chain,
+    utils::oauth::generate_grant_token,
+};
+
+// How many characters of a key are kept to show in listings, prefix included
+const KEY_HINT_LENGTH: usize = 7;
+
+// A new API key, and the hint shown for it once it is hidden
+pub fn generate_api_key() -> (SecretString, String) {
+    let key = format!(
+        "{}{}",
+        API_KEY_PREFIX,
+        generate_grant_token().expose_secret()
+    );
+    let hint = key[..KEY_HINT_LENGTH].to_owned();
+    (SecretString::from(key), hint)
+}
+
+// Whether `token` is an API key rather than a JWT
+pub fn is_api_key(token: &SecretString) -> bool {
+    token.expose_secret().starts_with(API_KEY_PREFIX)
+}
+
+// The key of an `Authorization: ApiKey <key>` header, if there is one
+pub fn api_key_header(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("ApiKey "))
+}
+
+// Check that `key` was issued, has not expired or been revoked, and that its owner still
+// exists and is not disabled, returning the key and its owner. Its use is recorded, so users
+// can tell which keys are still needed.
+#[tracing::instrument(name = "validate API key", skip_all)]
+pub async fn validate_api_key(state: &AppState, key: &SecretString) -> Result<(ApiKey, User)> {
+    let api_key = state
+        .api_keys
+        .read()
+        .await
+        .get_api_key(&api_key_hash(key))
+        .await
+        .wrap_err("failed to look up API key")?;
+    let owner = state
+        .user_store
+        .read()
+        .await
+        .get_user_by_id(api_key.user_id)
+        .await
+        .wrap_err("failed to look up API key owner")?;
+    if owner.status != AccountStatus::Active {
+        return Err(eyre!("API key owner is {}", owner.status.as_str()));
+    }
+
+    // A key revoked in the meantime is still valid for this request, and failing to record
+    // its use should not fail the request either
+    match state
+        .api_keys
+        .write()
+        .await
+        .touch_api_key(api_key.id, Utc::now())
+        .await
+    {
+        Ok(()) | Err(ApiKeyStoreError::ApiKeyNotFound) => {}
+        Err(e) => log_error_chain(&e),
+    }
+    Ok((api_key, owner))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn should_generate_prefixed_keys_with_hint() {
+        let (key, hint) = generate_api_key();
+
+        assert!(is_api_key(&key));
+        assert!(key.expose_secret().starts_with(&hint));
+        assert_eq!(hint.len(), KEY_HINT_LENGTH);
+        assert_ne!(generate_api_key().0.expose_secret(), key.expose_secret());
+    }
+
+    #[test]
+    fn should_read_api_key_header_only() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(api_key_header(&headers), None);
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer ak_abc"));
+        assert_eq!(api_key_header(&headers), None);
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("ApiKey ak_abc"));
+        assert_eq!(api_key_header(&headers), Some("ak_abc"));
+    }
+}
@@ -0,0 +1,131 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts},
+};
+use std::{convert::Infallible, net::IpAddr, net::SocketAddr};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuditEventKind, AuditOutcome},
+    log_error_chain,
+    utils::metrics::ErrorOutcome,
+};
+
+// Client-supplied values are cut short so a single request cannot bloat the audit log
+const MAX_ACTOR_LENGTH: usize = 256;
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+// Where a request came from, for the audit events it causes. Handlers that audit a request
+// take their other extractors, such as the caller's token or `RequireAdmin`, as a `Result`
+// rather than letting them reject it, so attempts turned away there are audited too.
+#[derive(Clone, Debug, Default)]
+pub struct AuditContext {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuditContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // Only set when the server was started with connect info, which `Application` does
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| truncate(value, MAX_USER_AGENT_LENGTH));
+        Ok(Self { ip, user_agent })
+    }
+}
+
+impl AuditContext {
+    // The event for a request made by `actor` that ended with `result`
+    pub fn event<T, E: ErrorOutcome>(
+        &self,
+        kind: AuditEventKind,
+        actor: Option<&str>,
+        result: &Result<T, E>,
+    ) -> AuditEvent {
+        let (outcome, reason) = match result {
+            Ok(_) => (AuditOutcome::Success, None),
+            Err(e) => (AuditOutcome::Failure, Some(e.outcome().to_owned())),
+        };
+        AuditEvent {
+            kind,
+            actor: actor.map(|actor| truncate(actor, MAX_ACTOR_LENGTH)),
+            admin: None,
+            ip: self.ip,
+            user_agent: self.user_agent.clone(),
+            outcome,
+            reason,
+        }
+    }
+
+    // The event for a request made by `admin` on behalf of `actor`. Requests rejected before
+    // the admin was identified have none.
+    pub fn admin_event<T, E: ErrorOutcome>(
+        &self,
+        kind: AuditEventKind,
+        admin: Option<&str>,
+        actor: Option<&str>,
+        result: &Result<T, E>,
+    ) -> AuditEvent {
+        AuditEvent {
+            admin: admin.map(|admin| truncate(admin, MAX_ACTOR_LENGTH)),
+            ..self.event(kind, actor, result)
+        }
+    }
+}
+
+// Audit failures are logged rather than failing the request they describe
+pub async fn record_audit_event(state: &AppState, event: AuditEvent) {
+    if let Err(e) = state.audit_log.write().await.append(event).await {
+        log_error_chain(&e);
+    }
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AuthAPIError;
+    use axum::{body::Body, extract::Request};
+
+    #[tokio::test]
+    async fn should_read_ip_and_user_agent() {
+        let mut request = Request::builder()
+            .header(USER_AGENT, "curl/8.0")
+            .body(Body::empty())
+            .unwrap();
+        let addr: SocketAddr = "203.0.113.7:51234".parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(addr));
+        let (mut parts, _) = request.into_parts();
+
+        let context = AuditContext::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+
+        assert_eq!(context.ip, Some(addr.ip()));
+        assert_eq!(context.user_agent.as_deref(), Some("curl/8.0"));
+    }
+
+    #[test]
+    fn should_record_failure_reason_and_truncate_actor() {
+        let context = AuditContext::default();
+        let actor = "a".repeat(1_000);
+        let result: Result<(), AuthAPIError> = Err(AuthAPIError::IncorrectCredentials);
+
+        let event = context.event(AuditEventKind::Login, Some(&actor), &result);
+
+        assert_eq!(event.outcome, AuditOutcome::Failure);
+        assert_eq!(event.reason.as_deref(), Some("incorrect_credentials"));
+        assert_eq!(event.actor.unwrap().len(), MAX_ACTOR_LENGTH);
+    }
+}
@@ -1,18 +1,87 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap},
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
 use chrono::Utc;
-use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use color_eyre::eyre::{eyre, Context, Result};
+use jsonwebtoken::{
+    decode, encode, jwk::Jwk, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use lazy_static::lazy_static;
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use uuid::Uuid;
 
-use crate::{app_state::BannedTokenStoreType, domain::email::Email};
+use crate::{
+    app_state::{AppState, BannedTokenStoreType, OAuthClientStoreType, UserStoreType},
+    domain::{AccountStatus, AuthAPIError, User, UserGrants, UserId},
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::{
+    constants::{JWT_COOKIE_NAME, JWT_SECRET},
+    oidc::{derive_signing_key, public_jwk},
+};
 
-// Create cookie with a new JWT auth token
+// Authentication methods (RFC 8176) of a login with a password alone, and of one that was
+// completed with an emailed 2FA code
+pub const PASSWORD_AMR: &[&str] = &["pwd"];
+pub const TWO_FACTOR_AMR: &[&str] = &["pwd", "otp", "mfa"];
+// The `typ` header of access tokens (RFC 9068 section 2.1), which sets them apart from the
+// ID tokens published keys also verify
+pub const ACCESS_TOKEN_TYPE: &str = "at+jwt";
+
+lazy_static! {
+    static ref ACCESS_TOKEN_KEY: AccessTokenKey =
+        AccessTokenKey::derive(&JWT_SECRET).expect("Failed to derive the access token key.");
+}
+
+// The ES256 key access tokens are signed with. It is derived from `JWT_SECRET`, so every
+// instance signs with the same key and there is no other secret to configure, and its public
+// half is published in the JWKS so that services can check tokens without calling us.
+struct AccessTokenKey {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Jwk,
+}
+
+impl AccessTokenKey {
+    fn derive(secret: &SecretString) -> Result<Self> {
+        let pem = derive_signing_key(secret, "access token")?;
+        let encoding_key = EncodingKey::from_ec_pem(pem.expose_secret().as_bytes())
+            .wrap_err("invalid access token key")?;
+        let jwk = public_jwk(&encoding_key)?;
+        let decoding_key =
+            DecodingKey::from_jwk(&jwk).wrap_err("failed to load the public access token key")?;
+        Ok(Self {
+            encoding_key,
+            decoding_key,
+            jwk,
+        })
+    }
+}
+
+// The public key access tokens are signed with, as published in the JWKS
+pub fn access_token_jwk() -> Jwk {
+    ACCESS_TOKEN_KEY.jwk.clone()
+}
+
+// Create cookie with a new JWT auth token for the given session, logged in with `amr`
 #[tracing::instrument(name = "generate auth cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+pub fn generate_auth_cookie(
+    issuer: &str,
+    user: &User,
+    session_id: Uuid,
+    ttl: Duration,
+    amr: &[&str],
+    grants: &UserGrants,
+) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(issuer, user, session_id, ttl, amr, grants)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -28,19 +97,89 @@ fn create_auth_cookie(token: SecretString) -> Cookie<'static> {
     cookie
 }
 
-// This value determines how long the JWT auth token is valid for
-pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
-
-// Create JWT auth token
+// Create JWT auth token that is valid for `ttl`, carrying the user's roles and permissions
 #[tracing::instrument(name = "generate JWT auth token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<SecretString> {
-    let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
-        .wrap_err("failed to create 10 minute time delta")?;
+fn generate_auth_token(
+    issuer: &str,
+    user: &User,
+    session_id: Uuid,
+    ttl: Duration,
+    amr: &[&str],
+    grants: &UserGrants,
+) -> Result<SecretString> {
+    let claims = Claims {
+        amr: amr.iter().map(|method| method.to_string()).collect(),
+        roles: grants.roles.clone(),
+        permissions: grants.permissions.clone(),
+        ..new_claims(issuer, user, session_id, ttl)?
+    };
+    create_token(&claims)
+}
+
+// Create JWT access token for an OAuth client, acting for `user` within `scope`
+#[tracing::instrument(name = "generate JWT client token", skip_all)]
+pub fn generate_client_token(
+    issuer: &str,
+    user: &User,
+    session_id: Uuid,
+    ttl: Duration,
+    client_id: &str,
+    scope: Option<&str>,
+) -> Result<SecretString> {
+    let claims = Claims {
+        client_id: Some(client_id.to_owned()),
+        scope: scope.map(str::to_owned),
+        ..new_claims(issuer, user, session_id, ttl)?
+    };
+    create_token(&claims)
+}
+
+// Create JWT access token for a confidential OAuth client acting on its own, within `scope`.
+// There is no user, so the client is the subject.
+#[tracing::instrument(name = "generate JWT service token", skip_all)]
+pub fn generate_service_token(
+    issuer: &str,
+    client_id: &str,
+    session_id: Uuid,
+    ttl: Duration,
+    scope: Option<&str>,
+) -> Result<SecretString> {
+    let now = Utc::now();
+    let delta =
+        chrono::Duration::from_std(ttl).wrap_err("failed to create token TTL time delta")?;
+    let exp = now
+        .checked_add_signed(delta)
+        .ok_or(eyre!("failed to add token TTL to current time"))?
+        .timestamp();
+    let claims = Claims {
+        iss: issuer.to_owned(),
+        sub: client_id.to_owned(),
+        sub_type: SubjectType::Client,
+        email: None,
+        sid: session_id,
+        generation: 0,
+        exp: exp
+            .try_into()
+            .wrap_err("failed to cast exp time to usize")?,
+        iat: now.timestamp().try_into().unwrap_or_default(),
+        scope: scope.map(str::to_owned),
+        client_id: Some(client_id.to_owned()),
+        amr: Vec::new(),
+        roles: Vec::new(),
+        permissions: Vec::new(),
+    };
+    create_token(&claims)
+}
+
+// Claims of a first-party token for `user` that is valid for `ttl`
+fn new_claims(issuer: &str, user: &User, session_id: Uuid, ttl: Duration) -> Result<Claims> {
+    let delta =
+        chrono::Duration::from_std(ttl).wrap_err("failed to create token TTL time delta")?;
 
     // Create JWT expiration time
     let exp = Utc::now()
         .checked_add_signed(delta)
-        .ok_or(eyre!("failed to add 10 minutes to current time"))?
+        .ok_or(eyre!("failed to add token TTL to current time"))?
         .timestamp();
 
     // Cast exp to a usize, which is what Claims expects
@@ -49,18 +188,33 @@ fn generate_auth_token(email: &Email) -> Result<SecretString> {
         exp
     ))?;
 
-    let sub = email.as_ref().to_owned();
-
-    let claims = Claims { sub, exp };
-
-    create_token(&claims)
+    Ok(Claims {
+        iss: issuer.to_owned(),
+        sub: user.id.to_string(),
+        sub_type: SubjectType::User,
+        email: Some(user.email.as_ref().to_owned()),
+        sid: session_id,
+        generation: user.token_generation,
+        exp,
+        iat: Utc::now().timestamp().try_into().unwrap_or_default(),
+        scope: None,
+        client_id: None,
+        amr: Vec::new(),
+        roles: Vec::new(),
+        permissions: Vec::new(),
+    })
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
+// Check if JWT auth token is valid by decoding it using the access token key, that neither it nor
+// its session was banned, that its user is not disabled, and that it was issued after the
+// user last logged out everywhere. Tokens of clients acting on their own are valid while
+// the client is registered.
 #[tracing::instrument(name = "validate JWT auth token", skip_all)]
 pub async fn validate_token(
     token: &SecretString,
     banned_tokens: BannedTokenStoreType,
+    user_store: UserStoreType,
+    clients: OAuthClientStoreType,
 ) -> Result<Claims> {
     match banned_tokens.read().await.check_token(token).await {
         Ok(result) => {
@@ -70,49 +224,323 @@ pub async fn validate_token(
         }
         Err(e) => return Err(e.into()),
     }
+    let claims = decode_token(token)?;
+    if banned_tokens
+        .read()
+        .await
+        .check_token(&session_ban_key(claims.sid))
+        .await?
+    {
+        return Err(eyre!("token's session was revoked"));
+    }
+
+    if claims.sub_type == SubjectType::Client {
+        let client = clients
+            .read()
+            .await
+            .get_client(&claims.sub)
+            .await
+            .wrap_err("failed to look up token owner")?;
+        if !client.is_confidential() || claims.client_id.as_deref() != Some(client.id.as_str()) {
+            return Err(eyre!("token was not issued to a confidential client"));
+        }
+        return Ok(claims);
+    }
+    let user = user_store
+        .read()
+        .await
+        .get_user_by_id(claims.user_id()?)
+        .await
+        .wrap_err("failed to look up token owner")?;
+    if user.status != AccountStatus::Active {
+        return Err(eyre!("token owner is {}", user.status.as_str()));
+    }
+    if claims.generation != user.token_generation {
+        return Err(eyre!(
+            "token was issued before the user logged out everywhere"
+        ));
+    }
+    Ok(claims)
+}
+
+// Revoking a session bans this key in the banned token store, which rejects every token
+// issued for the session without keeping the tokens themselves. No JWT looks like it.
+pub fn session_ban_key(session_id: Uuid) -> SecretString {
+    SecretString::from(format!("session:{session_id}"))
+}
+
+// Check the signature and expiry of a JWT auth token, and read its claims. Whether it was
+// banned or issued before the last logout everywhere is up to `validate_token`.
+pub fn decode_token(token: &SecretString) -> Result<Claims> {
     decode::<Claims>(
         token.expose_secret(),
-        &DecodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-        &Validation::default(),
+        &ACCESS_TOKEN_KEY.decoding_key,
+        &Validation::new(Algorithm::ES256),
     )
     .map(|data| data.claims)
     .wrap_err("failed to decode token")
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
+// Where the caller's auth token was read from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenSource {
+    Cookie,
+    Bearer,
+}
+
+// The caller's auth token, not validated yet. An `Authorization: Bearer` header
+// takes precedence over the JWT auth cookie.
+#[derive(Debug)]
+pub struct AuthToken {
+    pub token: SecretString,
+    pub source: TokenSource,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthToken {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(token) = bearer_token(&parts.headers) {
+            return Ok(Self {
+                token: SecretString::from(token),
+                source: TokenSource::Bearer,
+            });
+        }
+        let jar = CookieJar::from_headers(&parts.headers);
+        let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
+        Ok(Self {
+            token: SecretString::from(cookie.value()),
+            source: TokenSource::Cookie,
+        })
+    }
+}
+
+// The caller, if they used a token of their own rather than one an OAuth client got on
+// their behalf. Users manage their credentials and sessions themselves, and a client's
+// token must not be enough to mint credentials that outlive it or to end other sessions.
+pub fn first_party_user(
+    user: Result<AuthenticatedUser, AuthAPIError>,
+) -> Result<AuthenticatedUser, AuthAPIError> {
+    let user = user?;
+    match user.claims.client_id {
+        None => Ok(user),
+        Some(_) => Err(AuthAPIError::InvalidToken),
+    }
+}
+
+// The caller, once their auth token passed `validate_token` and turned out to be a user's
+#[derive(Debug)]
+pub struct AuthenticatedUser {
+    pub token: SecretString,
+    pub claims: Claims,
+    pub source: TokenSource,
+}
+
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let AuthToken { token, source } = AuthToken::from_request_parts(parts, state).await?;
+        let claims = validate_token(
+            &token,
+            state.banned_tokens.clone(),
+            state.user_store.clone(),
+            state.oauth.clients.clone(),
+        )
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+        // Routes for users have nothing to offer a client acting on its own
+        if claims.sub_type != SubjectType::User {
+            return Err(AuthAPIError::InvalidToken);
+        }
+        Ok(Self {
+            token,
+            claims,
+            source,
+        })
+    }
+}
+
+impl AuthenticatedUser {
+    // Drop the auth cookie from the client, if that is where the token came from. The path
+    // has to match the cookie's, or clients would only drop one scoped to the request path.
+    pub fn clear_cookie(&self, jar: CookieJar) -> CookieJar {
+        match self.source {
+            TokenSource::Cookie => jar.remove(Cookie::build(JWT_COOKIE_NAME).path("/")),
+            TokenSource::Bearer => jar,
+        }
+    }
+}
+
+// Compare a secret sent by a client with the configured one. Digests are compared, so the
+// time taken does not depend on how much of the secret matched.
+pub fn secrets_match(given: &str, expected: &SecretString) -> bool {
+    Sha256::digest(given.as_bytes()) == Sha256::digest(expected.expose_secret().as_bytes())
+}
+
+// The token of an `Authorization: Bearer <token>` header, if there is one
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+// Create JWT auth token by signing claims with the access token key
 #[tracing::instrument(name = "encode claims", skip_all)]
 fn create_token(claims: &Claims) -> Result<SecretString> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-    )
-    .map(|value: String| SecretString::new(value.into_boxed_str()))
-    .wrap_err("failed to create token")
+    let header = Header {
+        typ: Some(ACCESS_TOKEN_TYPE.to_owned()),
+        kid: ACCESS_TOKEN_KEY.jwk.common.key_id.clone(),
+        ..Header::new(Algorithm::ES256)
+    };
+    encode(&header, &claims, &ACCESS_TOKEN_KEY.encoding_key)
+        .map(|value: String| SecretString::new(value.into_boxed_str()))
+        .wrap_err("failed to create token")
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
+    // `oidc.issuer`, so that services checking tokens themselves can tell whose they are
+    pub iss: String,
+    // The user's id, or the client id for tokens of the client credentials grant
     pub sub: String,
+    #[serde(default, skip_serializing_if = "SubjectType::is_user")]
+    pub sub_type: SubjectType,
+    // The user's email as of when the token was issued. Clients acting on their own have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    // The session the token was issued for
+    pub sid: Uuid,
+    // The owner's token generation when the token was issued. Tokens from before
+    // generations were introduced have none, which matches the initial generation.
+    #[serde(rename = "gen", default)]
+    pub generation: i64,
     pub exp: usize,
+    // When the token was issued. Older tokens have none, and read as 0.
+    #[serde(default)]
+    pub iat: usize,
+    // Space-separated scopes and the client the token was issued to. First-party tokens,
+    // issued by `/login` and `/verify-2fa`, have neither.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    // How the user logged in, for first-party tokens. Older tokens have none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub amr: Vec<String>,
+    // The user's roles and the permissions they grant, as of when the token was issued. Only
+    // first-party tokens carry them; OAuth clients are limited by `scope` instead.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permissions: Vec<String>,
+}
+
+impl Claims {
+    // The user the token was issued to. Fails for tokens of clients acting on their own.
+    pub fn user_id(&self) -> Result<UserId> {
+        if self.sub_type != SubjectType::User {
+            return Err(eyre!("token was not issued to a user"));
+        }
+        UserId::parse(&self.sub)
+    }
+
+    // Who the token was issued to, as recorded in the audit log: the user's email, or the
+    // client id
+    pub fn actor(&self) -> String {
+        self.email.clone().unwrap_or_else(|| self.sub.clone())
+    }
+}
+
+// Whom a token was issued to. Only client tokens carry it, so older tokens read as users'.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubjectType {
+    #[default]
+    User,
+    Client,
+}
+
+impl SubjectType {
+    fn is_user(&self) -> bool {
+        *self == Self::User
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use axum::{body::Body, extract::Request, http::header::COOKIE};
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
     use crate::{
-        domain::BannedTokenStore,
-        services::data_stores::hashset_banned_token_store::HashsetBannedTokenStore,
+        domain::{
+            client_secret_hash, BannedTokenStore, Email, OAuthClient, OAuthClientStore, UserStore,
+        },
+        services::data_stores::{
+            hashmap_oauth_client_store::HashmapOAuthClientStore,
+            hashmap_user_store::HashmapUserStore,
+            hashset_banned_token_store::HashsetBannedTokenStore,
+        },
     };
 
     use super::*;
 
+    const TTL: Duration = Duration::from_secs(600);
+    const ISSUER: &str = "https://auth.example.com";
+
+    fn test_user() -> User {
+        User {
+            id: UserId::from(Uuid::from_u128(1)),
+            email: Email::parse("test@example.com".to_owned()).unwrap(),
+            ..User::default()
+        }
+    }
+
+    // A user store holding `test_user()`
+    async fn user_store() -> UserStoreType {
+        let mut store = HashmapUserStore::new();
+        store.add_user(test_user()).await.unwrap();
+        Arc::new(RwLock::new(store))
+    }
+
+    // A client store holding the confidential client `service` and the public client `app`
+    async fn clients() -> OAuthClientStoreType {
+        let mut store = HashmapOAuthClientStore::default();
+        let service = OAuthClient {
+            id: "service".to_owned(),
+            name: "Service".to_owned(),
+            redirect_uris: Vec::new(),
+            secret_hash: Some(client_secret_hash(&SecretString::from("s3cret"))),
+            allowed_scopes: vec!["reports:read".to_owned()],
+            created_at: Utc::now(),
+        };
+        let app = OAuthClient {
+            id: "app".to_owned(),
+            secret_hash: None,
+            allowed_scopes: Vec::new(),
+            ..service.clone()
+        };
+        store.add_client(service).await.unwrap();
+        store.add_client(app).await.unwrap();
+        Arc::new(RwLock::new(store))
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(
+            ISSUER,
+            &test_user(),
+            Uuid::new_v4(),
+            TTL,
+            PASSWORD_AMR,
+            &UserGrants::default(),
+        )
+        .unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -133,18 +561,89 @@ mod tests {
 
     #[tokio::test]
     async fn test_generate_auth_token() {
-        let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(
+            ISSUER,
+            &test_user(),
+            Uuid::new_v4(),
+            TTL,
+            PASSWORD_AMR,
+            &UserGrants::default(),
+        )
+        .unwrap();
         assert_eq!(result.expose_secret().split('.').count(), 3);
     }
 
+    #[tokio::test]
+    async fn test_auth_token_is_signed_with_the_published_key() {
+        let token = generate_auth_token(
+            ISSUER,
+            &test_user(),
+            Uuid::new_v4(),
+            TTL,
+            PASSWORD_AMR,
+            &UserGrants::default(),
+        )
+        .unwrap();
+        let header = jsonwebtoken::decode_header(token.expose_secret()).unwrap();
+        assert_eq!(header.alg, Algorithm::ES256);
+        assert_eq!(header.typ.as_deref(), Some(ACCESS_TOKEN_TYPE));
+        assert_eq!(header.kid, access_token_jwk().common.key_id);
+        assert_eq!(decode_token(&token).unwrap().iss, ISSUER);
+
+        // Tokens signed with the secret itself, as they used to be, are rejected
+        let claims = decode_token(&token).unwrap();
+        let hs256 = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
+        )
+        .unwrap();
+        assert!(decode_token(&SecretString::from(hs256)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_token_with_grants() {
+        let grants = UserGrants {
+            roles: vec!["editor".to_owned()],
+            permissions: vec!["articles:write".to_owned()],
+        };
+        let token = generate_auth_token(
+            ISSUER,
+            &test_user(),
+            Uuid::new_v4(),
+            TTL,
+            PASSWORD_AMR,
+            &grants,
+        )
+        .unwrap();
+
+        let claims = decode_token(&token).unwrap();
+
+        assert_eq!(claims.roles, grants.roles);
+        assert_eq!(claims.permissions, grants.permissions);
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
-        let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let session_id = Uuid::new_v4();
+        let token = generate_auth_token(
+            ISSUER,
+            &test_user(),
+            session_id,
+            TTL,
+            PASSWORD_AMR,
+            &UserGrants::default(),
+        )
+        .unwrap();
         let banned_tokens = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_tokens).await.unwrap();
-        assert_eq!(result.sub, "test@example.com");
+        let result = validate_token(&token, banned_tokens, user_store().await, clients().await)
+            .await
+            .unwrap();
+        assert_eq!(result.sub, test_user().id.to_string());
+        assert_eq!(result.user_id().unwrap(), test_user().id);
+        assert_eq!(result.email.as_deref(), Some("test@example.com"));
+        assert_eq!(result.sid, session_id);
+        assert_eq!(result.amr, PASSWORD_AMR);
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -158,18 +657,161 @@ mod tests {
     async fn test_validate_token_with_invalid_token() {
         let token = SecretString::new("invalid_token".to_owned().into_boxed_str());
         let banned_tokens = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_tokens).await;
+        let result =
+            validate_token(&token, banned_tokens, user_store().await, clients().await).await;
         assert!(result.is_err());
     }
 
     #[tokio::test]
     async fn test_validate_token_with_banned_token() {
-        let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let token = generate_auth_token(
+            ISSUER,
+            &test_user(),
+            Uuid::new_v4(),
+            TTL,
+            PASSWORD_AMR,
+            &UserGrants::default(),
+        )
+        .unwrap();
         let mut banned_store = HashsetBannedTokenStore::default();
         banned_store.add_token(token.clone()).await.unwrap();
         let banned_tokens = Arc::new(RwLock::new(banned_store));
-        let result = validate_token(&token, banned_tokens).await;
+        let result =
+            validate_token(&token, banned_tokens, user_store().await, clients().await).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_of_revoked_session() {
+        let session_id = Uuid::new_v4();
+        let token = generate_auth_token(
+            ISSUER,
+            &test_user(),
+            session_id,
+            TTL,
+            PASSWORD_AMR,
+            &UserGrants::default(),
+        )
+        .unwrap();
+        let mut banned_store = HashsetBannedTokenStore::default();
+        banned_store
+            .add_token(session_ban_key(session_id))
+            .await
+            .unwrap();
+        let banned_tokens = Arc::new(RwLock::new(banned_store));
+        let result =
+            validate_token(&token, banned_tokens, user_store().await, clients().await).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_validate_token_from_before_logout_everywhere() {
+        let token = generate_auth_token(
+            ISSUER,
+            &test_user(),
+            Uuid::new_v4(),
+            TTL,
+            PASSWORD_AMR,
+            &UserGrants::default(),
+        )
+        .unwrap();
+        let user_store = user_store().await;
+        user_store
+            .write()
+            .await
+            .bump_token_generation(test_user().id)
+            .await
+            .unwrap();
+        let banned_tokens = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(&token, banned_tokens, user_store, clients().await).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_service_token() {
+        let token =
+            generate_service_token(ISSUER, "service", Uuid::new_v4(), TTL, Some("reports:read"))
+                .unwrap();
+        let banned_tokens = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let result = validate_token(&token, banned_tokens, user_store().await, clients().await)
+            .await
+            .unwrap();
+
+        assert_eq!(result.sub, "service");
+        assert_eq!(result.sub_type, SubjectType::Client);
+        assert_eq!(result.email, None);
+        assert!(result.user_id().is_err());
+        assert_eq!(result.actor(), "service");
+        assert_eq!(result.client_id.as_deref(), Some("service"));
+        assert_eq!(result.scope.as_deref(), Some("reports:read"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_service_token_of_unknown_or_public_client() {
+        for client_id in ["deleted", "app"] {
+            let token =
+                generate_service_token(ISSUER, client_id, Uuid::new_v4(), TTL, None).unwrap();
+            let banned_tokens = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+            let result =
+                validate_token(&token, banned_tokens, user_store().await, clients().await).await;
+
+            assert!(result.is_err(), "Failed for input: {}", client_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_tokens_have_no_subject_type_claim() {
+        let token = generate_auth_token(
+            ISSUER,
+            &test_user(),
+            Uuid::new_v4(),
+            TTL,
+            PASSWORD_AMR,
+            &UserGrants::default(),
+        )
+        .unwrap();
+
+        let claims = decode_token(&token).unwrap();
+
+        assert_eq!(claims.sub_type, SubjectType::User);
+        let json = serde_json::to_value(&claims).unwrap();
+        assert!(json.get("sub_type").is_none());
+    }
+
+    async fn auth_token(request: Request) -> Result<AuthToken, AuthAPIError> {
+        let (mut parts, _) = request.into_parts();
+        AuthToken::from_request_parts(&mut parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_prefers_bearer_header() {
+        let request = Request::builder()
+            .header(AUTHORIZATION, "Bearer from-header")
+            .header(COOKIE, format!("{}=from-cookie", JWT_COOKIE_NAME))
+            .body(Body::empty())
+            .unwrap();
+
+        let token = auth_token(request).await.unwrap();
+
+        assert_eq!(token.token.expose_secret(), "from-header");
+        assert_eq!(token.source, TokenSource::Bearer);
+    }
+
+    #[tokio::test]
+    async fn test_auth_token_falls_back_to_cookie() {
+        let request = Request::builder()
+            .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+            .header(COOKIE, format!("{}=from-cookie", JWT_COOKIE_NAME))
+            .body(Body::empty())
+            .unwrap();
+
+        let token = auth_token(request).await.unwrap();
+
+        assert_eq!(token.token.expose_secret(), "from-cookie");
+        assert_eq!(token.source, TokenSource::Cookie);
+        let missing = auth_token(Request::new(Body::empty())).await;
+        assert!(matches!(missing, Err(AuthAPIError::MissingToken)));
+    }
 }
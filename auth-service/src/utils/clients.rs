@@ -0,0 +1,131 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use secrecy::SecretString;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, OAuthClient, OAuthClientStoreError, OAuthError},
+    utils::auth::secrets_match,
+};
+
+// A configured client that authenticated with HTTP Basic, as RFC 6749 section 2.3.1 describes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedClient {
+    pub id: String,
+}
+
+impl FromRequestParts<AppState> for AuthenticatedClient {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let (id, secret) = basic_credentials(&parts.headers).ok_or(AuthAPIError::InvalidClient)?;
+        let client = state
+            .settings
+            .clients
+            .iter()
+            .find(|client| client.id == id)
+            .ok_or(AuthAPIError::InvalidClient)?;
+        match secrets_match(&secret, &client.secret) {
+            true => Ok(Self { id }),
+            false => Err(AuthAPIError::InvalidClient),
+        }
+    }
+}
+
+// The registered OAuth client a request is from, given the `client_id` and `client_secret`
+// of its form. Clients with a secret have to prove themselves with it, either in the form
+// or with HTTP Basic (RFC 6749 section 2.3.1).
+pub async fn authenticate_oauth_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&SecretString>,
+) -> Result<OAuthClient, OAuthError> {
+    let (client_id, secret) = match basic_credentials(headers) {
+        // Only one way of authenticating may be used at a time
+        Some(_) if client_secret.is_some() => {
+            return Err(OAuthError::InvalidRequest(
+                "client_secret must not be sent along with HTTP Basic",
+            ))
+        }
+        Some((id, _)) if client_id.is_some_and(|form_id| form_id != id) => {
+            return Err(OAuthError::InvalidClient)
+        }
+        Some((id, secret)) => (id, Some(SecretString::from(secret))),
+        None => (
+            client_id.ok_or(OAuthError::InvalidClient)?.to_owned(),
+            client_secret.cloned(),
+        ),
+    };
+    let client = match state
+        .oauth
+        .clients
+        .read()
+        .await
+        .get_client(&client_id)
+        .await
+    {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => return Err(OAuthError::InvalidClient),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+    match client.verify_secret(secret.as_ref()) {
+        true => Ok(client),
+        false => Err(OAuthError::InvalidClient),
+    }
+}
+
+// The id and secret of an `Authorization: Basic` header, if there is a well-formed one
+pub fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let encoded = headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (id, secret) = decoded.split_once(':')?;
+    Some((id.to_owned(), secret.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn headers(authorization: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(authorization).unwrap());
+        headers
+    }
+
+    #[test]
+    fn should_read_basic_credentials() {
+        // base64 of "app-service:s3cr:et"
+        let credentials = basic_credentials(&headers("Basic YXBwLXNlcnZpY2U6czNjcjpldA=="));
+
+        assert_eq!(
+            credentials,
+            Some(("app-service".to_owned(), "s3cr:et".to_owned()))
+        );
+    }
+
+    #[test]
+    fn should_ignore_malformed_credentials() {
+        for authorization in ["Bearer abc", "Basic not-base64!", "Basic YXBwLXNlcnZpY2U="] {
+            assert_eq!(
+                basic_credentials(&headers(authorization)),
+                None,
+                "Failed for input: {}",
+                authorization
+            );
+        }
+        assert_eq!(basic_credentials(&HeaderMap::new()), None);
+    }
+}
@@ -6,8 +6,6 @@ use std::env as std_env;
 // Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
 lazy_static! {
     pub static ref JWT_SECRET: SecretString = set_token();
-    pub static ref DATABASE_URL: String = set_db_url();
-    pub static ref REDIS_HOST_NAME: String = set_redis_host();
 }
 
 fn set_token() -> SecretString {
@@ -19,29 +17,20 @@ fn set_token() -> SecretString {
     SecretString::new(secret.into_boxed_str())
 }
 
-fn set_db_url() -> String {
-    dotenv().ok();
-    std_env::var(env::DATABASE_URL_ENV_VAR).expect("DATABASE_URL must be set.")
-}
-
-fn set_redis_host() -> String {
-    dotenv().ok();
-    std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_string())
-}
-
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const CONFIG_FILE_ENV_VAR: &str = "AUTH_CONFIG_FILE";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
-
-pub mod prod {
-    pub const APP_ADDRESS: &str = "0.0.0.0:3000";
-}
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
 
 pub mod test {
     pub const APP_ADDRESS: &str = "127.0.0.1:0";
+    pub const ADMIN_TOKEN: &str = "test-admin-token-0123456789abcdef";
+    pub const CLIENT_ID: &str = "test-client";
+    pub const CLIENT_SECRET: &str = "test-client-secret-0123456789abcdef";
 }
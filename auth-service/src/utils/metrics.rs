@@ -0,0 +1,110 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::{sync::OnceLock, time::Instant};
+
+use crate::domain::{AuthAPIError, OAuthError};
+
+pub const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+pub const HTTP_REQUEST_DURATION_SECONDS: &str = "http_request_duration_seconds";
+pub const LOGINS_TOTAL: &str = "auth_logins_total";
+pub const TWO_FA_CODES_SENT_TOTAL: &str = "auth_2fa_codes_sent_total";
+pub const TWO_FA_VERIFICATIONS_TOTAL: &str = "auth_2fa_verifications_total";
+pub const TOKENS_BANNED_TOTAL: &str = "auth_tokens_banned_total";
+pub const OAUTH_TOKEN_REQUESTS_TOTAL: &str = "auth_oauth_token_requests_total";
+pub const PASSWORD_HASH_DURATION_SECONDS: &str = "auth_password_hash_duration_seconds";
+pub const STORE_OPERATION_DURATION_SECONDS: &str = "auth_store_operation_duration_seconds";
+
+// Argon2 takes tens of milliseconds, store operations and requests usually far less
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static PROMETHEUS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+// Install the global Prometheus recorder the first time this is called
+pub fn init_metrics() -> &'static PrometheusHandle {
+    PROMETHEUS_HANDLE.get_or_init(|| {
+        PrometheusBuilder::new()
+            .set_buckets_for_metric(Matcher::Suffix("_seconds".to_owned()), DURATION_BUCKETS)
+            .expect("duration buckets are not empty")
+            .install_recorder()
+            .expect("Failed to install Prometheus recorder")
+    })
+}
+
+// Count and time every request by method, matched route and status code.
+// The route is the path template (e.g. `/login`), so unknown paths share the `unmatched` label.
+pub async fn track_http_metrics(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_owned());
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    metrics::counter!(HTTP_REQUESTS_TOTAL, &labels).increment(1);
+    metrics::histogram!(HTTP_REQUEST_DURATION_SECONDS, &labels)
+        .record(start.elapsed().as_secs_f64());
+    response
+}
+
+// Outcome label for a request that failed with `error`
+pub fn error_outcome(error: &AuthAPIError) -> &'static str {
+    match error {
+        AuthAPIError::UserAlreadyExists => "user_already_exists",
+        AuthAPIError::IncorrectCredentials => "incorrect_credentials",
+        AuthAPIError::InvalidCredentials => "invalid_credentials",
+        AuthAPIError::MissingToken => "missing_token",
+        AuthAPIError::InvalidToken => "invalid_token",
+        AuthAPIError::SessionNotFound => "session_not_found",
+        AuthAPIError::UserNotFound => "user_not_found",
+        AuthAPIError::InvalidClient => "invalid_client",
+        AuthAPIError::UnauthorizedClient => "unauthorized_client",
+        AuthAPIError::ClientNotFound => "client_not_found",
+        AuthAPIError::InsufficientScope => "insufficient_scope",
+        AuthAPIError::UserCodeNotFound => "user_code_not_found",
+        AuthAPIError::ApiKeyNotFound => "api_key_not_found",
+        AuthAPIError::RoleNotFound => "role_not_found",
+        AuthAPIError::Forbidden => "forbidden",
+        AuthAPIError::AccountDisabled => "account_disabled",
+        AuthAPIError::AccountPendingVerification => "account_pending_verification",
+        AuthAPIError::AccountScheduledForDeletion => "account_scheduled_for_deletion",
+        AuthAPIError::PasswordResetRequired => "password_reset_required",
+        AuthAPIError::UnexpectedError(_) => "error",
+    }
+}
+
+// Errors that can label the outcome of the request that failed with them
+pub trait ErrorOutcome {
+    fn outcome(&self) -> &'static str;
+}
+
+impl ErrorOutcome for AuthAPIError {
+    fn outcome(&self) -> &'static str {
+        error_outcome(self)
+    }
+}
+
+impl ErrorOutcome for OAuthError {
+    fn outcome(&self) -> &'static str {
+        self.code()
+    }
+}
+
+impl<E: ErrorOutcome> ErrorOutcome for &E {
+    fn outcome(&self) -> &'static str {
+        (*self).outcome()
+    }
+}
@@ -1,3 +1,12 @@
+pub mod accounts;
+pub mod admin;
+pub mod api_keys;
+pub mod audit;
 pub mod auth;
+pub mod clients;
 pub mod constants;
+pub mod metrics;
+pub mod oauth;
+pub mod oidc;
+pub mod sessions;
 pub mod tracing;
@@ -0,0 +1,248 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use color_eyre::eyre::{Context, Result};
+use rand::Rng;
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+use url::{Host, Url};
+
+// A new authorization code or refresh token: 256 random bits, URL-safe
+pub fn generate_grant_token() -> SecretString {
+    let bytes: [u8; 32] = rand::random();
+    SecretString::from(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+// The S256 code challenge for `verifier`, as RFC 7636 section 4.2 defines it
+pub fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+// An S256 challenge is the base64url encoding of a SHA-256 digest
+pub fn is_valid_code_challenge(challenge: &str) -> bool {
+    challenge.len() == 43 && URL_SAFE_NO_PAD.decode(challenge).is_ok()
+}
+
+// Whether `verifier` is well-formed (RFC 7636 section 4.1) and hashes to `challenge`
+pub fn verify_code_verifier(verifier: &SecretString, challenge: &str) -> bool {
+    let verifier = verifier.expose_secret();
+    let well_formed = (43..=128).contains(&verifier.len())
+        && verifier
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c));
+    well_formed && code_challenge(verifier) == challenge
+}
+
+// Redirect URIs are registered as absolute URLs without a fragment (RFC 6749 section 3.1.2).
+// Plain HTTP is only allowed on loopback addresses, for apps under development.
+pub fn validate_redirect_uri(uri: &str) -> Result<(), &'static str> {
+    let url = Url::parse(uri).map_err(|_| "redirect URIs must be absolute URLs")?;
+    if url.fragment().is_some() {
+        return Err("redirect URIs must not have a fragment");
+    }
+    let loopback = match url.host() {
+        Some(Host::Domain(domain)) => domain == "localhost",
+        Some(Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    };
+    match url.scheme() {
+        "https" => Ok(()),
+        "http" if loopback => Ok(()),
+        _ => Err("redirect URIs must use https, or http on a loopback address"),
+    }
+}
+
+// `uri` with `params` added to its query, keeping the query it already has
+pub fn with_query_params(uri: &str, params: &[(&str, &str)]) -> Result<String> {
+    let mut url = Url::parse(uri).wrap_err("failed to parse redirect URI")?;
+    url.query_pairs_mut().extend_pairs(params);
+    Ok(url.into())
+}
+
+// Scopes are space-separated tokens (RFC 6749 section 3.3). They are kept in the order
+// they were asked for, without duplicates; `None` when there are none.
+pub fn normalize_scope(scope: Option<&str>) -> Option<Result<String, &'static str>> {
+    let mut tokens: Vec<&str> = Vec::new();
+    for token in scope?.split(' ').filter(|token| !token.is_empty()) {
+        if !token
+            .chars()
+            .all(|c| c == '!' || ('#'..='[').contains(&c) || (']'..='~').contains(&c))
+        {
+            return Some(Err("scope contains invalid characters"));
+        }
+        if !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    }
+    (!tokens.is_empty()).then(|| Ok(tokens.join(" ")))
+}
+
+// Whether every scope in `requested` was also `granted`
+pub fn scope_within(requested: &str, granted: Option<&str>) -> bool {
+    let granted: Vec<&str> = granted.unwrap_or_default().split(' ').collect();
+    requested.split(' ').all(|token| granted.contains(&token))
+}
+
+// User codes are typed in by hand, so they avoid vowels (and with them, words) and
+// characters that look alike (RFC 8628 section 6.1)
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LENGTH: usize = 8;
+
+// A new user code for the device grant, in its normalized form
+pub fn generate_user_code() -> String {
+    let mut rng = rand::rng();
+    (0..USER_CODE_LENGTH)
+        .map(|_| USER_CODE_ALPHABET[rng.random_range(0..USER_CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+// A user code as typed: case, dashes and spaces do not matter. `None` unless it could
+// have been generated.
+pub fn normalize_user_code(user_code: &str) -> Option<String> {
+    let code: String = user_code
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let valid =
+        code.len() == USER_CODE_LENGTH && code.bytes().all(|c| USER_CODE_ALPHABET.contains(&c));
+    valid.then_some(code)
+}
+
+// A normalized user code the way users are shown it, e.g. `BCDF-GHJK`
+pub fn format_user_code(user_code: &str) -> String {
+    let (first, second) = user_code.split_at(user_code.len() / 2);
+    format!("{}-{}", first, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_verify_code_verifier() {
+        // The example from RFC 7636 appendix B
+        let verifier = SecretString::from("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk");
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+        assert_eq!(code_challenge(verifier.expose_secret()), challenge);
+        assert!(is_valid_code_challenge(challenge));
+        assert!(verify_code_verifier(&verifier, challenge));
+        assert!(!verify_code_verifier(
+            &SecretString::from("a".repeat(43)),
+            challenge
+        ));
+    }
+
+    #[test]
+    fn should_reject_malformed_code_verifiers() {
+        let test_cases = [
+            "short".to_owned(),
+            "a".repeat(129),
+            format!("{}!", "a".repeat(43)),
+        ];
+        for verifier in test_cases {
+            let challenge = code_challenge(&verifier);
+            assert!(
+                !verify_code_verifier(&SecretString::from(verifier.clone()), &challenge),
+                "Failed for input: {}",
+                verifier
+            );
+        }
+    }
+
+    #[test]
+    fn should_generate_distinct_grant_tokens() {
+        let first = generate_grant_token();
+        let second = generate_grant_token();
+
+        assert_eq!(first.expose_secret().len(), 43);
+        assert_ne!(first.expose_secret(), second.expose_secret());
+    }
+
+    #[test]
+    fn should_validate_redirect_uris() {
+        let accepted = [
+            "https://app.example.com/callback",
+            "https://app.example.com/callback?tenant=1",
+            "http://localhost:8000/callback",
+            "http://127.0.0.1:8000/callback",
+            "http://[::1]/callback",
+        ];
+        let rejected = [
+            "/callback",
+            "https://app.example.com/callback#fragment",
+            "http://app.example.com/callback",
+            "javascript:alert(1)",
+        ];
+        for uri in accepted {
+            assert!(
+                validate_redirect_uri(uri).is_ok(),
+                "Failed for input: {}",
+                uri
+            );
+        }
+        for uri in rejected {
+            assert!(
+                validate_redirect_uri(uri).is_err(),
+                "Failed for input: {}",
+                uri
+            );
+        }
+    }
+
+    #[test]
+    fn should_add_query_params_to_redirect_uri() {
+        let uri = with_query_params(
+            "https://app.example.com/callback?tenant=1",
+            &[("code", "abc"), ("state", "a b&c")],
+        )
+        .unwrap();
+
+        assert_eq!(
+            uri,
+            "https://app.example.com/callback?tenant=1&code=abc&state=a+b%26c"
+        );
+    }
+
+    #[test]
+    fn should_normalize_scope() {
+        assert_eq!(normalize_scope(None), None);
+        assert_eq!(normalize_scope(Some("  ")), None);
+        assert_eq!(
+            normalize_scope(Some("profile  email profile")),
+            Some(Ok("profile email".to_owned()))
+        );
+        assert!(matches!(normalize_scope(Some("pro\"file")), Some(Err(_))));
+    }
+
+    #[test]
+    fn should_check_scope_is_within_grant() {
+        assert!(scope_within("email", Some("profile email")));
+        assert!(!scope_within("email admin", Some("profile email")));
+        assert!(!scope_within("email", None));
+    }
+
+    #[test]
+    fn should_generate_user_codes_that_normalize_to_themselves() {
+        let code = generate_user_code();
+
+        assert_eq!(normalize_user_code(&code), Some(code.clone()));
+        assert_eq!(normalize_user_code(&format_user_code(&code)), Some(code));
+    }
+
+    #[test]
+    fn should_normalize_user_code() {
+        assert_eq!(
+            normalize_user_code(" bcdf-ghjk "),
+            Some("BCDFGHJK".to_owned())
+        );
+        assert_eq!(
+            normalize_user_code("BCDF GHJK"),
+            Some("BCDFGHJK".to_owned())
+        );
+        assert_eq!(normalize_user_code("BCDF-GHJ"), None);
+        assert_eq!(normalize_user_code("BCDF-GHJA"), None);
+        assert_eq!(normalize_user_code("BCDF-GHJ\u{e9}"), None);
+        assert_eq!(format_user_code("BCDFGHJK"), "BCDF-GHJK");
+    }
+}
@@ -0,0 +1,199 @@
+use axum_extra::extract::cookie::Cookie;
+use chrono::Utc;
+use color_eyre::eyre::{Context, Result};
+use secrecy::SecretString;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Session, SessionStoreError, User, UserGrants, UserId, UserStoreError},
+    log_error_chain,
+    utils::{
+        audit::AuditContext,
+        auth::{generate_auth_cookie, generate_client_token, session_ban_key},
+        metrics::TOKENS_BANNED_TOTAL,
+    },
+};
+
+// Issue an auth cookie for `user`, who logged in with `amr`, and record it as a new session
+#[tracing::instrument(name = "Start session", skip_all)]
+pub async fn start_session(
+    state: &AppState,
+    user: &User,
+    context: &AuditContext,
+    amr: &[&str],
+) -> Result<Cookie<'static>> {
+    let id = Uuid::new_v4();
+    let grants = user_grants(state, user.id).await?;
+    let cookie = generate_auth_cookie(
+        &state.settings.oidc.issuer,
+        user,
+        id,
+        state.settings.ttl.token(),
+        amr,
+        &grants,
+    )?;
+    record_session(state, user, context, id, None).await?;
+    Ok(cookie)
+}
+
+// Issue an access token to an OAuth client acting for `user`. It is recorded as a session
+// like any other, so the user can see and revoke it.
+#[tracing::instrument(name = "Start client session", skip_all)]
+pub async fn start_client_session(
+    state: &AppState,
+    user: &User,
+    context: &AuditContext,
+    client_id: &str,
+    scope: Option<&str>,
+) -> Result<SecretString> {
+    let id = Uuid::new_v4();
+    let ttl = state.settings.ttl.token();
+    let issuer = &state.settings.oidc.issuer;
+    let token = generate_client_token(issuer, user, id, ttl, client_id, scope)?;
+    record_session(state, user, context, id, Some(client_id)).await?;
+    Ok(token)
+}
+
+// The roles of the user and the permissions they grant, to embed in a new token
+pub async fn user_grants(state: &AppState, user_id: UserId) -> Result<UserGrants> {
+    let roles = state
+        .roles
+        .read()
+        .await
+        .get_user_roles(user_id)
+        .await
+        .wrap_err("failed to look up user roles")?;
+    Ok(UserGrants::from_roles(&roles))
+}
+
+async fn record_session(
+    state: &AppState,
+    user: &User,
+    context: &AuditContext,
+    id: Uuid,
+    client_id: Option<&str>,
+) -> Result<()> {
+    let ttl = state.settings.ttl.token();
+    let now = Utc::now();
+    let session = Session {
+        id,
+        user_id: user.id,
+        client_id: client_id.map(str::to_owned),
+        created_at: now,
+        last_seen_at: now,
+        expires_at: now + chrono::Duration::from_std(ttl).wrap_err("invalid token TTL")?,
+        ip: context.ip,
+        user_agent: context.user_agent.clone(),
+    };
+    state
+        .sessions
+        .write()
+        .await
+        .add_session(session)
+        .await
+        .wrap_err("failed to record session")
+}
+
+// Tokens issued before sessions were recorded have no session to update, which is fine.
+// Other failures are logged rather than failing the request that used the token.
+pub async fn touch_session(state: &AppState, session_id: Uuid) {
+    match state
+        .sessions
+        .write()
+        .await
+        .touch_session(session_id, Utc::now())
+        .await
+    {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => {}
+        Err(e) => log_error_chain(&e),
+    }
+}
+
+// Ban every token issued for a session and forget the session
+#[tracing::instrument(name = "Revoke session", skip_all)]
+pub async fn revoke_session(state: &AppState, session: Session) -> Result<(), AuthAPIError> {
+    revoke_token(state, session_ban_key(session.id), session.id).await
+}
+
+// Ban a token and forget its session. Revoking a token twice is fine.
+#[tracing::instrument(name = "Revoke token", skip_all)]
+pub async fn revoke_token(
+    state: &AppState,
+    token: SecretString,
+    session_id: Uuid,
+) -> Result<(), AuthAPIError> {
+    let mut banned_tokens = state.banned_tokens.write().await;
+    let already_banned = banned_tokens
+        .check_token(&token)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    if !already_banned {
+        banned_tokens
+            .add_token(token)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        metrics::counter!(TOKENS_BANNED_TOTAL).increment(1);
+    }
+    drop(banned_tokens);
+    end_session(state, session_id).await
+}
+
+// Forget a session whose token has already been banned
+pub async fn end_session(state: &AppState, session_id: Uuid) -> Result<(), AuthAPIError> {
+    match state
+        .sessions
+        .write()
+        .await
+        .remove_session(session_id)
+        .await
+    {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => Ok(()),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Revoke the access tokens `client_id` holds for the user. Expired ones are left alone.
+#[tracing::instrument(name = "End client sessions", skip_all)]
+pub async fn end_client_sessions(
+    state: &AppState,
+    user_id: UserId,
+    client_id: &str,
+) -> Result<(), AuthAPIError> {
+    let sessions = state
+        .sessions
+        .read()
+        .await
+        .get_sessions(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    for session in sessions {
+        if session.client_id.as_deref() == Some(client_id) {
+            revoke_session(state, session).await?;
+        }
+    }
+    Ok(())
+}
+
+// Invalidate every token issued to the user so far, and forget their sessions
+#[tracing::instrument(name = "End all sessions", skip_all)]
+pub async fn end_all_sessions(state: &AppState, user_id: UserId) -> Result<(), AuthAPIError> {
+    state
+        .user_store
+        .write()
+        .await
+        .bump_token_generation(user_id)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+    state
+        .sessions
+        .write()
+        .await
+        .remove_sessions(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(())
+}
@@ -1,32 +1,183 @@
-use axum::{body::Body, extract::Request, response::Response};
-use color_eyre::eyre::Result;
-use std::time::Duration;
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use opentelemetry::{
+    propagation::TextMapPropagator,
+    trace::{TraceContextExt, TraceId, TracerProvider},
+};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_sdk::{
+    error::{OTelSdkError, OTelSdkResult},
+    propagation::TraceContextPropagator,
+    trace::{SdkTracerProvider, SpanData, SpanExporter},
+    Resource,
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::OpenOptions,
+    io::{self, Write},
+    sync::Mutex,
+    time::Duration,
+};
 use tracing::{Level, Span};
 use tracing_error::ErrorLayer;
-use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    fmt::{self as tracing_fmt, MakeWriter},
+    prelude::*,
+    registry::LookupSpan,
+    EnvFilter, Layer,
+};
 
-pub fn init_tracing() -> Result<()> {
-    let fmt_layer = fmt::layer().compact();
+use crate::settings::{LogFormat, LoggingSettings, TraceExporter, TracingSettings};
+
+// Accepted from callers, generated otherwise, and echoed on every response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+// Longer caller-supplied ids are replaced, so a client cannot bloat every log line
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+// Returns the tracer provider when spans are exported, so the caller can flush it on shutdown
+pub fn init_tracing(
+    logging: &LoggingSettings,
+    tracing: &TracingSettings,
+) -> Result<Option<SdkTracerProvider>> {
     let filter_layer = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info"))?;
+    let provider = build_tracer_provider(tracing)?;
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("auth-service"))
+    });
     tracing_subscriber::registry()
         .with(filter_layer) // control log verbosity
-        .with(fmt_layer) // use compact log output format
+        .with(fmt_layer(logging.format, io::stdout)) // use the configured log output format
+        .with(otel_layer) // export spans, if an exporter is configured
         .with(ErrorLayer::default()) // capture error contexts
         .init();
 
-    Ok(())
+    Ok(provider)
+}
+
+pub fn fmt_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Compact => tracing_fmt::layer().compact().with_writer(writer).boxed(),
+        // Every line carries the fields of its enclosing spans, including the request id
+        LogFormat::Json => tracing_fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_writer(writer)
+            .boxed(),
+    }
+}
+
+pub fn build_tracer_provider(settings: &TracingSettings) -> Result<Option<SdkTracerProvider>> {
+    let builder = SdkTracerProvider::builder().with_resource(
+        Resource::builder()
+            .with_service_name(settings.service_name.clone())
+            .build(),
+    );
+    let provider = match settings.exporter {
+        TraceExporter::None => return Ok(None),
+        // Spans are written as they end, so nothing is lost if the process is killed
+        TraceExporter::Stdout => builder.with_simple_exporter(JsonSpanExporter::new(io::stdout())),
+        TraceExporter::File => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&settings.file_path)
+                .wrap_err_with(|| format!("failed to open trace file {}", settings.file_path))?;
+            builder.with_simple_exporter(JsonSpanExporter::new(file))
+        }
+        #[cfg(feature = "otlp")]
+        TraceExporter::Otlp => {
+            use opentelemetry_otlp::WithExportConfig;
+
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(&settings.otlp_endpoint)
+                .build()
+                .wrap_err("failed to build OTLP span exporter")?;
+            builder.with_batch_exporter(exporter)
+        }
+        #[allow(unreachable_patterns)]
+        exporter => color_eyre::eyre::bail!(
+            "the {:?} trace exporter was not compiled into this build",
+            exporter
+        ),
+    };
+    Ok(Some(provider.build()))
+}
+
+// Make sure every request carries a usable X-Request-Id before it is traced, and echo it back
+pub async fn propagate_request_id(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .filter(|value| is_valid_request_id(value))
+        .cloned()
+        .unwrap_or_else(|| {
+            HeaderValue::from_str(&uuid::Uuid::new_v4().to_string())
+                .expect("a UUID is a valid header value")
+        });
+    let header = HeaderName::from_static(REQUEST_ID_HEADER);
+    request
+        .headers_mut()
+        .insert(header.clone(), request_id.clone());
+
+    let mut response = next.run(request).await;
+    response.headers_mut().insert(header, request_id);
+    response
+}
+
+// Only short ids made of URL-safe characters are accepted, so they can be logged verbatim
+fn is_valid_request_id(value: &HeaderValue) -> bool {
+    let bytes = value.as_bytes();
+    !bytes.is_empty()
+        && bytes.len() <= MAX_REQUEST_ID_LENGTH
+        && bytes
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
 }
 
 pub fn make_span_with_request_id(request: &Request<Body>) -> Span {
-    let request_id = uuid::Uuid::new_v4();
-    tracing::span!(
+    // Set by `propagate_request_id`, which runs before this
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let span = tracing::span!(
         Level::INFO,
         "[REQUEST]",
         method = tracing::field::display(request.method()),
-        uri = tracing::field::display(request.uri()),
+        // The path alone, as query strings can carry credentials such as an `id_token_hint`
+        uri = tracing::field::display(request.uri().path()),
         version = tracing::field::debug(request.version()),
         request_id = tracing::field::display(request_id),
-    )
+        trace_id = tracing::field::Empty,
+    );
+
+    // Continue the caller's trace when it sent a W3C `traceparent` header.
+    // This only fails when spans are not exported, in which case there is nothing to link.
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(request.headers()));
+    let _ = span.set_parent(parent);
+    let trace_id = span.context().span().span_context().trace_id();
+    if trace_id != TraceId::INVALID {
+        span.record("trace_id", tracing::field::display(trace_id));
+    }
+    span
 }
 
 pub fn on_request(_request: &Request<Body>, _span: &Span) {
@@ -57,3 +208,259 @@ pub fn on_response(response: &Response, latency: Duration, _span: &Span) {
         }
     };
 }
+
+// Writes every finished span as one JSON line, so traces can be inspected without a collector
+pub struct JsonSpanExporter {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonSpanExporter {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+}
+
+impl fmt::Debug for JsonSpanExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("JsonSpanExporter")
+    }
+}
+
+#[derive(Serialize)]
+struct JsonSpan {
+    name: String,
+    trace_id: String,
+    span_id: String,
+    // None for the root span of a trace
+    parent_span_id: Option<String>,
+    kind: String,
+    start_time: String,
+    duration_ms: f64,
+    status: String,
+    attributes: BTreeMap<String, String>,
+}
+
+impl From<&SpanData> for JsonSpan {
+    fn from(span: &SpanData) -> Self {
+        let parent_span_id = match span.parent_span_id {
+            opentelemetry::trace::SpanId::INVALID => None,
+            parent => Some(parent.to_string()),
+        };
+        let duration = span
+            .end_time
+            .duration_since(span.start_time)
+            .unwrap_or_default();
+        Self {
+            name: span.name.to_string(),
+            trace_id: span.span_context.trace_id().to_string(),
+            span_id: span.span_context.span_id().to_string(),
+            parent_span_id,
+            kind: format!("{:?}", span.span_kind),
+            start_time: DateTime::<Utc>::from(span.start_time).to_rfc3339(),
+            duration_ms: duration.as_secs_f64() * 1_000.0,
+            status: format!("{:?}", span.status),
+            attributes: span
+                .attributes
+                .iter()
+                .map(|kv| (kv.key.to_string(), kv.value.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl SpanExporter for JsonSpanExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let mut writer = self
+            .writer
+            .lock()
+            .map_err(|_| OTelSdkError::InternalFailure("span writer lock poisoned".to_owned()))?;
+        for span in &batch {
+            let mut line = serde_json::to_vec(&JsonSpan::from(span))
+                .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+            line.push(b'\n');
+            writer
+                .write_all(&line)
+                .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+        }
+        writer
+            .flush()
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf};
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+    // Handle a request with spans exported to a temporary file, and return the exported spans
+    fn export_request_span(request: Request<Body>) -> Vec<serde_json::Value> {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("auth-spans-{}.jsonl", uuid::Uuid::new_v4()));
+        let file = fs::File::create(&path).unwrap();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(JsonSpanExporter::new(file))
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = make_span_with_request_id(&request);
+            span.in_scope(|| tracing::info!("handling request"));
+        });
+        provider.shutdown().unwrap();
+
+        let spans = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        fs::remove_file(&path).unwrap();
+        spans
+    }
+
+    #[test]
+    fn should_continue_incoming_trace() {
+        let request = Request::builder()
+            .uri("/verify-token")
+            .header(
+                "traceparent",
+                format!("00-{}-{}-01", TRACE_ID, PARENT_SPAN_ID),
+            )
+            .body(Body::empty())
+            .unwrap();
+
+        let spans = export_request_span(request);
+
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0]["name"], "[REQUEST]");
+        assert_eq!(spans[0]["trace_id"], TRACE_ID);
+        assert_eq!(spans[0]["parent_span_id"], PARENT_SPAN_ID);
+        assert_eq!(spans[0]["attributes"]["trace_id"], TRACE_ID);
+    }
+
+    #[test]
+    fn should_start_new_trace_without_traceparent() {
+        let request = Request::builder()
+            .uri("/verify-token")
+            .body(Body::empty())
+            .unwrap();
+
+        let spans = export_request_span(request);
+
+        assert_eq!(spans.len(), 1);
+        assert_ne!(spans[0]["trace_id"], TRACE_ID);
+        assert_eq!(spans[0]["parent_span_id"], serde_json::Value::Null);
+        assert_eq!(spans[0]["attributes"]["trace_id"], spans[0]["trace_id"]);
+    }
+
+    #[test]
+    fn should_not_record_query_string() {
+        let request = Request::builder()
+            .uri("/end-session?id_token_hint=secret-hint&state=abc")
+            .body(Body::empty())
+            .unwrap();
+
+        let spans = export_request_span(request);
+
+        assert_eq!(spans[0]["attributes"]["uri"], "/end-session");
+    }
+
+    #[test]
+    fn should_accept_only_short_url_safe_request_ids() {
+        let accepted = ["f81d4fae-7dec-11d0-a765-00a0c91e6bf6", "req_42.retry:1"];
+        let rejected = ["", "has space", "new\nline", "caf\u{e9}", &"a".repeat(129)];
+
+        for id in accepted {
+            assert!(
+                is_valid_request_id(&HeaderValue::from_str(id).unwrap()),
+                "{}",
+                id
+            );
+        }
+        for id in rejected {
+            let value =
+                HeaderValue::from_bytes(id.as_bytes()).unwrap_or(HeaderValue::from_static(""));
+            assert!(!is_valid_request_id(&value), "{:?}", id);
+        }
+    }
+
+    // Collects everything a fmt layer writes
+    #[derive(Clone, Default)]
+    struct CapturedLogs(std::sync::Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for CapturedLogs {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn should_not_log_secrets_in_json_logs() {
+        use crate::{
+            domain::{Email, EmailClient, LoginAttemptId, TwoFACode},
+            services::data_stores::mock_email_client::MockEmailClient,
+        };
+        use secrecy::{ExposeSecret, SecretString};
+
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(fmt_layer(LogFormat::Json, logs.clone()))
+            .with(EnvFilter::new("trace"));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let code = TwoFACode::default();
+        let login_attempt_id = LoginAttemptId::default();
+        let password = SecretString::from("correct horse battery staple");
+
+        let span = tracing::info_span!("[REQUEST]", request_id = "req-1");
+        let _entered = span.enter();
+        tracing::info!(?code, ?login_attempt_id, ?password, "handling secrets");
+        MockEmailClient
+            .send_email(
+                &Email::parse("user@example.com".to_owned()).unwrap(),
+                "2FA Code",
+                code.as_ref().expose_secret(),
+            )
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines
+            .iter()
+            .all(|line| line["span"]["request_id"] == "req-1"));
+        assert_eq!(lines[1]["content_length"], 6);
+        assert!(!output.contains(code.as_ref().expose_secret()));
+        assert!(!output.contains(login_attempt_id.as_ref().expose_secret()));
+        assert!(!output.contains(password.expose_secret()));
+    }
+
+    #[test]
+    fn should_not_export_spans_without_exporter() {
+        let provider = build_tracer_provider(&TracingSettings::default()).unwrap();
+
+        assert!(provider.is_none());
+    }
+}
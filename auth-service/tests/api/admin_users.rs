@@ -0,0 +1,720 @@
+use auth_service::{
+    domain::{AccountStatus, AuditEventKind, AuditOutcome, UserId},
+    routes::{
+        admin_users::{UserDetailsResponse, UsersResponse},
+        api_keys::CreateApiKeyResponse,
+        login::TwoFactorAuthResponse,
+    },
+    utils::{
+        admin::ADMIN_TOKEN_IDENTITY,
+        constants::{test::ADMIN_TOKEN, JWT_COOKIE_NAME},
+    },
+    ErrorResponse,
+};
+use secrecy::ExposeSecret;
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+// Sign up a user and return their email
+async fn signup(app: &TestApp, requires_2fa: bool) -> String {
+    let email = TestApp::get_random_email();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": requires_2fa
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    email
+}
+
+// Log in without 2FA and return the token that was issued
+async fn login(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    auth_cookie.value().to_owned()
+}
+
+async fn token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+// The login attempt id and code that were last sent to the user
+async fn pending_code(app: &TestApp, email: &str) -> Option<(String, String)> {
+    let (id, code) = app
+        .two_fa_codes
+        .read()
+        .await
+        .get_code(app.user_id(email).await)
+        .await
+        .ok()?;
+    Some((
+        id.as_ref().expose_secret().to_owned(),
+        code.as_ref().expose_secret().to_owned(),
+    ))
+}
+
+// The emails of the users that match the query
+async fn search(app: &TestApp, query: &[(&str, &str)]) -> Vec<String> {
+    let response = app.get_admin_users(query, Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: UsersResponse = response.json().await.expect("Failed to parse users");
+    body.users.into_iter().map(|user| user.email).collect()
+}
+
+async fn user_details(app: &TestApp, email: &str) -> UserDetailsResponse {
+    let response = app.get_admin_user(email, Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.expect("Failed to parse user")
+}
+
+#[api_test]
+async fn should_search_users() {
+    let mut emails = Vec::new();
+    for _ in 0..3 {
+        emails.push(signup(&app, false).await);
+    }
+    emails.sort();
+
+    assert_eq!(search(&app, &[]).await, emails);
+    assert_eq!(search(&app, &[("limit", "2")]).await, emails[..2]);
+    assert_eq!(
+        search(&app, &[("limit", "2"), ("offset", "2")]).await,
+        emails[2..]
+    );
+    // Matched case-insensitively anywhere in the email
+    let part = emails[1][4..12].to_uppercase();
+    assert_eq!(search(&app, &[("query", &part)]).await, emails[1..2]);
+    assert!(search(&app, &[("query", "nobody")]).await.is_empty());
+
+    for query in [[("limit", "0")], [("limit", "201")], [("offset", "-1")]] {
+        let response = app.get_admin_users(&query, Some(ADMIN_TOKEN)).await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for {:?}", query);
+    }
+}
+
+#[api_test]
+async fn should_return_user_details() {
+    let email = signup(&app, true).await;
+    let details = user_details(&app, &email).await;
+    assert_eq!(details.user.email, email);
+    assert!(details.user.requires_2fa);
+    assert!(!details.user.disabled);
+    assert!(!details.user.password_reset_required);
+    assert!(details.roles.is_empty());
+    assert_eq!((details.active_sessions, details.api_keys), (0, 0));
+
+    let email = signup(&app, false).await;
+    app.put_admin_role("viewer", &["reports:read"], Some(ADMIN_TOKEN))
+        .await;
+    app.put_admin_user_role(&email, "viewer", Some(ADMIN_TOKEN))
+        .await;
+    login(&app, &email).await;
+    app.post_api_keys(&serde_json::json!({ "name": "deploy" }))
+        .await;
+
+    let details = user_details(&app, &email).await;
+    assert_eq!(details.roles, ["viewer"]);
+    assert_eq!((details.active_sessions, details.api_keys), (1, 1));
+}
+
+#[api_test]
+async fn should_look_up_users_by_id() {
+    let email = signup(&app, false).await;
+    let id = app.user_id(&email).await;
+
+    let details = user_details(&app, &id.to_string()).await;
+    assert_eq!(details.user.id, id);
+    assert_eq!(details.user.email, email);
+
+    let response = app
+        .post_admin_user_action(&id.to_string(), "disable", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    assert!(user_details(&app, &email).await.user.disabled);
+
+    let response = app
+        .get_admin_user(&UserId::new().to_string(), Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[api_test]
+async fn should_disable_and_enable_users() {
+    let email = signup(&app, false).await;
+    let token = login(&app, &email).await;
+
+    let response = app
+        .post_admin_user_action(&email, "disable", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    assert!(user_details(&app, &email).await.user.disabled);
+    assert_eq!(token_status(&app, &token).await, 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    // A wrong password is still just a wrong password
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "wrong-password" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_admin_user_action(&email, "enable", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    let token = login(&app, &email).await;
+    assert_eq!(token_status(&app, &token).await, 200);
+
+    let events: Vec<_> = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .filter(|entry| {
+            matches!(
+                entry.event.kind,
+                AuditEventKind::AdminDisableUser | AuditEventKind::AdminEnableUser
+            )
+        })
+        .map(|entry| (entry.event.kind, entry.event.admin, entry.event.actor))
+        .collect();
+    let admin = Some(ADMIN_TOKEN_IDENTITY.to_owned());
+    assert_eq!(
+        events,
+        [
+            (
+                AuditEventKind::AdminDisableUser,
+                admin.clone(),
+                Some(email.clone())
+            ),
+            (AuditEventKind::AdminEnableUser, admin, Some(email.clone())),
+        ]
+    );
+}
+
+#[api_test]
+async fn should_reject_codes_of_disabled_users() {
+    let email = signup(&app, true).await;
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let (id, code) = pending_code(&app, &email).await.unwrap();
+
+    app.post_admin_user_action(&email, "disable", Some(ADMIN_TOKEN))
+        .await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": id,
+            "2FACode": code
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[api_test]
+async fn should_force_password_reset() {
+    let email = signup(&app, true).await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    let (old_id, old_code) = pending_code(&app, &email).await.unwrap();
+
+    let response = app
+        .post_admin_user_action(&email, "password-reset", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    assert!(
+        user_details(&app, &email)
+            .await
+            .user
+            .password_reset_required
+    );
+
+    // Codes sent before the reset was forced are discarded
+    let verify_request = serde_json::json!({
+        "email": email,
+        "loginAttemptId": old_id,
+        "2FACode": old_code
+    });
+    let response = app.post_verify_2fa(&verify_request).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": old_id,
+            "2FACode": old_code,
+            "newPassword": "new-password123"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The old password only gets the user a code for the reset
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let body: TwoFactorAuthResponse = response.json().await.expect("Failed to parse response");
+    assert_eq!(body.message, "Password reset required");
+    let (id, code) = pending_code(&app, &email).await.unwrap();
+    assert_eq!(body.login_attempt_id, id);
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": id,
+            "2FACode": code
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": id,
+            "2FACode": code,
+            "newPassword": "short"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let reset_request = serde_json::json!({
+        "email": email,
+        "loginAttemptId": id,
+        "2FACode": code,
+        "newPassword": "new-password123"
+    });
+    let response = app.post_reset_password(&reset_request).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        !user_details(&app, &email)
+            .await
+            .user
+            .password_reset_required
+    );
+    // The code is used up
+    let response = app.post_reset_password(&reset_request).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "new-password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(pending_code(&app, &email).await.is_some());
+
+    let resets: Vec<_> = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .filter(|entry| entry.event.kind == AuditEventKind::ResetPassword)
+        .map(|entry| entry.event.outcome)
+        .collect();
+    assert_eq!(
+        resets,
+        [
+            AuditOutcome::Failure,
+            AuditOutcome::Failure,
+            AuditOutcome::Success,
+            AuditOutcome::Failure
+        ]
+    );
+}
+
+#[api_test]
+async fn should_only_reset_passwords_when_required() {
+    let email = signup(&app, true).await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    let (id, code) = pending_code(&app, &email).await.unwrap();
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": id,
+            "2FACode": code,
+            "newPassword": "new-password123"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    // The code can still be used for the login it was sent for
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": id,
+            "2FACode": code
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_reset_and_disable_2fa() {
+    let email = signup(&app, true).await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert!(pending_code(&app, &email).await.is_some());
+
+    let response = app
+        .post_admin_user_action(&email, "2fa/reset", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    assert!(pending_code(&app, &email).await.is_none());
+    assert!(user_details(&app, &email).await.user.requires_2fa);
+    // Resetting again is fine, with no code pending
+    let response = app
+        .post_admin_user_action(&email, "2fa/reset", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app
+        .post_admin_user_action(&email, "2fa/disable", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    assert!(!user_details(&app, &email).await.user.requires_2fa);
+    login(&app, &email).await;
+}
+
+#[api_test]
+async fn should_revoke_user_sessions() {
+    let email = signup(&app, false).await;
+    let first = login(&app, &email).await;
+    let second = login(&app, &email).await;
+
+    let response = app
+        .delete_admin_user_sessions(&email, Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(token_status(&app, &first).await, 401);
+    assert_eq!(token_status(&app, &second).await, 401);
+    assert_eq!(user_details(&app, &email).await.active_sessions, 0);
+
+    let token = login(&app, &email).await;
+    assert_eq!(token_status(&app, &token).await, 200);
+}
+
+#[api_test]
+async fn should_delete_users_and_everything_kept_for_them() {
+    let email = signup(&app, false).await;
+    app.put_admin_role("viewer", &["reports:read"], Some(ADMIN_TOKEN))
+        .await;
+    app.put_admin_user_role(&email, "viewer", Some(ADMIN_TOKEN))
+        .await;
+    let token = login(&app, &email).await;
+    let key: CreateApiKeyResponse = app
+        .post_api_keys(&serde_json::json!({ "name": "deploy" }))
+        .await
+        .json()
+        .await
+        .expect("Failed to parse API key");
+
+    let response = app.delete_admin_user(&email, Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app.get_admin_user(&email, Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 404);
+    let response = app.delete_admin_user(&email, Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    // Someone signing up with the same email starts from scratch
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    assert_eq!(token_status(&app, &token).await, 401);
+    assert_eq!(
+        app.post_verify_api_key(&key.key).await.status().as_u16(),
+        401
+    );
+    let details = user_details(&app, &email).await;
+    assert!(details.roles.is_empty());
+    assert_eq!((details.active_sessions, details.api_keys), (0, 0));
+}
+
+#[api_test]
+async fn should_return_404_for_unknown_users() {
+    let unknown = TestApp::get_random_email();
+    let statuses = [
+        app.get_admin_user(&unknown, Some(ADMIN_TOKEN))
+            .await
+            .status()
+            .as_u16(),
+        app.delete_admin_user(&unknown, Some(ADMIN_TOKEN))
+            .await
+            .status()
+            .as_u16(),
+        app.post_admin_user_action(&unknown, "disable", Some(ADMIN_TOKEN))
+            .await
+            .status()
+            .as_u16(),
+        app.delete_admin_user_sessions(&unknown, Some(ADMIN_TOKEN))
+            .await
+            .status()
+            .as_u16(),
+    ];
+    assert_eq!(statuses, [404; 4]);
+
+    let response = app
+        .post_admin_user_action("not-an-email", "enable", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_reject_user_routes_without_valid_admin_token() {
+    let email = signup(&app, false).await;
+
+    for admin_token in [None, Some("not-the-admin-token")] {
+        let expected = if admin_token.is_some() { 401 } else { 400 };
+        let statuses = [
+            app.get_admin_users(&[], admin_token)
+                .await
+                .status()
+                .as_u16(),
+            app.get_admin_user(&email, admin_token)
+                .await
+                .status()
+                .as_u16(),
+            app.delete_admin_user(&email, admin_token)
+                .await
+                .status()
+                .as_u16(),
+            app.post_admin_user_action(&email, "disable", admin_token)
+                .await
+                .status()
+                .as_u16(),
+            app.delete_admin_user_sessions(&email, admin_token)
+                .await
+                .status()
+                .as_u16(),
+        ];
+        assert_eq!(statuses, [expected; 5], "Failed for {:?}", admin_token);
+    }
+    assert!(!user_details(&app, &email).await.user.disabled);
+}
+
+#[api_test]
+async fn should_accept_users_with_the_admin_permission() {
+    let admin = signup(&app, false).await;
+    let email = signup(&app, false).await;
+    app.put_admin_role("operators", &["admin"], Some(ADMIN_TOKEN))
+        .await;
+    app.put_admin_user_role(&admin, "operators", Some(ADMIN_TOKEN))
+        .await;
+    let admin_token = login(&app, &admin).await;
+    let user_token = login(&app, &email).await;
+
+    // Valid tokens without the permission are forbidden
+    let response = app.get_admin_users(&[], Some(&user_token)).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app
+        .post_admin_user_action(&admin, "disable", Some(&user_token))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app.get_admin_users(&[], Some(&admin_token)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .post_admin_user_action(&email, "disable", Some(&admin_token))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let events: Vec<_> = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .filter(|entry| entry.event.kind == AuditEventKind::AdminDisableUser)
+        .map(|entry| (entry.event.admin, entry.event.actor, entry.event.outcome))
+        .collect();
+    // Admins are recorded by id, which stays the same when their email changes
+    let admin_id = app.user_id(&admin).await.to_string();
+    assert_eq!(
+        events,
+        [
+            (None, Some(admin.clone()), AuditOutcome::Failure),
+            (Some(admin_id), Some(email), AuditOutcome::Success),
+        ]
+    );
+}
+
+// The status and message of a login with the right password
+async fn login_error(app: &TestApp, email: &str) -> (u16, String) {
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    let status = response.status().as_u16();
+    let body: ErrorResponse = response.json().await.expect("Failed to parse error");
+    (status, body.error)
+}
+
+#[api_test]
+async fn should_keep_users_pending_verification_out() {
+    let email = signup(&app, false).await;
+
+    let response = app
+        .put_admin_user_status(
+            &email,
+            &serde_json::json!({ "status": "pending_verification", "reason": "Awaiting ID check" }),
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    let details = user_details(&app, &email).await;
+    assert_eq!(details.user.status, AccountStatus::PendingVerification);
+    assert_eq!(
+        details.user.status_reason.as_deref(),
+        Some("Awaiting ID check")
+    );
+    assert!(!details.user.disabled);
+    assert_eq!(
+        login_error(&app, &email).await,
+        (403, "Account pending verification".to_owned())
+    );
+
+    app.post_admin_user_action(&email, "enable", Some(ADMIN_TOKEN))
+        .await;
+    let details = user_details(&app, &email).await;
+    assert_eq!(details.user.status, AccountStatus::Active);
+    assert_eq!(details.user.status_reason, None);
+    login(&app, &email).await;
+}
+
+#[api_test]
+async fn should_restore_users_scheduled_for_deletion() {
+    let email = signup(&app, false).await;
+    let token = login(&app, &email).await;
+    app.post_api_keys(&serde_json::json!({ "name": "deploy" }))
+        .await;
+    let before = user_details(&app, &email).await.user.status_changed_at;
+
+    let response = app
+        .put_admin_user_status(
+            &email,
+            &serde_json::json!({ "status": "scheduled_for_deletion" }),
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    let details = user_details(&app, &email).await;
+    assert_eq!(details.user.status, AccountStatus::ScheduledForDeletion);
+    assert!(details.user.status_changed_at > before);
+    assert_eq!(token_status(&app, &token).await, 401);
+    assert_eq!(
+        login_error(&app, &email).await,
+        (403, "Account scheduled for deletion".to_owned())
+    );
+
+    // Nothing is gone until the account is purged
+    app.post_admin_user_action(&email, "enable", Some(ADMIN_TOKEN))
+        .await;
+    let token = login(&app, &email).await;
+    assert_eq!(token_status(&app, &token).await, 200);
+    assert_eq!(user_details(&app, &email).await.api_keys, 1);
+
+    let events: Vec<_> = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .filter(|entry| entry.event.kind == AuditEventKind::AdminSetUserStatus)
+        .map(|entry| (entry.event.actor, entry.event.reason))
+        .collect();
+    assert_eq!(
+        events,
+        [(Some(email), Some("scheduled_for_deletion".to_owned()))]
+    );
+}
+
+#[api_test]
+async fn should_record_why_users_were_disabled() {
+    let email = signup(&app, false).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/admin/users/{}/disable", &app.address, email))
+        .bearer_auth(ADMIN_TOKEN)
+        .json(&serde_json::json!({ "reason": "Chargeback" }))
+        .send()
+        .await
+        .expect("failed to execute request.");
+    assert_eq!(response.status().as_u16(), 204);
+
+    let details = user_details(&app, &email).await;
+    assert_eq!(details.user.status, AccountStatus::Disabled);
+    assert_eq!(details.user.status_reason.as_deref(), Some("Chargeback"));
+    assert!(details.user.disabled);
+}
+
+#[api_test]
+async fn should_reject_unknown_statuses() {
+    let email = signup(&app, false).await;
+
+    let response = app
+        .put_admin_user_status(
+            &email,
+            &serde_json::json!({ "status": "deleted" }),
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+    let response = app
+        .put_admin_user_status(&email, &serde_json::json!({ "status": "disabled" }), None)
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        user_details(&app, &email).await.user.status,
+        AccountStatus::Active
+    );
+}
+
+#[api_test]
+async fn should_change_emails_of_users() {
+    let email = signup(&app, false).await;
+    let other = signup(&app, false).await;
+    let token = login(&app, &email).await;
+    let new_email = TestApp::get_random_email();
+
+    let response = app
+        .put_admin_user_email(&email, &new_email, Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    // Tokens name the user by id, so they keep working
+    assert_eq!(token_status(&app, &token).await, 200);
+    let response = app.get_admin_user(&email, Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 404);
+    login(&app, &new_email.to_uppercase()).await;
+
+    // Emails stay unique, ignoring case
+    let response = app
+        .put_admin_user_email(&new_email, &other.to_uppercase(), Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+    let response = app
+        .put_admin_user_email(&new_email, "not-an-email", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let entries = app.audit_entries().await;
+    assert!(entries.iter().any(|entry| {
+        entry.event.kind == AuditEventKind::AdminChangeEmail
+            && entry.event.actor.as_deref() == Some(email.as_str())
+            && entry.event.outcome == AuditOutcome::Success
+    }));
+}
@@ -0,0 +1,242 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::{
+        api_keys::{ApiKeysResponse, CreateApiKeyResponse},
+        introspect::IntrospectResponse,
+        verify_token::ValidateTokenResponse,
+    },
+    utils::constants::test::{CLIENT_ID, CLIENT_SECRET},
+};
+use chrono::{Duration, Utc};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+// Sign up and log in a user without 2FA, returning their email
+async fn login(app: &TestApp) -> String {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    email
+}
+
+async fn create_api_key(app: &TestApp, body: &serde_json::Value) -> CreateApiKeyResponse {
+    let response = app.post_api_keys(body).await;
+    assert_eq!(response.status().as_u16(), 201);
+    response
+        .json()
+        .await
+        .expect("Could not deserialize response body to CreateApiKeyResponse")
+}
+
+async fn api_keys(app: &TestApp) -> ApiKeysResponse {
+    let response = app.get_api_keys().await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json()
+        .await
+        .expect("Could not deserialize response body to ApiKeysResponse")
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app
+        .post_api_keys(&serde_json::json!({ "name": "deploy" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_show_key_once_and_list_it_hidden() {
+    login(&app).await;
+
+    let created = create_api_key(
+        &app,
+        &serde_json::json!({ "name": "deploy", "scope": "reports:read reports:read" }),
+    )
+    .await;
+
+    assert!(created.key.starts_with("ak_"));
+    assert!(created.key.starts_with(&created.api_key.hint));
+    assert_eq!(created.api_key.scope.as_deref(), Some("reports:read"));
+    assert_eq!(created.api_key.expires_at, None);
+    let response = app.get_api_keys().await;
+    let body = response.text().await.unwrap();
+    assert!(!body.contains(&created.key));
+    let keys: ApiKeysResponse = serde_json::from_str(&body).unwrap();
+    assert_eq!(keys.api_keys.len(), 1);
+    assert_eq!(keys.api_keys[0].id, created.api_key.id);
+    assert_eq!(keys.api_keys[0].name, "deploy");
+    assert_eq!(keys.api_keys[0].last_used_at, None);
+}
+
+#[api_test]
+async fn should_return_400_for_invalid_key_requests() {
+    login(&app).await;
+    let test_cases = [
+        serde_json::json!({ "name": " " }),
+        serde_json::json!({ "name": "deploy", "scope": "reports\\read" }),
+        serde_json::json!({
+            "name": "deploy",
+            "expiresAt": Utc::now() - Duration::minutes(1)
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_api_keys(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[api_test]
+async fn should_verify_api_key_and_record_its_use() {
+    login(&app).await;
+    let created = create_api_key(&app, &serde_json::json!({ "name": "deploy" })).await;
+
+    let response = app.post_verify_api_key(&created.key).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": created.key }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.post_verify_api_key("ak_unknown").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let keys = api_keys(&app).await.api_keys;
+    assert!(keys[0].last_used_at.is_some());
+}
+
+#[api_test]
+async fn should_describe_api_key_owner_when_verifying() {
+    let email = login(&app).await;
+    let created = create_api_key(
+        &app,
+        &serde_json::json!({ "name": "reports", "scope": "reports:read" }),
+    )
+    .await;
+
+    let response = app.post_verify_api_key(&created.key).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: ValidateTokenResponse = response.json().await.unwrap();
+    assert_eq!(body.sub, app.user_id(&email).await.to_string());
+    assert_eq!(body.email, Some(email));
+    assert_eq!(body.scope.as_deref(), Some("reports:read"));
+    assert_eq!((body.sid, body.exp), (None, None));
+    // API keys carry none of their owner's permissions
+    assert!(body.permissions.is_empty());
+}
+
+#[api_test]
+async fn should_introspect_api_key_like_user_token() {
+    let email = login(&app).await;
+    let expires_at = Utc::now() + Duration::days(30);
+    let created = create_api_key(
+        &app,
+        &serde_json::json!({
+            "name": "reports",
+            "scope": "reports:read",
+            "expiresAt": expires_at
+        }),
+    )
+    .await;
+
+    let response = app
+        .post_introspect(
+            &[("token", created.key.as_str())],
+            Some((CLIENT_ID, CLIENT_SECRET)),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let response: IntrospectResponse = response.json().await.unwrap();
+    assert!(response.active);
+    assert_eq!(response.sub, Some(app.user_id(&email).await.to_string()));
+    assert_eq!(response.username, Some(email));
+    assert_eq!(response.scope.as_deref(), Some("reports:read"));
+    assert_eq!(response.token_type.as_deref(), Some("ApiKey"));
+    assert_eq!(response.exp, Some(expires_at.timestamp() as usize));
+    assert_eq!(response.client_id, None);
+}
+
+#[api_test]
+async fn should_reject_revoked_api_key() {
+    let email = login(&app).await;
+    let created = create_api_key(&app, &serde_json::json!({ "name": "deploy" })).await;
+    let id = created.api_key.id.to_string();
+
+    let response = app.delete_api_key(&id).await;
+
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app.post_verify_api_key(&created.key).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(api_keys(&app).await.api_keys.is_empty());
+    let response = app.delete_api_key(&id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let entries = app.audit_entries().await;
+    let outcomes: Vec<_> = entries
+        .iter()
+        .filter(|entry| {
+            [AuditEventKind::CreateApiKey, AuditEventKind::RevokeApiKey].contains(&entry.event.kind)
+        })
+        .map(|entry| (entry.event.kind, entry.event.outcome))
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            (AuditEventKind::CreateApiKey, AuditOutcome::Success),
+            (AuditEventKind::RevokeApiKey, AuditOutcome::Success),
+            (AuditEventKind::RevokeApiKey, AuditOutcome::Failure),
+        ]
+    );
+    assert!(entries
+        .iter()
+        .filter(|entry| entry.event.kind == AuditEventKind::CreateApiKey)
+        .all(|entry| entry.event.actor.as_deref() == Some(email.as_str())));
+}
+
+#[api_test]
+async fn should_return_404_when_revoking_key_of_other_user() {
+    login(&app).await;
+    let created = create_api_key(&app, &serde_json::json!({ "name": "deploy" })).await;
+    login(&app).await;
+
+    let response = app.delete_api_key(&created.api_key.id.to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    let response = app.post_verify_api_key(&created.key).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_not_accept_api_key_to_manage_keys() {
+    login(&app).await;
+    let created = create_api_key(&app, &serde_json::json!({ "name": "deploy" })).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/api-keys", &app.address))
+        .bearer_auth(&created.key)
+        .json(&serde_json::json!({ "name": "another" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
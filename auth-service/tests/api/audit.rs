@@ -0,0 +1,106 @@
+use auth_service::domain::{AuditEventKind, AuditOutcome, ChainVerification};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+#[api_test]
+async fn should_record_signup_and_login_attempts() {
+    let email = TestApp::get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    app.post_signup(&signup_body).await;
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header("User-Agent", "audit-test/1.0")
+        .json(&serde_json::json!({ "email": email, "password": "wrong-password" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 400);
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+
+    let entries = app.audit_entries().await;
+
+    let events: Vec<_> = entries
+        .iter()
+        .map(|entry| (entry.event.kind, entry.event.outcome))
+        .collect();
+    assert_eq!(
+        events,
+        [
+            (AuditEventKind::Signup, AuditOutcome::Success),
+            (AuditEventKind::Login, AuditOutcome::Failure),
+            (AuditEventKind::Login, AuditOutcome::Success),
+        ]
+    );
+    let failed_login = &entries[1].event;
+    assert_eq!(failed_login.actor.as_deref(), Some(email.as_str()));
+    assert_eq!(failed_login.ip, Some("127.0.0.1".parse().unwrap()));
+    assert_eq!(failed_login.user_agent.as_deref(), Some("audit-test/1.0"));
+    assert_eq!(failed_login.reason.as_deref(), Some("invalid_credentials"));
+}
+
+#[api_test]
+async fn should_record_logout_for_token_owner() {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+
+    app.post_logout().await;
+    app.post_logout().await;
+
+    let entries = app.audit_entries().await;
+    let logouts: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.event.kind == AuditEventKind::Logout)
+        .map(|entry| (entry.event.actor.as_deref(), entry.event.outcome))
+        .collect();
+    assert_eq!(
+        logouts,
+        [
+            (Some(email.as_str()), AuditOutcome::Success),
+            (None, AuditOutcome::Failure),
+        ]
+    );
+}
+
+#[api_test]
+async fn should_keep_audit_chain_intact() {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    }))
+    .await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": "not-a-uuid",
+        "2FACode": "123456"
+    }))
+    .await;
+
+    let verification = app.audit_log.read().await.verify().await.unwrap();
+
+    assert!(
+        matches!(verification, ChainVerification::Intact { entries: 3, .. }),
+        "{:?}",
+        verification
+    );
+    let entries = app.audit_entries().await;
+    assert_eq!(entries[1].event.reason.as_deref(), Some("2fa_required"));
+    assert_eq!(entries[2].event.kind, AuditEventKind::Verify2FA);
+}
@@ -0,0 +1,271 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    utils::{
+        constants::{test::ADMIN_TOKEN, JWT_COOKIE_NAME},
+        oauth::code_challenge,
+    },
+    OAuthErrorResponse,
+};
+use reqwest::header::LOCATION;
+use std::collections::HashMap;
+use test_helpers::api_test;
+use url::Url;
+
+use crate::helpers::TestApp;
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+// Sign up and log in a user without 2FA, returning their email and token
+async fn login(app: &TestApp) -> (String, String) {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    let token = auth_cookie.value().to_owned();
+    (email, token)
+}
+
+fn location(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get(LOCATION)
+        .expect("No redirect")
+        .to_str()
+        .expect("Invalid redirect")
+        .to_owned()
+}
+
+// The parameters the client was redirected back with
+fn redirect_params(response: &reqwest::Response) -> HashMap<String, String> {
+    let url = Url::parse(&location(response)).expect("Redirect is not absolute");
+    assert!(url.as_str().starts_with(REDIRECT_URI));
+    url.query_pairs().into_owned().collect()
+}
+
+fn query<'a>(client_id: &'a str, challenge: &'a str) -> Vec<(&'a str, &'a str)> {
+    vec![
+        ("response_type", "code"),
+        ("client_id", client_id),
+        ("redirect_uri", REDIRECT_URI),
+        ("code_challenge", challenge),
+        ("code_challenge_method", "S256"),
+        ("state", "xyz 123"),
+    ]
+}
+
+#[api_test]
+async fn should_redirect_to_client_with_code_and_state() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let (email, _) = login(&app).await;
+    let challenge = code_challenge(VERIFIER);
+
+    let response = app.get_authorize(&query(&client_id, &challenge)).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    let params = redirect_params(&response);
+    assert_eq!(params.get("code").map(String::len), Some(43));
+    assert_eq!(params.get("state").map(String::as_str), Some("xyz 123"));
+
+    let authorize = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .find(|entry| entry.event.kind == AuditEventKind::Authorize)
+        .expect("No authorize event");
+    assert_eq!(authorize.event.outcome, AuditOutcome::Success);
+    assert_eq!(authorize.event.actor.as_deref(), Some(email.as_str()));
+}
+
+#[api_test]
+async fn should_redirect_to_login_page_when_not_logged_in() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let challenge = code_challenge(VERIFIER);
+
+    let response = app.get_authorize(&query(&client_id, &challenge)).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    let login_page = Url::parse(&format!("{}{}", app.address, location(&response))).unwrap();
+    assert_eq!(login_page.path(), "/");
+    let (name, next) = login_page.query_pairs().next().expect("No next parameter");
+    assert_eq!(name, "next");
+    assert!(next.starts_with("/authorize?"));
+    assert!(next.contains(&format!("client_id={}", client_id)));
+}
+
+#[api_test]
+async fn should_require_login_in_browser() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let (_, token) = login(&app).await;
+    app.post_logout().await;
+    let challenge = code_challenge(VERIFIER);
+
+    // A bearer token is not a login in this browser
+    let response = app
+        .http_client
+        .get(format!("{}/authorize", &app.address))
+        .query(&query(&client_id, &challenge))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(location(&response).starts_with("/?next="));
+}
+
+#[api_test]
+async fn should_not_redirect_to_unregistered_uris() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    login(&app).await;
+    let challenge = code_challenge(VERIFIER);
+    let test_cases = [
+        vec![("client_id", "unknown"), ("redirect_uri", REDIRECT_URI)],
+        vec![("client_id", client_id.as_str())],
+        vec![
+            ("client_id", client_id.as_str()),
+            ("redirect_uri", "https://evil.example.com/callback"),
+        ],
+        // Redirect URIs must match exactly
+        vec![
+            ("client_id", client_id.as_str()),
+            ("redirect_uri", "https://app.example.com/callback/"),
+        ],
+    ];
+
+    for test_case in test_cases {
+        let mut query = test_case.clone();
+        query.extend([
+            ("response_type", "code"),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+        ]);
+        let response = app.get_authorize(&query).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+        let body: OAuthErrorResponse = response.json().await.expect("Failed to parse error");
+        assert_eq!(body.error, "invalid_request");
+    }
+}
+
+#[api_test]
+async fn should_redirect_request_errors_to_client() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let challenge = code_challenge(VERIFIER);
+    let test_cases = [
+        (("response_type", "token"), "unsupported_response_type"),
+        (("code_challenge_method", "plain"), "invalid_request"),
+        (("code_challenge", "too-short"), "invalid_request"),
+        (("scope", "pro\"file"), "invalid_scope"),
+    ];
+
+    for ((name, value), error) in test_cases {
+        let mut query = query(&client_id, &challenge);
+        query.retain(|(param, _)| *param != name);
+        query.push((name, value));
+        // Reported even before the user logs in
+        let response = app.get_authorize(&query).await;
+
+        assert_eq!(response.status().as_u16(), 303);
+        let params = redirect_params(&response);
+        assert_eq!(
+            params.get("error").map(String::as_str),
+            Some(error),
+            "Failed for input: {}={}",
+            name,
+            value
+        );
+        assert_eq!(params.get("state").map(String::as_str), Some("xyz 123"));
+        assert!(!params.contains_key("code"));
+    }
+}
+
+#[api_test]
+async fn should_reject_scopes_the_client_was_not_registered_with() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let response = app
+        .post_admin_clients(
+            &serde_json::json!({ "name": "No scopes", "redirectUris": [REDIRECT_URI] }),
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+    let body: serde_json::Value = response.json().await.expect("Failed to parse client");
+    let unscoped_client_id = body["clientId"].as_str().expect("No client id");
+    let challenge = code_challenge(VERIFIER);
+    login(&app).await;
+    let test_cases = [
+        (client_id.as_str(), "admin"),
+        (client_id.as_str(), "openid admin"),
+        (unscoped_client_id, "openid"),
+        (unscoped_client_id, "email"),
+    ];
+
+    for (client_id, scope) in test_cases {
+        let mut query = query(client_id, &challenge);
+        query.push(("scope", scope));
+        let response = app.get_authorize(&query).await;
+
+        assert_eq!(response.status().as_u16(), 303);
+        let params = redirect_params(&response);
+        assert_eq!(
+            params.get("error").map(String::as_str),
+            Some("invalid_scope"),
+            "Failed for scope {} of {}",
+            scope,
+            client_id
+        );
+        assert!(!params.contains_key("code"));
+    }
+
+    // Registered scopes, or none at all, still get a code
+    for (client_id, scope) in [
+        (client_id.as_str(), Some("email openid")),
+        (unscoped_client_id, None),
+    ] {
+        let mut query = query(client_id, &challenge);
+        query.extend(scope.map(|scope| ("scope", scope)));
+        let response = app.get_authorize(&query).await;
+
+        assert!(redirect_params(&response).contains_key("code"));
+    }
+}
+
+#[api_test]
+async fn should_require_pkce() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    login(&app).await;
+
+    let response = app
+        .get_authorize(&[
+            ("response_type", "code"),
+            ("client_id", &client_id),
+            ("redirect_uri", REDIRECT_URI),
+        ])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    let params = redirect_params(&response);
+    assert_eq!(
+        params.get("error").map(String::as_str),
+        Some("invalid_request")
+    );
+    assert_eq!(
+        params.get("error_description").map(String::as_str),
+        Some("code_challenge is required")
+    );
+}
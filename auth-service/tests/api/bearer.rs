@@ -0,0 +1,147 @@
+use auth_service::{
+    routes::login::{LoginResponse, TokenResponse},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use secrecy::ExposeSecret;
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+// Sign up a user and log them in with the token returned in the body
+async fn login_for_token(app: &TestApp, requires_2fa: bool) -> (String, reqwest::Response) {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": requires_2fa
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "tokenDelivery": "body"
+        }))
+        .await;
+    (email, response)
+}
+
+async fn token_from(response: reqwest::Response) -> String {
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+    let body: TokenResponse = response
+        .json()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert_eq!(body.token_type, "Bearer");
+    assert_eq!(body.expires_in, 600);
+    body.token
+}
+
+async fn post_with_bearer(app: &TestApp, path: &str, token: &str) -> reqwest::Response {
+    app.http_client
+        .post(format!("{}{}", &app.address, path))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("failed to execute request.")
+}
+
+#[api_test]
+async fn should_return_token_in_body_instead_of_cookie() {
+    let (_, response) = login_for_token(&app, false).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let token = token_from(response).await;
+    assert_eq!(token.split('.').count(), 3);
+}
+
+#[api_test]
+async fn should_deserialize_token_login_response() {
+    let (_, response) = login_for_token(&app, false).await;
+
+    let body: LoginResponse = response.json().await.unwrap();
+
+    assert!(matches!(body, LoginResponse::TokenAuth(_)));
+}
+
+#[api_test]
+async fn should_return_token_in_body_after_2fa() {
+    let (email, response) = login_for_token(&app, true).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let (attempt_id, code) = app
+        .two_fa_codes
+        .read()
+        .await
+        .get_code(app.user_id(&email).await)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": attempt_id.as_ref().expose_secret(),
+            "2FACode": code.as_ref().expose_secret(),
+            "tokenDelivery": "body"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let token = token_from(response).await;
+    assert_eq!(
+        post_with_bearer(&app, "/verify-token", &token)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+}
+
+#[api_test]
+async fn should_authenticate_with_bearer_token() {
+    let (_, response) = login_for_token(&app, false).await;
+    let token = token_from(response).await;
+
+    let response = post_with_bearer(&app, "/verify-token", &token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .http_client
+        .get(format!("{}/sessions", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_logout_with_bearer_token() {
+    let (_, response) = login_for_token(&app, false).await;
+    let token = token_from(response).await;
+
+    let response = post_with_bearer(&app, "/logout", &token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    // Nothing to clear, as the token was never in a cookie
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+    let response = post_with_bearer(&app, "/logout", &token).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = post_with_bearer(&app, "/verify-token", &token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_from_verify_token_without_any_token() {
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", &app.address))
+        .send()
+        .await
+        .expect("failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,263 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::{introspect::IntrospectResponse, token::AccessTokenResponse},
+    utils::constants::test::{ADMIN_TOKEN, CLIENT_ID, CLIENT_SECRET},
+    OAuthErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+async fn error(response: reqwest::Response) -> String {
+    let body: OAuthErrorResponse = response.json().await.expect("Failed to parse error");
+    body.error
+}
+
+async fn introspect(app: &TestApp, token: &str) -> IntrospectResponse {
+    app.post_introspect(&[("token", token)], Some((CLIENT_ID, CLIENT_SECRET)))
+        .await
+        .json()
+        .await
+        .expect("Failed to parse introspection")
+}
+
+#[api_test]
+async fn should_issue_token_with_basic_credentials() {
+    let (client_id, secret) = app
+        .register_confidential_client(&["reports:read", "reports:write"])
+        .await;
+
+    let response = app
+        .post_token_with_credentials(
+            &[("grant_type", "client_credentials")],
+            (&client_id, &secret),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let tokens: AccessTokenResponse = response.json().await.expect("Failed to parse tokens");
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.expires_in, 600);
+    // Without a scope, the client gets every scope it is allowed
+    assert_eq!(tokens.scope.as_deref(), Some("reports:read reports:write"));
+    assert!(tokens.refresh_token.is_none());
+    assert!(tokens.id_token.is_none());
+
+    let token = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .find(|entry| entry.event.kind == AuditEventKind::OAuthToken)
+        .expect("No token event");
+    assert_eq!(token.event.outcome, AuditOutcome::Success);
+    assert_eq!(token.event.actor.as_deref(), Some(client_id.as_str()));
+}
+
+#[api_test]
+async fn should_issue_token_with_form_credentials_for_narrower_scope() {
+    let (client_id, secret) = app
+        .register_confidential_client(&["reports:read", "reports:write"])
+        .await;
+
+    let response = app
+        .post_token(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", &client_id),
+            ("client_secret", &secret),
+            ("scope", "reports:read"),
+        ])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let tokens: AccessTokenResponse = response.json().await.expect("Failed to parse tokens");
+    assert_eq!(tokens.scope.as_deref(), Some("reports:read"));
+}
+
+#[api_test]
+async fn should_describe_client_tokens() {
+    let (client_id, secret) = app.register_confidential_client(&["reports:read"]).await;
+    let tokens: AccessTokenResponse = app
+        .post_token_with_credentials(
+            &[("grant_type", "client_credentials")],
+            (&client_id, &secret),
+        )
+        .await
+        .json()
+        .await
+        .expect("Failed to parse tokens");
+
+    let response = introspect(&app, &tokens.access_token).await;
+    assert!(response.active);
+    assert_eq!(response.sub.as_deref(), Some(client_id.as_str()));
+    assert_eq!(response.client_id.as_deref(), Some(client_id.as_str()));
+    assert_eq!(response.scope.as_deref(), Some("reports:read"));
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Client tokens do not stand in for a user
+    assert_eq!(app.get_userinfo(&tokens.access_token).await.status(), 401);
+
+    // Nor do they outlive the client
+    app.delete_admin_client(&client_id, Some(ADMIN_TOKEN)).await;
+    assert!(!introspect(&app, &tokens.access_token).await.active);
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_let_clients_revoke_their_tokens() {
+    let (client_id, secret) = app.register_confidential_client(&[]).await;
+    let tokens: AccessTokenResponse = app
+        .post_token_with_credentials(
+            &[("grant_type", "client_credentials")],
+            (&client_id, &secret),
+        )
+        .await
+        .json()
+        .await
+        .expect("Failed to parse tokens");
+    assert!(tokens.scope.is_none());
+
+    // Confidential clients have to prove themselves to revoke, too
+    let form = [
+        ("token", tokens.access_token.as_str()),
+        ("client_id", client_id.as_str()),
+    ];
+    let response = app.post_revoke(&form, None, None).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(introspect(&app, &tokens.access_token).await.active);
+
+    let form = [
+        ("token", tokens.access_token.as_str()),
+        ("client_id", client_id.as_str()),
+        ("client_secret", secret.as_str()),
+    ];
+    let response = app.post_revoke(&form, None, None).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!introspect(&app, &tokens.access_token).await.active);
+}
+
+#[api_test]
+async fn should_reject_scopes_the_client_is_not_allowed() {
+    let (client_id, secret) = app.register_confidential_client(&["reports:read"]).await;
+
+    for scope in ["reports:write", "reports:read reports:write", "caf\u{e9}"] {
+        let response = app
+            .post_token_with_credentials(
+                &[("grant_type", "client_credentials"), ("scope", scope)],
+                (&client_id, &secret),
+            )
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for scope: {}",
+            scope
+        );
+        assert_eq!(error(response).await, "invalid_scope");
+    }
+}
+
+#[api_test]
+async fn should_reject_wrong_client_credentials() {
+    let (client_id, secret) = app.register_confidential_client(&[]).await;
+    let grant = ("grant_type", "client_credentials");
+
+    let test_cases = [
+        app.post_token_with_credentials(&[grant], (&client_id, "wrong-secret"))
+            .await,
+        app.post_token_with_credentials(&[grant], ("unknown", &secret))
+            .await,
+        // The secret is required
+        app.post_token(&[grant, ("client_id", &client_id)]).await,
+        // The ids have to agree
+        app.post_token_with_credentials(&[grant, ("client_id", "other")], (&client_id, &secret))
+            .await,
+    ];
+    for response in test_cases {
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(error(response).await, "invalid_client");
+    }
+
+    // Only one way of authenticating at a time
+    let response = app
+        .post_token_with_credentials(&[grant, ("client_secret", &secret)], (&client_id, &secret))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "invalid_request");
+}
+
+#[api_test]
+async fn should_not_issue_client_tokens_to_public_clients() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+
+    let response = app
+        .post_token(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", &client_id),
+        ])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "unauthorized_client");
+}
+
+#[api_test]
+async fn should_require_secret_of_confidential_clients_to_redeem_codes() {
+    let response = app
+        .post_admin_clients(
+            &serde_json::json!({
+                "name": "Server-side app",
+                "redirectUris": [REDIRECT_URI],
+                "confidential": true
+            }),
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+    let body: serde_json::Value = response.json().await.expect("Failed to parse client");
+    let client_id = body["clientId"].as_str().unwrap().to_owned();
+    let secret = body["clientSecret"].as_str().unwrap().to_owned();
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    let code = app
+        .authorization_code(&client_id, REDIRECT_URI, VERIFIER, None)
+        .await;
+    let form = [
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", REDIRECT_URI),
+        ("code_verifier", VERIFIER),
+    ];
+
+    let mut without_secret = form.to_vec();
+    without_secret.push(("client_id", &client_id));
+    let response = app.post_token(&without_secret).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(error(response).await, "invalid_client");
+
+    let response = app
+        .post_token_with_credentials(&form, (&client_id, &secret))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let tokens: AccessTokenResponse = response.json().await.expect("Failed to parse tokens");
+    assert!(tokens.refresh_token.is_some());
+    assert_eq!(
+        introspect(&app, &tokens.access_token).await.username,
+        Some(email)
+    );
+}
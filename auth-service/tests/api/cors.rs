@@ -0,0 +1,63 @@
+use reqwest::{
+    header::{
+        ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_HEADERS,
+        ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+    },
+    Method,
+};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+const ALLOWED_ORIGIN: &str = "http://localhost:8000";
+
+#[api_test]
+async fn should_allow_preflight_for_every_admin_method() {
+    for method in ["GET", "POST", "PUT", "DELETE"] {
+        let response = app
+            .http_client
+            .request(
+                Method::OPTIONS,
+                format!("{}/admin/users/some-user/status", &app.address),
+            )
+            .header(ORIGIN, ALLOWED_ORIGIN)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, method)
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, "authorization,content-type")
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(
+            response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            ALLOWED_ORIGIN
+        );
+        let allowed = response
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_METHODS)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(allowed.split(',').any(|m| m.trim() == method), "{}", method);
+    }
+}
+
+#[api_test]
+async fn should_not_allow_other_origins() {
+    let response = app
+        .http_client
+        .request(
+            Method::OPTIONS,
+            format!("{}/admin/roles/editor", &app.address),
+        )
+        .header(ORIGIN, "https://evil.example")
+        .header(ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert!(response
+        .headers()
+        .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+        .is_none());
+}
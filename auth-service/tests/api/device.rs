@@ -0,0 +1,265 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::{
+        device::{DeviceCodeResponse, DeviceVerificationResponse},
+        introspect::IntrospectResponse,
+        token::{AccessTokenResponse, DEVICE_CODE_GRANT_TYPE},
+    },
+    utils::constants::test::{CLIENT_ID, CLIENT_SECRET},
+    OAuthErrorResponse,
+};
+use std::time::Duration;
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+
+async fn error(response: reqwest::Response) -> String {
+    let body: OAuthErrorResponse = response.json().await.expect("Failed to parse error");
+    body.error
+}
+
+async fn device_code(app: &TestApp, client_id: &str, scope: &str) -> DeviceCodeResponse {
+    let response = app
+        .post_device_code(&[("client_id", client_id), ("scope", scope)])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.expect("Failed to parse device code")
+}
+
+async fn poll(app: &TestApp, client_id: &str, device_code: &str) -> reqwest::Response {
+    app.post_token(&[
+        ("grant_type", DEVICE_CODE_GRANT_TYPE),
+        ("device_code", device_code),
+        ("client_id", client_id),
+    ])
+    .await
+}
+
+async fn log_in(app: &TestApp) -> String {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    email
+}
+
+#[api_test]
+async fn should_issue_device_and_user_codes() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+
+    let response = app
+        .post_device_code(&[("client_id", client_id.as_str()), ("scope", "profile")])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get("cache-control")
+            .unwrap()
+            .to_str()
+            .unwrap(),
+        "no-store"
+    );
+    let codes: DeviceCodeResponse = response.json().await.expect("Failed to parse device code");
+    assert_eq!(codes.expires_in, 600);
+    assert_eq!(codes.interval, 1);
+    assert_eq!(codes.user_code.len(), 9);
+    assert_eq!(&codes.user_code[4..5], "-");
+    assert!(codes.verification_uri.ends_with("/device"));
+    assert_eq!(
+        codes.verification_uri_complete,
+        format!("{}?user_code={}", codes.verification_uri, codes.user_code)
+    );
+
+    let event = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .find(|entry| entry.event.kind == AuditEventKind::DeviceAuthorization)
+        .expect("No device authorization event");
+    assert_eq!(event.event.outcome, AuditOutcome::Success);
+    assert_eq!(event.event.actor, Some(client_id));
+}
+
+#[api_test]
+async fn should_reject_unknown_clients_and_invalid_scopes() {
+    let response = app.post_device_code(&[("client_id", "unknown")]).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(error(response).await, "invalid_client");
+
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    for scope in ["caf\u{e9}", "admin", "profile admin"] {
+        let response = app
+            .post_device_code(&[("client_id", client_id.as_str()), ("scope", scope)])
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for scope {}",
+            scope
+        );
+        assert_eq!(error(response).await, "invalid_scope");
+    }
+}
+
+#[api_test]
+async fn should_ask_device_to_wait_and_slow_down() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let codes = device_code(&app, &client_id, "profile").await;
+
+    let response = poll(&app, &client_id, &codes.device_code).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "authorization_pending");
+
+    // Polling again right away is too soon
+    let response = poll(&app, &client_id, &codes.device_code).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "slow_down");
+
+    // Only the client the code was issued to may poll for it
+    let other_client = app.register_oauth_client(REDIRECT_URI).await;
+    let response = poll(&app, &other_client, &codes.device_code).await;
+    assert_eq!(error(response).await, "invalid_grant");
+    let response = poll(&app, &client_id, "unknown").await;
+    assert_eq!(error(response).await, "invalid_grant");
+}
+
+#[api_test]
+async fn should_issue_tokens_once_user_approves() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let codes = device_code(&app, &client_id, "profile").await;
+    let email = log_in(&app).await;
+
+    // The code is accepted the way people type it
+    let typed = codes.user_code.to_lowercase().replace('-', " ");
+    let response = app.get_device_verify(&typed).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let verification: DeviceVerificationResponse =
+        response.json().await.expect("Failed to parse verification");
+    assert_eq!(verification.client_id, client_id);
+    assert_eq!(verification.client_name, "Test app");
+    assert_eq!(verification.scope.as_deref(), Some("profile"));
+
+    let response = app
+        .post_device_verify(&serde_json::json!({ "userCode": typed, "approve": true }))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    // The decision cannot be changed
+    let response = app
+        .post_device_verify(&serde_json::json!({ "userCode": typed, "approve": false }))
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = poll(&app, &client_id, &codes.device_code).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let tokens: AccessTokenResponse = response.json().await.expect("Failed to parse tokens");
+    assert_eq!(tokens.scope.as_deref(), Some("profile"));
+    assert!(tokens.refresh_token.is_some());
+    assert!(tokens.id_token.is_none());
+    let introspection: IntrospectResponse = app
+        .post_introspect(
+            &[("token", tokens.access_token.as_str())],
+            Some((CLIENT_ID, CLIENT_SECRET)),
+        )
+        .await
+        .json()
+        .await
+        .expect("Failed to parse introspection");
+    assert!(introspection.active);
+    assert_eq!(introspection.username.as_deref(), Some(email.as_str()));
+    assert_eq!(introspection.client_id.as_deref(), Some(client_id.as_str()));
+
+    // The device code is used up
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let response = poll(&app, &client_id, &codes.device_code).await;
+    assert_eq!(error(response).await, "invalid_grant");
+
+    let entries = app.audit_entries().await;
+    let approval = entries
+        .iter()
+        .find(|entry| entry.event.kind == AuditEventKind::DeviceApproval)
+        .expect("No device approval event");
+    assert_eq!(approval.event.actor.as_deref(), Some(email.as_str()));
+    let token = entries
+        .iter()
+        .find(|entry| {
+            entry.event.kind == AuditEventKind::OAuthToken
+                && entry.event.outcome == AuditOutcome::Success
+        })
+        .expect("No token event");
+    assert_eq!(token.event.actor.as_deref(), Some(email.as_str()));
+}
+
+#[api_test]
+async fn should_tell_device_when_user_denies() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let codes = device_code(&app, &client_id, "profile").await;
+    log_in(&app).await;
+
+    let response = app
+        .post_device_verify(&serde_json::json!({ "userCode": codes.user_code, "approve": false }))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = poll(&app, &client_id, &codes.device_code).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "access_denied");
+    // Once told, the device has nothing left to poll for
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let response = poll(&app, &client_id, &codes.device_code).await;
+    assert_eq!(error(response).await, "invalid_grant");
+}
+
+#[api_test]
+async fn should_require_login_to_verify_devices() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let codes = device_code(&app, &client_id, "profile").await;
+
+    let response = app.get_device_verify(&codes.user_code).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_device_verify(&serde_json::json!({ "userCode": codes.user_code, "approve": true }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let event = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .find(|entry| entry.event.kind == AuditEventKind::DeviceApproval)
+        .expect("No device approval event");
+    assert_eq!(event.event.outcome, AuditOutcome::Failure);
+    assert_eq!(event.event.actor, None);
+}
+
+#[api_test]
+async fn should_return_404_for_unknown_user_codes() {
+    log_in(&app).await;
+
+    for user_code in ["BCDF-GHJK", "not-a-code"] {
+        let response = app.get_device_verify(user_code).await;
+        assert_eq!(
+            response.status().as_u16(),
+            404,
+            "Failed for input: {}",
+            user_code
+        );
+        let response = app
+            .post_device_verify(&serde_json::json!({ "userCode": user_code, "approve": true }))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            404,
+            "Failed for input: {}",
+            user_code
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use auth_service::{
+    domain::DependencyState,
+    routes::health::{HealthResponse, ReadinessResponse},
+};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+#[api_test]
+async fn healthz_returns_200() {
+    let response = app.get_healthz().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<HealthResponse>()
+        .await
+        .expect("Could not deserialize response body to HealthResponse");
+    assert_eq!(body.status, "ok");
+}
+
+#[api_test]
+async fn readyz_returns_200_when_all_dependencies_are_up() {
+    let response = app.get_readyz().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<ReadinessResponse>()
+        .await
+        .expect("Could not deserialize response body to ReadinessResponse");
+    assert_eq!(body.status, "ready");
+    assert!(body
+        .checks
+        .values()
+        .all(|check| check.status == DependencyState::Up));
+}
+
+#[api_test]
+async fn readyz_reports_each_configured_dependency() {
+    let response = app.get_readyz().await;
+
+    let body = response
+        .json::<ReadinessResponse>()
+        .await
+        .expect("Could not deserialize response body to ReadinessResponse");
+    let expected: Vec<&str> = if cfg!(all(feature = "postgres", feature = "redis")) {
+        vec!["postgres", "redis"]
+    } else {
+        vec![]
+    };
+    assert_eq!(body.checks.keys().collect::<Vec<_>>(), expected);
+}
+
+#[tokio::test]
+async fn readyz_returns_empty_report_for_in_memory_stores() {
+    let mut app = TestApp::new_offline().await;
+
+    let response = app.get_readyz().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<ReadinessResponse>()
+        .await
+        .expect("Could not deserialize response body to ReadinessResponse");
+    assert!(body.checks.is_empty());
+    app.clean_up().await;
+}
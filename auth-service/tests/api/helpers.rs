@@ -1,30 +1,35 @@
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType},
-    get_postgres_pool, get_redis_client,
-    services::data_stores::{
-        hashmap_2fa_code_store::HashmapTwoFACodeStore, hashmap_user_store::HashmapUserStore,
-        hashset_banned_token_store::HashsetBannedTokenStore, mock_email_client::MockEmailClient,
-        postgrep_user_store::PostgresUserStore, redis_banned_token_store::RedisBannedTokenStore,
-        redis_two_fa_code_store::RedisTwoFACodeStore,
-    },
-    utils::constants::{test, DATABASE_URL, DEFAULT_REDIS_HOSTNAME},
+    app_state::{AppState, AuditLogType, BannedTokenStoreType, TwoFACodeStoreType, UserStoreType},
+    domain::{AuditEntry, Email, UserId},
+    settings::{ClientSettings, Settings, StoreSettings},
+    utils::{constants::test, oauth::code_challenge},
     Application,
 };
-use reqwest::{cookie::Jar, Client};
+use reqwest::{cookie::Jar, header::LOCATION, Client};
+#[cfg(all(feature = "postgres", feature = "redis"))]
+use secrecy::ExposeSecret;
+use secrecy::SecretString;
+#[cfg(all(feature = "postgres", feature = "redis"))]
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
-    Connection, Executor, PgConnection, PgPool,
+    Connection, Executor, PgConnection,
 };
-use std::{str::FromStr, sync::Arc};
-use tokio::sync::RwLock;
+#[cfg(all(feature = "postgres", feature = "redis"))]
+use std::str::FromStr;
+use std::sync::Arc;
+use url::Url;
 use uuid::Uuid;
 
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
     pub http_client: Client,
+    pub user_store: UserStoreType,
     pub banned_tokens: BannedTokenStoreType,
     pub two_fa_codes: TwoFACodeStoreType,
+    pub audit_log: AuditLogType,
+    // Only set (and dropped again on clean up) for tests that run against Postgres
+    #[allow(unused)]
     pub db_name: String,
     pub clean_up_called: bool,
 }
@@ -32,62 +37,49 @@ pub struct TestApp {
 impl TestApp {
     #[allow(unused)]
     pub async fn new_offline() -> Self {
-        let user_store = Arc::new(RwLock::new(HashmapUserStore::new()));
-        let banned_tokens = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let two_fa_codes = Arc::new(RwLock::new(HashmapTwoFACodeStore::default()));
-        let email_client = Arc::new(RwLock::new(MockEmailClient {}));
-        let app_state = AppState::new(
-            user_store,
-            banned_tokens.clone(),
-            two_fa_codes.clone(),
-            email_client.clone(),
-        );
-        let app = Application::build(app_state, test::APP_ADDRESS)
-            .await
-            .expect("Failed to build app");
-
-        let address = format!("http://{}", app.address.clone());
-
-        // Run the auth service in a separate async task
-        // to avoid blocking the main test thread.
-        #[allow(clippy::let_underscore_future)]
-        let _ = tokio::spawn(app.run());
-
-        let cookie_jar = Arc::new(Jar::default());
-        let http_client = Client::builder()
-            .cookie_provider(cookie_jar.clone())
-            .build()
-            .expect("Failed to build HTTP client");
-
-        // Create new `TestApp` instance and return it
-        Self {
-            address,
-            cookie_jar,
-            http_client,
-            banned_tokens,
-            two_fa_codes,
-            db_name: "".to_string(),
-            clean_up_called: false,
-        }
+        let settings = Settings {
+            stores: StoreSettings::in_memory(),
+            ..Settings::default()
+        };
+        Self::spawn(settings, "".to_string()).await
     }
 
+    #[cfg(all(feature = "postgres", feature = "redis"))]
     pub async fn new() -> Self {
         let db_name = Uuid::new_v4().to_string();
-        let pg_pool = configure_postgresql(&db_name).await;
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
-        let redis_connection = Arc::new(RwLock::new(configure_redis()));
-        let banned_tokens = Arc::new(RwLock::new(RedisBannedTokenStore::new(
-            redis_connection.clone(),
-        )));
-        let two_fa_codes = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
-        let email_client = Arc::new(RwLock::new(MockEmailClient {}));
-        let app_state = AppState::new(
-            user_store,
-            banned_tokens.clone(),
-            two_fa_codes.clone(),
-            email_client.clone(),
-        );
-        let app = Application::build(app_state, test::APP_ADDRESS)
+        let mut settings = Settings::load().expect("Failed to load settings");
+        configure_database(settings.database.url.expose_secret(), &db_name).await;
+        settings.database.url = SecretString::from(format!(
+            "{}/{}",
+            settings.database.url.expose_secret(),
+            db_name
+        ));
+        Self::spawn(settings, db_name).await
+    }
+
+    // Builds without the default Postgres and Redis backends run the API tests in memory
+    #[cfg(not(all(feature = "postgres", feature = "redis")))]
+    pub async fn new() -> Self {
+        Self::new_offline().await
+    }
+
+    async fn spawn(mut settings: Settings, db_name: String) -> Self {
+        settings.server.address = test::APP_ADDRESS.to_owned();
+        settings.admin.token = SecretString::from(test::ADMIN_TOKEN);
+        // Devices would otherwise have to wait 5 seconds between polls
+        settings.ttl.device_poll_interval_seconds = 1;
+        settings.clients = vec![ClientSettings {
+            id: test::CLIENT_ID.to_owned(),
+            secret: SecretString::from(test::CLIENT_SECRET),
+        }];
+        let app_state = AppState::from_settings(settings)
+            .await
+            .expect("Failed to configure stores");
+        let user_store = app_state.user_store.clone();
+        let banned_tokens = app_state.banned_tokens.clone();
+        let two_fa_codes = app_state.two_fa_codes.clone();
+        let audit_log = app_state.audit_log.clone();
+        let app = Application::build(app_state)
             .await
             .expect("Failed to build app");
 
@@ -99,8 +91,10 @@ impl TestApp {
         let _ = tokio::spawn(app.run());
 
         let cookie_jar = Arc::new(Jar::default());
+        // Redirects are checked by the tests rather than followed
         let http_client = Client::builder()
             .cookie_provider(cookie_jar.clone())
+            .redirect(reqwest::redirect::Policy::none())
             .build()
             .expect("Failed to build HTTP client");
 
@@ -109,8 +103,10 @@ impl TestApp {
             address,
             cookie_jar,
             http_client,
+            user_store,
             banned_tokens,
             two_fa_codes,
+            audit_log,
             db_name,
             clean_up_called: false,
         }
@@ -124,6 +120,30 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_healthz(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/healthz", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_readyz(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/readyz", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_metrics(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/metrics", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -160,6 +180,481 @@ impl TestApp {
             .expect("failed to execute request.")
     }
 
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn post_admin_logout_all<Body>(
+        &self,
+        body: &Body,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/logout-all", &self.address))
+            .json(body);
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn post_admin_clients<Body>(
+        &self,
+        body: &Body,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/clients", &self.address))
+            .json(body);
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn get_admin_clients(&self, admin_token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/admin/clients", &self.address));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn delete_admin_client(
+        &self,
+        id: &str,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .delete(format!("{}/admin/clients/{}", &self.address, id));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn get_admin_roles(&self, admin_token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/admin/roles", &self.address));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn put_admin_role(
+        &self,
+        name: &str,
+        permissions: &[&str],
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .put(format!("{}/admin/roles/{}", &self.address, name))
+            .json(&serde_json::json!({ "permissions": permissions }));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn delete_admin_role(
+        &self,
+        name: &str,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .delete(format!("{}/admin/roles/{}", &self.address, name));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn get_admin_user_roles(
+        &self,
+        email: &str,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/admin/users/{}/roles", &self.address, email));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn put_admin_user_role(
+        &self,
+        email: &str,
+        role: &str,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self.http_client.put(format!(
+            "{}/admin/users/{}/roles/{}",
+            &self.address, email, role
+        ));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn delete_admin_user_role(
+        &self,
+        email: &str,
+        role: &str,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self.http_client.delete(format!(
+            "{}/admin/users/{}/roles/{}",
+            &self.address, email, role
+        ));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn get_admin_users(
+        &self,
+        query: &[(&str, &str)],
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/admin/users", &self.address))
+            .query(query);
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn get_admin_user(
+        &self,
+        email: &str,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/admin/users/{}", &self.address, email));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn delete_admin_user(
+        &self,
+        email: &str,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .delete(format!("{}/admin/users/{}", &self.address, email));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    // `action` is one of `disable`, `enable`, `password-reset`, `2fa/reset` and `2fa/disable`
+    pub async fn post_admin_user_action(
+        &self,
+        email: &str,
+        action: &str,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self.http_client.post(format!(
+            "{}/admin/users/{}/{}",
+            &self.address, email, action
+        ));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn put_admin_user_status<Body>(
+        &self,
+        email: &str,
+        body: &Body,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .put(format!("{}/admin/users/{}/status", &self.address, email))
+            .json(body);
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn put_admin_user_email(
+        &self,
+        email: &str,
+        new_email: &str,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .put(format!("{}/admin/users/{}/email", &self.address, email))
+            .json(&serde_json::json!({ "email": new_email }));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    pub async fn delete_admin_user_sessions(
+        &self,
+        email: &str,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .delete(format!("{}/admin/users/{}/sessions", &self.address, email));
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    // Register an OAuth client with a single redirect URI and the scopes the tests ask for,
+    // returning its id
+    pub async fn register_oauth_client(&self, redirect_uri: &str) -> String {
+        let response = self
+            .post_admin_clients(
+                &serde_json::json!({
+                    "name": "Test app",
+                    "redirectUris": [redirect_uri],
+                    "allowedScopes": ["openid", "email", "profile"]
+                }),
+                Some(test::ADMIN_TOKEN),
+            )
+            .await;
+        let body: serde_json::Value = response.json().await.expect("Failed to parse client");
+        body["clientId"].as_str().expect("No client id").to_owned()
+    }
+
+    // Register a confidential OAuth client, returning its id and secret
+    pub async fn register_confidential_client(&self, allowed_scopes: &[&str]) -> (String, String) {
+        let response = self
+            .post_admin_clients(
+                &serde_json::json!({
+                    "name": "Test job",
+                    "confidential": true,
+                    "allowedScopes": allowed_scopes
+                }),
+                Some(test::ADMIN_TOKEN),
+            )
+            .await;
+        let body: serde_json::Value = response.json().await.expect("Failed to parse client");
+        (
+            body["clientId"].as_str().expect("No client id").to_owned(),
+            body["clientSecret"]
+                .as_str()
+                .expect("No client secret")
+                .to_owned(),
+        )
+    }
+
+    pub async fn get_authorize(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/authorize", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    // Authorize a logged-in user for the client and return the code it was redirected with
+    pub async fn authorization_code(
+        &self,
+        client_id: &str,
+        redirect_uri: &str,
+        verifier: &str,
+        scope: Option<&str>,
+    ) -> String {
+        let challenge = code_challenge(verifier);
+        let mut query = vec![
+            ("response_type", "code"),
+            ("client_id", client_id),
+            ("redirect_uri", redirect_uri),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+        ];
+        if let Some(scope) = scope {
+            query.push(("scope", scope));
+        }
+        let response = self.get_authorize(&query).await;
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .expect("No redirect")
+            .to_str()
+            .expect("Invalid redirect");
+        Url::parse(location)
+            .expect("Redirect is not absolute")
+            .query_pairs()
+            .find(|(name, _)| name == "code")
+            .expect("No code in redirect")
+            .1
+            .into_owned()
+    }
+
+    pub async fn post_token(&self, form: &[(&str, &str)]) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/token", &self.address))
+            .form(form)
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    // Confidential clients may send their `credentials` with HTTP Basic instead of in the form
+    pub async fn post_token_with_credentials(
+        &self,
+        form: &[(&str, &str)],
+        credentials: (&str, &str),
+    ) -> reqwest::Response {
+        let (id, secret) = credentials;
+        self.http_client
+            .post(format!("{}/token", &self.address))
+            .basic_auth(id, Some(secret))
+            .form(form)
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn get_openid_configuration(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    // Clients send their access token as a bearer token
+    pub async fn post_device_code(&self, form: &[(&str, &str)]) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/device/code", &self.address))
+            .form(form)
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn get_device_verify(&self, user_code: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/device/verify", &self.address))
+            .query(&[("user_code", user_code)])
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn post_device_verify<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/device/verify", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn get_userinfo(&self, access_token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/userinfo", &self.address))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn get_end_session(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/end-session", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    // `credentials` are the client id and secret, sent with HTTP Basic
+    pub async fn post_introspect(
+        &self,
+        form: &[(&str, &str)],
+        credentials: Option<(&str, &str)>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .post(format!("{}/introspect", &self.address))
+            .form(form);
+        if let Some((id, secret)) = credentials {
+            request = request.basic_auth(id, Some(secret));
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
+    // Clients send their `credentials` with HTTP Basic, admins their token as a bearer token
+    pub async fn post_revoke(
+        &self,
+        form: &[(&str, &str)],
+        credentials: Option<(&str, &str)>,
+        admin_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .post(format!("{}/revoke", &self.address))
+            .form(form);
+        if let Some((id, secret)) = credentials {
+            request = request.basic_auth(id, Some(secret));
+        }
+        if let Some(token) = admin_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("failed to execute request.")
+    }
+
     pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -172,6 +667,18 @@ impl TestApp {
             .expect("failed to execute request.")
     }
 
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/reset-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
     pub async fn post_verify_token<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -184,11 +691,90 @@ impl TestApp {
             .expect("failed to execute request.")
     }
 
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, id))
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn post_api_keys<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/api-keys", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn get_api_keys(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/api-keys", &self.address))
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    pub async fn delete_api_key(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/api-keys/{}", &self.address, id))
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    // Verify the caller's own API key, sent as `Authorization: ApiKey <key>`
+    pub async fn post_verify_api_key(&self, key: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/verify-token", &self.address))
+            .header("Authorization", format!("ApiKey {}", key))
+            .send()
+            .await
+            .expect("failed to execute request.")
+    }
+
+    // The id of the user who signed up with `email`
+    pub async fn user_id(&self, email: &str) -> UserId {
+        let email = Email::parse(email.to_owned()).expect("Invalid email");
+        self.user_store
+            .read()
+            .await
+            .get_user(&email)
+            .await
+            .expect("Failed to get user")
+            .id
+    }
+
+    // Every audit entry recorded so far, oldest first
+    pub async fn audit_entries(&self) -> Vec<AuditEntry> {
+        self.audit_log
+            .read()
+            .await
+            .entries(0, i64::MAX)
+            .await
+            .expect("Failed to read audit log")
+    }
+
     pub async fn clean_up(&mut self) {
         if self.clean_up_called {
             return;
         }
-        delete_database(&self.db_name).await;
+        #[cfg(all(feature = "postgres", feature = "redis"))]
+        if !self.db_name.is_empty() {
+            delete_database(&self.db_name).await;
+        }
         self.clean_up_called = true;
     }
 }
@@ -201,16 +787,7 @@ impl Drop for TestApp {
     }
 }
 
-async fn configure_postgresql(db_name: &str) -> PgPool {
-    let postgresql_conn_url = DATABASE_URL.to_string();
-    configure_database(&postgresql_conn_url, &db_name).await;
-
-    let postgresql_conn_url_with_db = format!("{}/{}", postgresql_conn_url, db_name);
-    get_postgres_pool(&postgresql_conn_url_with_db)
-        .await
-        .expect("Failed to create Postgres connection pool!")
-}
-
+#[cfg(all(feature = "postgres", feature = "redis"))]
 async fn configure_database(db_conn_string: &str, db_name: &str) {
     let connection = PgPoolOptions::new()
         .connect(db_conn_string)
@@ -220,22 +797,13 @@ async fn configure_database(db_conn_string: &str, db_name: &str) {
         .execute(format!(r#"create database "{}";"#, db_name).as_str())
         .await
         .expect("Failed to create test database.");
-
-    let db_conn_string = format!("{}/{}", db_conn_string, db_name);
-    let connection = PgPoolOptions::new()
-        .connect(&db_conn_string)
-        .await
-        .expect("Failed to create Postgres connection pool.");
-
-    sqlx::migrate!()
-        .run(&connection)
-        .await
-        .expect("Failed to migrate the database");
 }
 
+#[cfg(all(feature = "postgres", feature = "redis"))]
 async fn delete_database(db_name: &str) {
-    let postgresql_conn_url = DATABASE_URL.to_string();
-    let connection_options = PgConnectOptions::from_str(&postgresql_conn_url)
+    let settings = Settings::load().expect("Failed to load settings");
+    let postgresql_conn_url = settings.database.url.expose_secret();
+    let connection_options = PgConnectOptions::from_str(postgresql_conn_url)
         .expect("Failed to parse PostgreSQL connection string");
     let mut connection = PgConnection::connect_with(&connection_options)
         .await
@@ -260,11 +828,3 @@ async fn delete_database(db_name: &str) {
         .await
         .expect("Failed to drop the database.");
 }
-
-fn configure_redis() -> redis::Connection {
-    let redis_hostname = DEFAULT_REDIS_HOSTNAME.to_string();
-    get_redis_client(redis_hostname)
-        .expect("Failed to get Redis client")
-        .get_connection()
-        .expect("Failed to get Redis connection")
-}
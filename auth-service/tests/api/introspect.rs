@@ -0,0 +1,122 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::introspect::IntrospectResponse,
+    utils::constants::{
+        test::{CLIENT_ID, CLIENT_SECRET},
+        JWT_COOKIE_NAME,
+    },
+};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+const CREDENTIALS: Option<(&str, &str)> = Some((CLIENT_ID, CLIENT_SECRET));
+
+// Sign up and log in a user without 2FA, returning their email and token
+async fn login(app: &TestApp) -> (String, String) {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    let token = auth_cookie.value().to_owned();
+    (email, token)
+}
+
+async fn introspect(app: &TestApp, token: &str) -> IntrospectResponse {
+    let response = app.post_introspect(&[("token", token)], CREDENTIALS).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json()
+        .await
+        .expect("Could not deserialize response body to IntrospectResponse")
+}
+
+#[api_test]
+async fn should_describe_active_token() {
+    let (email, token) = login(&app).await;
+
+    let response = introspect(&app, &token).await;
+
+    assert!(response.active);
+    assert_eq!(response.sub, Some(app.user_id(&email).await.to_string()));
+    assert_eq!(response.username, Some(email));
+    assert_eq!(response.token_type.as_deref(), Some("Bearer"));
+    let (iat, exp) = (response.iat.unwrap(), response.exp.unwrap());
+    assert_eq!(exp - iat, 600);
+    assert_eq!(response.scope, None);
+    assert_eq!(response.client_id, None);
+}
+
+#[api_test]
+async fn should_report_only_inactive_for_invalid_tokens() {
+    let (_, token) = login(&app).await;
+    app.post_logout().await;
+
+    for token in [token.as_str(), "not-a-token"] {
+        let response = app
+            .post_introspect(
+                &[("token", token), ("token_type_hint", "access_token")],
+                CREDENTIALS,
+            )
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body, serde_json::json!({ "active": false }));
+    }
+}
+
+#[api_test]
+async fn should_return_401_for_unknown_or_missing_client() {
+    let (_, token) = login(&app).await;
+    let test_cases = [
+        None,
+        Some((CLIENT_ID, "wrong-secret")),
+        Some(("unknown-client", CLIENT_SECRET)),
+    ];
+
+    for credentials in test_cases {
+        let response = app.post_introspect(&[("token", &token)], credentials).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            credentials
+        );
+        assert_eq!(
+            response.headers()["www-authenticate"],
+            "Basic realm=\"auth-service\""
+        );
+    }
+    let outcomes: Vec<_> = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .filter(|entry| entry.event.kind == AuditEventKind::Introspect)
+        .map(|entry| (entry.event.outcome, entry.event.reason))
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![(AuditOutcome::Failure, Some("invalid_client".to_owned())); 3]
+    );
+}
+
+#[api_test]
+async fn should_return_422_without_token() {
+    let response = app
+        .post_introspect(&[("token_type_hint", "access_token")], CREDENTIALS)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+}
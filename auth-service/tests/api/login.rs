@@ -1,6 +1,4 @@
-use auth_service::{
-    domain::Email, routes::login::TwoFactorAuthResponse, utils::constants::JWT_COOKIE_NAME,
-};
+use auth_service::{routes::login::TwoFactorAuthResponse, utils::constants::JWT_COOKIE_NAME};
 use secrecy::ExposeSecret;
 use test_helpers::api_test;
 
@@ -140,7 +138,7 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     assert_eq!(json_body.message, "2FA required".to_owned());
     let two_fa_codes = app.two_fa_codes.read().await;
     let code_tuple = two_fa_codes
-        .get_code(&Email::parse(random_email).unwrap())
+        .get_code(app.user_id(&random_email).await)
         .await
         .expect("Failed to get 2FA code");
     assert_eq!(
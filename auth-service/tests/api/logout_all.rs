@@ -0,0 +1,119 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    utils::constants::{test::ADMIN_TOKEN, JWT_COOKIE_NAME},
+};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+// Sign up a user without 2FA and return their email
+async fn signup(app: &TestApp) -> String {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    email
+}
+
+// Log in and return the token that was issued
+async fn login(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    auth_cookie.value().to_owned()
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_invalidate_every_token_of_caller() {
+    let email = signup(&app).await;
+    let first_token = login(&app, &email).await;
+    let second_token = login(&app, &email).await;
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(auth_cookie.value().is_empty());
+    assert_eq!(verify_token_status(&app, &first_token).await, 401);
+    assert_eq!(verify_token_status(&app, &second_token).await, 401);
+
+    // Logging in again works, and only the new session is listed
+    let new_token = login(&app, &email).await;
+    assert_eq!(verify_token_status(&app, &new_token).await, 200);
+    let sessions: serde_json::Value = app.get_sessions().await.json().await.unwrap();
+    assert_eq!(sessions["sessions"].as_array().unwrap().len(), 1);
+}
+
+#[api_test]
+async fn should_reject_admin_logout_all_without_valid_admin_token() {
+    let email = signup(&app).await;
+    let token = login(&app, &email).await;
+    let body = serde_json::json!({ "email": email });
+
+    let missing = app.post_admin_logout_all(&body, None).await;
+    let wrong = app
+        .post_admin_logout_all(&body, Some("not-the-admin-token"))
+        .await;
+
+    assert_eq!(missing.status().as_u16(), 400);
+    assert_eq!(wrong.status().as_u16(), 401);
+    assert_eq!(verify_token_status(&app, &token).await, 200);
+    let attempts: Vec<_> = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .filter(|entry| entry.event.kind == AuditEventKind::AdminLogoutAll)
+        .map(|entry| entry.event.outcome)
+        .collect();
+    assert_eq!(attempts, [AuditOutcome::Failure, AuditOutcome::Failure]);
+}
+
+#[api_test]
+async fn should_log_user_out_everywhere_with_admin_token() {
+    let email = signup(&app).await;
+    let token = login(&app, &email).await;
+
+    let response = app
+        .post_admin_logout_all(&serde_json::json!({ "email": email }), Some(ADMIN_TOKEN))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+}
+
+#[api_test]
+async fn should_return_404_for_admin_logout_all_of_unknown_user() {
+    let response = app
+        .post_admin_logout_all(
+            &serde_json::json!({ "email": TestApp::get_random_email() }),
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -1,7 +1,28 @@
+#![allow(clippy::needless_borrows_for_generic_args, clippy::useless_vec)]
+
+mod admin_users;
+mod api_keys;
+mod audit;
+mod authorize;
+mod bearer;
+mod client_credentials;
+mod cors;
+mod device;
+mod health;
 mod helpers;
+mod introspect;
 mod login;
 mod logout;
+mod logout_all;
+mod metrics;
+mod oauth_clients;
+mod oidc;
+mod request_id;
+mod revoke;
+mod roles;
 mod root;
+mod sessions;
 mod signup;
+mod token;
 mod verify_2fa;
 mod verify_token;
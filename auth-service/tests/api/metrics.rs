@@ -0,0 +1,86 @@
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+#[api_test]
+async fn metrics_returns_prometheus_text() {
+    app.get_healthz().await;
+
+    let response = app.get_metrics().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .headers()
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let body = response.text().await.unwrap();
+    assert!(
+        body.contains(r#"http_requests_total{method="GET",route="/healthz",status="200"}"#),
+        "{}",
+        body
+    );
+    assert!(body.contains("http_request_duration_seconds_bucket"));
+}
+
+#[api_test]
+async fn metrics_count_login_outcomes() {
+    let email = TestApp::get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    app.post_signup(&signup_body).await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "wrong-password" }))
+        .await;
+
+    let body = app.get_metrics().await.text().await.unwrap();
+
+    assert!(body.contains(r#"auth_logins_total{outcome="success"}"#));
+    assert!(body.contains(r#"auth_logins_total{outcome="invalid_credentials"}"#));
+    assert!(body.contains(r#"auth_password_hash_duration_seconds_bucket{operation="hash""#));
+    assert!(body.contains(r#"auth_password_hash_duration_seconds_bucket{operation="verify""#));
+}
+
+#[api_test]
+async fn metrics_record_store_operation_latency() {
+    let signup_body = serde_json::json!({
+        "email": TestApp::get_random_email(),
+        "password": "password123",
+        "requires2FA": false
+    });
+    app.post_signup(&signup_body).await;
+
+    let body = app.get_metrics().await.text().await.unwrap();
+
+    assert!(
+        body.lines().any(|line| line
+            .starts_with("auth_store_operation_duration_seconds_count{store=\"users\"")
+            && line.contains("operation=\"add_user\"")),
+        "{}",
+        body
+    );
+}
+
+#[api_test]
+async fn metrics_count_2fa_codes_sent() {
+    let email = TestApp::get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+    app.post_signup(&signup_body).await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+
+    let body = app.get_metrics().await.text().await.unwrap();
+
+    assert!(body.contains(r#"auth_logins_total{outcome="2fa_required"}"#));
+    assert!(body.contains("auth_2fa_codes_sent_total"));
+}
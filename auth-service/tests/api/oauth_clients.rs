@@ -0,0 +1,163 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::admin::{ClientResponse, ClientsResponse},
+    utils::constants::test::ADMIN_TOKEN,
+};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+
+#[api_test]
+async fn should_register_client() {
+    let response = app
+        .post_admin_clients(
+            &serde_json::json!({
+                "name": "Wiki",
+                "redirectUris": [REDIRECT_URI],
+                "allowedScopes": ["openid"]
+            }),
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+    let client: ClientResponse = response.json().await.expect("Failed to parse client");
+    assert_eq!(client.name, "Wiki");
+    assert_eq!(client.redirect_uris, [REDIRECT_URI]);
+    assert_eq!(client.allowed_scopes, ["openid"]);
+    assert!(!client.confidential);
+
+    let events: Vec<_> = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .map(|entry| (entry.event.kind, entry.event.outcome))
+        .collect();
+    assert_eq!(
+        events,
+        [(AuditEventKind::AdminRegisterClient, AuditOutcome::Success)]
+    );
+}
+
+#[api_test]
+async fn should_register_confidential_client() {
+    let response = app
+        .post_admin_clients(
+            &serde_json::json!({
+                "name": "Nightly report",
+                "confidential": true,
+                "allowedScopes": ["reports:read"]
+            }),
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+    let client: ClientResponse = response.json().await.expect("Failed to parse client");
+    assert!(client.confidential);
+    assert_eq!(client.allowed_scopes, ["reports:read"]);
+    assert!(client.redirect_uris.is_empty());
+    assert_eq!(client.client_secret.as_deref().map(str::len), Some(43));
+
+    // The secret is only shown once
+    let body: ClientsResponse = app
+        .get_admin_clients(Some(ADMIN_TOKEN))
+        .await
+        .json()
+        .await
+        .expect("Failed to parse clients");
+    assert!(body.clients[0].confidential);
+    assert!(body.clients[0].client_secret.is_none());
+}
+
+#[api_test]
+async fn should_return_400_for_invalid_clients() {
+    let test_cases = [
+        serde_json::json!({ "name": "", "redirectUris": [REDIRECT_URI] }),
+        serde_json::json!({ "name": "Wiki", "redirectUris": [] }),
+        serde_json::json!({ "name": "Wiki", "redirectUris": ["http://app.example.com/callback"] }),
+        serde_json::json!({ "name": "Wiki", "redirectUris": ["/callback"] }),
+        serde_json::json!({ "name": "Wiki", "redirectUris": [format!("{}#top", REDIRECT_URI)] }),
+        serde_json::json!({ "name": "Wiki", "redirectUris": [REDIRECT_URI], "allowedScopes": ["a b"] }),
+        serde_json::json!({ "name": "Job", "confidential": true, "allowedScopes": [""] }),
+        serde_json::json!({ "name": "Job", "confidential": true, "allowedScopes": ["a b"] }),
+        serde_json::json!({ "name": "Job", "confidential": true, "allowedScopes": ["caf\u{e9}"] }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_admin_clients(test_case, Some(ADMIN_TOKEN)).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[api_test]
+async fn should_require_admin_token() {
+    let body = serde_json::json!({ "name": "Wiki", "redirectUris": [REDIRECT_URI] });
+
+    assert_eq!(
+        app.post_admin_clients(&body, Some("wrong-token"))
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+    assert_eq!(
+        app.post_admin_clients(&body, None).await.status().as_u16(),
+        400
+    );
+    assert_eq!(
+        app.get_admin_clients(Some("wrong-token"))
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+    assert_eq!(
+        app.delete_admin_client("client", Some("wrong-token"))
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+}
+
+#[api_test]
+async fn should_list_and_delete_clients() {
+    let first = app.register_oauth_client(REDIRECT_URI).await;
+    let second = app.register_oauth_client(REDIRECT_URI).await;
+
+    let response = app.get_admin_clients(Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: ClientsResponse = response.json().await.expect("Failed to parse clients");
+    let ids: Vec<_> = body
+        .clients
+        .iter()
+        .map(|client| &client.client_id)
+        .collect();
+    assert_eq!(ids, [&first, &second]);
+
+    let response = app.delete_admin_client(&first, Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app.delete_admin_client(&first, Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let body: ClientsResponse = app
+        .get_admin_clients(Some(ADMIN_TOKEN))
+        .await
+        .json()
+        .await
+        .expect("Failed to parse clients");
+    let ids: Vec<_> = body
+        .clients
+        .iter()
+        .map(|client| &client.client_id)
+        .collect();
+    assert_eq!(ids, [&second]);
+}
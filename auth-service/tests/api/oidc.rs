@@ -0,0 +1,409 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::{
+        oidc::{ProviderMetadata, UserInfoResponse},
+        token::AccessTokenResponse,
+    },
+    utils::{
+        auth::{Claims, ACCESS_TOKEN_TYPE},
+        constants::JWT_COOKIE_NAME,
+        oauth::code_challenge,
+        oidc::IdTokenClaims,
+    },
+    OAuthErrorResponse,
+};
+use jsonwebtoken::{decode, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::header::{LOCATION, WWW_AUTHENTICATE};
+use secrecy::ExposeSecret;
+use test_helpers::api_test;
+use url::Url;
+
+use crate::helpers::TestApp;
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+// Sign up and log in a user without 2FA, returning their email
+async fn login(app: &TestApp) -> String {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    email
+}
+
+fn location(response: &reqwest::Response) -> String {
+    response
+        .headers()
+        .get(LOCATION)
+        .expect("No redirect")
+        .to_str()
+        .expect("Invalid redirect")
+        .to_owned()
+}
+
+// Authorize the logged-in user for the client and redeem the code, with a nonce
+async fn tokens(app: &TestApp, client_id: &str, scope: &str) -> AccessTokenResponse {
+    let challenge = code_challenge(VERIFIER);
+    let response = app
+        .get_authorize(&[
+            ("response_type", "code"),
+            ("client_id", client_id),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+            ("scope", scope),
+            ("nonce", "n-0S6_WzA2Mj"),
+        ])
+        .await;
+    let code = Url::parse(&location(&response))
+        .expect("Redirect is not absolute")
+        .query_pairs()
+        .find(|(name, _)| name == "code")
+        .expect("No code in redirect")
+        .1
+        .into_owned();
+    let response = app
+        .post_token(&[
+            ("grant_type", "authorization_code"),
+            ("client_id", client_id),
+            ("code", &code),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", VERIFIER),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.expect("Failed to parse tokens")
+}
+
+async fn metadata(app: &TestApp) -> ProviderMetadata {
+    let response = app.get_openid_configuration().await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.expect("Failed to parse metadata")
+}
+
+// The published key a token was signed with
+async fn published_key(app: &TestApp, token: &str) -> DecodingKey {
+    let jwks: JwkSet = app
+        .get_jwks()
+        .await
+        .json()
+        .await
+        .expect("Failed to parse JWKS");
+    let kid = jsonwebtoken::decode_header(token)
+        .expect("Invalid token header")
+        .kid
+        .expect("No key id");
+    let jwk = jwks.find(&kid).expect("Signing key is not published");
+    DecodingKey::from_jwk(jwk).expect("Invalid JWK")
+}
+
+// Verify an ID token the way a client would, with the published key
+async fn verify_id_token(app: &TestApp, id_token: &str, client_id: &str) -> IdTokenClaims {
+    let issuer = metadata(app).await.issuer;
+    let mut validation = Validation::new(Algorithm::ES256);
+    validation.set_audience(&[client_id]);
+    validation.set_issuer(&[issuer]);
+    decode::<IdTokenClaims>(id_token, &published_key(app, id_token).await, &validation)
+        .expect("ID token does not verify")
+        .claims
+}
+
+async fn error(response: reqwest::Response) -> String {
+    let body: OAuthErrorResponse = response.json().await.expect("Failed to parse error");
+    body.error
+}
+
+#[api_test]
+async fn should_publish_provider_metadata() {
+    let metadata = metadata(&app).await;
+
+    assert_eq!(
+        metadata.authorization_endpoint,
+        format!("{}/authorize", metadata.issuer)
+    );
+    assert_eq!(
+        metadata.jwks_uri,
+        format!("{}/.well-known/jwks.json", metadata.issuer)
+    );
+    assert_eq!(
+        metadata.device_authorization_endpoint,
+        format!("{}/device/code", metadata.issuer)
+    );
+    assert_eq!(metadata.id_token_signing_alg_values_supported, ["ES256"]);
+    assert!(metadata.scopes_supported.contains(&"openid".to_owned()));
+    assert_eq!(metadata.code_challenge_methods_supported, ["S256"]);
+}
+
+#[api_test]
+async fn should_issue_id_token_for_openid_scope() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let email = login(&app).await;
+
+    let tokens = tokens(&app, &client_id, "openid email").await;
+
+    let id_token = tokens.id_token.expect("No ID token");
+    let claims = verify_id_token(&app, &id_token, &client_id).await;
+    assert_eq!(claims.sub, app.user_id(&email).await.to_string());
+    assert_eq!(claims.email.as_deref(), Some(email.as_str()));
+    assert_eq!(claims.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+    assert_eq!(claims.amr, ["pwd"]);
+    assert!(claims.auth_time <= claims.iat);
+}
+
+#[api_test]
+async fn should_only_issue_id_token_for_openid_codes() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    login(&app).await;
+
+    let without_openid = tokens(&app, &client_id, "email").await;
+    assert!(without_openid.id_token.is_none());
+
+    let with_openid = tokens(&app, &client_id, "openid").await;
+    let claims = verify_id_token(&app, &with_openid.id_token.unwrap(), &client_id).await;
+    assert!(claims.email.is_none());
+    let refreshed = app
+        .post_token(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", &client_id),
+            (
+                "refresh_token",
+                with_openid.refresh_token.as_deref().unwrap(),
+            ),
+        ])
+        .await;
+    let refreshed: AccessTokenResponse = refreshed.json().await.expect("Failed to parse tokens");
+    assert!(refreshed.id_token.is_none());
+}
+
+#[api_test]
+async fn should_sign_access_tokens_with_a_published_key() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let email = login(&app).await;
+    let tokens = tokens(&app, &client_id, "openid email").await;
+
+    // Services checking tokens locally must be able to tell access tokens from ID tokens
+    let header = jsonwebtoken::decode_header(&tokens.access_token).unwrap();
+    assert_eq!(header.typ.as_deref(), Some(ACCESS_TOKEN_TYPE));
+    let id_token = tokens.id_token.unwrap();
+    assert_ne!(
+        jsonwebtoken::decode_header(&id_token)
+            .unwrap()
+            .typ
+            .as_deref(),
+        Some(ACCESS_TOKEN_TYPE)
+    );
+
+    let mut validation = Validation::new(Algorithm::ES256);
+    validation.set_issuer(&[metadata(&app).await.issuer]);
+    let key = published_key(&app, &tokens.access_token).await;
+    let claims = decode::<Claims>(&tokens.access_token, &key, &validation)
+        .expect("Access token does not verify")
+        .claims;
+    assert_eq!(claims.sub, app.user_id(&email).await.to_string());
+    assert_eq!(claims.client_id.as_deref(), Some(client_id.as_str()));
+}
+
+#[api_test]
+async fn should_report_two_factor_login_in_amr() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    }))
+    .await;
+    app.post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    let (attempt_id, code) = app
+        .two_fa_codes
+        .read()
+        .await
+        .get_code(app.user_id(&email).await)
+        .await
+        .unwrap();
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": attempt_id.as_ref().expose_secret(),
+            "2FACode": code.as_ref().expose_secret()
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = tokens(&app, &client_id, "openid").await;
+
+    let claims = verify_id_token(&app, &tokens.id_token.unwrap(), &client_id).await;
+    assert_eq!(claims.amr, ["pwd", "otp", "mfa"]);
+}
+
+#[api_test]
+async fn should_return_userinfo_for_openid_tokens() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let email = login(&app).await;
+
+    let with_email = tokens(&app, &client_id, "openid email").await;
+    let response = app.get_userinfo(&with_email.access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: UserInfoResponse = response.json().await.expect("Failed to parse userinfo");
+    let user_id = app.user_id(&email).await.to_string();
+    assert_eq!(body.sub, user_id);
+    assert_eq!(body.email.as_deref(), Some(email.as_str()));
+
+    let openid_only = tokens(&app, &client_id, "openid").await;
+    let body: UserInfoResponse = app
+        .get_userinfo(&openid_only.access_token)
+        .await
+        .json()
+        .await
+        .expect("Failed to parse userinfo");
+    assert_eq!(body.sub, user_id);
+    assert!(body.email.is_none());
+}
+
+#[api_test]
+async fn should_reject_userinfo_without_openid_scope() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    login(&app).await;
+    let tokens = tokens(&app, &client_id, "email").await;
+
+    let response = app.get_userinfo(&tokens.access_token).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    let challenge = response
+        .headers()
+        .get(WWW_AUTHENTICATE)
+        .and_then(|value| value.to_str().ok())
+        .expect("No challenge");
+    assert!(challenge.contains("insufficient_scope"));
+
+    let response = app.get_userinfo("not-a-token").await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_end_session_and_revoke_client_tokens() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let email = login(&app).await;
+    let tokens = tokens(&app, &client_id, "openid").await;
+    let id_token = tokens.id_token.unwrap();
+
+    let response = app
+        .get_end_session(&[
+            ("id_token_hint", &id_token),
+            ("post_logout_redirect_uri", REDIRECT_URI),
+            ("state", "af0ifjsldkj"),
+        ])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(
+        location(&response),
+        format!("{}?state=af0ifjsldkj", REDIRECT_URI)
+    );
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("Auth cookie was not cleared");
+    assert!(auth_cookie.value().is_empty());
+
+    // The client's tokens are revoked, and the user has to log in again
+    assert_eq!(
+        app.get_userinfo(&tokens.access_token)
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+    let response = app
+        .post_token(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", &client_id),
+            ("refresh_token", tokens.refresh_token.as_deref().unwrap()),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let challenge = code_challenge(VERIFIER);
+    let response = app
+        .get_authorize(&[
+            ("response_type", "code"),
+            ("client_id", &client_id),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+        ])
+        .await;
+    assert!(location(&response).starts_with("/?next="));
+
+    let end_session = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .find(|entry| entry.event.kind == AuditEventKind::EndSession)
+        .expect("No end session event");
+    assert_eq!(end_session.event.outcome, AuditOutcome::Success);
+    let user_id = app.user_id(&email).await.to_string();
+    assert_eq!(end_session.event.actor, Some(user_id));
+}
+
+#[api_test]
+async fn should_redirect_to_login_page_without_post_logout_uri() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    login(&app).await;
+    let tokens = tokens(&app, &client_id, "openid").await;
+
+    let response = app
+        .get_end_session(&[("id_token_hint", &tokens.id_token.unwrap())])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(location(&response), "/");
+}
+
+#[api_test]
+async fn should_reject_invalid_end_session_requests() {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let other_client = app.register_oauth_client(REDIRECT_URI).await;
+    login(&app).await;
+    let tokens = tokens(&app, &client_id, "openid").await;
+    let id_token = tokens.id_token.unwrap();
+    let test_cases = [
+        vec![],
+        vec![("id_token_hint", "not-a-token")],
+        vec![("id_token_hint", tokens.access_token.as_str())],
+        vec![
+            ("id_token_hint", id_token.as_str()),
+            ("post_logout_redirect_uri", "https://evil.example.com/"),
+        ],
+        vec![
+            ("id_token_hint", id_token.as_str()),
+            ("client_id", other_client.as_str()),
+        ],
+    ];
+
+    for test_case in test_cases {
+        let response = app.get_end_session(&test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+        assert_eq!(error(response).await, "invalid_request");
+    }
+    // None of them logged the user out
+    assert_eq!(
+        app.get_userinfo(&tokens.access_token)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+}
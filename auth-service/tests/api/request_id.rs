@@ -0,0 +1,45 @@
+use auth_service::utils::tracing::REQUEST_ID_HEADER;
+use test_helpers::api_test;
+use uuid::Uuid;
+
+use crate::helpers::TestApp;
+
+#[api_test]
+async fn should_generate_request_id_when_missing() {
+    let response = app.get_healthz().await;
+
+    let request_id = response.headers().get(REQUEST_ID_HEADER).unwrap();
+    assert!(Uuid::parse_str(request_id.to_str().unwrap()).is_ok());
+}
+
+#[api_test]
+async fn should_echo_caller_request_id() {
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", &app.address))
+        .header(REQUEST_ID_HEADER, "checkout-1234")
+        .json(&serde_json::json!({ "token": "invalid" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.headers().get(REQUEST_ID_HEADER).unwrap(),
+        "checkout-1234"
+    );
+}
+
+#[api_test]
+async fn should_replace_invalid_request_id() {
+    let response = app
+        .http_client
+        .get(format!("{}/healthz", &app.address))
+        .header(REQUEST_ID_HEADER, "a".repeat(200))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    let request_id = response.headers().get(REQUEST_ID_HEADER).unwrap();
+    assert!(Uuid::parse_str(request_id.to_str().unwrap()).is_ok());
+}
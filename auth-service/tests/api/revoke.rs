@@ -0,0 +1,268 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::token::AccessTokenResponse,
+    utils::constants::{
+        test::{ADMIN_TOKEN, CLIENT_ID, CLIENT_SECRET},
+        JWT_COOKIE_NAME,
+    },
+};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+const CREDENTIALS: Option<(&str, &str)> = Some((CLIENT_ID, CLIENT_SECRET));
+
+// Sign up and log in a user without 2FA, returning their token
+async fn login(app: &TestApp) -> String {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    let token = auth_cookie.value().to_owned();
+    token
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[api_test]
+async fn should_revoke_token_for_client() {
+    let token = login(&app).await;
+
+    let response = app
+        .post_revoke(
+            &[("token", &token), ("token_type_hint", "access_token")],
+            CREDENTIALS,
+            None,
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+    // The session went with the token
+    assert_eq!(app.get_sessions().await.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_revoke_token_for_admin() {
+    let token = login(&app).await;
+
+    let response = app
+        .post_revoke(&[("token", &token)], None, Some(ADMIN_TOKEN))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+}
+
+#[api_test]
+async fn should_be_idempotent_and_ignore_unknown_tokens() {
+    let token = login(&app).await;
+    let test_cases = [token.as_str(), token.as_str(), "not-a-token"];
+
+    for token in test_cases {
+        let response = app
+            .post_revoke(
+                &[("token", token), ("token_type_hint", "refresh_token")],
+                CREDENTIALS,
+                None,
+            )
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            200,
+            "Failed for input: {}",
+            token
+        );
+    }
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+}
+
+#[api_test]
+async fn should_reject_unauthenticated_callers() {
+    let token = login(&app).await;
+    let test_cases = [
+        (None, None),
+        (Some((CLIENT_ID, "wrong-secret")), None),
+        (None, Some("not-the-admin-token")),
+    ];
+
+    for (credentials, admin_token) in test_cases {
+        let response = app
+            .post_revoke(&[("token", &token)], credentials, admin_token)
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            (credentials, admin_token)
+        );
+    }
+    assert_eq!(verify_token_status(&app, &token).await, 200);
+    let outcomes: Vec<_> = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .filter(|entry| entry.event.kind == AuditEventKind::RevokeToken)
+        .map(|entry| entry.event.outcome)
+        .collect();
+    assert_eq!(outcomes, [AuditOutcome::Failure; 3]);
+}
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+// Register an OAuth client and get tokens from it for a logged-in user
+async fn oauth_tokens(app: &TestApp) -> (String, AccessTokenResponse) {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let code = app
+        .authorization_code(&client_id, REDIRECT_URI, VERIFIER, None)
+        .await;
+    let response = app
+        .post_token(&[
+            ("grant_type", "authorization_code"),
+            ("client_id", &client_id),
+            ("code", &code),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", VERIFIER),
+        ])
+        .await;
+    let tokens = response.json().await.expect("Failed to parse tokens");
+    (client_id, tokens)
+}
+
+async fn refresh_status(app: &TestApp, client_id: &str, refresh_token: &str) -> u16 {
+    app.post_token(&[
+        ("grant_type", "refresh_token"),
+        ("client_id", client_id),
+        ("refresh_token", refresh_token),
+    ])
+    .await
+    .status()
+    .as_u16()
+}
+
+#[api_test]
+async fn should_revoke_refresh_token_for_oauth_client() {
+    login(&app).await;
+    let (client_id, tokens) = oauth_tokens(&app).await;
+
+    let response = app
+        .post_revoke(
+            &[
+                ("token", tokens.refresh_token.as_deref().unwrap()),
+                ("token_type_hint", "refresh_token"),
+                ("client_id", &client_id),
+            ],
+            None,
+            None,
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        refresh_status(&app, &client_id, tokens.refresh_token.as_deref().unwrap()).await,
+        400
+    );
+}
+
+#[api_test]
+async fn should_revoke_access_token_for_oauth_client() {
+    login(&app).await;
+    let (client_id, tokens) = oauth_tokens(&app).await;
+
+    let response = app
+        .post_revoke(
+            &[("token", &tokens.access_token), ("client_id", &client_id)],
+            None,
+            None,
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(verify_token_status(&app, &tokens.access_token).await, 401);
+}
+
+#[api_test]
+async fn should_revoke_refresh_token_for_admin() {
+    login(&app).await;
+    let (client_id, tokens) = oauth_tokens(&app).await;
+
+    let response = app
+        .post_revoke(
+            &[("token", tokens.refresh_token.as_deref().unwrap())],
+            None,
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        refresh_status(&app, &client_id, tokens.refresh_token.as_deref().unwrap()).await,
+        400
+    );
+}
+
+#[api_test]
+async fn should_only_let_oauth_clients_revoke_their_own_tokens() {
+    let first_party = login(&app).await;
+    let (client_id, tokens) = oauth_tokens(&app).await;
+    let other_client = app.register_oauth_client(REDIRECT_URI).await;
+    let test_cases = [
+        (first_party.as_str(), client_id.as_str(), 403),
+        (tokens.access_token.as_str(), other_client.as_str(), 403),
+        (
+            tokens.refresh_token.as_deref().unwrap(),
+            other_client.as_str(),
+            403,
+        ),
+        // Configured clients cannot revoke refresh tokens of OAuth clients either
+        (tokens.refresh_token.as_deref().unwrap(), CLIENT_ID, 401),
+        (tokens.refresh_token.as_deref().unwrap(), "unknown", 401),
+    ];
+
+    for (token, caller, status) in test_cases {
+        let response = app
+            .post_revoke(&[("token", token), ("client_id", caller)], None, None)
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            status,
+            "Failed for caller: {}",
+            caller
+        );
+    }
+    assert_eq!(
+        app.post_revoke(
+            &[("token", tokens.refresh_token.as_deref().unwrap())],
+            CREDENTIALS,
+            None
+        )
+        .await
+        .status()
+        .as_u16(),
+        403
+    );
+    assert_eq!(verify_token_status(&app, &first_party).await, 200);
+    assert_eq!(
+        refresh_status(&app, &client_id, tokens.refresh_token.as_deref().unwrap()).await,
+        200
+    );
+}
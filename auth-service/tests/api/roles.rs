@@ -0,0 +1,271 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::roles::{RoleResponse, RolesResponse, UserRolesResponse},
+    utils::{
+        auth::decode_token,
+        constants::{test::ADMIN_TOKEN, JWT_COOKIE_NAME},
+    },
+};
+use secrecy::SecretString;
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+// Sign up a user without 2FA and return their email
+async fn signup(app: &TestApp) -> String {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    email
+}
+
+// Log in and return the token that was issued
+async fn login(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    auth_cookie.value().to_owned()
+}
+
+#[api_test]
+async fn should_put_list_and_delete_roles() {
+    let response = app
+        .put_admin_role(
+            "editor",
+            &["reports:write", "reports:read", "reports:write"],
+            Some(ADMIN_TOKEN),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let role: RoleResponse = response.json().await.expect("Failed to parse role");
+    assert_eq!(role.permissions, ["reports:read", "reports:write"]);
+
+    // Putting a role again replaces its permissions
+    app.put_admin_role("viewer", &["a:read"], Some(ADMIN_TOKEN))
+        .await;
+    app.put_admin_role("viewer", &["reports:read"], Some(ADMIN_TOKEN))
+        .await;
+    let body: RolesResponse = app
+        .get_admin_roles(Some(ADMIN_TOKEN))
+        .await
+        .json()
+        .await
+        .expect("Failed to parse roles");
+    let roles: Vec<_> = body
+        .roles
+        .iter()
+        .map(|role| (role.name.as_str(), role.permissions.clone()))
+        .collect();
+    assert_eq!(
+        roles,
+        [
+            (
+                "editor",
+                vec!["reports:read".to_owned(), "reports:write".to_owned()]
+            ),
+            ("viewer", vec!["reports:read".to_owned()]),
+        ]
+    );
+
+    let response = app.delete_admin_role("editor", Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app.delete_admin_role("editor", Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let events: Vec<_> = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .map(|entry| (entry.event.kind, entry.event.outcome))
+        .collect();
+    assert_eq!(
+        events,
+        [
+            (AuditEventKind::AdminPutRole, AuditOutcome::Success),
+            (AuditEventKind::AdminPutRole, AuditOutcome::Success),
+            (AuditEventKind::AdminPutRole, AuditOutcome::Success),
+            (AuditEventKind::AdminDeleteRole, AuditOutcome::Success),
+            (AuditEventKind::AdminDeleteRole, AuditOutcome::Failure),
+        ]
+    );
+}
+
+#[api_test]
+async fn should_return_400_for_invalid_roles() {
+    let test_cases = [
+        ("two%20words", vec!["reports:read"]),
+        ("editor", vec![""]),
+        ("editor", vec!["reports read"]),
+    ];
+
+    for (name, permissions) in test_cases {
+        let response = app
+            .put_admin_role(name, &permissions, Some(ADMIN_TOKEN))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for role: {} {:?}",
+            name,
+            permissions
+        );
+    }
+}
+
+#[api_test]
+async fn should_reject_role_routes_without_valid_admin_token() {
+    let email = signup(&app).await;
+
+    for admin_token in [None, Some("not-the-admin-token")] {
+        let expected = if admin_token.is_some() { 401 } else { 400 };
+        let statuses = [
+            app.get_admin_roles(admin_token).await.status().as_u16(),
+            app.put_admin_role("editor", &[], admin_token)
+                .await
+                .status()
+                .as_u16(),
+            app.delete_admin_role("editor", admin_token)
+                .await
+                .status()
+                .as_u16(),
+            app.get_admin_user_roles(&email, admin_token)
+                .await
+                .status()
+                .as_u16(),
+            app.put_admin_user_role(&email, "editor", admin_token)
+                .await
+                .status()
+                .as_u16(),
+            app.delete_admin_user_role(&email, "editor", admin_token)
+                .await
+                .status()
+                .as_u16(),
+        ];
+        assert_eq!(statuses, [expected; 6], "Failed for {:?}", admin_token);
+    }
+}
+
+#[api_test]
+async fn should_embed_assigned_roles_in_tokens() {
+    let email = signup(&app).await;
+    app.put_admin_role("viewer", &["reports:read"], Some(ADMIN_TOKEN))
+        .await;
+    app.put_admin_role(
+        "editor",
+        &["reports:read", "reports:write"],
+        Some(ADMIN_TOKEN),
+    )
+    .await;
+
+    let claims = decode_token(&SecretString::from(login(&app, &email).await)).unwrap();
+    assert!(claims.roles.is_empty());
+    assert!(claims.permissions.is_empty());
+
+    for role in ["viewer", "editor", "editor"] {
+        let response = app
+            .put_admin_user_role(&email, role, Some(ADMIN_TOKEN))
+            .await;
+        assert_eq!(response.status().as_u16(), 204);
+    }
+
+    let body: UserRolesResponse = app
+        .get_admin_user_roles(&email, Some(ADMIN_TOKEN))
+        .await
+        .json()
+        .await
+        .expect("Failed to parse user roles");
+    assert_eq!(body.roles, ["editor", "viewer"]);
+    assert_eq!(body.permissions, ["reports:read", "reports:write"]);
+
+    // Changes show up in the tokens issued at the next login
+    let claims = decode_token(&SecretString::from(login(&app, &email).await)).unwrap();
+    assert_eq!(claims.roles, ["editor", "viewer"]);
+    assert_eq!(claims.permissions, ["reports:read", "reports:write"]);
+
+    let response = app
+        .delete_admin_user_role(&email, "editor", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    let claims = decode_token(&SecretString::from(login(&app, &email).await)).unwrap();
+    assert_eq!(claims.roles, ["viewer"]);
+    assert_eq!(claims.permissions, ["reports:read"]);
+
+    // Deleted roles are taken away from their users
+    app.delete_admin_role("viewer", Some(ADMIN_TOKEN)).await;
+    let claims = decode_token(&SecretString::from(login(&app, &email).await)).unwrap();
+    assert!(claims.roles.is_empty());
+
+    let assignments: Vec<_> = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .filter(|entry| {
+            matches!(
+                entry.event.kind,
+                AuditEventKind::AdminAssignRole | AuditEventKind::AdminUnassignRole
+            )
+        })
+        .map(|entry| (entry.event.kind, entry.event.actor, entry.event.outcome))
+        .collect();
+    assert_eq!(
+        assignments,
+        [
+            (
+                AuditEventKind::AdminAssignRole,
+                Some(email.clone()),
+                AuditOutcome::Success
+            ),
+            (
+                AuditEventKind::AdminAssignRole,
+                Some(email.clone()),
+                AuditOutcome::Success
+            ),
+            (
+                AuditEventKind::AdminAssignRole,
+                Some(email.clone()),
+                AuditOutcome::Success
+            ),
+            (
+                AuditEventKind::AdminUnassignRole,
+                Some(email.clone()),
+                AuditOutcome::Success
+            ),
+        ]
+    );
+}
+
+#[api_test]
+async fn should_return_404_for_unknown_users_and_roles() {
+    let email = signup(&app).await;
+    let unknown = TestApp::get_random_email();
+    app.put_admin_role("viewer", &["reports:read"], Some(ADMIN_TOKEN))
+        .await;
+
+    let test_cases = [
+        app.get_admin_user_roles(&unknown, Some(ADMIN_TOKEN)).await,
+        app.put_admin_user_role(&unknown, "viewer", Some(ADMIN_TOKEN))
+            .await,
+        app.put_admin_user_role(&email, "missing", Some(ADMIN_TOKEN))
+            .await,
+        // Only roles the user has can be unassigned
+        app.delete_admin_user_role(&email, "viewer", Some(ADMIN_TOKEN))
+            .await,
+    ];
+    for response in test_cases {
+        assert_eq!(response.status().as_u16(), 404);
+    }
+
+    let response = app
+        .put_admin_user_role("not-an-email", "viewer", Some(ADMIN_TOKEN))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
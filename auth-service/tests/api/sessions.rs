@@ -0,0 +1,191 @@
+use auth_service::{
+    routes::{sessions::SessionsResponse, token::AccessTokenResponse},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+// Sign up a user without 2FA and return the credentials to log in with
+async fn signup(app: &TestApp) -> serde_json::Value {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    serde_json::json!({ "email": email, "password": "password123" })
+}
+
+// Log in and return the token that was issued
+async fn login(app: &TestApp, login_body: &serde_json::Value) -> String {
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header("User-Agent", "sessions-test/1.0")
+        .json(login_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    auth_cookie.value().to_owned()
+}
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+// Register an OAuth client and return the access token it gets for the logged-in user
+async fn client_token(app: &TestApp) -> String {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    let code = app
+        .authorization_code(&client_id, REDIRECT_URI, VERIFIER, None)
+        .await;
+    let response = app
+        .post_token(&[
+            ("grant_type", "authorization_code"),
+            ("client_id", &client_id),
+            ("code", &code),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", VERIFIER),
+        ])
+        .await;
+    let tokens: AccessTokenResponse = response.json().await.expect("Failed to parse tokens");
+    tokens.access_token
+}
+
+async fn sessions(app: &TestApp) -> SessionsResponse {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_list_sessions_of_caller_newest_first() {
+    let login_body = signup(&app).await;
+    login(&app, &login_body).await;
+    login(&app, &login_body).await;
+
+    let sessions = sessions(&app).await.sessions;
+
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions[0].current);
+    assert!(!sessions[1].current);
+    assert!(sessions[0].created_at >= sessions[1].created_at);
+    assert_eq!(sessions[0].ip, Some("127.0.0.1".parse().unwrap()));
+    assert_eq!(sessions[0].user_agent.as_deref(), Some("sessions-test/1.0"));
+}
+
+#[api_test]
+async fn should_revoke_other_session() {
+    let login_body = signup(&app).await;
+    let old_token = login(&app, &login_body).await;
+    login(&app, &login_body).await;
+    let old_session = sessions(&app).await.sessions.remove(1);
+
+    let response = app.delete_session(&old_session.id.to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 204);
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let sessions = sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+}
+
+#[api_test]
+async fn should_log_out_when_revoking_current_session() {
+    let login_body = signup(&app).await;
+    login(&app, &login_body).await;
+    let current = sessions(&app).await.sessions.remove(0);
+
+    let response = app.delete_session(&current.id.to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 204);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(auth_cookie.value().is_empty());
+    assert_eq!(app.get_sessions().await.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_return_404_for_session_of_other_user() {
+    let other_login_body = signup(&app).await;
+    login(&app, &other_login_body).await;
+    let other_session = sessions(&app).await.sessions.remove(0);
+    let login_body = signup(&app).await;
+    login(&app, &login_body).await;
+
+    let response = app.delete_session(&other_session.id.to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    let response = app
+        .delete_session("00000000-0000-0000-0000-000000000000")
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[api_test]
+async fn should_end_session_on_logout() {
+    let login_body = signup(&app).await;
+    login(&app, &login_body).await;
+    login(&app, &login_body).await;
+
+    app.post_logout().await;
+    login(&app, &login_body).await;
+
+    assert_eq!(sessions(&app).await.sessions.len(), 2);
+}
+
+#[api_test]
+async fn should_reject_tokens_of_oauth_clients() {
+    let login_body = signup(&app).await;
+    login(&app, &login_body).await;
+    let token = client_token(&app).await;
+    let session = sessions(&app).await.sessions.remove(0);
+
+    let list = app
+        .http_client
+        .get(format!("{}/sessions", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let delete = app
+        .http_client
+        .delete(format!("{}/sessions/{}", &app.address, session.id))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let logout_all = app
+        .http_client
+        .post(format!("{}/logout-all", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(list.status().as_u16(), 401);
+    assert_eq!(delete.status().as_u16(), 401);
+    assert_eq!(logout_all.status().as_u16(), 401);
+    assert_eq!(sessions(&app).await.sessions.len(), 2);
+}
@@ -0,0 +1,292 @@
+use auth_service::{
+    domain::{AuditEventKind, AuditOutcome},
+    routes::token::AccessTokenResponse,
+    utils::{auth::decode_token, constants::JWT_COOKIE_NAME},
+    OAuthErrorResponse,
+};
+use reqwest::header::CACHE_CONTROL;
+use secrecy::SecretString;
+use test_helpers::api_test;
+
+use crate::helpers::TestApp;
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+// Sign up and log in a user without 2FA, returning their email
+async fn login(app: &TestApp) -> String {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+    email
+}
+
+fn redeem_form<'a>(client_id: &'a str, code: &'a str) -> Vec<(&'a str, &'a str)> {
+    vec![
+        ("grant_type", "authorization_code"),
+        ("client_id", client_id),
+        ("code", code),
+        ("redirect_uri", REDIRECT_URI),
+        ("code_verifier", VERIFIER),
+    ]
+}
+
+// Register a client, log a user in and get them a code for it
+async fn authorize(app: &TestApp, scope: Option<&str>) -> (String, String) {
+    let client_id = app.register_oauth_client(REDIRECT_URI).await;
+    login(app).await;
+    let code = app
+        .authorization_code(&client_id, REDIRECT_URI, VERIFIER, scope)
+        .await;
+    (client_id, code)
+}
+
+async fn tokens(response: reqwest::Response) -> AccessTokenResponse {
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.expect("Failed to parse tokens")
+}
+
+async fn error(response: reqwest::Response) -> String {
+    let body: OAuthErrorResponse = response.json().await.expect("Failed to parse error");
+    body.error
+}
+
+async fn refresh(
+    app: &TestApp,
+    client_id: &str,
+    refresh_token: &str,
+    scope: Option<&str>,
+) -> reqwest::Response {
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("client_id", client_id),
+        ("refresh_token", refresh_token),
+    ];
+    if let Some(scope) = scope {
+        form.push(("scope", scope));
+    }
+    app.post_token(&form).await
+}
+
+#[api_test]
+async fn should_exchange_code_for_tokens() {
+    let (client_id, code) = authorize(&app, Some("profile email")).await;
+
+    let response = app.post_token(&redeem_form(&client_id, &code)).await;
+
+    assert_eq!(
+        response
+            .headers()
+            .get(CACHE_CONTROL)
+            .and_then(|value| value.to_str().ok()),
+        Some("no-store")
+    );
+    let tokens = tokens(response).await;
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.scope.as_deref(), Some("profile email"));
+    assert_eq!(tokens.refresh_token.as_deref().map(str::len), Some(43));
+
+    let claims = decode_token(&SecretString::from(tokens.access_token.clone())).unwrap();
+    assert_eq!(claims.client_id.as_deref(), Some(client_id.as_str()));
+    assert_eq!(claims.scope.as_deref(), Some("profile email"));
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token_event = app
+        .audit_entries()
+        .await
+        .into_iter()
+        .find(|entry| entry.event.kind == AuditEventKind::OAuthToken)
+        .expect("No token event");
+    assert_eq!(token_event.event.outcome, AuditOutcome::Success);
+    assert_eq!(token_event.event.actor.as_deref(), claims.email.as_deref());
+}
+
+#[api_test]
+async fn should_accept_each_code_once() {
+    let (client_id, code) = authorize(&app, None).await;
+
+    tokens(app.post_token(&redeem_form(&client_id, &code)).await).await;
+    let response = app.post_token(&redeem_form(&client_id, &code)).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "invalid_grant");
+}
+
+#[api_test]
+async fn should_reject_codes_that_do_not_match_the_request() {
+    let (client_id, _) = authorize(&app, None).await;
+    let other_client = app.register_oauth_client(REDIRECT_URI).await;
+    let wrong_verifier = "a".repeat(43);
+    let test_cases = [
+        ("client_id", other_client.as_str()),
+        ("redirect_uri", "https://app.example.com/other"),
+        ("code_verifier", wrong_verifier.as_str()),
+        ("code", "unknown-code"),
+    ];
+
+    for (name, value) in test_cases {
+        let code = app
+            .authorization_code(&client_id, REDIRECT_URI, VERIFIER, None)
+            .await;
+        let mut form = redeem_form(&client_id, &code);
+        form.retain(|(param, _)| *param != name);
+        form.push((name, value));
+        let response = app.post_token(&form).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {}={}",
+            name,
+            value
+        );
+        assert_eq!(error(response).await, "invalid_grant");
+        // A failed attempt with the code uses it up
+        if name != "code" {
+            let response = app.post_token(&redeem_form(&client_id, &code)).await;
+            assert_eq!(response.status().as_u16(), 400);
+        }
+    }
+}
+
+#[api_test]
+async fn should_reject_unknown_clients_and_grant_types() {
+    let (client_id, code) = authorize(&app, None).await;
+
+    let response = app.post_token(&redeem_form("unknown", &code)).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(error(response).await, "invalid_client");
+
+    let response = app
+        .post_token(&[("grant_type", "password"), ("client_id", &client_id)])
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "unsupported_grant_type");
+
+    let response = app
+        .post_token(&[
+            ("grant_type", "authorization_code"),
+            ("client_id", &client_id),
+        ])
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "invalid_request");
+}
+
+#[api_test]
+async fn should_rotate_refresh_tokens() {
+    let (client_id, code) = authorize(&app, Some("profile")).await;
+    let first = tokens(app.post_token(&redeem_form(&client_id, &code)).await).await;
+
+    let second = tokens(
+        refresh(
+            &app,
+            &client_id,
+            first.refresh_token.as_deref().unwrap(),
+            None,
+        )
+        .await,
+    )
+    .await;
+
+    assert_ne!(second.refresh_token, first.refresh_token);
+    assert_eq!(second.scope.as_deref(), Some("profile"));
+    let response = refresh(
+        &app,
+        &client_id,
+        first.refresh_token.as_deref().unwrap(),
+        None,
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "invalid_grant");
+}
+
+#[api_test]
+async fn should_narrow_scope_on_refresh() {
+    let (client_id, code) = authorize(&app, Some("profile email")).await;
+    let first = tokens(app.post_token(&redeem_form(&client_id, &code)).await).await;
+
+    let response = refresh(
+        &app,
+        &client_id,
+        first.refresh_token.as_deref().unwrap(),
+        Some("admin"),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "invalid_scope");
+
+    let narrowed = tokens(
+        refresh(
+            &app,
+            &client_id,
+            first.refresh_token.as_deref().unwrap(),
+            Some("email"),
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(narrowed.scope.as_deref(), Some("email"));
+    // The refresh token still carries the whole grant
+    let widened = tokens(
+        refresh(
+            &app,
+            &client_id,
+            narrowed.refresh_token.as_deref().unwrap(),
+            None,
+        )
+        .await,
+    )
+    .await;
+    assert_eq!(widened.scope.as_deref(), Some("profile email"));
+}
+
+#[api_test]
+async fn should_reject_refresh_tokens_of_other_clients() {
+    let (client_id, code) = authorize(&app, None).await;
+    let other_client = app.register_oauth_client(REDIRECT_URI).await;
+    let first = tokens(app.post_token(&redeem_form(&client_id, &code)).await).await;
+
+    let response = refresh(
+        &app,
+        &other_client,
+        first.refresh_token.as_deref().unwrap(),
+        None,
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "invalid_grant");
+}
+
+#[api_test]
+async fn should_reject_refresh_tokens_after_logout_all() {
+    let (client_id, code) = authorize(&app, None).await;
+    let first = tokens(app.post_token(&redeem_form(&client_id, &code)).await).await;
+
+    assert_eq!(app.post_logout_all().await.status().as_u16(), 200);
+    let response = refresh(
+        &app,
+        &client_id,
+        first.refresh_token.as_deref().unwrap(),
+        None,
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(error(response).await, "invalid_grant");
+}
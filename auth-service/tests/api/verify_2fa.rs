@@ -1,5 +1,5 @@
 use auth_service::{
-    domain::{Email, HashedPassword, LoginAttemptId, TwoFACode},
+    domain::{HashedPassword, LoginAttemptId, TwoFACode},
     utils::constants::JWT_COOKIE_NAME,
 };
 use fake::{faker::internet::en::Password as FakePassword, Fake};
@@ -67,7 +67,7 @@ async fn should_return_401_if_incorrect_credentials() {
         .two_fa_codes
         .read()
         .await
-        .get_code(&Email::parse(random_email.clone()).unwrap())
+        .get_code(app.user_id(&random_email).await)
         .await
         .unwrap();
     let first_attempt_id = first_code.0.as_ref().expose_secret().to_string();
@@ -97,7 +97,7 @@ async fn should_return_401_if_old_code() {
         .two_fa_codes
         .read()
         .await
-        .get_code(&Email::parse(random_email.clone()).unwrap())
+        .get_code(app.user_id(&random_email).await)
         .await
         .unwrap();
     let first_attempt_id = first_code.0.as_ref().expose_secret().to_string();
@@ -127,7 +127,7 @@ async fn should_return_200_if_credentials_are_valid() {
         .two_fa_codes
         .read()
         .await
-        .get_code(&Email::parse(random_email.clone()).unwrap())
+        .get_code(app.user_id(&random_email).await)
         .await
         .unwrap();
     let first_attempt_id = first_code.0.as_ref().expose_secret().to_string();
@@ -146,7 +146,7 @@ async fn should_return_200_if_credentials_are_valid() {
         .two_fa_codes
         .read()
         .await
-        .get_code(&Email::parse(random_email.clone()).unwrap())
+        .get_code(app.user_id(&random_email).await)
         .await;
     assert!(final_code.is_err());
 }
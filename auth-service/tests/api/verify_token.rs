@@ -1,4 +1,6 @@
-use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use auth_service::{
+    routes::verify_token::ValidateTokenResponse, utils::constants::JWT_COOKIE_NAME, ErrorResponse,
+};
 use test_helpers::api_test;
 
 use crate::helpers::TestApp;
@@ -51,6 +53,37 @@ async fn should_return_200_if_valid_token() {
     assert_eq!(response.status().as_u16(), 200, "Failed for valid token");
 }
 
+#[api_test]
+async fn should_describe_owner_of_valid_token() {
+    let email = TestApp::get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false,
+    }))
+    .await;
+    let login_response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    let token = login_response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: ValidateTokenResponse = response.json().await.unwrap();
+    assert_eq!(body.sub, app.user_id(&email).await.to_string());
+    assert_eq!(body.email, Some(email));
+    assert!(body.sid.is_some());
+    assert!(body.exp.is_some());
+}
+
 #[api_test]
 async fn should_return_401_if_invalid_token() {
     let verify_token_body = serde_json::json!({ "token": "invalid", });